@@ -0,0 +1,77 @@
+use std::path::Path;
+
+/// Characters which are letters in command names besides the ASCII letters
+///
+/// `\makeatletter` and `\makeatother` toggle whether `@` is a letter, `\ExplSyntaxOn` and
+/// `\ExplSyntaxOff` toggle whether `_` and `:` are. Changes last until they are toggled
+/// back, they are not undone at the end of a group.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Catcodes {
+    at_letter: bool,
+    expl_syntax: bool,
+}
+
+impl Catcodes {
+    /// Create catcodes where only ASCII letters are letters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Catcodes a file starts with, `@` is a letter in `.sty` and `.cls` files
+    pub fn for_path(path: impl AsRef<Path>) -> Self {
+        let package = path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension == "sty" || extension == "cls");
+
+        Self::new().with_at_letter(package)
+    }
+
+    /// Set whether `@` is a letter
+    pub fn with_at_letter(mut self, at_letter: bool) -> Self {
+        self.at_letter = at_letter;
+        self
+    }
+
+    /// Set whether `_` and `:` are letters
+    pub fn with_expl_syntax(mut self, expl_syntax: bool) -> Self {
+        self.expl_syntax = expl_syntax;
+        self
+    }
+
+    /// Returns whether `@` is a letter
+    pub fn at_letter(&self) -> bool {
+        self.at_letter
+    }
+
+    /// Returns whether `_` and `:` are letters
+    pub fn expl_syntax(&self) -> bool {
+        self.expl_syntax
+    }
+
+    /// Whether a character can be part of a command name
+    pub fn is_letter(&self, c: char) -> bool {
+        c.is_ascii_alphabetic()
+            || self.at_letter && c == '@'
+            || self.expl_syntax && matches!(c, '_' | ':')
+    }
+
+    /// Whether a command changes the catcodes
+    pub fn is_change(label: &str) -> bool {
+        matches!(
+            label,
+            "makeatletter" | "makeatother" | "ExplSyntaxOn" | "ExplSyntaxOff"
+        )
+    }
+
+    /// Update the catcodes after a command
+    pub fn apply(&mut self, label: &str) {
+        match label {
+            "makeatletter" => self.at_letter = true,
+            "makeatother" => self.at_letter = false,
+            "ExplSyntaxOn" => self.expl_syntax = true,
+            "ExplSyntaxOff" => self.expl_syntax = false,
+            _ => {}
+        }
+    }
+}
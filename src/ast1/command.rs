@@ -45,6 +45,11 @@ impl Command {
         &self.arguments
     }
 
+    /// Return mutable argument of the command
+    pub fn arguments_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.arguments
+    }
+
     /// Return owned label of the command
     pub fn label_owned(self) -> String {
         self.label
@@ -6,10 +6,15 @@ use crate::{
     },
     ast2,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Limit, ParseLimits, Position,
 };
 
-use super::{chunk::Chunk, into_chunks::IntoChunks};
+use super::{
+    chunk::Chunk,
+    events::{Event, EventKind, Lexer},
+    into_chunks::IntoChunks,
+    Catcodes, Match, Node, Query,
+};
 
 /// Main struct for stage 1 AST
 ///
@@ -25,11 +30,38 @@ impl Document {
         &self.0
     }
 
+    /// Index all nodes of the document for searching
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    /// Returns the path from the outermost to the innermost node containing the position
+    pub fn node_at(&self, position: Position) -> Vec<Match<'_>> {
+        self.query()
+            .node_at(position)
+            .into_iter()
+            .copied()
+            .collect()
+    }
+
+    /// Returns the start position and the position right after the end of a node
+    /// borrowed from this document
+    pub fn span_of(&self, node: Node) -> Option<(Position, Position)> {
+        self.query()
+            .locate(node)
+            .map(|found| (found.start(), found.end()))
+    }
+
     /// Return all the owned chunks within the document
     pub fn chunks_owned(self) -> Vec<Chunk> {
         self.0
     }
 
+    /// Returns the mutable chunks inside the document
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.0
+    }
+
     /// Create new document from chunks
     pub fn new(chunks: Vec<Chunk>) -> Result<Self, InternalError> {
         let out = Self(chunks);
@@ -152,345 +184,223 @@ impl From<ast2::Document> for Document {
     }
 }
 
-/// Cursor buffer type
-#[derive(Clone)]
-enum Buffer {
-    /// Currently in a scope,
-    /// reading in characters until the scope is closed,
-    /// then it will be parsed
-    Scope {
-        content: String,
-        variant: ScopeVariant,
-        depth: u32,
-    },
-    /// Currently in a command
-    /// Enters this mode after a character is escaped
-    /// Exit this mode when it hits a Text element
-    /// note that following scopes will just be parsed as arguments for the command
-    Command {
-        label: String,
-        scopes: Vec<(String, ScopeVariant, String)>, // content, variant, trailing
-        depth: u32,
-        trailing: String,
-    },
-    /// Stores all read characters in a buffer
-    /// exit this mode when it hits a scope or command
-    Text { content: String },
+// a list of chunks being parsed, the document or the content of a scope or argument
+struct Frame {
+    chunks: Vec<Chunk>,
+    // line of the opening parenthesis, or of the start of the document
+    line: u32,
+    // environments opened in this frame and not yet closed
+    environments: usize,
+    kind: FrameKind,
 }
 
-impl Buffer {
-    /// Checkes whether a command has no attached scopes (arguments)
-    pub fn no_scope(&self) -> bool {
-        match self {
-            Self::Command { scopes, .. } => scopes.is_empty(),
-            _ => unreachable!("not a command"),
-        }
-    }
+enum FrameKind {
+    Document,
+    Scope(ScopeVariant),
+    // an argument with the whitespace before it
+    Argument(ScopeVariant, String),
+}
+
+// builds a document from events without recursing into nested scopes
+struct Builder {
+    frames: Vec<Frame>,
+    // environments open in all frames
+    environments: usize,
+    nodes: usize,
+    limits: ParseLimits,
+}
 
-    /// Create a scope buffer from variant
-    pub fn scope(variant: ScopeVariant) -> Self {
-        Self::Scope {
-            content: String::new(),
-            depth: 1,
-            variant,
+impl Builder {
+    fn new(limits: ParseLimits) -> Self {
+        Self {
+            frames: vec![Frame {
+                chunks: Vec::new(),
+                line: 1,
+                environments: 0,
+                kind: FrameKind::Document,
+            }],
+            environments: 0,
+            nodes: 0,
+            limits,
         }
     }
 
-    /// Create a command buffer
-    pub fn command() -> Self {
-        Self::Command {
-            label: String::new(),
-            scopes: Vec::new(),
-            depth: 0,
-            trailing: String::new(),
-        }
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
     }
 
-    /// Create a text buffer
-    pub fn text() -> Self {
-        Self::Text {
-            content: String::new(),
+    // count a new node, failing when there are too many
+    fn count(&mut self, at: Position) -> Result<(), crate::Error> {
+        self.nodes += 1;
+
+        if self.limits.max_nodes().is_some_and(|max| self.nodes > max) {
+            return Err(crate::Error::new(
+                at,
+                crate::ErrorType::LimitExceeded(Limit::Nodes),
+            ));
         }
+
+        Ok(())
     }
 
-    /// push a char to buffer
-    pub fn push(&mut self, c: char) {
-        match self {
-            Self::Scope { content, .. } => content.push(c),
-            Self::Command { label, scopes, .. } if scopes.is_empty() => label.push(c),
-            Self::Command { scopes, .. } => scopes.last_mut().unwrap().0.push(c),
-            Self::Text { content } => content.push(c),
+    // push a chunk starting in an absolute line to the innermost frame
+    fn push(&mut self, line: u32, variant: ChunkVariant, at: Position) -> Result<(), crate::Error> {
+        let frame = self.frame();
+
+        if let (ChunkVariant::Text(s), Some(last)) = (&variant, frame.chunks.last_mut()) {
+            if let ChunkVariant::Text(last) = last.variant_mut() {
+                last.push_str(s);
+                return Ok(());
+            }
         }
+
+        let line_no = line - frame.line + 1;
+        frame.chunks.push(Chunk::new_unchecked(line_no, variant));
+        self.count(at)
     }
 
-    /// Push a str to buffer
-    pub fn push_str(&mut self, s: &str) {
-        match self {
-            Self::Scope { content, .. } => content.push_str(s),
-            Self::Command { label, scopes, .. } if scopes.is_empty() => label.push_str(s),
-            Self::Command { scopes, .. } => scopes.last_mut().unwrap().0.push_str(s),
-            Self::Text { content } => content.push_str(s),
-        }
+    fn open(&mut self, line: u32, kind: FrameKind) {
+        self.frames.push(Frame {
+            chunks: Vec::new(),
+            line,
+            environments: 0,
+            kind,
+        })
     }
 
-    /// Push a scope to command arguments
-    pub fn push_scope(&mut self, variant: ScopeVariant) {
-        match self {
-            Buffer::Command {
-                scopes,
-                depth,
-                trailing,
-                ..
-            } => {
-                *depth = 1;
-                scopes.push((String::new(), variant, String::new()));
-                if !trailing.is_empty() {
-                    scopes.last_mut().unwrap().2 = std::mem::take(trailing);
-                }
-            }
-            _ => unreachable!("pushing scope to non command element"),
-        }
+    fn close(&mut self) -> Frame {
+        let frame = self.frames.pop().unwrap();
+        self.environments -= frame.environments;
+        frame
     }
-}
 
-impl FromStr for Document {
-    type Err = crate::Error;
+    fn event(&mut self, event: Event) -> Result<(), crate::Error> {
+        let at = event.start;
+
+        match event.kind {
+            EventKind::Text(s) => self.push(at.line, ChunkVariant::Text(s), at)?,
+            EventKind::CommandStart(label) => {
+                match label.as_str() {
+                    "begin" => {
+                        self.frame().environments += 1;
+                        self.environments += 1;
+
+                        if self.environments > self.limits.max_environment_depth() {
+                            return Err(crate::Error::new(
+                                at,
+                                crate::ErrorType::LimitExceeded(Limit::EnvironmentDepth),
+                            ));
+                        }
+                    }
+                    "end" if self.frame().environments != 0 => {
+                        self.frame().environments -= 1;
+                        self.environments -= 1;
+                    }
+                    _ => {}
+                }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut chunks = Vec::new();
-
-        // the current line number
-        let mut line_no: u32 = 1;
-
-        // whether the next character should be escaped
-        let mut escaped = false;
-
-        // whether the current character is a comment
-        let mut comment = false;
-
-        // line which the current buffer starts in
-        let mut buffer_line_no = line_no;
-        let mut buffer = Buffer::Text {
-            content: String::new(),
-        };
-
-        // flush buffer content into chunks
-        fn flush(
-            buffer: &mut Buffer,
-            buffer_line_no: u32,
-            chunks: &mut Vec<Chunk>,
-        ) -> Result<(), crate::Error> {
-            macro_rules! eval_scope {
-                ($i:ident) => {
-                    Document::from_str($i)?.chunks_owned()
-                };
+                self.push(
+                    at.line,
+                    ChunkVariant::Command(Command::new_unchecked(label, Vec::new())),
+                    at,
+                )?
             }
+            EventKind::ArgumentOpen(variant) => {
+                let chunks = &mut self.frame().chunks;
+
+                // whitespace between the command and the argument
+                let prec = match chunks.last().map(Chunk::variant) {
+                    Some(ChunkVariant::Text(s)) => {
+                        let s = s.clone();
+                        chunks.pop();
+                        s
+                    }
+                    _ => String::new(),
+                };
 
-            match buffer {
-                Buffer::Text { ref mut content } if !content.is_empty() => {
-                    chunks.push(Chunk::new_unchecked(
-                        buffer_line_no,
-                        ChunkVariant::Text(std::mem::take(content)),
-                    ))
+                if !prec.is_empty() {
+                    self.nodes -= 1;
                 }
-                Buffer::Text { .. } => {}
-                Buffer::Scope { depth, variant, .. } if *depth != 0 => {
-                    return Err(crate::Error::new(
-                        1,
-                        crate::ErrorType::UnclosedScope(*variant),
-                    ))
-                }
-                Buffer::Scope { content, .. } => chunks.push(Chunk::new_unchecked(
-                    buffer_line_no,
-                    ChunkVariant::Scope(Scope::new_unchecked(
-                        eval_scope!(content),
-                        ScopeVariant::Curly,
-                    )),
-                )),
-                Buffer::Command { depth, scopes, .. } if *depth != 0 => {
-                    return Err(crate::Error::new(
-                        1,
-                        crate::ErrorType::UnclosedArgument(scopes.last().unwrap().1),
-                    ))
-                }
-                Buffer::Command { label, scopes, .. } => {
-                    let mut arguments = Vec::with_capacity(scopes.len());
-
-                    for (content, variant, preceding) in scopes.iter_mut() {
-                        arguments.push((
-                            std::mem::take(preceding),
-                            Scope::new_unchecked(eval_scope!(content), *variant),
-                        ))
-                    }
 
-                    chunks.push(Chunk::new_unchecked(
-                        buffer_line_no,
-                        ChunkVariant::Command(Command::new_unchecked(
-                            std::mem::take(label),
-                            arguments,
-                        )),
-                    ))
+                self.count(at)?;
+                self.open(at.line, FrameKind::Argument(variant, prec))
+            }
+            EventKind::ArgumentClose => {
+                let frame = self.close();
+                let FrameKind::Argument(variant, prec) = frame.kind else {
+                    unreachable!("closing an argument which is not open")
+                };
+
+                match self.frame().chunks.last_mut().map(Chunk::variant_mut) {
+                    Some(ChunkVariant::Command(command)) => command
+                        .arguments_mut()
+                        .push((prec, Scope::new_unchecked(frame.chunks, variant))),
+                    _ => unreachable!("argument without a command"),
                 }
             }
+            EventKind::ScopeOpen(variant) => self.open(at.line, FrameKind::Scope(variant)),
+            EventKind::ScopeClose => {
+                let frame = self.close();
+                let FrameKind::Scope(variant) = frame.kind else {
+                    unreachable!("closing a scope which is not open")
+                };
 
-            Ok(())
+                self.push(
+                    frame.line,
+                    ChunkVariant::Scope(Scope::new_unchecked(frame.chunks, variant)),
+                    at,
+                )?
+            }
+            EventKind::Comment(_) => {}
+            EventKind::Error(error) => return Err(error),
         }
 
-        // map flush errors lines to its absolute line number
-        macro_rules! flush {
-            () => {
-                flush(&mut buffer, buffer_line_no, &mut chunks).map_err(|mut e| {
-                    e.line += buffer_line_no - 1;
-                    e
-                })?;
-
-                buffer_line_no = line_no;
-
-                let _ = buffer_line_no; // theres a really annoying warning, this removes it
-            };
-        }
+        Ok(())
+    }
 
-        for c in s.chars() {
-            match c {
-                '\n' => {
-                    comment = false;
-                    line_no += 1
-                }
-                '%' if !escaped => {
-                    comment = true;
-                    continue;
-                }
-                '\\' if !escaped => {
-                    escaped = true;
-                    continue;
-                }
-                _ if comment => continue,
-                _ => {}
-            }
+    fn finish(mut self) -> Document {
+        Document(self.close().chunks)
+    }
+}
 
-            match &mut buffer {
-                Buffer::Text { .. } => match c {
-                    c if escaped => {
-                        flush!();
-                        buffer = Buffer::command();
-                        buffer.push(c);
+impl Document {
+    /// Parse a document, failing with [`ErrorType::LimitExceeded`](crate::ErrorType::LimitExceeded)
+    /// when it goes over any of the limits
+    pub fn parse_with_limits(s: &str, limits: ParseLimits) -> Result<Self, crate::Error> {
+        Self::parse_with_catcodes(s, limits, Catcodes::default())
+    }
 
-                        if !c.is_ascii_alphabetic() {
-                            flush!();
-                            buffer = Buffer::text();
-                        }
-                    }
-                    c if ScopeVariant::is_opening(c) => {
-                        flush!();
-                        buffer = Buffer::scope(ScopeVariant::from_opening(c))
-                    }
-                    c => buffer.push(c),
-                },
-                Buffer::Scope { depth, variant, .. } => match c {
-                    c if *depth != 0 && escaped => {
-                        buffer.push('\\');
-                        buffer.push(c);
-                    }
-                    c if variant.open() == c => {
-                        *depth += 1;
-                        buffer.push(c)
-                    }
-                    c if variant.close() == c => {
-                        *depth -= 1;
-                        if *depth == 0 {
-                            flush!();
-                            buffer = Buffer::text()
-                        } else {
-                            buffer.push(c)
-                        }
-                    }
-                    c => buffer.push(c),
-                },
-                Buffer::Command {
-                    depth,
-                    scopes,
-                    trailing,
-                    ..
-                } => match c {
-                    c if *depth == 0 && ScopeVariant::is_opening(c) => {
-                        buffer.push_scope(ScopeVariant::from_opening(c))
-                    }
-                    c if *depth == 0 && c.is_whitespace() => trailing.push(c),
-                    c if *depth == 0 && escaped => {
-                        let trailing = trailing.clone();
-                        flush!();
-
-                        if !trailing.is_empty() {
-                            buffer = Buffer::text();
-                            buffer.push_str(&trailing);
-
-                            flush(
-                                &mut buffer,
-                                buffer_line_no
-                                    - trailing.chars().filter(|c| *c == '\n').count() as u32,
-                                &mut chunks,
-                            )?;
-                        }
+    /// Parse a document starting with the given catcodes, such as the source of a package
+    pub fn parse_with_catcodes(
+        s: &str,
+        limits: ParseLimits,
+        catcodes: Catcodes,
+    ) -> Result<Self, crate::Error> {
+        let mut lexer = Lexer::new(limits, catcodes);
+        let mut builder = Builder::new(limits);
 
-                        buffer_line_no = line_no;
+        for c in s.chars() {
+            lexer.step(c)?;
 
-                        buffer = Buffer::command();
-                        buffer.push(c);
-                        if !c.is_ascii_alphabetic() {
-                            flush!();
-                            buffer = Buffer::text();
-                        }
-                    }
-                    c if *depth != 0 && escaped => {
-                        buffer.push('\\');
-                        buffer.push(c);
-                    }
-                    c if *depth == 0 && !trailing.is_empty() => {
-                        let trailing = trailing.clone();
-                        flush!();
-                        buffer = Buffer::text();
-                        buffer.push_str(&trailing);
-                        buffer.push(c);
-
-                        buffer_line_no =
-                            line_no - trailing.chars().filter(|c| *c == '\n').count() as u32;
-                    }
-                    c if *depth != 0 && scopes.last().unwrap().1.open() == c => *depth += 1,
-                    c if *depth != 0 && scopes.last().unwrap().1.close() == c => *depth -= 1,
-                    c if *depth == 0 && ScopeVariant::is_closing(c) => {
-                        return Err(crate::Error::new(
-                            line_no,
-                            crate::ErrorType::UnexpectedClosing(ScopeVariant::from_closing(c)),
-                        ))
-                    }
-                    c if *depth == 0 && !buffer.no_scope() => {
-                        flush!();
-                        buffer = Buffer::text();
-                        buffer.push(c);
-                    }
-                    c => buffer.push(c),
-                },
+            while let Some(event) = lexer.pop() {
+                builder.event(event)?
             }
-
-            escaped = false;
         }
 
-        match &buffer {
-            // if its a command at the end of document,
-            // there may be unflushed Text in the trailing buffer
-            // so create an extra chunk if there are content in the trailing buffer
-            Buffer::Command { trailing, .. } if !trailing.is_empty() => {
-                let trailing = trailing.clone();
-                flush!();
-                buffer = Buffer::text();
-                buffer.push_str(&trailing);
-                flush!();
-            }
-            _ => {
-                flush!();
-            }
+        lexer.finish()?;
+
+        while let Some(event) = lexer.pop() {
+            builder.event(event)?
         }
 
-        Ok(Self(chunks))
+        Ok(builder.finish())
+    }
+}
+
+impl FromStr for Document {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_limits(s, ParseLimits::default())
     }
 }
@@ -0,0 +1,273 @@
+//! Editing operations which keep line numbers consistent
+//!
+//! Each operation renumbers the chunks of the container being edited. Editing a nested
+//! container changes the number of lines of its parents, so nested containers are edited
+//! within `edit_chunk` of the document, which renumbers the chunk holding them and the chunks
+//! after it.
+
+use std::ops::Range;
+
+use crate::{
+    traits::{Lines, Validate},
+    InternalError,
+};
+
+use super::{Chunk, ChunkVariant, Command, Document, Scope, ScopeVariant};
+
+impl Document {
+    /// Insert a variant at an index, shifting all chunks after it
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, variant: ChunkVariant) -> Result<(), InternalError> {
+        variant.validate()?;
+        self.insert_at_unchecked(index, variant);
+        Ok(())
+    }
+
+    /// Insert a variant at an index without checking
+    pub fn insert_at_unchecked(&mut self, index: usize, variant: ChunkVariant) {
+        insert(self.chunks_mut(), index, variant)
+    }
+
+    /// Remove and return the chunk at an index
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Chunk {
+        remove(self.chunks_mut(), index)
+    }
+
+    /// Replace the chunk at an index, returning the original chunk
+    ///
+    /// Panics if `index >= len`.
+    pub fn replace(&mut self, index: usize, variant: ChunkVariant) -> Result<Chunk, InternalError> {
+        variant.validate()?;
+        Ok(self.replace_unchecked(index, variant))
+    }
+
+    /// Replace the chunk at an index without checking
+    pub fn replace_unchecked(&mut self, index: usize, variant: ChunkVariant) -> Chunk {
+        replace(self.chunks_mut(), index, variant)
+    }
+
+    /// Edit the chunk at an index and anything nested in it, then renumber the chunk and
+    /// the chunks after it, returning what `edit` returns
+    ///
+    /// Panics if `index >= len`.
+    pub fn edit_chunk<T>(&mut self, index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+        edit_chunk(self.chunks_mut(), index, edit)
+    }
+
+    /// Surround a range of chunks with `\begin{label}` and `\end{label}`
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn wrap_in_environment(
+        &mut self,
+        range: Range<usize>,
+        label: &str,
+    ) -> Result<(), InternalError> {
+        ChunkVariant::Text(label.to_string()).validate()?;
+        self.wrap_in_environment_unchecked(range, label);
+        Ok(())
+    }
+
+    /// Surround a range of chunks with `\begin{label}` and `\end{label}` without checking
+    pub fn wrap_in_environment_unchecked(&mut self, range: Range<usize>, label: &str) {
+        wrap(self.chunks_mut(), range, label)
+    }
+
+    /// Recalculate the line numbers of every chunk in the document
+    pub fn renumber(&mut self) {
+        renumber(self.chunks_mut())
+    }
+}
+
+impl Scope {
+    /// Insert a variant at an index, shifting all chunks after it
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, variant: ChunkVariant) -> Result<(), InternalError> {
+        variant.validate()?;
+        self.insert_at_unchecked(index, variant);
+        Ok(())
+    }
+
+    /// Insert a variant at an index without checking
+    pub fn insert_at_unchecked(&mut self, index: usize, variant: ChunkVariant) {
+        insert(self.chunks_mut(), index, variant)
+    }
+
+    /// Remove and return the chunk at an index
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Chunk {
+        remove(self.chunks_mut(), index)
+    }
+
+    /// Replace the chunk at an index, returning the original chunk
+    ///
+    /// Panics if `index >= len`.
+    pub fn replace(&mut self, index: usize, variant: ChunkVariant) -> Result<Chunk, InternalError> {
+        variant.validate()?;
+        Ok(self.replace_unchecked(index, variant))
+    }
+
+    /// Replace the chunk at an index without checking
+    pub fn replace_unchecked(&mut self, index: usize, variant: ChunkVariant) -> Chunk {
+        replace(self.chunks_mut(), index, variant)
+    }
+
+    /// Edit the chunk at an index and anything nested in it, then renumber the chunk and
+    /// the chunks after it, returning what `edit` returns
+    ///
+    /// Panics if `index >= len`.
+    pub fn edit_chunk<T>(&mut self, index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+        edit_chunk(self.chunks_mut(), index, edit)
+    }
+
+    /// Surround a range of chunks with `\begin{label}` and `\end{label}`
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn wrap_in_environment(
+        &mut self,
+        range: Range<usize>,
+        label: &str,
+    ) -> Result<(), InternalError> {
+        ChunkVariant::Text(label.to_string()).validate()?;
+        self.wrap_in_environment_unchecked(range, label);
+        Ok(())
+    }
+
+    /// Surround a range of chunks with `\begin{label}` and `\end{label}` without checking
+    pub fn wrap_in_environment_unchecked(&mut self, range: Range<usize>, label: &str) {
+        wrap(self.chunks_mut(), range, label)
+    }
+
+    /// Recalculate the line numbers of every chunk in the scope
+    pub fn renumber(&mut self) {
+        renumber(self.chunks_mut())
+    }
+}
+
+impl Command {
+    /// Set the argument at an index, returning the original argument
+    ///
+    /// The argument is appended if `index == len`, panics if `index > len`.
+    pub fn set_argument(
+        &mut self,
+        index: usize,
+        argument: Scope,
+    ) -> Result<Option<Scope>, InternalError> {
+        argument.validate()?;
+        Ok(self.set_argument_unchecked(index, argument))
+    }
+
+    /// Set the argument at an index without checking
+    pub fn set_argument_unchecked(&mut self, index: usize, mut argument: Scope) -> Option<Scope> {
+        argument.renumber();
+        set_argument(self.arguments_mut(), index, argument)
+    }
+
+    /// Recalculate the line numbers of every chunk in the arguments
+    pub fn renumber(&mut self) {
+        self.arguments_mut()
+            .iter_mut()
+            .for_each(|(_, arg)| arg.renumber())
+    }
+}
+
+impl Chunk {
+    /// Recalculate the line numbers of every chunk nested in this chunk
+    pub fn renumber(&mut self) {
+        match self.variant_mut() {
+            ChunkVariant::Text(_) => {}
+            ChunkVariant::Command(c) => c.renumber(),
+            ChunkVariant::Scope(sc) => sc.renumber(),
+        }
+    }
+}
+
+fn insert(chunks: &mut Vec<Chunk>, index: usize, variant: ChunkVariant) {
+    let mut chunk = Chunk::new_unchecked(0, variant);
+    chunk.renumber();
+    chunks.insert(index, chunk);
+    renumber_from(chunks, index)
+}
+
+fn remove(chunks: &mut Vec<Chunk>, index: usize) -> Chunk {
+    let out = chunks.remove(index);
+    renumber_from(chunks, index);
+    out
+}
+
+fn replace(chunks: &mut [Chunk], index: usize, variant: ChunkVariant) -> Chunk {
+    let mut chunk = Chunk::new_unchecked(0, variant);
+    chunk.renumber();
+    let out = std::mem::replace(&mut chunks[index], chunk);
+    renumber_from(chunks, index);
+    out
+}
+
+fn edit_chunk<T>(chunks: &mut [Chunk], index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+    let out = edit(&mut chunks[index]);
+    chunks[index].renumber();
+    renumber_from(chunks, index);
+    out
+}
+
+fn wrap(chunks: &mut Vec<Chunk>, range: Range<usize>, label: &str) {
+    let marker = |command: &str| {
+        Chunk::new_unchecked(
+            0,
+            ChunkVariant::Command(Command::new_unchecked(
+                command.to_string(),
+                vec![(
+                    String::new(),
+                    Scope::new_unchecked(
+                        vec![Chunk::new_unchecked(
+                            1,
+                            ChunkVariant::Text(label.to_string()),
+                        )],
+                        ScopeVariant::Curly,
+                    ),
+                )],
+            )),
+        )
+    };
+
+    assert!(range.start <= range.end, "range starts after it ends");
+
+    chunks.insert(range.end, marker("end"));
+    chunks.insert(range.start, marker("begin"));
+    renumber_from(chunks, range.start)
+}
+
+fn set_argument(
+    arguments: &mut Vec<(String, Scope)>,
+    index: usize,
+    argument: Scope,
+) -> Option<Scope> {
+    if index == arguments.len() {
+        arguments.push((String::new(), argument));
+        None
+    } else {
+        Some(std::mem::replace(&mut arguments[index].1, argument))
+    }
+}
+
+// recalculates line numbers from an index, the chunks before it are assumed to be correct
+fn renumber_from(chunks: &mut [Chunk], index: usize) {
+    let mut line_no = match index.checked_sub(1).and_then(|last| chunks.get(last)) {
+        Some(last) => last.line_no() + last.lines() - 1,
+        None => 1,
+    };
+
+    for chunk in chunks[index..].iter_mut() {
+        *chunk.line_no_mut() = line_no;
+        line_no += chunk.lines() - 1;
+    }
+}
+
+fn renumber(chunks: &mut [Chunk]) {
+    chunks.iter_mut().for_each(Chunk::renumber);
+    renumber_from(chunks, 0)
+}
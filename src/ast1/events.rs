@@ -0,0 +1,661 @@
+//! Streaming events over the stage 1 tokens
+//!
+//! [`Events`] reads a document incrementally and yields its text, commands, arguments,
+//! scopes and comments in order without building a tree. Memory use is bounded by the
+//! nesting depth, long text and comments are split over several events, except in the
+//! branch after `\iffalse`.
+//!
+//! The events are the same ones [`Document`](super::Document) is built from, so they
+//! have the same structure and errors, with comments kept.
+//!
+//! The branch after `\iffalse`, which TeX skips without matching braces, is read whole
+//! before any of its events are given, so it is held in memory up to the `\else`, `\or` or
+//! `\fi` ending it, or the end of the input when it is unclosed. An input length
+//! limit set with [`Events::with_limits`] bounds it on untrusted input. When its braces
+//! do not balance it is given as text, so `\iffalse { \fi` does not fail with an unclosed
+//! scope.
+
+use std::{collections::VecDeque, io::Read};
+
+use crate::{ast2, Error, ErrorType, Limit, ParseLimits, Position};
+
+use super::{Catcodes, ScopeVariant};
+
+// text and comments are split into events of about this many bytes
+const SPLIT_LEN: usize = 4096;
+
+// bytes requested from the reader at a time
+const READ_LEN: usize = 8192;
+
+/// Content of an event
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub enum EventKind {
+    /// Plain text, including whitespace between a command and its arguments, or the
+    /// branch after `\iffalse` as it is written when its braces do not balance
+    Text(String),
+    /// `\label`, followed by the events of its arguments if it has any
+    CommandStart(String),
+    /// Opening parenthesis of a command argument
+    ArgumentOpen(ScopeVariant),
+    /// Closing parenthesis of the innermost open argument
+    ArgumentClose,
+    /// Opening parenthesis of a scope
+    ScopeOpen(ScopeVariant),
+    /// Closing parenthesis of the innermost open scope
+    ScopeClose,
+    /// Content of a comment, without the `%` and the line break ending it
+    Comment(String),
+    /// The input could not be read or parsed, no events follow
+    Error(Error),
+}
+
+/// An event and where it is in the input
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Event {
+    pub kind: EventKind,
+    /// Position of the first character
+    pub start: Position,
+    /// Position right after the last character
+    pub end: Position,
+}
+
+/// Iterator of events read from `R`
+pub struct Events<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    // the bytes read but not yet decoded are `buffer[start..end]`
+    start: usize,
+    end: usize,
+    eof: bool,
+    done: bool,
+    lexer: Lexer,
+}
+
+// Turns characters into events, shared by `Events` and the tree parser
+pub(super) struct Lexer {
+    // position of the next character
+    position: Position,
+    // bytes of input read
+    len: usize,
+    // position of the backslash escaping the next character
+    escape: Option<Position>,
+    comment: Option<(String, Position)>,
+    text: Option<(String, Position)>,
+    state: State,
+    stack: Vec<Open>,
+    ready: VecDeque<Event>,
+    limits: ParseLimits,
+    catcodes: Catcodes,
+    // commands still to be read as the names of a definition rather than as commands
+    names: usize,
+    // the last command was `\unless`, which inverts the test after it
+    unless: bool,
+    skip: Option<Skip>,
+}
+
+// the branch after `\iffalse`, read up to the `\else`, `\or` or `\fi` ending it without
+// giving any events
+struct Skip {
+    raw: String,
+    // position of the first character
+    start: Position,
+    // conditionals opened in the branch and not yet closed
+    depth: usize,
+    names: usize,
+    comment: bool,
+    // index in `raw` of the backslash of the command being read
+    label: Option<usize>,
+}
+
+// what the characters read are part of
+enum State {
+    Text,
+    // reading the label of a command starting at the backslash
+    Label(String, Position),
+    // after a command label or argument, which may be followed by more arguments
+    Command { trailing: bool },
+}
+
+// an open argument or scope
+struct Open {
+    variant: ScopeVariant,
+    start: Position,
+    argument: bool,
+}
+
+impl<R: Read> Events<R> {
+    /// Create an iterator reading from the start of a reader
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![0; READ_LEN],
+            start: 0,
+            end: 0,
+            eof: false,
+            done: false,
+            lexer: Lexer::new(ParseLimits::default(), Catcodes::default()),
+        }
+    }
+
+    /// Stop with an error when the input goes over the nesting depth or input size limits
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.lexer.limits = limits;
+        self
+    }
+
+    /// Set the catcodes the input starts with
+    pub fn with_catcodes(mut self, catcodes: Catcodes) -> Self {
+        self.lexer.catcodes = catcodes;
+        self
+    }
+
+    // read the next character, refilling the buffer from the reader when needed
+    fn read_char(&mut self) -> Result<Option<char>, ErrorType> {
+        loop {
+            let available = &self.buffer[self.start..self.end];
+
+            match available.first() {
+                Some(first) => {
+                    let width = match first {
+                        0x00..=0x7f => 1,
+                        0xc2..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        0xf0..=0xf4 => 4,
+                        _ => return Err(ErrorType::InvalidUtf8),
+                    };
+
+                    if width <= available.len() {
+                        let c = std::str::from_utf8(&available[..width])
+                            .map_err(|_| ErrorType::InvalidUtf8)?
+                            .chars()
+                            .next();
+                        self.start += width;
+                        return Ok(c);
+                    }
+
+                    if self.eof {
+                        return Err(ErrorType::InvalidUtf8);
+                    }
+                }
+                None if self.eof => return Ok(None),
+                None => {}
+            }
+
+            // move an incomplete character to the front and read after it
+            self.buffer.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+
+            let read = self.reader.read(&mut self.buffer[self.end..]);
+
+            match read {
+                Ok(0) => self.eof = true,
+                Ok(len) => self.end += len,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(ErrorType::Read(e.kind())),
+            }
+        }
+    }
+}
+
+impl Lexer {
+    pub(super) fn new(limits: ParseLimits, catcodes: Catcodes) -> Self {
+        Self {
+            position: Position::start(),
+            len: 0,
+            escape: None,
+            comment: None,
+            text: None,
+            state: State::Text,
+            stack: Vec::new(),
+            ready: VecDeque::new(),
+            limits,
+            catcodes,
+            names: 0,
+            unless: false,
+            skip: None,
+        }
+    }
+
+    // the next event ready to be given
+    pub(super) fn pop(&mut self) -> Option<Event> {
+        self.ready.pop_front()
+    }
+
+    fn emit(&mut self, kind: EventKind, start: Position, end: Position) {
+        self.ready.push_back(Event { kind, start, end })
+    }
+
+    fn push_text(&mut self, c: char, here: Position) {
+        let (text, _) = self.text.get_or_insert_with(|| (String::new(), here));
+        text.push(c);
+
+        if text.len() >= SPLIT_LEN {
+            self.flush_text()
+        }
+    }
+
+    fn flush_text(&mut self) {
+        if let Some((text, start)) = self.text.take() {
+            let end = start.after(&text);
+            self.emit(EventKind::Text(text), start, end)
+        }
+    }
+
+    fn flush_comment(&mut self, end: Position) {
+        if let Some((comment, start)) = self.comment.take() {
+            self.emit(EventKind::Comment(comment), start, end)
+        }
+    }
+
+    // the label of a command is complete, its arguments may follow, returns whether the
+    // command starts a branch TeX skips
+    fn end_label(&mut self) -> bool {
+        if let State::Label(label, start) =
+            std::mem::replace(&mut self.state, State::Command { trailing: false })
+        {
+            let end = start.after(&format!("\\{label}"));
+            self.catcodes.apply(&label);
+            let skips = self.command(&label);
+            self.emit(EventKind::CommandStart(label), start, end);
+            skips
+        } else {
+            unreachable!("not reading a label")
+        }
+    }
+
+    // follow the names of definitions and `\unless`, `\let\ifdraft\iffalse` and
+    // `\unless\iffalse` do not start a branch TeX skips
+    fn command(&mut self, label: &str) -> bool {
+        let name = self.names > 0;
+        self.names = match name {
+            true => self.names - 1,
+            false => definition_names(label),
+        };
+        let unless = std::mem::replace(&mut self.unless, label == "unless");
+
+        !name && !unless && label == "iffalse"
+    }
+
+    // flush everything read but not yet given as an event
+    pub(super) fn flush(&mut self) {
+        self.flush_comment(self.position);
+
+        if matches!(self.state, State::Label(..)) {
+            self.end_label();
+        }
+
+        self.flush_text()
+    }
+
+    pub(super) fn step(&mut self, c: char) -> Result<(), Error> {
+        if self.skip.is_some() {
+            return self.skip_char(c);
+        }
+
+        let here = self.position;
+        self.position.advance(c);
+        self.len += c.len_utf8();
+
+        if self
+            .limits
+            .max_input_len()
+            .is_some_and(|max| self.len > max)
+        {
+            return Err(Error::new(here, ErrorType::LimitExceeded(Limit::InputLen)));
+        }
+
+        if let Some((comment, _)) = self.comment.as_mut() {
+            if c != '\n' {
+                comment.push(c);
+
+                // the rest of a long comment continues in another event
+                if comment.len() >= SPLIT_LEN {
+                    self.flush_comment(self.position);
+                    self.comment = Some((String::new(), self.position));
+                }
+                return Ok(());
+            }
+
+            self.flush_comment(here)
+        }
+
+        let escape = self.escape.take();
+
+        // a backslash or `%` ending the label is the first character of the branch
+        if escape.is_none()
+            && matches!(c, '\\' | '%')
+            && matches!(self.state, State::Label(..))
+            && self.end_label()
+        {
+            return self.start_skip(c, here);
+        }
+
+        match c {
+            '%' if escape.is_none() => {
+                self.flush();
+                self.comment = Some((String::new(), here));
+                return Ok(());
+            }
+            '\\' if escape.is_none() => {
+                self.escape = Some(here);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if let State::Label(label, _) = &mut self.state {
+            if escape.is_none() && self.catcodes.is_letter(c) {
+                label.push(c);
+                return Ok(());
+            }
+
+            if self.end_label() {
+                return self.start_skip(c, here);
+            }
+        }
+
+        if let Some(start) = escape {
+            self.flush_text();
+
+            if self.catcodes.is_letter(c) {
+                self.state = State::Label(c.to_string(), start)
+            } else {
+                self.state = State::Text;
+                self.command(&c.to_string());
+                self.emit(EventKind::CommandStart(c.to_string()), start, self.position)
+            }
+
+            return Ok(());
+        }
+
+        if ScopeVariant::is_opening(c) {
+            let variant = ScopeVariant::from_opening(c);
+            let argument = matches!(self.state, State::Command { .. });
+
+            if self.stack.len() >= self.limits.max_depth() {
+                return Err(Error::new(here, ErrorType::LimitExceeded(Limit::Depth)));
+            }
+
+            self.flush_text();
+            self.emit(
+                if argument {
+                    EventKind::ArgumentOpen(variant)
+                } else {
+                    EventKind::ScopeOpen(variant)
+                },
+                here,
+                self.position,
+            );
+            self.stack.push(Open {
+                variant,
+                start: here,
+                argument,
+            });
+            if !argument {
+                self.names = 0;
+                self.unless = false;
+            }
+            self.state = State::Text;
+            return Ok(());
+        }
+
+        if ScopeVariant::is_closing(c) {
+            let variant = ScopeVariant::from_closing(c);
+
+            if let Some(index) = self.stack.iter().rposition(|open| open.variant == variant) {
+                // everything opened inside must be closed first
+                if let Some(inner) = self.stack.get(index + 1) {
+                    return Err(unclosed(inner).with_related(here));
+                }
+
+                let open = self.stack.pop().unwrap();
+
+                self.flush_text();
+                if open.argument {
+                    self.emit(EventKind::ArgumentClose, here, self.position);
+                    self.state = State::Command { trailing: false };
+                } else {
+                    self.emit(EventKind::ScopeClose, here, self.position);
+                    self.state = State::Text;
+                }
+
+                return Ok(());
+            }
+
+            if let State::Command { trailing: false } = self.state {
+                return Err(Error::new(here, ErrorType::UnexpectedClosing(variant)));
+            }
+        }
+
+        if !c.is_whitespace() {
+            self.unless = false;
+
+            if !matches!(c, '=' | '*') {
+                self.names = 0
+            }
+        }
+
+        // commands take arguments across whitespace
+        self.state = match self.state {
+            State::Command { .. } if c.is_whitespace() => State::Command { trailing: true },
+            _ => State::Text,
+        };
+
+        self.push_text(c, here);
+        Ok(())
+    }
+
+    // start a branch TeX skips with the character ending the label of `\iffalse`
+    fn start_skip(&mut self, c: char, here: Position) -> Result<(), Error> {
+        self.position = here;
+        self.len -= c.len_utf8();
+        self.skip = Some(Skip::new(here));
+        self.skip_char(c)
+    }
+
+    // read a character of a branch TeX skips, the branch is read again as usual when it
+    // ends if its braces balance
+    fn skip_char(&mut self, c: char) -> Result<(), Error> {
+        let skip = self.skip.as_mut().unwrap();
+        let index = skip.raw.len();
+        skip.raw.push(c);
+
+        if self
+            .limits
+            .max_input_len()
+            .is_some_and(|max| self.len + skip.raw.len() > max)
+        {
+            let here = skip.start.after(&skip.raw[..index]);
+            return Err(Error::new(here, ErrorType::LimitExceeded(Limit::InputLen)));
+        }
+
+        if skip.comment {
+            skip.comment = c != '\n';
+            return Ok(());
+        }
+
+        if let Some(label) = skip.label {
+            if self.catcodes.is_letter(c) {
+                return Ok(());
+            }
+
+            // an escaped character, which is not a keyword
+            if label + 1 == index {
+                skip.label = None;
+                skip.names = skip.names.saturating_sub(1);
+                return Ok(());
+            }
+
+            if let Some(end) = self.end_skip_label() {
+                return self.end_skip(Some(end));
+            }
+        }
+
+        let skip = self.skip.as_mut().unwrap();
+        match c {
+            '\\' => skip.label = Some(index),
+            '%' => skip.comment = true,
+            '=' | '*' => {}
+            c if c.is_whitespace() => {}
+            _ => skip.names = 0,
+        }
+
+        Ok(())
+    }
+
+    // the command being read in a skipped branch is complete, returns where the branch
+    // ends if it is the keyword ending it
+    fn end_skip_label(&mut self) -> Option<usize> {
+        let skip = self.skip.as_mut().unwrap();
+        let start = skip.label.take()?;
+        let label = skip.raw[start + 1..]
+            .split(|c| !self.catcodes.is_letter(c))
+            .next()
+            .unwrap_or_default();
+
+        if skip.names > 0 {
+            skip.names -= 1;
+            return None;
+        }
+        skip.names = definition_names(label);
+
+        match label {
+            "else" | "or" | "fi" if skip.depth == 0 => return Some(start),
+            "fi" => skip.depth -= 1,
+            _ if ast2::is_conditional(label) => skip.depth += 1,
+            _ => {}
+        }
+
+        None
+    }
+
+    // the skipped branch has ended before `end`, or with the input when there is none
+    fn end_skip(&mut self, end: Option<usize>) -> Result<(), Error> {
+        let raw = self.skip.take().unwrap().raw;
+        let (branch, rest) = raw.split_at(end.unwrap_or(raw.len()));
+
+        if end.is_none() || self.balanced(branch) {
+            return raw.chars().try_for_each(|c| self.step(c));
+        }
+
+        // the branch is text, with its comments as usual
+        let mut escape = false;
+        for c in branch.chars() {
+            let here = self.position;
+            self.position.advance(c);
+            self.len += c.len_utf8();
+
+            if let Some((comment, _)) = self.comment.as_mut() {
+                if c != '\n' {
+                    comment.push(c);
+                    continue;
+                }
+                self.flush_comment(here);
+            } else if c == '%' && !escape {
+                self.flush_text();
+                self.comment = Some((String::new(), here));
+                continue;
+            }
+
+            escape = c == '\\' && !escape;
+            self.push_text(c, here);
+        }
+        debug_assert!(self.comment.is_none(), "comment before the end of a branch");
+        self.flush_text();
+        self.state = State::Text;
+        self.names = 0;
+
+        rest.chars().try_for_each(|c| self.step(c))
+    }
+
+    // whether the braces of a skipped branch balance, so it can be read as usual
+    fn balanced(&self, branch: &str) -> bool {
+        let mut lexer = Self::new(self.limits, self.catcodes);
+        lexer.state = State::Command { trailing: false };
+
+        // a closing brace which matches nothing is read as text
+        branch.chars().try_for_each(|c| lexer.step(c)).is_ok()
+            && lexer.finish().is_ok()
+            && !lexer
+                .ready
+                .iter()
+                .any(|event| matches!(&event.kind, EventKind::Text(s) if s.contains('}')))
+    }
+
+    // the input has ended
+    pub(super) fn finish(&mut self) -> Result<(), Error> {
+        while self.skip.is_some() {
+            let end = self.end_skip_label();
+            self.end_skip(end)?;
+        }
+
+        self.flush();
+
+        match self.stack.first() {
+            Some(open) => Err(unclosed(open).with_related(self.position)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Skip {
+    fn new(start: Position) -> Self {
+        Self {
+            raw: String::new(),
+            start,
+            depth: 0,
+            names: 0,
+            comment: false,
+            label: None,
+        }
+    }
+}
+
+// how many names follow a command defining them
+fn definition_names(label: &str) -> usize {
+    ast2::DEFINITIONS
+        .iter()
+        .find(|(definition, _)| *definition == label)
+        .map_or(0, |(_, names)| *names)
+}
+
+fn unclosed(open: &Open) -> Error {
+    Error::new(
+        open.start,
+        if open.argument {
+            ErrorType::UnclosedArgument(open.variant)
+        } else {
+            ErrorType::UnclosedScope(open.variant)
+        },
+    )
+}
+
+impl<R: Read> Iterator for Events<R> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.lexer.ready.is_empty() && !self.done {
+            let result = match self.read_char() {
+                Ok(Some(c)) => self.lexer.step(c),
+                Ok(None) => {
+                    self.done = true;
+                    self.lexer.finish()
+                }
+                Err(kind) => Err(Error::new(self.lexer.position, kind)),
+            };
+
+            if let Err(error) = result {
+                self.done = true;
+                self.lexer.flush();
+
+                let position = error.position;
+                self.lexer.emit(EventKind::Error(error), position, position);
+            }
+        }
+
+        self.lexer.pop()
+    }
+}
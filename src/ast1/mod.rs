@@ -11,25 +11,39 @@
 //! The stage 1 AST can be reconstructed a one-to-one copy
 //! of the original document with no loss of information.
 //!
-//! ## Peformance
+//! Command names are made of letters as given by [`Catcodes`], which follows
+//! `\makeatletter` and `\ExplSyntaxOn` while parsing.
+//!
+//! [`Events`] reads the same tokens as a stream of events from any reader, for linear scans
+//! over documents too large to hold as a tree.
 //!
-//! Peformance is highly affected by maximum scope depth.
+//! ## Peformance
 //!
-//! - Worst case O(n<sup>2</sup>) when the document looks like `{{{{{{{{}}}}}}}}`
-//! - Average case O(n) if the scoping isn't crazy
+//! Parsing is O(n), nested scopes are kept on a stack instead of recursing. Use
+//! [`Document::parse_with_limits`] with [`ParseLimits`](crate::ParseLimits) to bound
+//! the resources used by untrusted input.
 
+mod catcodes;
 mod chunk;
 mod chunkvariant;
 mod command;
 mod document;
+mod edit;
+mod events;
 mod into_chunks;
+mod query;
 mod scope;
 mod scopevariant;
+pub mod visit;
 
+pub use catcodes::Catcodes;
 pub use chunk::Chunk;
 pub use chunkvariant::ChunkVariant;
 pub use command::Command;
 pub use document::Document;
+pub use events::{Event, EventKind, Events};
 pub use into_chunks::IntoChunks;
+pub use query::{Match, Node, Query};
 pub use scope::Scope;
 pub use scopevariant::ScopeVariant;
+pub use visit::{Fold, Visit, VisitMut};
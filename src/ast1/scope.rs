@@ -37,6 +37,11 @@ impl Scope {
         &self.chunks
     }
 
+    /// Returns all mutable chunks within
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.chunks
+    }
+
     /// Returns all owned chunks within
     pub fn chunks_owned(self) -> Vec<Chunk> {
         self.chunks
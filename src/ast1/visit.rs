@@ -0,0 +1,221 @@
+//! Traversal of the stage 1 AST
+//!
+//! Every method of `Visit`, `VisitMut` and `Fold` defaults to recursing into all children
+//! of the node, including command arguments, so implementors only need to
+//! override the methods for the nodes they care about. The `walk_*` and `fold_*` functions
+//! are the default behaviours, call them from an overridden method to keep recursing.
+//!
+//! Note that `VisitMut` and `Fold` do not update line numbers, changing the number of
+//! lines in a node leaves the tree with inconsistent line numbers until `Document::renumber`
+//! is called.
+
+use super::{Chunk, ChunkVariant, Command, Document, Scope};
+
+/// Walks a stage 1 AST by reference
+pub trait Visit {
+    /// Visit a whole document
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document)
+    }
+
+    /// Visit a chunk
+    fn visit_chunk(&mut self, chunk: &Chunk) {
+        walk_chunk(self, chunk)
+    }
+
+    /// Visit a block of text
+    fn visit_text(&mut self, _text: &String) {}
+
+    /// Visit a command
+    fn visit_command(&mut self, command: &Command) {
+        walk_command(self, command)
+    }
+
+    /// Visit a scope which is not an argument
+    fn visit_scope(&mut self, scope: &Scope) {
+        walk_scope(self, scope)
+    }
+
+    /// Visit an argument of a command
+    fn visit_argument(&mut self, _prec: &String, argument: &Scope) {
+        walk_scope(self, argument)
+    }
+}
+
+/// Visit every chunk of the document
+pub fn walk_document<V: Visit + ?Sized>(visitor: &mut V, document: &Document) {
+    walk_chunks(visitor, document.chunks())
+}
+
+/// Visit every chunk in order
+pub fn walk_chunks<V: Visit + ?Sized>(visitor: &mut V, chunks: &[Chunk]) {
+    chunks.iter().for_each(|chunk| visitor.visit_chunk(chunk))
+}
+
+/// Visit the variant of a chunk
+pub fn walk_chunk<V: Visit + ?Sized>(visitor: &mut V, chunk: &Chunk) {
+    match chunk.variant() {
+        ChunkVariant::Text(s) => visitor.visit_text(s),
+        ChunkVariant::Command(c) => visitor.visit_command(c),
+        ChunkVariant::Scope(sc) => visitor.visit_scope(sc),
+    }
+}
+
+/// Visit the arguments of a command
+pub fn walk_command<V: Visit + ?Sized>(visitor: &mut V, command: &Command) {
+    for (prec, arg) in command.arguments() {
+        visitor.visit_argument(prec, arg)
+    }
+}
+
+/// Visit the content of a scope
+pub fn walk_scope<V: Visit + ?Sized>(visitor: &mut V, scope: &Scope) {
+    walk_chunks(visitor, scope.chunks())
+}
+
+/// Walks a stage 1 AST by mutable reference
+pub trait VisitMut {
+    /// Visit a whole document
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document)
+    }
+
+    /// Visit a chunk
+    fn visit_chunk_mut(&mut self, chunk: &mut Chunk) {
+        walk_chunk_mut(self, chunk)
+    }
+
+    /// Visit a block of text
+    fn visit_text_mut(&mut self, _text: &mut String) {}
+
+    /// Visit a command
+    fn visit_command_mut(&mut self, command: &mut Command) {
+        walk_command_mut(self, command)
+    }
+
+    /// Visit a scope which is not an argument
+    fn visit_scope_mut(&mut self, scope: &mut Scope) {
+        walk_scope_mut(self, scope)
+    }
+
+    /// Visit an argument of a command
+    fn visit_argument_mut(&mut self, _prec: &mut String, argument: &mut Scope) {
+        walk_scope_mut(self, argument)
+    }
+}
+
+/// Visit every chunk of the document
+pub fn walk_document_mut<V: VisitMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    walk_chunks_mut(visitor, document.chunks_mut())
+}
+
+/// Visit every chunk in order
+pub fn walk_chunks_mut<V: VisitMut + ?Sized>(visitor: &mut V, chunks: &mut [Chunk]) {
+    chunks
+        .iter_mut()
+        .for_each(|chunk| visitor.visit_chunk_mut(chunk))
+}
+
+/// Visit the variant of a chunk
+pub fn walk_chunk_mut<V: VisitMut + ?Sized>(visitor: &mut V, chunk: &mut Chunk) {
+    match chunk.variant_mut() {
+        ChunkVariant::Text(s) => visitor.visit_text_mut(s),
+        ChunkVariant::Command(c) => visitor.visit_command_mut(c),
+        ChunkVariant::Scope(sc) => visitor.visit_scope_mut(sc),
+    }
+}
+
+/// Visit the arguments of a command
+pub fn walk_command_mut<V: VisitMut + ?Sized>(visitor: &mut V, command: &mut Command) {
+    for (prec, arg) in command.arguments_mut() {
+        visitor.visit_argument_mut(prec, arg)
+    }
+}
+
+/// Visit the content of a scope
+pub fn walk_scope_mut<V: VisitMut + ?Sized>(visitor: &mut V, scope: &mut Scope) {
+    walk_chunks_mut(visitor, scope.chunks_mut())
+}
+
+/// Transforms a stage 1 AST by value
+pub trait Fold {
+    /// Fold a whole document
+    fn fold_document(&mut self, document: Document) -> Document {
+        fold_document(self, document)
+    }
+
+    /// Fold a chunk
+    fn fold_chunk(&mut self, chunk: Chunk) -> Chunk {
+        fold_chunk(self, chunk)
+    }
+
+    /// Fold a block of text
+    fn fold_text(&mut self, text: String) -> String {
+        text
+    }
+
+    /// Fold a command
+    fn fold_command(&mut self, command: Command) -> Command {
+        fold_command(self, command)
+    }
+
+    /// Fold a scope which is not an argument
+    fn fold_scope(&mut self, scope: Scope) -> Scope {
+        fold_scope(self, scope)
+    }
+
+    /// Fold an argument of a command
+    fn fold_argument(&mut self, prec: String, argument: Scope) -> (String, Scope) {
+        (prec, fold_scope(self, argument))
+    }
+}
+
+/// Fold every chunk of the document
+pub fn fold_document<F: Fold + ?Sized>(folder: &mut F, document: Document) -> Document {
+    Document::new_unchecked(fold_chunks(folder, document.chunks_owned()))
+}
+
+/// Fold every chunk in order
+pub fn fold_chunks<F: Fold + ?Sized>(folder: &mut F, chunks: Vec<Chunk>) -> Vec<Chunk> {
+    chunks
+        .into_iter()
+        .map(|chunk| folder.fold_chunk(chunk))
+        .collect()
+}
+
+/// Fold every argument in order
+pub fn fold_arguments<F: Fold + ?Sized>(
+    folder: &mut F,
+    arguments: Vec<(String, Scope)>,
+) -> Vec<(String, Scope)> {
+    arguments
+        .into_iter()
+        .map(|(prec, arg)| folder.fold_argument(prec, arg))
+        .collect()
+}
+
+/// Fold the variant of a chunk
+pub fn fold_chunk<F: Fold + ?Sized>(folder: &mut F, chunk: Chunk) -> Chunk {
+    let (line_no, variant) = chunk.decompose();
+
+    Chunk::new_unchecked(
+        line_no,
+        match variant {
+            ChunkVariant::Text(s) => ChunkVariant::Text(folder.fold_text(s)),
+            ChunkVariant::Command(c) => ChunkVariant::Command(folder.fold_command(c)),
+            ChunkVariant::Scope(sc) => ChunkVariant::Scope(folder.fold_scope(sc)),
+        },
+    )
+}
+
+/// Fold the arguments of a command
+pub fn fold_command<F: Fold + ?Sized>(folder: &mut F, command: Command) -> Command {
+    let (label, arguments) = command.decompose();
+    Command::new_unchecked(label, fold_arguments(folder, arguments))
+}
+
+/// Fold the content of a scope
+pub fn fold_scope<F: Fold + ?Sized>(folder: &mut F, scope: Scope) -> Scope {
+    let (chunks, variant) = scope.decompose();
+    Scope::new_unchecked(fold_chunks(folder, chunks), variant)
+}
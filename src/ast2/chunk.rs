@@ -33,8 +33,29 @@ impl ast1::IntoChunks for Chunk {
             self.line_no,
             match self.variant {
                 ChunkVariant::Text(s) => ast1::ChunkVariant::Text(s),
+                ChunkVariant::Parameter(p) => ast1::ChunkVariant::Text(p.to_string()),
                 ChunkVariant::Scope(sc) => ast1::ChunkVariant::Scope(sc.to_ast1_scope()),
                 ChunkVariant::Command(c) => ast1::ChunkVariant::Command(c.into()),
+                ChunkVariant::MacroDefinition(definition) => {
+                    return definition
+                        .into_chunks()
+                        .into_iter()
+                        .map(|mut chunk| {
+                            *chunk.line_no_mut() += self.line_no - 1;
+                            chunk
+                        })
+                        .collect()
+                }
+                ChunkVariant::Conditional(conditional) => {
+                    return conditional
+                        .into_chunks()
+                        .into_iter()
+                        .map(|mut chunk| {
+                            *chunk.line_no_mut() += self.line_no - 1;
+                            chunk
+                        })
+                        .collect()
+                }
                 ChunkVariant::Environment(env) => {
                     let (label, args, content, prec_begin, prec_end) = env.decompose();
 
@@ -42,10 +63,10 @@ impl ast1::IntoChunks for Chunk {
                         s.chars().filter(|c| c == &'\n').count()
                     }
 
-                    let end_line_no = self.line_no
+                    // the content starts in the line the arguments end
+                    let content_line_no = self.line_no
                         + (lines(&prec_begin)
                             + label.chars().filter(|c| c == &'\n').count()
-                            + lines(&Document::new_unchecked(content.clone()).to_string())
                             + args
                                 .iter()
                                 .map(|(prec, arg)| {
@@ -59,6 +80,8 @@ impl ast1::IntoChunks for Chunk {
                                         )
                                 })
                                 .sum::<usize>()) as u32;
+                    let end_line_no = content_line_no
+                        + lines(&Document::new_unchecked(content.clone()).to_string()) as u32;
 
                     let label = ast1::Scope::new_unchecked(
                         ast1::Document::from_str(&label).unwrap().chunks_owned(),
@@ -80,7 +103,7 @@ impl ast1::IntoChunks for Chunk {
                     )]
                     .into_iter()
                     .chain(content.into_iter().flat_map(|mut chunk| {
-                        chunk.line_no += self.line_no - 1;
+                        chunk.line_no += content_line_no - 1;
                         chunk.into_chunks()
                     }))
                     .chain(
@@ -106,6 +129,18 @@ impl Validate for Chunk {
     }
 }
 
+impl Chunk {
+    // validate a chunk in a group, where a `\begin` or `\end` without its pair is a plain command
+    pub(crate) fn validate_in_group(&self) -> Result<(), crate::InternalError> {
+        match self.variant() {
+            ChunkVariant::Command(c) if matches!(c.label().as_str(), "begin" | "end") => {
+                c.arguments().iter().try_for_each(|(_, arg)| arg.validate())
+            }
+            _ => self.validate(),
+        }
+    }
+}
+
 impl Lines for Chunk {
     fn lines(&self) -> u32 {
         self.variant.lines()
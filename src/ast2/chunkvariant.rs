@@ -5,7 +5,7 @@ use crate::{
     traits::{Lines, Validate},
 };
 
-use super::{Command, Environment, Scope};
+use super::{Command, Conditional, Environment, MacroDefinition, Parameter, Scope};
 
 /// Different types of things a chunk can be
 #[derive(Clone)]
@@ -20,6 +20,12 @@ pub enum ChunkVariant {
     Scope(Scope),
     /// An environmentnis a labelled scope with options
     Environment(Environment),
+    /// An `\if...` test with its branches
+    Conditional(Conditional),
+    /// A parameter token such as `#1`
+    Parameter(Parameter),
+    /// A `\def` and its parameter text and body
+    MacroDefinition(MacroDefinition),
 }
 
 impl Display for ChunkVariant {
@@ -29,6 +35,9 @@ impl Display for ChunkVariant {
             Self::Command(c) => c.fmt(f),
             Self::Scope(s) => s.fmt(f),
             Self::Environment(e) => e.fmt(f),
+            Self::Conditional(c) => c.fmt(f),
+            Self::Parameter(p) => p.fmt(f),
+            Self::MacroDefinition(d) => d.fmt(f),
         }
     }
 }
@@ -49,6 +58,9 @@ impl Validate for ChunkVariant {
             Self::Command(c) => return c.validate(),
             Self::Scope(sc) => return sc.validate(),
             Self::Environment(e) => return e.validate(),
+            Self::Conditional(c) => return c.validate(),
+            Self::Parameter(p) => return p.validate(),
+            Self::MacroDefinition(d) => return d.validate(),
         }
 
         Ok(())
@@ -62,6 +74,9 @@ impl Lines for ChunkVariant {
             Self::Command(c) => c.lines(),
             Self::Scope(sc) => sc.lines(),
             Self::Environment(e) => e.lines(),
+            Self::Conditional(c) => c.lines(),
+            Self::Parameter(p) => p.lines(),
+            Self::MacroDefinition(d) => d.lines(),
         }
     }
 }
@@ -4,10 +4,10 @@ use std::fmt::Write;
 use crate::{
     ast1, ast3,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
-use super::Scope;
+use super::{fold, Scope};
 
 /// Represents a command and its arguments
 #[derive(Clone)]
@@ -60,6 +60,11 @@ impl Command {
         &self.arguments
     }
 
+    /// Return mutable argument of the command
+    pub fn arguments_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.arguments
+    }
+
     /// Return owned label of the command
     pub fn label_owned(self) -> String {
         self.label
@@ -79,13 +84,46 @@ impl Command {
 impl TryFrom<crate::ast1::Command> for Command {
     type Error = crate::Error;
 
+    /// Error positions are relative to the backslash of the command
     fn try_from(value: crate::ast1::Command) -> Result<Self, Self::Error> {
+        let (label, arguments_o) = Self::from_ast1(value)?.decompose();
+        let mut arguments = Vec::with_capacity(arguments_o.len());
+
+        for (prec, arg) in arguments_o {
+            let (chunks, variant) = arg.decompose();
+            let chunks = fold::fold(chunks, 1).map_err(|e| {
+                e.offset(Position::new(1, 2)).offset(
+                    Position::start()
+                        .after(&format!("\\{label}"))
+                        .after_arguments(&arguments)
+                        .after(&prec),
+                )
+            })?;
+            arguments.push((prec, Scope::new_unchecked(chunks, variant)));
+        }
+
+        Ok(Self { label, arguments })
+    }
+}
+
+impl Command {
+    // folds environments, conditionals are folded once the whole tree is built
+    pub(super) fn from_ast1(value: crate::ast1::Command) -> Result<Self, crate::Error> {
         let (label, arguments_o) = value.decompose();
 
         let mut arguments = Vec::with_capacity(arguments_o.len());
 
         for (prec, scope) in arguments_o {
-            arguments.push((prec, scope.try_into()?))
+            // the position of the argument is only worked out for an error
+            let scope = Scope::from_ast1(scope).map_err(|e: crate::Error| {
+                e.offset(
+                    Position::start()
+                        .after(&format!("\\{label}"))
+                        .after_arguments(&arguments)
+                        .after(&prec),
+                )
+            })?;
+            arguments.push((prec, scope));
         }
 
         Ok(Self { label, arguments })
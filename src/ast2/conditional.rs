@@ -0,0 +1,960 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use crate::{
+    ast1, ast3,
+    traits::{Lines, Validate},
+    ErrorType, InternalError, Limit, ParseLimits, Position,
+};
+
+use super::{fold::explode, Chunk, ChunkVariant, Command, IntoChunks as IntoChunks2};
+
+/// Commands starting with `if` which take arguments instead of ending with `\fi`
+pub const NOT_CONDITIONALS: [&str; 47] = [
+    "ifthenelse",
+    "iflanguage",
+    "ifbool",
+    "iftoggle",
+    "ifdef",
+    "ifundef",
+    "ifcsdef",
+    "ifcsundef",
+    "ifdefmacro",
+    "ifcsmacro",
+    "ifdefparam",
+    "ifcsparam",
+    "ifdefprefix",
+    "ifcsprefix",
+    "ifdefprotected",
+    "ifcsprotected",
+    "ifdefcounter",
+    "ifcscounter",
+    "ifdeflength",
+    "ifcslength",
+    "ifdefdimen",
+    "ifcsdimen",
+    "ifdefempty",
+    "ifcsempty",
+    "ifdefvoid",
+    "ifcsvoid",
+    "ifdefequal",
+    "ifcsequal",
+    "ifdefstring",
+    "ifcsstring",
+    "ifdefstrequal",
+    "ifcsstrequal",
+    "ifstrequal",
+    "ifstrempty",
+    "ifblank",
+    "ifnumcomp",
+    "ifnumequal",
+    "ifnumgreater",
+    "ifnumless",
+    "ifnumodd",
+    "ifdimcomp",
+    "ifdimequal",
+    "ifdimgreater",
+    "ifdimless",
+    "ifboolexpr",
+    "ifinlist",
+    "ifoot",
+];
+
+// commands followed by the names they define, and how many names follow
+pub(crate) const DEFINITIONS: [(&str, usize); 11] = [
+    ("def", 1),
+    ("gdef", 1),
+    ("edef", 1),
+    ("xdef", 1),
+    ("let", 2),
+    ("futurelet", 3),
+    ("newif", 1),
+    ("newcommand", 1),
+    ("renewcommand", 1),
+    ("providecommand", 1),
+    ("DeclareRobustCommand", 1),
+];
+
+/// A conditional is an `\if…` test and its branches up to the matching `\fi`
+///
+/// The content is read when the test is true, or for case 0 of `\ifcase`. Each `\or` starts
+/// another case, and `\else` starts the branch read when no other branch is.
+/// `\unless` right before the test is part of the conditional, and inverts the test.
+///
+/// Conditionals are folded once the tree of scopes and environments is built, so a branch
+/// can only hold whole scopes and environments. The exception is the content of `\iffalse`,
+/// which TeX skips without matching braces: when its braces do not balance it is kept as the
+/// text it is written as, see [`Conditional::is_raw`].
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Conditional {
+    // whitespace after `\unless`, when the test is inverted by it
+    unless: Option<String>,
+    label: String,
+    condition: Vec<Chunk>,
+    content: Vec<Chunk>,
+    cases: Vec<Vec<Chunk>>,
+    otherwise: Option<Vec<Chunk>>,
+}
+
+impl Display for Conditional {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}{}{}",
+            self.opening(),
+            concat(&self.condition),
+            concat(&self.content)
+        ))?;
+
+        for case in self.cases.iter() {
+            f.write_fmt(format_args!("\\or{}", concat(case)))?;
+        }
+
+        if let Some(otherwise) = &self.otherwise {
+            f.write_fmt(format_args!("\\else{}", concat(otherwise)))?;
+        }
+
+        f.write_str("\\fi")
+    }
+}
+
+impl Conditional {
+    /// Constructs a new Conditional
+    pub fn new(
+        label: String,
+        condition: Vec<Chunk>,
+        content: Vec<Chunk>,
+        cases: Vec<Vec<Chunk>>,
+        otherwise: Option<Vec<Chunk>>,
+    ) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(label, condition, content, cases, otherwise);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new Conditional without checking
+    pub fn new_unchecked(
+        label: String,
+        condition: Vec<Chunk>,
+        content: Vec<Chunk>,
+        cases: Vec<Vec<Chunk>>,
+        otherwise: Option<Vec<Chunk>>,
+    ) -> Self {
+        Self {
+            unless: None,
+            label,
+            condition,
+            content,
+            cases,
+            otherwise,
+        }
+    }
+
+    /// Invert the test with `\unless` and the whitespace after it, or remove it with `None`
+    pub fn with_unless(mut self, unless: Option<String>) -> Self {
+        self.unless = unless;
+        self
+    }
+
+    /// Returns the whitespace between `\unless` and the test, if the test is inverted
+    pub fn unless(&self) -> Option<&String> {
+        self.unless.as_ref()
+    }
+
+    /// Returns the label of the test, such as `ifx`
+    pub fn label(&self) -> &String {
+        &self.label
+    }
+
+    /// Returns the test as it is written, such as `\ifx` or `\unless\ifx`
+    pub fn opening(&self) -> String {
+        match &self.unless {
+            Some(unless) => format!("\\unless{unless}\\{}", self.label),
+            None => format!("\\{}", self.label),
+        }
+    }
+
+    /// Returns the chunks read by the test, such as `\a\b` of `\ifx\a\b`
+    pub fn condition(&self) -> &Vec<Chunk> {
+        &self.condition
+    }
+
+    /// Returns the mutable chunks read by the test
+    pub fn condition_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.condition
+    }
+
+    /// Returns the chunks read when the test is true
+    pub fn content(&self) -> &Vec<Chunk> {
+        &self.content
+    }
+
+    /// Returns the mutable chunks read when the test is true
+    pub fn content_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.content
+    }
+
+    /// Returns the chunks after each `\or`
+    pub fn cases(&self) -> &Vec<Vec<Chunk>> {
+        &self.cases
+    }
+
+    /// Returns the mutable chunks after each `\or`
+    pub fn cases_mut(&mut self) -> &mut Vec<Vec<Chunk>> {
+        &mut self.cases
+    }
+
+    /// Returns the chunks after `\else`, if there is one
+    pub fn otherwise(&self) -> Option<&Vec<Chunk>> {
+        self.otherwise.as_ref()
+    }
+
+    /// Returns the mutable chunks after `\else`, if there is one
+    pub fn otherwise_mut(&mut self) -> Option<&mut Vec<Chunk>> {
+        self.otherwise.as_mut()
+    }
+
+    /// Returns the content of `Conditional`
+    #[allow(clippy::type_complexity)]
+    pub fn decompose(
+        self,
+    ) -> (
+        String,
+        Vec<Chunk>,
+        Vec<Chunk>,
+        Vec<Vec<Chunk>>,
+        Option<Vec<Chunk>>,
+    ) {
+        (
+            self.label,
+            self.condition,
+            self.content,
+            self.cases,
+            self.otherwise,
+        )
+    }
+
+    /// Whether the content is text as it is written, which is how the content of `\iffalse` is
+    /// kept when its braces do not balance, as TeX skips it without matching them
+    pub fn is_raw(&self) -> bool {
+        self.label == "iffalse"
+            && self.unless.is_none()
+            && self
+                .content
+                .iter()
+                .all(|chunk| matches!(chunk.variant(), ChunkVariant::Text(_)))
+            && self.content.iter().any(|chunk| match chunk.variant() {
+                ChunkVariant::Text(s) => s.chars().any(|c| {
+                    matches!(c, '\\' | '%')
+                        || ast1::ScopeVariant::is_opening(c)
+                        || ast1::ScopeVariant::is_closing(c)
+                }),
+                _ => false,
+            })
+    }
+
+    /// Returns the condition and every branch in order
+    pub fn parts(&self) -> impl Iterator<Item = &Vec<Chunk>> {
+        [&self.condition, &self.content]
+            .into_iter()
+            .chain(self.cases.iter())
+            .chain(self.otherwise.iter())
+    }
+
+    /// Returns the mutable condition and every branch in order
+    pub fn parts_mut(&mut self) -> impl Iterator<Item = &mut Vec<Chunk>> {
+        [&mut self.condition, &mut self.content]
+            .into_iter()
+            .chain(self.cases.iter_mut())
+            .chain(self.otherwise.iter_mut())
+    }
+}
+
+impl Validate for Conditional {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if !self.label.starts_with("if") {
+            return Err(InternalError::NotConditional);
+        }
+
+        for c in self.label.chars() {
+            if matches!(c, '\\' | '%')
+                || ast1::ScopeVariant::is_opening(c)
+                || ast1::ScopeVariant::is_closing(c)
+            {
+                return Err(InternalError::UnsanitisedCharInString(c));
+            }
+        }
+
+        // a conditional can be in a group, so its branches are checked as part of one
+        let raw = self.is_raw();
+        for (index, part) in self.parts().enumerate() {
+            if !(raw && index == 1) {
+                part.iter().try_for_each(Chunk::validate_in_group)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for Conditional {
+    fn lines(&self) -> u32 {
+        let unless = self.unless.iter().flat_map(|s| s.matches('\n')).count() as u32;
+        self.parts().map(|part| lines(part)).sum::<u32>() + unless + 1
+    }
+}
+
+impl Conditional {
+    /// Returns the chunks of the conditional with `\unless`, `\if…`, `\or`, `\else` and `\fi`
+    /// as plain commands, numbered from the first line of the conditional
+    pub fn flatten(self) -> Vec<Chunk> {
+        let keyword = |line_no: u32, label: &str| {
+            Chunk::new_unchecked(
+                line_no,
+                ChunkVariant::Command(Command::new_unchecked(label.to_string(), Vec::new())),
+            )
+        };
+
+        let mut line_no = 1;
+        let mut out = Vec::new();
+
+        if let Some(unless) = self.unless {
+            out.push(keyword(1, "unless"));
+            if !unless.is_empty() {
+                line_no += unless.matches('\n').count() as u32;
+                out.push(Chunk::new_unchecked(1, ChunkVariant::Text(unless)));
+            }
+        }
+        out.push(keyword(line_no, &self.label));
+
+        let mut part = |out: &mut Vec<Chunk>, chunks: Vec<Chunk>| {
+            let lines = lines(&chunks);
+            out.extend(chunks.into_iter().map(|mut chunk| {
+                *chunk.line_no_mut() += line_no - 1;
+                chunk
+            }));
+            line_no += lines;
+            line_no
+        };
+
+        part(&mut out, self.condition);
+        part(&mut out, self.content);
+
+        for case in self.cases {
+            let line_no = part(&mut out, Vec::new());
+            out.push(keyword(line_no, "or"));
+            part(&mut out, case);
+        }
+
+        if let Some(otherwise) = self.otherwise {
+            let line_no = part(&mut out, Vec::new());
+            out.push(keyword(line_no, "else"));
+            part(&mut out, otherwise);
+        }
+
+        let line_no = part(&mut out, Vec::new());
+        out.push(keyword(line_no, "fi"));
+        out
+    }
+}
+
+impl ast1::IntoChunks for Conditional {
+    fn into_chunks(self) -> Vec<ast1::Chunk> {
+        self.flatten()
+            .into_iter()
+            .flat_map(ast1::IntoChunks::into_chunks)
+            .collect()
+    }
+}
+
+impl From<ast3::Conditional> for Conditional {
+    fn from(value: ast3::Conditional) -> Self {
+        let unless = value.unless().cloned();
+        let (label, condition, content, cases, otherwise) = value.decompose();
+        let chunks = |chunks: Vec<ast3::Chunk>| {
+            chunks
+                .into_iter()
+                .flat_map(|chunk| chunk.into_chunks())
+                .collect::<Vec<_>>()
+        };
+
+        Self::new_unchecked(
+            label,
+            chunks(condition),
+            chunks(content),
+            cases.into_iter().map(chunks).collect(),
+            otherwise.map(chunks),
+        )
+        .with_unless(unless)
+    }
+}
+
+// where the part after `before` starts, relative to the start of the opening
+pub(super) fn part_start(opening: &str, before: &[Vec<Chunk>], has_otherwise: bool) -> Position {
+    // cases are after `\or` and the last part is after `\else` if there is one
+    let keyword = |index: usize, last: bool| match index {
+        0 | 1 => "",
+        _ if last && has_otherwise => "\\else",
+        _ => "\\or",
+    };
+
+    before
+        .iter()
+        .enumerate()
+        .fold(
+            Position::start().after(opening),
+            |position, (index, part)| position.after(keyword(index, false)).after_all(part),
+        )
+        .after(keyword(before.len(), true))
+}
+
+fn concat(chunks: &[Chunk]) -> String {
+    chunks.iter().map(ToString::to_string).collect()
+}
+
+// lines added by the chunks
+fn lines(chunks: &[Chunk]) -> u32 {
+    chunks.iter().map(|chunk| chunk.lines() - 1).sum()
+}
+
+/// Whether a command starts a conditional, unless it is the name of a definition
+pub fn is_conditional(label: &str) -> bool {
+    label.starts_with("if") && !NOT_CONDITIONALS.contains(&label)
+}
+
+// whether a command can start, continue or end a conditional
+pub(crate) fn is_keyword(label: &str) -> bool {
+    is_conditional(label) || matches!(label, "or" | "else" | "fi")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Keyword {
+    If,
+    Or,
+    Else,
+    Fi,
+}
+
+// the keyword each chunk is, commands which are the names of definitions are not keywords
+fn keywords(chunks: &[Chunk]) -> Vec<Option<Keyword>> {
+    let mut names = 0;
+
+    chunks
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Command(_) if names > 0 => {
+                names -= 1;
+                None
+            }
+            ChunkVariant::Command(command) => {
+                let label = command.label().as_str();
+                names = DEFINITIONS
+                    .iter()
+                    .find(|(definition, _)| *definition == label)
+                    .map_or(0, |(_, names)| *names);
+
+                match label {
+                    "or" => Some(Keyword::Or),
+                    "else" => Some(Keyword::Else),
+                    "fi" => Some(Keyword::Fi),
+                    _ if is_conditional(label) => Some(Keyword::If),
+                    _ => None,
+                }
+            }
+            ChunkVariant::Text(s)
+                if s.chars()
+                    .all(|c| c.is_whitespace() || matches!(c, '=' | '*')) =>
+            {
+                None
+            }
+            _ => {
+                names = 0;
+                None
+            }
+        })
+        .collect()
+}
+
+// fold the conditionals in a list of chunks, inner ones first, where `depth` is the number of
+// scopes and conditionals around the chunks
+//
+// The chunks keep the lines they are on, so each part is numbered once, from the line it
+// starts on, instead of counting the lines of the conditionals in it.
+pub(super) fn group(chunks: Vec<Chunk>, depth: usize) -> Result<Vec<Chunk>, crate::Error> {
+    let keywords = keywords(&chunks);
+
+    // each `\if…` with the `\or` and `\else` commands belonging to it, and how deep the
+    // conditionals in it are nested
+    let mut open: Vec<(usize, Vec<usize>, usize)> = Vec::new();
+    // each matching `\if…`, the commands belonging to it and its `\fi`
+    let mut matched: Vec<(usize, Vec<usize>, usize)> = Vec::new();
+    let mut height = 0;
+
+    for (index, keyword) in keywords.iter().enumerate() {
+        match keyword {
+            Some(Keyword::If) => open.push((index, Vec::new(), 0)),
+            Some(Keyword::Or | Keyword::Else) => {
+                if let Some((_, markers, _)) = open.last_mut() {
+                    markers.push(index)
+                }
+            }
+            Some(Keyword::Fi) => {
+                if let Some((start, markers, inner)) = open.pop() {
+                    // `\else` is last, and there is only one
+                    let ordered = markers
+                        .iter()
+                        .rev()
+                        .skip(1)
+                        .all(|marker| keywords[*marker] == Some(Keyword::Or));
+                    let outer = inner + ordered as usize;
+
+                    match open.last_mut() {
+                        Some((_, _, height)) => *height = outer.max(*height),
+                        None => height = outer.max(height),
+                    }
+
+                    if ordered {
+                        matched.push((start, markers, index))
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    if matched.is_empty() {
+        return Ok(chunks);
+    }
+
+    // conditionals which are never closed are not counted
+    let height = open
+        .iter()
+        .map(|(_, _, inner)| *inner)
+        .fold(height, usize::max);
+    matched.sort_unstable_by_key(|(start, _, _)| *start);
+
+    if depth + height > ParseLimits::MAX_DEPTH {
+        // the ends of the conditionals around each one
+        let mut ends: Vec<usize> = Vec::new();
+
+        for (start, _, end) in matched.iter() {
+            while ends.last().is_some_and(|outer| outer < start) {
+                ends.pop();
+            }
+            ends.push(*end);
+
+            if depth + ends.len() > ParseLimits::MAX_DEPTH {
+                return Err(crate::Error::new(
+                    Position::start().after_all(&chunks[..*start]),
+                    ErrorType::LimitExceeded(Limit::Depth),
+                ));
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(chunks.len());
+    // the conditionals which are open, innermost last
+    let mut stack: Vec<Open> = Vec::new();
+    let mut matched = matched.into_iter().peekable();
+
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let open = stack.last_mut();
+
+        if open.as_ref().is_some_and(|open| open.end == index) {
+            let conditional = stack.pop().unwrap().finish(chunk);
+            current(&mut stack, &mut out).extend(conditional);
+        } else if open
+            .as_ref()
+            .is_some_and(|open| open.markers.last() == Some(&index))
+        {
+            let open = open.unwrap();
+            open.markers.pop();
+            open.parts.push((chunk, Vec::new()));
+        } else if let Some((_, mut markers, end)) = matched.next_if(|(start, _, _)| *start == index)
+        {
+            let (line_no, unless) = match unless(current(&mut stack, &mut out)) {
+                Some((unless_line_no, unless)) => (unless_line_no, Some(unless)),
+                None => (chunk.line_no(), None),
+            };
+
+            markers.reverse();
+            stack.push(Open {
+                line_no,
+                unless,
+                parts: vec![(chunk, Vec::new())],
+                markers,
+                end,
+            });
+        } else {
+            current(&mut stack, &mut out).push(chunk);
+        }
+    }
+
+    Ok(out)
+}
+
+// the chunks the next chunk goes into
+fn current<'a>(stack: &'a mut [Open], out: &'a mut Vec<Chunk>) -> &'a mut Vec<Chunk> {
+    match stack.last_mut() {
+        Some(open) => &mut open.parts.last_mut().unwrap().1,
+        None => out,
+    }
+}
+
+// a conditional which is being read, with the chunks numbered from the start of the list
+struct Open {
+    // the line of `\unless`, or of the test
+    line_no: u32,
+    unless: Option<String>,
+    // each keyword and the chunks after it
+    parts: Vec<(Chunk, Vec<Chunk>)>,
+    // the `\or` and `\else` commands which are not read yet, the next one last
+    markers: Vec<usize>,
+    end: usize,
+}
+
+impl Open {
+    // the conditional ended by `\fi`, followed by the arguments taken by it
+    fn finish(self, fi: Chunk) -> Vec<Chunk> {
+        let mut parts = self.parts.into_iter().map(|(keyword, chunks)| {
+            let line_no = keyword.line_no();
+            let mut part = exploded(keyword);
+            let ChunkVariant::Command(label) = part.remove(0).variant_owned() else {
+                unreachable!("keyword which is not a command")
+            };
+            part.extend(chunks);
+            (line_no, label.decompose().0, part)
+        });
+
+        let (line_no, label, first) = parts.next().unwrap();
+        let mut reader = Reader {
+            rest: first.into(),
+            condition: Vec::new(),
+        };
+        reader.test(&label);
+
+        // the content starts where the condition ends
+        let content = Vec::from(reader.rest);
+        let content_line_no = content.first().map_or(line_no, Chunk::line_no);
+
+        let mut cases = Vec::new();
+        let mut otherwise = None;
+
+        for (line_no, keyword, part) in parts {
+            match keyword.as_str() {
+                "else" => otherwise = Some(rebased(part, line_no)),
+                _ => cases.push(rebased(part, line_no)),
+            }
+        }
+
+        let conditional = Conditional::new_unchecked(
+            label,
+            rebased(reader.condition, line_no),
+            rebased(content, content_line_no),
+            cases,
+            otherwise,
+        )
+        .with_unless(self.unless);
+
+        let mut out = vec![Chunk::new_unchecked(
+            self.line_no,
+            ChunkVariant::Conditional(conditional),
+        )];
+        out.extend(exploded(fi).into_iter().skip(1));
+        out
+    }
+}
+
+// a command without its arguments, followed by each argument and the text before it,
+// on the lines they are on
+fn exploded(command: Chunk) -> Vec<Chunk> {
+    let (mut line_no, variant) = command.decompose();
+    let ChunkVariant::Command(command) = variant else {
+        unreachable!("keyword which is not a command")
+    };
+
+    explode(command)
+        .into_iter()
+        .map(|variant| {
+            let chunk_line_no = line_no;
+            line_no += variant.lines() - 1;
+            Chunk::new_unchecked(chunk_line_no, variant)
+        })
+        .collect()
+}
+
+// chunks numbered from the line they start on
+fn rebased(mut chunks: Vec<Chunk>, line_no: u32) -> Vec<Chunk> {
+    chunks
+        .iter_mut()
+        .for_each(|chunk| *chunk.line_no_mut() -= line_no - 1);
+    chunks
+}
+
+// takes `\unless` and the whitespace after it from the end of the chunks before a test,
+// with the line it is on
+fn unless(before: &mut Vec<Chunk>) -> Option<(u32, String)> {
+    let is_unless = |chunk: &Chunk| {
+        matches!(chunk.variant(), ChunkVariant::Command(command)
+            if command.label() == "unless" && command.arguments().is_empty())
+    };
+
+    let whitespace = match before.last()?.variant() {
+        ChunkVariant::Text(s) if s.chars().all(char::is_whitespace) => {
+            if !is_unless(before.get(before.len().checked_sub(2)?)?) {
+                return None;
+            }
+            s.clone()
+        }
+        _ if is_unless(before.last()?) => String::new(),
+        _ => return None,
+    };
+
+    if !whitespace.is_empty() {
+        before.pop();
+    }
+    Some((before.pop()?.line_no(), whitespace))
+}
+
+// reads the tokens of a test from the start of the content
+struct Reader {
+    rest: VecDeque<Chunk>,
+    condition: Vec<Chunk>,
+}
+
+impl Reader {
+    // read the condition of a test
+    fn test(&mut self, label: &str) {
+        match label {
+            "if" | "ifcat" | "ifx" => {
+                self.token();
+                self.token();
+            }
+            "ifdefined" => {
+                self.token();
+            }
+            "ifcsname" => self.until_endcsname(),
+            "ifnum" => {
+                self.number();
+                self.relation();
+                self.number();
+            }
+            "ifdim" => {
+                self.dimension();
+                self.relation();
+                self.dimension();
+            }
+            "ifodd" | "ifcase" | "ifvoid" | "ifhbox" | "ifvbox" | "ifeof" => self.number(),
+            _ => {}
+        }
+    }
+
+    // move the first bytes of the text at the start into the condition
+    fn take_text(&mut self, len: usize) {
+        let Some(front) = self.rest.front_mut() else {
+            return;
+        };
+        let line_no = front.line_no();
+        let ChunkVariant::Text(s) = front.variant_mut() else {
+            return;
+        };
+
+        if len == 0 {
+            return;
+        }
+
+        let rest = s.split_off(len);
+        let taken = std::mem::replace(s, rest);
+
+        if s.is_empty() {
+            self.rest.pop_front();
+        } else {
+            *front.line_no_mut() += taken.matches('\n').count() as u32;
+        }
+
+        match self.condition.last_mut().map(Chunk::variant_mut) {
+            Some(ChunkVariant::Text(last)) => last.push_str(&taken),
+            _ => self
+                .condition
+                .push(Chunk::new_unchecked(line_no, ChunkVariant::Text(taken))),
+        }
+    }
+
+    // move the characters matching at the start of the text into the condition,
+    // returning how many bytes were moved
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> usize {
+        let len = match self.rest.front().map(Chunk::variant) {
+            Some(ChunkVariant::Text(s)) => s.find(|c| !f(c)).unwrap_or(s.len()),
+            _ => 0,
+        };
+
+        self.take_text(len);
+        len
+    }
+
+    // TeX takes one optional space after a number
+    fn optional_space(&mut self) {
+        if let Some(ChunkVariant::Text(s)) = self.rest.front().map(Chunk::variant) {
+            if s.starts_with(char::is_whitespace) {
+                self.take_text(s.chars().next().unwrap().len_utf8())
+            }
+        }
+    }
+
+    // move a command into the condition, its arguments are moved too when `arguments` is set
+    fn command(&mut self, arguments: bool) -> bool {
+        match self.rest.front().map(Chunk::variant) {
+            Some(ChunkVariant::Command(_)) if arguments => {
+                let command = self.rest.pop_front().unwrap();
+                self.condition.push(command);
+                true
+            }
+            Some(ChunkVariant::Command(_)) => {
+                let mut exploded = exploded(self.rest.pop_front().unwrap());
+                self.condition.push(exploded.remove(0));
+                exploded
+                    .into_iter()
+                    .rev()
+                    .for_each(|chunk| self.rest.push_front(chunk));
+                true
+            }
+            // conditionals are folded before the test is read, so one read as a token is
+            // read as its keywords
+            Some(ChunkVariant::Conditional(_)) => {
+                let (line_no, variant) = self.rest.pop_front().unwrap().decompose();
+                let ChunkVariant::Conditional(conditional) = variant else {
+                    unreachable!()
+                };
+
+                let mut chunks = conditional.flatten();
+                let Some(ChunkVariant::Command(fi)) = chunks.last_mut().map(Chunk::variant_mut)
+                else {
+                    unreachable!("conditional not ending with a command")
+                };
+
+                // the arguments taken by `\fi` follow the conditional
+                loop {
+                    let prec = match self.rest.front().map(Chunk::variant) {
+                        Some(ChunkVariant::Text(s)) if s.trim().is_empty() => s.clone(),
+                        _ => String::new(),
+                    };
+
+                    let index = !prec.is_empty() as usize;
+                    if !matches!(
+                        self.rest.get(index).map(Chunk::variant),
+                        Some(ChunkVariant::Scope(_))
+                    ) {
+                        break;
+                    }
+
+                    self.rest.drain(..index);
+                    let ChunkVariant::Scope(scope) = self.rest.pop_front().unwrap().variant_owned()
+                    else {
+                        unreachable!()
+                    };
+                    fi.arguments_mut().push((prec, scope));
+                }
+
+                // text at the end of a part runs into text at the start of the next one
+                let mut flat: Vec<Chunk> = Vec::with_capacity(chunks.len());
+
+                for mut chunk in chunks {
+                    if let (Some(ChunkVariant::Text(last)), ChunkVariant::Text(s)) =
+                        (flat.last_mut().map(Chunk::variant_mut), chunk.variant())
+                    {
+                        last.push_str(s);
+                        continue;
+                    }
+
+                    *chunk.line_no_mut() += line_no - 1;
+                    flat.push(chunk);
+                }
+
+                flat.into_iter()
+                    .rev()
+                    .for_each(|chunk| self.rest.push_front(chunk));
+                self.command(arguments)
+            }
+            _ => false,
+        }
+    }
+
+    // a single token, whitespace before it is ignored
+    fn token(&mut self) {
+        self.take_while(char::is_whitespace);
+
+        if self.command(false) {
+            return;
+        }
+
+        match self.rest.front().map(Chunk::variant) {
+            Some(ChunkVariant::Text(s)) => {
+                let len = s.chars().next().map_or(0, char::len_utf8);
+                self.take_text(len)
+            }
+            Some(ChunkVariant::Scope(_)) => {
+                let scope = self.rest.pop_front().unwrap();
+                self.condition.push(scope)
+            }
+            _ => {}
+        }
+    }
+
+    fn number(&mut self) {
+        self.take_while(|c| c.is_whitespace() || matches!(c, '+' | '-'));
+
+        if self.command(true) {
+            return;
+        }
+
+        let digits = match self.rest.front().map(Chunk::variant) {
+            Some(ChunkVariant::Text(s)) if s.starts_with(['\'', '"']) => {
+                self.take_text(1);
+                self.take_while(|c| c.is_ascii_hexdigit())
+            }
+            _ => self.take_while(|c| c.is_ascii_digit()),
+        };
+
+        if digits > 0 {
+            self.optional_space()
+        }
+    }
+
+    fn dimension(&mut self) {
+        self.take_while(|c| c.is_whitespace() || matches!(c, '+' | '-'));
+        let digits = self.take_while(|c| c.is_ascii_digit() || matches!(c, '.' | ','));
+
+        // a length such as `\textwidth`, which may be multiplied
+        if self.command(true) {
+            return;
+        }
+
+        if digits > 0 && self.take_while(|c| c.is_ascii_alphabetic()) > 0 {
+            self.optional_space()
+        }
+    }
+
+    fn relation(&mut self) {
+        self.take_while(char::is_whitespace);
+
+        if let Some(ChunkVariant::Text(s)) = self.rest.front().map(Chunk::variant) {
+            if s.starts_with(['<', '=', '>']) {
+                self.take_text(1)
+            }
+        }
+    }
+
+    fn until_endcsname(&mut self) {
+        while let Some(chunk) = self.rest.pop_front() {
+            let end = matches!(chunk.variant(), ChunkVariant::Command(command) if command.label() == "endcsname");
+
+            if end {
+                self.rest.push_front(chunk);
+                self.command(false);
+                return;
+            }
+
+            self.condition.push(chunk)
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use crate::{
-    ast1, ast3,
+    ast1::{self, Catcodes},
+    ast3,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, ParseLimits, Position,
 };
 
-use super::{Chunk, ChunkVariant, Environment, IntoChunks};
+use super::{
+    fold, Chunk, ChunkVariant, Command, Environment, IntoChunks, Match, Node, Query, Scope,
+};
 
 use std::{fmt::Display, mem, str::FromStr};
 
@@ -17,6 +20,21 @@ use std::{fmt::Display, mem, str::FromStr};
 pub struct Document(Vec<Chunk>);
 
 impl Document {
+    /// Parse a document, failing with [`ErrorType::LimitExceeded`](crate::ErrorType::LimitExceeded)
+    /// when it goes over any of the limits
+    pub fn parse_with_limits(s: &str, limits: ParseLimits) -> Result<Self, crate::Error> {
+        Self::parse_with_catcodes(s, limits, Catcodes::default())
+    }
+
+    /// Parse a document starting with the given catcodes, such as the source of a package
+    pub fn parse_with_catcodes(
+        s: &str,
+        limits: ParseLimits,
+        catcodes: Catcodes,
+    ) -> Result<Self, crate::Error> {
+        ast1::Document::parse_with_catcodes(s, limits, catcodes)?.try_into()
+    }
+
     /// Create new document from chunks
     pub fn new(chunks: Vec<Chunk>) -> Result<Self, InternalError> {
         let out = Self(chunks);
@@ -34,11 +52,38 @@ impl Document {
         &self.0
     }
 
+    /// Index all nodes of the document for searching
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    /// Returns the path from the outermost to the innermost node containing the position
+    pub fn node_at(&self, position: Position) -> Vec<Match<'_>> {
+        self.query()
+            .node_at(position)
+            .into_iter()
+            .copied()
+            .collect()
+    }
+
+    /// Returns the start position and the position right after the end of a node
+    /// borrowed from this document
+    pub fn span_of(&self, node: Node) -> Option<(Position, Position)> {
+        self.query()
+            .locate(node)
+            .map(|found| (found.start(), found.end()))
+    }
+
     /// Returns the owned chunks inside the document
     pub fn chunks_owned(self) -> Vec<Chunk> {
         self.0
     }
 
+    /// Returns the mutable chunks inside the document
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.0
+    }
+
     /// Push a variant to the document
     pub fn push(&mut self, variant: ChunkVariant) -> Result<(), InternalError> {
         variant.validate()?;
@@ -96,7 +141,7 @@ impl FromStr for Document {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(ast1::Document::from_str(s)?)
+        Self::parse_with_limits(s, ParseLimits::default())
     }
 }
 
@@ -139,6 +184,7 @@ impl Lines for Document {
 
 impl From<ast3::Document> for Document {
     fn from(value: ast3::Document) -> Self {
+        let has_body = value.has_body();
         let (_, _, preamable, body, body_args, body_begin_prec, body_end_prec, trailing) =
             value.decompose();
 
@@ -150,6 +196,10 @@ impl From<ast3::Document> for Document {
 
         let mut out = Document::new_unchecked(preamable);
 
+        if !has_body {
+            return out;
+        }
+
         let body_offset = body_args
             .iter()
             .map(|(s, arg)| s.chars().filter(|c| c == &'\n').count() as u32 + arg.lines() - 1)
@@ -197,18 +247,69 @@ impl From<ast3::Document> for Document {
 impl TryFrom<crate::ast1::Document> for Document {
     type Error = crate::Error;
     fn try_from(value: crate::ast1::Document) -> Result<Self, Self::Error> {
+        Ok(Self(fold::fold(Self::from_ast1(value, false)?.0, 0)?))
+    }
+}
+
+impl Document {
+    // folds environments, conditionals are folded once the whole tree is built
+    //
+    // In a group, such as an argument or a macro body, a `\begin` or `\end` without its pair
+    // in the group is kept as a plain command, as in `\newcommand{\bi}{\begin{itemize}}`.
+    pub(super) fn from_ast1(
+        value: crate::ast1::Document,
+        group: bool,
+    ) -> Result<Self, crate::Error> {
         let originals = value.chunks_owned();
+        let plain = match group {
+            true => unpaired(&originals),
+            false => vec![false; originals.len()],
+        };
         let mut chunks = Vec::new();
 
-        let mut buffer_stack = Vec::new();
+        // labels of open environments, and how many chunks were buffered before their `\begin`
+        let mut buffer_stack: Vec<(Vec<ast1::Chunk>, usize)> = Vec::new();
         let mut buffer_start = 0;
-        let mut env_args = Vec::new();
+        // the `\begin` of the outermost open environment, and the line it is in
+        let mut begin: Option<ast1::Command> = None;
+        let mut begin_line_no = 0;
         let mut buffer: Vec<ast1::Chunk> = Vec::new();
-        let mut prec_begin = String::new();
 
-        for original in originals {
+        // positions are only worked out for errors, from the chunks read so far, as doing it
+        // for every chunk would go over each subtree once per level of nesting
+        fn position(
+            chunks: &[Chunk],
+            begin: Option<&ast1::Command>,
+            buffered: &[ast1::Chunk],
+        ) -> Position {
+            Position::start()
+                .after_all(chunks)
+                .after_all(begin)
+                .after_all(buffered)
+        }
+
+        // position of the `\begin` of an open environment
+        fn begin_position(
+            chunks: &[Chunk],
+            begin: Option<&ast1::Command>,
+            buffer: &[ast1::Chunk],
+            stack: &[(Vec<ast1::Chunk>, usize)],
+        ) -> Position {
+            match stack.len() {
+                1 => position(chunks, None, &[]),
+                _ => position(chunks, begin, &buffer[..stack.last().unwrap().1]),
+            }
+        }
+
+        for (original, plain) in originals.into_iter().zip(plain) {
             let line_no = original.line_no();
 
+            macro_rules! here {
+                () => {
+                    position(&chunks, begin.as_ref(), &buffer)
+                };
+            }
+
             macro_rules! push_chunks {
                 ($x:expr) => {
                     chunks.push(Chunk::new_unchecked(line_no, $x))
@@ -223,25 +324,7 @@ impl TryFrom<crate::ast1::Document> for Document {
 
             macro_rules! map_e {
                 ($x:expr) => {
-                    match $x {
-                        Ok(k) => k,
-                        Err(mut e) => {
-                            e.line += line_no;
-                            return Err(e);
-                        }
-                    }
-                };
-            }
-
-            macro_rules! map_env_e {
-                ($x:expr) => {
-                    match $x {
-                        Ok(k) => k,
-                        Err(mut e) => {
-                            e.line += buffer_start;
-                            return Err(e);
-                        }
-                    }
+                    $x.map_err(|e: crate::Error| e.offset(here!()))?
                 };
             }
 
@@ -251,13 +334,15 @@ impl TryFrom<crate::ast1::Document> for Document {
                 }
                 ast1::ChunkVariant::Text(s) => push_buffer!(ast1::ChunkVariant::Text(s)),
                 ast1::ChunkVariant::Scope(s) if buffer_stack.is_empty() => {
-                    push_chunks!(ChunkVariant::Scope(map_e!(s.try_into())))
+                    let scope = map_e!(Scope::from_ast1(s));
+                    push_chunks!(ChunkVariant::Scope(scope))
                 }
                 ast1::ChunkVariant::Scope(s) => push_buffer!(ast1::ChunkVariant::Scope(s)),
                 ast1::ChunkVariant::Command(c) => {
-                    if !matches!(c.label().as_str(), "begin" | "end") {
+                    if plain || !matches!(c.label().as_str(), "begin" | "end") {
                         if buffer_stack.is_empty() {
-                            push_chunks!(ChunkVariant::Command(c.try_into()?));
+                            let command = map_e!(Command::from_ast1(c));
+                            push_chunks!(ChunkVariant::Command(command));
                         } else {
                             push_buffer!(ast1::ChunkVariant::Command(c));
                         }
@@ -269,17 +354,17 @@ impl TryFrom<crate::ast1::Document> for Document {
                         .first()
                         .is_some_and(|arg| arg.1.variant() == ast1::ScopeVariant::Curly)
                     {
-                        return Err(crate::Error {
-                            line: line_no,
-                            r#type: crate::ErrorType::NoEnvironmentLabel,
-                        });
+                        return Err(crate::Error::new(
+                            here!(),
+                            crate::ErrorType::NoEnvironmentLabel,
+                        ));
                     }
 
                     match c.label().as_str() {
                         "begin" => {
-                            let (prec, content) = c.arguments().first().unwrap();
+                            let (_, content) = c.arguments().first().unwrap();
 
-                            buffer_stack.push(content.chunks().clone());
+                            buffer_stack.push((content.chunks().clone(), buffer.len()));
 
                             if buffer_stack.len() == 1 {
                                 // was empty
@@ -288,37 +373,46 @@ impl TryFrom<crate::ast1::Document> for Document {
                                     s.chars().filter(|c| c == &'\n').count()
                                 }
 
+                                begin_line_no = line_no;
                                 buffer_start = line_no
                                     + c.arguments()
                                         .iter()
                                         .map(|(prec, arg)| lines(prec) + lines(&arg.to_string()))
                                         .sum::<usize>()
                                         as u32;
-                                prec_begin = prec.to_string();
-
-                                env_args = c.arguments_owned().into_iter().skip(1).collect();
+                                begin = Some(c);
                             } else {
                                 push_buffer!(ast1::ChunkVariant::Command(c));
                             }
                         }
                         "end"
-                            if buffer_stack.last()
+                            if buffer_stack.last().map(|(label, _)| label)
                                 != Some(c.arguments().first().unwrap().1.chunks()) =>
                         {
-                            return Err(crate::Error::new(
-                                line_no,
+                            let err = crate::Error::new(
+                                here!(),
                                 crate::ErrorType::UnexpectedEnd(
                                     ast1::Document::new_unchecked(
                                         c.arguments_owned().remove(0).1.chunks_owned(),
                                     )
                                     .to_string(),
                                 ),
-                            ))
+                            );
+
+                            return Err(match buffer_stack.is_empty() {
+                                true => err,
+                                false => err.with_related(begin_position(
+                                    &chunks,
+                                    begin.as_ref(),
+                                    &buffer,
+                                    &buffer_stack,
+                                )),
+                            });
                         }
                         "end" => {
                             if c.arguments().len() > 1 {
                                 return Err(crate::Error::new(
-                                    line_no,
+                                    here!(),
                                     crate::ErrorType::TooManyArgsEnd,
                                 ));
                             }
@@ -327,26 +421,40 @@ impl TryFrom<crate::ast1::Document> for Document {
 
                             if buffer_stack.is_empty() {
                                 let (prec_end, label) = c.arguments_owned().remove(0);
-                                let arguments = std::mem::take(&mut env_args);
+                                let label =
+                                    ast1::Document::new_unchecked(label.chunks_owned()).to_string();
+                                let (_, mut arguments) = begin.take().unwrap().decompose();
+                                let (prec_begin, _) = arguments.remove(0);
+
+                                // the arguments and content start after `\begin{label}`
+                                let start = || {
+                                    position(&chunks, None, &[])
+                                        .after(&format!("\\begin{prec_begin}{{{label}}}"))
+                                };
 
                                 let mut args_new = Vec::with_capacity(arguments.len());
 
                                 for (prec, scope) in arguments {
-                                    args_new.push((prec, scope.try_into()?))
+                                    let scope =
+                                        Scope::from_ast1(scope).map_err(|e: crate::Error| {
+                                            e.offset(
+                                                start().after_arguments(&args_new).after(&prec),
+                                            )
+                                        })?;
+                                    args_new.push((prec, scope));
                                 }
 
+                                let content = Document::from_ast1(
+                                    ast1::Document::new_unchecked(mem::take(&mut buffer)),
+                                    false,
+                                )
+                                .map_err(|e| e.offset(start().after_arguments(&args_new)))?
+                                .chunks_owned();
+
                                 chunks.push(Chunk::new_unchecked(
-                                    buffer_start,
+                                    begin_line_no,
                                     ChunkVariant::Environment(Environment::new_unchecked(
-                                        ast1::Document::new_unchecked(label.chunks_owned())
-                                            .to_string(),
-                                        args_new,
-                                        map_env_e!(Document::try_from(
-                                            ast1::Document::new_unchecked(mem::take(&mut buffer))
-                                        ))
-                                        .chunks_owned(),
-                                        mem::take(&mut prec_begin),
-                                        prec_end,
+                                        label, args_new, content, prec_begin, prec_end,
                                     )),
                                 ))
                             } else {
@@ -359,15 +467,54 @@ impl TryFrom<crate::ast1::Document> for Document {
             }
         }
 
-        if let Some(label) = buffer_stack.first() {
+        if let Some((label, _)) = buffer_stack.first() {
             return Err(crate::Error::new(
-                buffer_start,
+                position(&chunks, None, &[]),
                 crate::ErrorType::UnclosedEnvironment(
                     ast1::Document::new_unchecked(label.clone()).to_string(),
                 ),
-            ));
+            )
+            .with_related(position(&chunks, begin.as_ref(), &buffer)));
         }
 
         Ok(Self(chunks))
     }
 }
+
+// which `\begin` and `\end` commands do not pair up with one another within the chunks
+fn unpaired(chunks: &[ast1::Chunk]) -> Vec<bool> {
+    let mut out = vec![false; chunks.len()];
+    let mut open: Vec<(usize, String)> = Vec::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let ast1::ChunkVariant::Command(command) = chunk.variant() else {
+            continue;
+        };
+        let Some((_, label)) = command
+            .arguments()
+            .first()
+            .filter(|(_, arg)| arg.variant() == ast1::ScopeVariant::Curly)
+        else {
+            continue;
+        };
+
+        match command.label().as_str() {
+            "begin" => open.push((index, label.to_string())),
+            "end"
+                if open
+                    .last()
+                    .is_some_and(|(_, open)| *open == label.to_string()) =>
+            {
+                open.pop();
+            }
+            "end" => out[index] = true,
+            _ => {}
+        }
+    }
+
+    for (index, _) in open {
+        out[index] = true;
+    }
+
+    out
+}
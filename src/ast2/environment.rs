@@ -145,6 +145,36 @@ impl Environment {
         &self.label
     }
 
+    /// Returns arguments of environment, not including the label
+    pub fn arguments(&self) -> &Vec<(String, Scope)> {
+        &self.arguments
+    }
+
+    /// Returns mutable arguments of environment, not including the label
+    pub fn arguments_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.arguments
+    }
+
+    /// Returns the chunks within the environment
+    pub fn content(&self) -> &Vec<Chunk> {
+        &self.content
+    }
+
+    /// Returns the mutable chunks within the environment
+    pub fn content_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.content
+    }
+
+    /// Returns the string between `\begin` and its label
+    pub fn prec_begin(&self) -> &String {
+        &self.prec_begin
+    }
+
+    /// Returns the string between `\end` and its label
+    pub fn prec_end(&self) -> &String {
+        &self.prec_end
+    }
+
     /// Returns the content of `Environment`
     pub fn decompose(self) -> (String, Vec<(String, Scope)>, Vec<Chunk>, String, String) {
         (
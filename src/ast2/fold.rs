@@ -0,0 +1,163 @@
+use crate::Position;
+
+use super::{
+    conditional, edit::renumber_from, macrodefinition, parameter, Chunk, ChunkVariant, Command,
+    Conditional,
+};
+
+/// Split parameter tokens out of text, then fold macro definitions and conditionals in every
+/// nested list of chunks, where `depth` is the number of scopes and conditionals around the
+/// chunks
+///
+/// Definitions are folded first, as their parameter text can contain keywords such as `\fi`.
+/// Conditionals count towards the nesting depth, failing with
+/// [`ErrorType::LimitExceeded`](crate::ErrorType::LimitExceeded) when they go over it, with
+/// positions relative to the start of the chunks.
+pub(crate) fn fold(chunks: Vec<Chunk>, depth: usize) -> Result<Vec<Chunk>, crate::Error> {
+    let chunks = parameter::split(chunks);
+    let chunks = conditional::group(macrodefinition::group(chunks), depth)?;
+
+    let mut out = Vec::with_capacity(chunks.len());
+
+    for chunk in chunks {
+        // the position of the chunk is only worked out for an error
+        let chunk =
+            descend(chunk, depth).map_err(|e| e.offset(Position::start().after_all(&out)))?;
+        out.push(chunk);
+    }
+
+    Ok(out)
+}
+
+// fold the lists of chunks nested in a chunk
+fn descend(chunk: Chunk, depth: usize) -> Result<Chunk, crate::Error> {
+    let (line_no, variant) = chunk.decompose();
+
+    // positions of errors in the arguments are relative to `start`
+    let arguments = |arguments: &mut Vec<(String, super::Scope)>, start: Position| {
+        for index in 0..arguments.len() {
+            let chunks = std::mem::take(arguments[index].1.chunks_mut());
+            let chunks = fold(chunks, depth + 1).map_err(|e| {
+                e.offset(Position::new(1, 2)).offset(
+                    start
+                        .after_arguments(&arguments[..index])
+                        .after(&arguments[index].0),
+                )
+            })?;
+            *arguments[index].1.chunks_mut() = chunks;
+        }
+
+        Ok::<_, crate::Error>(())
+    };
+
+    let variant = match variant {
+        ChunkVariant::Text(_) | ChunkVariant::Parameter(_) => variant,
+        ChunkVariant::Command(mut command) => {
+            let start = Position::start().after(&format!("\\{}", command.label()));
+            arguments(command.arguments_mut(), start)?;
+            ChunkVariant::Command(command)
+        }
+        ChunkVariant::Scope(mut scope) => {
+            let chunks = std::mem::take(scope.chunks_mut());
+            *scope.chunks_mut() =
+                fold(chunks, depth + 1).map_err(|e| e.offset(Position::new(1, 2)))?;
+            ChunkVariant::Scope(scope)
+        }
+        ChunkVariant::Environment(mut environment) => {
+            let start = Position::start().after(&format!(
+                "\\begin{}{{{}}}",
+                environment.prec_begin(),
+                environment.label()
+            ));
+            arguments(environment.arguments_mut(), start)?;
+
+            let start = start.after_arguments(environment.arguments());
+            let chunks = std::mem::take(environment.content_mut());
+            *environment.content_mut() = fold(chunks, depth).map_err(|e| e.offset(start))?;
+            ChunkVariant::Environment(environment)
+        }
+        ChunkVariant::Conditional(conditional) => {
+            let unless = conditional.unless().cloned();
+            let opening = conditional.opening();
+            let (label, condition, content, cases, otherwise) = conditional.decompose();
+            let has_otherwise = otherwise.is_some();
+
+            let mut parts = Vec::with_capacity(cases.len() + 3);
+
+            for part in [condition, content]
+                .into_iter()
+                .chain(cases)
+                .chain(otherwise)
+            {
+                let part = fold(part, depth + 1).map_err(|e| {
+                    e.offset(conditional::part_start(&opening, &parts, has_otherwise))
+                })?;
+                parts.push(part);
+            }
+
+            let mut parts = parts.into_iter();
+            let condition = parts.next().unwrap();
+            let content = parts.next().unwrap();
+            let otherwise = has_otherwise.then(|| parts.next_back().unwrap());
+
+            ChunkVariant::Conditional(
+                Conditional::new_unchecked(label, condition, content, parts.collect(), otherwise)
+                    .with_unless(unless),
+            )
+        }
+        // the parameter text is delimiters, which are not folded themselves
+        ChunkVariant::MacroDefinition(mut definition) => {
+            let start = Position::start().after(&format!(
+                "\\{}{}\\{}",
+                definition.command(),
+                definition.prec(),
+                definition.label()
+            ));
+
+            let mut parameters = Vec::with_capacity(definition.parameters().len());
+
+            for chunk in std::mem::take(definition.parameters_mut()) {
+                let chunk = descend(chunk, depth + 1)
+                    .map_err(|e| e.offset(start.after_all(&parameters)))?;
+                parameters.push(chunk);
+            }
+
+            *definition.parameters_mut() = parameters;
+
+            let body = std::mem::take(definition.body_mut());
+            *definition.body_mut() = fold(body, depth + 1)
+                .map_err(|e| e.offset(start.after_all(definition.parameters()).after("{")))?;
+            ChunkVariant::MacroDefinition(definition)
+        }
+    };
+
+    Ok(Chunk::new_unchecked(line_no, variant))
+}
+
+// a command without its arguments, followed by each argument and the text before it
+pub(super) fn explode(command: Command) -> Vec<ChunkVariant> {
+    let (label, arguments) = command.decompose();
+    let mut out = vec![ChunkVariant::Command(Command::new_unchecked(
+        label,
+        Vec::new(),
+    ))];
+
+    for (prec, arg) in arguments {
+        if !prec.is_empty() {
+            out.push(ChunkVariant::Text(prec))
+        }
+        out.push(ChunkVariant::Scope(arg))
+    }
+
+    out
+}
+
+// chunks numbered from the first line
+pub(super) fn numbered(variants: Vec<ChunkVariant>) -> Vec<Chunk> {
+    let mut out = variants
+        .into_iter()
+        .map(|variant| Chunk::new_unchecked(1, variant))
+        .collect::<Vec<_>>();
+    renumber_from(&mut out, 0);
+    out
+}
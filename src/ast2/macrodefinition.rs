@@ -0,0 +1,410 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use crate::{
+    ast1, ast3,
+    traits::{Lines, Validate},
+    InternalError,
+};
+
+use super::{
+    fold::{explode, numbered},
+    Chunk, ChunkVariant, Command, IntoChunks as IntoChunks2, Scope, ScopeVariant,
+};
+
+/// Commands which define a macro from a parameter text and a body
+pub const DEF_COMMANDS: [&str; 4] = ["def", "gdef", "edef", "xdef"];
+
+/// A macro defined with `\def` or similar, such as `\def\foo#1.{#1}`
+///
+/// The parameter text is everything between the name and the body, which is the parameters
+/// and the delimiters after them.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct MacroDefinition {
+    command: String,
+    prec: String,
+    label: String,
+    parameters: Vec<Chunk>,
+    body: Vec<Chunk>,
+}
+
+impl Display for MacroDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "\\{}{}\\{}{}{{{}}}",
+            self.command,
+            self.prec,
+            self.label,
+            concat(&self.parameters),
+            concat(&self.body)
+        ))
+    }
+}
+
+impl MacroDefinition {
+    /// Constructs a new MacroDefinition
+    pub fn new(
+        command: String,
+        prec: String,
+        label: String,
+        parameters: Vec<Chunk>,
+        body: Vec<Chunk>,
+    ) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(command, prec, label, parameters, body);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new MacroDefinition without checking
+    pub fn new_unchecked(
+        command: String,
+        prec: String,
+        label: String,
+        parameters: Vec<Chunk>,
+        body: Vec<Chunk>,
+    ) -> Self {
+        Self {
+            command,
+            prec,
+            label,
+            parameters,
+            body,
+        }
+    }
+
+    /// Returns the defining command, such as `gdef`
+    pub fn command(&self) -> &String {
+        &self.command
+    }
+
+    /// Returns the whitespace between the defining command and the name
+    pub fn prec(&self) -> &String {
+        &self.prec
+    }
+
+    /// Returns the name of the macro, such as `foo` for `\foo`
+    pub fn label(&self) -> &String {
+        &self.label
+    }
+
+    /// Returns the chunks of the parameter text, such as `#1.` of `\def\foo#1.{#1}`
+    pub fn parameters(&self) -> &Vec<Chunk> {
+        &self.parameters
+    }
+
+    /// Returns the mutable chunks of the parameter text
+    pub fn parameters_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.parameters
+    }
+
+    /// Returns the chunks of the body, without its braces
+    pub fn body(&self) -> &Vec<Chunk> {
+        &self.body
+    }
+
+    /// Returns the mutable chunks of the body
+    pub fn body_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.body
+    }
+
+    /// Returns all fields of this struct
+    pub fn decompose(self) -> (String, String, String, Vec<Chunk>, Vec<Chunk>) {
+        (
+            self.command,
+            self.prec,
+            self.label,
+            self.parameters,
+            self.body,
+        )
+    }
+
+    /// Returns the number of parameters the macro takes
+    pub fn arity(&self) -> usize {
+        arity(&self.parameters)
+    }
+}
+
+impl Validate for MacroDefinition {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if !DEF_COMMANDS.contains(&self.command.as_str()) {
+            return Err(InternalError::NotMacroDefinition);
+        }
+
+        if self.label.len() != 1 {
+            for c in self.label.chars() {
+                if matches!(c, '\\' | '%')
+                    || ast1::ScopeVariant::is_opening(c)
+                    || ast1::ScopeVariant::is_closing(c)
+                {
+                    return Err(InternalError::UnsanitisedCharInString(c));
+                }
+            }
+        }
+
+        for chunk in self.parameters.iter() {
+            chunk.validate()?
+        }
+
+        for chunk in self.body.iter() {
+            chunk.validate_in_group()?
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for MacroDefinition {
+    fn lines(&self) -> u32 {
+        newlines(&self.prec)
+            + newlines(&self.label)
+            + lines(&self.parameters)
+            + lines(&self.body)
+            + 1
+    }
+}
+
+impl MacroDefinition {
+    /// Returns the chunks of the definition with the defining command and the name as plain
+    /// commands and the body as a scope, numbered from the first line of the definition
+    pub fn flatten(self) -> Vec<Chunk> {
+        let command =
+            |label: String| ChunkVariant::Command(Command::new_unchecked(label, Vec::new()));
+        let prec = (!self.prec.is_empty()).then_some(ChunkVariant::Text(self.prec));
+
+        let mut out = numbered(
+            [command(self.command)]
+                .into_iter()
+                .chain(prec)
+                .chain([command(self.label)])
+                .collect(),
+        );
+
+        let mut line_no = out
+            .last()
+            .map_or(1, |last| last.line_no() + last.lines() - 1);
+        for mut chunk in self.parameters {
+            *chunk.line_no_mut() += line_no - 1;
+            out.push(chunk);
+        }
+
+        line_no = out
+            .last()
+            .map_or(1, |last| last.line_no() + last.lines() - 1);
+        out.push(Chunk::new_unchecked(
+            line_no,
+            ChunkVariant::Scope(Scope::new_unchecked(self.body, ScopeVariant::Curly)),
+        ));
+        out
+    }
+}
+
+impl ast1::IntoChunks for MacroDefinition {
+    fn into_chunks(self) -> Vec<ast1::Chunk> {
+        self.flatten()
+            .into_iter()
+            .flat_map(ast1::IntoChunks::into_chunks)
+            .collect()
+    }
+}
+
+impl From<ast3::MacroDefinition> for MacroDefinition {
+    fn from(value: ast3::MacroDefinition) -> Self {
+        let (command, prec, label, parameters, body) = value.decompose();
+        let chunks = |chunks: Vec<ast3::Chunk>| {
+            chunks
+                .into_iter()
+                .flat_map(|chunk| chunk.into_chunks())
+                .collect::<Vec<_>>()
+        };
+
+        Self::new_unchecked(command, prec, label, chunks(parameters), chunks(body))
+    }
+}
+
+fn concat(chunks: &[Chunk]) -> String {
+    chunks.iter().map(ToString::to_string).collect()
+}
+
+fn newlines(s: &str) -> u32 {
+    s.matches('\n').count() as u32
+}
+
+// lines added by the chunks
+fn lines(chunks: &[Chunk]) -> u32 {
+    chunks.iter().map(|chunk| chunk.lines() - 1).sum()
+}
+
+// a defining command without arguments, which may start a definition
+fn is_def(chunk: &Chunk) -> bool {
+    matches!(
+        chunk.variant(),
+        ChunkVariant::Command(command)
+            if command.arguments().is_empty() && DEF_COMMANDS.contains(&command.label().as_str())
+    )
+}
+
+// where the parts of a definition are in the chunks after its defining command
+struct Found {
+    // the name is after whitespace
+    prec: bool,
+    // the chunk the body is in, and which argument of it if it is a command
+    body: usize,
+    argument: Option<usize>,
+}
+
+// find the name and the body of a definition in the chunks after its defining command
+fn find(chunks: &[Chunk]) -> Option<Found> {
+    let prec = matches!(chunks.first()?.variant(), ChunkVariant::Text(s) if s.trim().is_empty());
+    let name = prec as usize;
+
+    if !matches!(chunks.get(name)?.variant(), ChunkVariant::Command(_)) {
+        return None;
+    }
+
+    // the parameter text ends at the first brace, which ast1 may take as an argument
+    for (index, chunk) in chunks.iter().enumerate().skip(name) {
+        match chunk.variant() {
+            ChunkVariant::Command(command) => {
+                let argument = command
+                    .arguments()
+                    .iter()
+                    .position(|(_, arg)| arg.variant() == ScopeVariant::Curly);
+
+                if argument.is_some() {
+                    return Some(Found {
+                        prec,
+                        body: index,
+                        argument,
+                    });
+                }
+            }
+            ChunkVariant::Scope(scope) if scope.variant() == ScopeVariant::Curly => {
+                return Some(Found {
+                    prec,
+                    body: index,
+                    argument: None,
+                })
+            }
+            ChunkVariant::Environment(_) | ChunkVariant::MacroDefinition(_) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// fold definitions from the left, a definition in the parameter text of another is part
+// of its delimiters
+pub(super) fn group(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    if !chunks.iter().any(is_def) {
+        return chunks;
+    }
+
+    let mut out = Vec::with_capacity(chunks.len());
+    let mut rest = VecDeque::from(chunks);
+
+    while let Some(chunk) = rest.pop_front() {
+        let found = match is_def(&chunk) {
+            true => find(rest.make_contiguous()),
+            false => None,
+        };
+        let Some(found) = found else {
+            out.push(chunk);
+            continue;
+        };
+
+        let (line_no, variant) = chunk.decompose();
+        let ChunkVariant::Command(command) = variant else {
+            unreachable!("definition not starting with a command")
+        };
+
+        let mut taken = rest
+            .drain(..=found.body)
+            .map(Chunk::variant_owned)
+            .collect::<VecDeque<_>>();
+
+        let prec = match found.prec {
+            true => match taken.pop_front() {
+                Some(ChunkVariant::Text(s)) => s,
+                _ => unreachable!("whitespace which is not text"),
+            },
+            false => String::new(),
+        };
+
+        // split the chunk with the body into the delimiters before it, the body and the
+        // arguments after it
+        let (body, after) = match (taken.pop_back(), found.argument) {
+            (Some(ChunkVariant::Scope(scope)), None) => (scope, Vec::new()),
+            (Some(ChunkVariant::Command(holder)), Some(argument)) => {
+                let (label, mut arguments) = holder.decompose();
+                let after = arguments.split_off(argument + 1);
+                let (prec, body) = arguments.pop().unwrap();
+
+                taken.push_back(ChunkVariant::Command(Command::new_unchecked(
+                    label, arguments,
+                )));
+                if !prec.is_empty() {
+                    taken.push_back(ChunkVariant::Text(prec));
+                }
+
+                let after = explode(Command::new_unchecked(String::new(), after))
+                    .into_iter()
+                    .skip(1)
+                    .collect();
+                (body, after)
+            }
+            _ => unreachable!("body which is not a scope or an argument"),
+        };
+
+        let name = match taken.pop_front() {
+            Some(ChunkVariant::Command(name)) => name,
+            _ => unreachable!("name which is not a command"),
+        };
+        let (label, arguments) = name.decompose();
+
+        // commands in the parameter text are delimiters, which do not take arguments
+        let parameters = explode(Command::new_unchecked(String::new(), arguments))
+            .into_iter()
+            .skip(1)
+            .chain(taken.into_iter().flat_map(|variant| match variant {
+                ChunkVariant::Command(command) => explode(command),
+                variant => vec![variant],
+            }))
+            .collect();
+
+        let definition = MacroDefinition::new_unchecked(
+            command.label_owned(),
+            prec,
+            label,
+            numbered(parameters),
+            body.chunks_owned(),
+        );
+        let mut after_line_no = line_no + definition.lines() - 1;
+        out.push(Chunk::new_unchecked(
+            line_no,
+            ChunkVariant::MacroDefinition(definition),
+        ));
+
+        // arguments after the body follow the definition
+        for variant in after {
+            let lines = variant.lines();
+            out.push(Chunk::new_unchecked(after_line_no, variant));
+            after_line_no += lines - 1;
+        }
+    }
+
+    out
+}
+
+// numbered parameters in the chunks, including those in delimiters such as `[#1]`
+fn arity(chunks: &[Chunk]) -> usize {
+    chunks
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Parameter(parameter) => parameter.number().is_some() as usize,
+            ChunkVariant::Scope(scope) => arity(scope.chunks()),
+            _ => 0,
+        })
+        .sum()
+}
@@ -8,6 +8,9 @@
 //! |Scope|Scope to represent grouping of elements.|
 //! |Command|`\commandName` followed by multiple arguments.|
 //! |Environment|Content between a `\begin{label}{arguments...}` and a `\end{label}`.|
+//! |Conditional|`\if...` test and its branches up to the matching `\fi`.|
+//! |MacroDefinition|`\def\name` followed by a parameter text and a body.|
+//! |Parameter|`#1` and similar parameter tokens.|
 //!
 //! The stage 2 AST can be reconstructed a one-to-one copy
 //! of the stage 1 AST, and hence the original document with no loss of information.
@@ -22,17 +25,30 @@
 mod chunk;
 mod chunkvariant;
 mod command;
+mod conditional;
 mod document;
+mod edit;
 mod environment;
+mod fold;
 mod into_chunks;
+mod macrodefinition;
+mod parameter;
+mod query;
 mod scope;
 mod scopevariant;
+pub mod visit;
 
 pub use chunk::Chunk;
 pub use chunkvariant::ChunkVariant;
 pub use command::Command;
+pub use conditional::{is_conditional, Conditional, NOT_CONDITIONALS};
+pub(crate) use conditional::{is_keyword, DEFINITIONS};
 pub use document::Document;
 pub use environment::Environment;
 pub use into_chunks::IntoChunks;
+pub use macrodefinition::{MacroDefinition, DEF_COMMANDS};
+pub use parameter::Parameter;
+pub use query::{Match, Node, Query};
 pub use scope::Scope;
 pub use scopevariant::ScopeVariant;
+pub use visit::{Fold, Visit, VisitMut};
@@ -0,0 +1,148 @@
+use std::fmt::Display;
+
+use crate::{
+    ast3,
+    traits::{Lines, Validate},
+    InternalError,
+};
+
+use super::{Chunk, ChunkVariant};
+
+/// A parameter token, such as `#1` in the body of a macro
+///
+/// Each `##` in a body stands for a single `#` once the macro is used, so `##1` is a
+/// parameter of a macro defined by the body. `#` without a number is the `#{` at the end
+/// of a parameter text, or a stray `#`.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Parameter {
+    hashes: usize,
+    number: Option<u8>,
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&"#".repeat(self.hashes))?;
+
+        match self.number {
+            Some(number) => f.write_fmt(format_args!("{number}")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Parameter {
+    /// Constructs a new Parameter
+    pub fn new(hashes: usize, number: Option<u8>) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(hashes, number);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new Parameter without checking
+    pub fn new_unchecked(hashes: usize, number: Option<u8>) -> Self {
+        Self { hashes, number }
+    }
+
+    /// Returns the number of `#` characters
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the number of the parameter, from 1 to 9
+    pub fn number(&self) -> Option<u8> {
+        self.number
+    }
+
+    /// Returns all fields of this struct
+    pub fn decompose(self) -> (usize, Option<u8>) {
+        (self.hashes, self.number)
+    }
+}
+
+impl Validate for Parameter {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if self.hashes == 0 || self.number.is_some_and(|number| !(1..=9).contains(&number)) {
+            return Err(InternalError::InvalidParameter);
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for Parameter {
+    fn lines(&self) -> u32 {
+        1
+    }
+}
+
+impl From<ast3::Parameter> for Parameter {
+    fn from(value: ast3::Parameter) -> Self {
+        let (hashes, number) = value.decompose();
+        Self::new_unchecked(hashes, number)
+    }
+}
+
+/// Split `#` runs out of text, with the digit after each one
+pub(super) fn split(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    if !chunks
+        .iter()
+        .any(|chunk| matches!(chunk.variant(), ChunkVariant::Text(s) if s.contains('#')))
+    {
+        return chunks;
+    }
+
+    let mut out = Vec::with_capacity(chunks.len());
+
+    for chunk in chunks {
+        let (mut line_no, variant) = chunk.decompose();
+        let ChunkVariant::Text(s) = variant else {
+            out.push(Chunk::new_unchecked(line_no, variant));
+            continue;
+        };
+
+        let mut rest = s.as_str();
+
+        while let Some(start) = rest.find('#') {
+            let text = &rest[..start];
+            if !text.is_empty() {
+                out.push(Chunk::new_unchecked(
+                    line_no,
+                    ChunkVariant::Text(text.to_string()),
+                ));
+                line_no += text.matches('\n').count() as u32;
+            }
+
+            rest = &rest[start..];
+            let hashes = rest.len() - rest.trim_start_matches('#').len();
+            rest = &rest[hashes..];
+
+            let number = rest
+                .chars()
+                .next()
+                .and_then(|c| c.to_digit(10))
+                .filter(|digit| *digit != 0);
+            if number.is_some() {
+                rest = &rest[1..];
+            }
+
+            out.push(Chunk::new_unchecked(
+                line_no,
+                ChunkVariant::Parameter(Parameter::new_unchecked(
+                    hashes,
+                    number.map(|digit| digit as u8),
+                )),
+            ));
+        }
+
+        if !rest.is_empty() {
+            out.push(Chunk::new_unchecked(
+                line_no,
+                ChunkVariant::Text(rest.to_string()),
+            ));
+        }
+    }
+
+    out
+}
@@ -4,10 +4,10 @@ use crate::{
     ast1::{self, IntoChunks},
     ast3,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
-use super::{Chunk, Document, IntoChunks as IntoChunks2, ScopeVariant};
+use super::{fold, Chunk, Document, IntoChunks as IntoChunks2, ScopeVariant};
 
 /// A scoped block
 ///
@@ -51,7 +51,7 @@ impl Scope {
 impl Validate for Scope {
     fn validate(&self) -> Result<(), crate::InternalError> {
         for chunk in self.chunks() {
-            chunk.validate()?
+            chunk.validate_in_group()?
         }
 
         Ok(())
@@ -86,6 +86,11 @@ impl Scope {
         &self.chunks
     }
 
+    /// Returns all mutable chunks within
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.chunks
+    }
+
     /// Returns all owned chunks within
     pub fn chunks_owned(self) -> Vec<Chunk> {
         self.chunks
@@ -105,10 +110,21 @@ impl Scope {
 impl TryFrom<crate::ast1::Scope> for Scope {
     type Error = crate::Error;
 
+    /// Error positions are relative to the opening parenthesis of the scope
     fn try_from(value: crate::ast1::Scope) -> Result<Self, Self::Error> {
+        let (chunks, variant) = Self::from_ast1(value)?.decompose();
+        let chunks = fold::fold(chunks, 1).map_err(|e| e.offset(Position::new(1, 2)))?;
+        Ok(Self::new_unchecked(chunks, variant))
+    }
+}
+
+impl Scope {
+    // folds environments, conditionals are folded once the whole tree is built
+    pub(super) fn from_ast1(value: crate::ast1::Scope) -> Result<Self, crate::Error> {
         Ok(Self {
             variant: value.variant().into(),
-            chunks: Document::try_from(ast1::Document::new_unchecked(value.chunks_owned()))?
+            chunks: Document::from_ast1(ast1::Document::new_unchecked(value.chunks_owned()), true)
+                .map_err(|e| e.offset(Position::new(1, 2)))?
                 .chunks_owned(),
         })
     }
@@ -0,0 +1,385 @@
+//! Traversal of the stage 2 AST
+//!
+//! Every method of `Visit`, `VisitMut` and `Fold` defaults to recursing into all children
+//! of the node, including command and environment arguments, the branches of conditionals
+//! and the parameter text and body of macro definitions, so implementors only need to
+//! override the methods for the nodes they care about. The `walk_*` and `fold_*` functions
+//! are the default behaviours, call them from an overridden method to keep recursing.
+//!
+//! Note that `VisitMut` and `Fold` do not update line numbers, changing the number of
+//! lines in a node leaves the tree with inconsistent line numbers until `Document::renumber`
+//! is called.
+
+use super::{
+    Chunk, ChunkVariant, Command, Conditional, Document, Environment, MacroDefinition, Parameter,
+    Scope,
+};
+
+/// Walks a stage 2 AST by reference
+pub trait Visit {
+    /// Visit a whole document
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document)
+    }
+
+    /// Visit a chunk
+    fn visit_chunk(&mut self, chunk: &Chunk) {
+        walk_chunk(self, chunk)
+    }
+
+    /// Visit a block of text
+    fn visit_text(&mut self, _text: &String) {}
+
+    /// Visit a parameter token
+    fn visit_parameter(&mut self, _parameter: &Parameter) {}
+
+    /// Visit a command
+    fn visit_command(&mut self, command: &Command) {
+        walk_command(self, command)
+    }
+
+    /// Visit a scope which is not an argument
+    fn visit_scope(&mut self, scope: &Scope) {
+        walk_scope(self, scope)
+    }
+
+    /// Visit an environment
+    fn visit_environment(&mut self, environment: &Environment) {
+        walk_environment(self, environment)
+    }
+
+    /// Visit a conditional
+    fn visit_conditional(&mut self, conditional: &Conditional) {
+        walk_conditional(self, conditional)
+    }
+
+    /// Visit a macro definition
+    fn visit_macro_definition(&mut self, definition: &MacroDefinition) {
+        walk_macro_definition(self, definition)
+    }
+
+    /// Visit an argument of a command or environment
+    fn visit_argument(&mut self, _prec: &String, argument: &Scope) {
+        walk_scope(self, argument)
+    }
+}
+
+/// Visit every chunk of the document
+pub fn walk_document<V: Visit + ?Sized>(visitor: &mut V, document: &Document) {
+    walk_chunks(visitor, document.chunks())
+}
+
+/// Visit every chunk in order
+pub fn walk_chunks<V: Visit + ?Sized>(visitor: &mut V, chunks: &[Chunk]) {
+    chunks.iter().for_each(|chunk| visitor.visit_chunk(chunk))
+}
+
+/// Visit the variant of a chunk
+pub fn walk_chunk<V: Visit + ?Sized>(visitor: &mut V, chunk: &Chunk) {
+    match chunk.variant() {
+        ChunkVariant::Text(s) => visitor.visit_text(s),
+        ChunkVariant::Command(c) => visitor.visit_command(c),
+        ChunkVariant::Scope(sc) => visitor.visit_scope(sc),
+        ChunkVariant::Environment(env) => visitor.visit_environment(env),
+        ChunkVariant::Conditional(c) => visitor.visit_conditional(c),
+        ChunkVariant::Parameter(p) => visitor.visit_parameter(p),
+        ChunkVariant::MacroDefinition(d) => visitor.visit_macro_definition(d),
+    }
+}
+
+/// Visit the arguments of a command
+pub fn walk_command<V: Visit + ?Sized>(visitor: &mut V, command: &Command) {
+    for (prec, arg) in command.arguments() {
+        visitor.visit_argument(prec, arg)
+    }
+}
+
+/// Visit the content of a scope
+pub fn walk_scope<V: Visit + ?Sized>(visitor: &mut V, scope: &Scope) {
+    walk_chunks(visitor, scope.chunks())
+}
+
+/// Visit the arguments, then the content of an environment
+pub fn walk_environment<V: Visit + ?Sized>(visitor: &mut V, environment: &Environment) {
+    for (prec, arg) in environment.arguments() {
+        visitor.visit_argument(prec, arg)
+    }
+
+    walk_chunks(visitor, environment.content())
+}
+
+/// Visit the condition, then every branch of a conditional
+pub fn walk_conditional<V: Visit + ?Sized>(visitor: &mut V, conditional: &Conditional) {
+    conditional
+        .parts()
+        .for_each(|part| walk_chunks(visitor, part))
+}
+
+/// Visit the parameter text, then the body of a macro definition
+pub fn walk_macro_definition<V: Visit + ?Sized>(visitor: &mut V, definition: &MacroDefinition) {
+    walk_chunks(visitor, definition.parameters());
+    walk_chunks(visitor, definition.body())
+}
+
+/// Walks a stage 2 AST by mutable reference
+pub trait VisitMut {
+    /// Visit a whole document
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document)
+    }
+
+    /// Visit a chunk
+    fn visit_chunk_mut(&mut self, chunk: &mut Chunk) {
+        walk_chunk_mut(self, chunk)
+    }
+
+    /// Visit a block of text
+    fn visit_text_mut(&mut self, _text: &mut String) {}
+
+    /// Visit a parameter token
+    fn visit_parameter_mut(&mut self, _parameter: &mut Parameter) {}
+
+    /// Visit a command
+    fn visit_command_mut(&mut self, command: &mut Command) {
+        walk_command_mut(self, command)
+    }
+
+    /// Visit a scope which is not an argument
+    fn visit_scope_mut(&mut self, scope: &mut Scope) {
+        walk_scope_mut(self, scope)
+    }
+
+    /// Visit an environment
+    fn visit_environment_mut(&mut self, environment: &mut Environment) {
+        walk_environment_mut(self, environment)
+    }
+
+    /// Visit a conditional
+    fn visit_conditional_mut(&mut self, conditional: &mut Conditional) {
+        walk_conditional_mut(self, conditional)
+    }
+
+    /// Visit a macro definition
+    fn visit_macro_definition_mut(&mut self, definition: &mut MacroDefinition) {
+        walk_macro_definition_mut(self, definition)
+    }
+
+    /// Visit an argument of a command or environment
+    fn visit_argument_mut(&mut self, _prec: &mut String, argument: &mut Scope) {
+        walk_scope_mut(self, argument)
+    }
+}
+
+/// Visit every chunk of the document
+pub fn walk_document_mut<V: VisitMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    walk_chunks_mut(visitor, document.chunks_mut())
+}
+
+/// Visit every chunk in order
+pub fn walk_chunks_mut<V: VisitMut + ?Sized>(visitor: &mut V, chunks: &mut [Chunk]) {
+    chunks
+        .iter_mut()
+        .for_each(|chunk| visitor.visit_chunk_mut(chunk))
+}
+
+/// Visit the variant of a chunk
+pub fn walk_chunk_mut<V: VisitMut + ?Sized>(visitor: &mut V, chunk: &mut Chunk) {
+    match chunk.variant_mut() {
+        ChunkVariant::Text(s) => visitor.visit_text_mut(s),
+        ChunkVariant::Command(c) => visitor.visit_command_mut(c),
+        ChunkVariant::Scope(sc) => visitor.visit_scope_mut(sc),
+        ChunkVariant::Environment(env) => visitor.visit_environment_mut(env),
+        ChunkVariant::Conditional(c) => visitor.visit_conditional_mut(c),
+        ChunkVariant::Parameter(p) => visitor.visit_parameter_mut(p),
+        ChunkVariant::MacroDefinition(d) => visitor.visit_macro_definition_mut(d),
+    }
+}
+
+/// Visit the arguments of a command
+pub fn walk_command_mut<V: VisitMut + ?Sized>(visitor: &mut V, command: &mut Command) {
+    for (prec, arg) in command.arguments_mut() {
+        visitor.visit_argument_mut(prec, arg)
+    }
+}
+
+/// Visit the content of a scope
+pub fn walk_scope_mut<V: VisitMut + ?Sized>(visitor: &mut V, scope: &mut Scope) {
+    walk_chunks_mut(visitor, scope.chunks_mut())
+}
+
+/// Visit the arguments, then the content of an environment
+pub fn walk_environment_mut<V: VisitMut + ?Sized>(visitor: &mut V, environment: &mut Environment) {
+    for (prec, arg) in environment.arguments_mut() {
+        visitor.visit_argument_mut(prec, arg)
+    }
+
+    walk_chunks_mut(visitor, environment.content_mut())
+}
+
+/// Visit the condition, then every branch of a conditional
+pub fn walk_conditional_mut<V: VisitMut + ?Sized>(visitor: &mut V, conditional: &mut Conditional) {
+    conditional
+        .parts_mut()
+        .for_each(|part| walk_chunks_mut(visitor, part))
+}
+
+/// Visit the parameter text, then the body of a macro definition
+pub fn walk_macro_definition_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    definition: &mut MacroDefinition,
+) {
+    walk_chunks_mut(visitor, definition.parameters_mut());
+    walk_chunks_mut(visitor, definition.body_mut())
+}
+
+/// Transforms a stage 2 AST by value
+pub trait Fold {
+    /// Fold a whole document
+    fn fold_document(&mut self, document: Document) -> Document {
+        fold_document(self, document)
+    }
+
+    /// Fold a chunk
+    fn fold_chunk(&mut self, chunk: Chunk) -> Chunk {
+        fold_chunk(self, chunk)
+    }
+
+    /// Fold a block of text
+    fn fold_text(&mut self, text: String) -> String {
+        text
+    }
+
+    /// Fold a parameter token
+    fn fold_parameter(&mut self, parameter: Parameter) -> Parameter {
+        parameter
+    }
+
+    /// Fold a command
+    fn fold_command(&mut self, command: Command) -> Command {
+        fold_command(self, command)
+    }
+
+    /// Fold a scope which is not an argument
+    fn fold_scope(&mut self, scope: Scope) -> Scope {
+        fold_scope(self, scope)
+    }
+
+    /// Fold an environment
+    fn fold_environment(&mut self, environment: Environment) -> Environment {
+        fold_environment(self, environment)
+    }
+
+    /// Fold a conditional
+    fn fold_conditional(&mut self, conditional: Conditional) -> Conditional {
+        fold_conditional(self, conditional)
+    }
+
+    /// Fold a macro definition
+    fn fold_macro_definition(&mut self, definition: MacroDefinition) -> MacroDefinition {
+        fold_macro_definition(self, definition)
+    }
+
+    /// Fold an argument of a command or environment
+    fn fold_argument(&mut self, prec: String, argument: Scope) -> (String, Scope) {
+        (prec, fold_scope(self, argument))
+    }
+}
+
+/// Fold every chunk of the document
+pub fn fold_document<F: Fold + ?Sized>(folder: &mut F, document: Document) -> Document {
+    Document::new_unchecked(fold_chunks(folder, document.chunks_owned()))
+}
+
+/// Fold every chunk in order
+pub fn fold_chunks<F: Fold + ?Sized>(folder: &mut F, chunks: Vec<Chunk>) -> Vec<Chunk> {
+    chunks
+        .into_iter()
+        .map(|chunk| folder.fold_chunk(chunk))
+        .collect()
+}
+
+/// Fold every argument in order
+pub fn fold_arguments<F: Fold + ?Sized>(
+    folder: &mut F,
+    arguments: Vec<(String, Scope)>,
+) -> Vec<(String, Scope)> {
+    arguments
+        .into_iter()
+        .map(|(prec, arg)| folder.fold_argument(prec, arg))
+        .collect()
+}
+
+/// Fold the variant of a chunk
+pub fn fold_chunk<F: Fold + ?Sized>(folder: &mut F, chunk: Chunk) -> Chunk {
+    let (line_no, variant) = chunk.decompose();
+
+    Chunk::new_unchecked(
+        line_no,
+        match variant {
+            ChunkVariant::Text(s) => ChunkVariant::Text(folder.fold_text(s)),
+            ChunkVariant::Command(c) => ChunkVariant::Command(folder.fold_command(c)),
+            ChunkVariant::Scope(sc) => ChunkVariant::Scope(folder.fold_scope(sc)),
+            ChunkVariant::Environment(env) => {
+                ChunkVariant::Environment(folder.fold_environment(env))
+            }
+            ChunkVariant::Conditional(c) => ChunkVariant::Conditional(folder.fold_conditional(c)),
+            ChunkVariant::Parameter(p) => ChunkVariant::Parameter(folder.fold_parameter(p)),
+            ChunkVariant::MacroDefinition(d) => {
+                ChunkVariant::MacroDefinition(folder.fold_macro_definition(d))
+            }
+        },
+    )
+}
+
+/// Fold the arguments of a command
+pub fn fold_command<F: Fold + ?Sized>(folder: &mut F, command: Command) -> Command {
+    let (label, arguments) = command.decompose();
+    Command::new_unchecked(label, fold_arguments(folder, arguments))
+}
+
+/// Fold the content of a scope
+pub fn fold_scope<F: Fold + ?Sized>(folder: &mut F, scope: Scope) -> Scope {
+    let (chunks, variant) = scope.decompose();
+    Scope::new_unchecked(fold_chunks(folder, chunks), variant)
+}
+
+/// Fold the arguments, then the content of an environment
+pub fn fold_environment<F: Fold + ?Sized>(folder: &mut F, environment: Environment) -> Environment {
+    let (label, arguments, content, prec_begin, prec_end) = environment.decompose();
+    let arguments = fold_arguments(folder, arguments);
+
+    Environment::new_unchecked(
+        label,
+        arguments,
+        fold_chunks(folder, content),
+        prec_begin,
+        prec_end,
+    )
+}
+
+/// Fold the condition, then every branch of a conditional
+pub fn fold_conditional<F: Fold + ?Sized>(folder: &mut F, conditional: Conditional) -> Conditional {
+    let unless = conditional.unless().cloned();
+    let (label, condition, content, cases, otherwise) = conditional.decompose();
+    let condition = fold_chunks(folder, condition);
+    let content = fold_chunks(folder, content);
+    let cases = cases
+        .into_iter()
+        .map(|case| fold_chunks(folder, case))
+        .collect();
+    let otherwise = otherwise.map(|otherwise| fold_chunks(folder, otherwise));
+
+    Conditional::new_unchecked(label, condition, content, cases, otherwise).with_unless(unless)
+}
+
+/// Fold the parameter text, then the body of a macro definition
+pub fn fold_macro_definition<F: Fold + ?Sized>(
+    folder: &mut F,
+    definition: MacroDefinition,
+) -> MacroDefinition {
+    let (command, prec, label, parameters, body) = definition.decompose();
+    let parameters = fold_chunks(folder, parameters);
+    let body = fold_chunks(folder, body);
+
+    MacroDefinition::new_unchecked(command, prec, label, parameters, body)
+}
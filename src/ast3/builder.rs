@@ -0,0 +1,269 @@
+use super::{
+    escape, Chunk, ChunkVariant, Chunks, Command, Document, EscapeMode, Scope, ScopeVariant,
+};
+
+/// Builds a document from text, escaping it and numbering lines as it goes
+///
+/// Blocks such as sections and paragraphs are separated by blank lines.
+///
+/// ```
+/// use latex_ast::ast3::DocumentBuilder;
+///
+/// let document = DocumentBuilder::article()
+///     .usepackage("amsmath")
+///     .section("Intro")
+///     .paragraph("Costs 5% & more")
+///     .build();
+///
+/// assert_eq!(
+///     document.to_string(),
+///     "\\documentclass{article}\n\\usepackage{amsmath}\n\n\\begin{document}\n\\section{Intro}\n\nCosts 5\\% \\& more\n\\end{document}\n"
+/// );
+/// ```
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct DocumentBuilder {
+    documentclass: String,
+    options: Vec<String>,
+    packages: Vec<String>,
+    preamble: Vec<(String, String)>,
+    blocks: Vec<Vec<Chunk>>,
+}
+
+impl DocumentBuilder {
+    /// Start a document of a class
+    pub fn new(documentclass: &str) -> Self {
+        Self {
+            documentclass: documentclass.to_string(),
+            options: Vec::new(),
+            packages: Vec::new(),
+            preamble: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Start an `article`
+    pub fn article() -> Self {
+        Self::new("article")
+    }
+
+    /// Start a `report`
+    pub fn report() -> Self {
+        Self::new("report")
+    }
+
+    /// Start a `book`
+    pub fn book() -> Self {
+        Self::new("book")
+    }
+
+    /// Add an option to `\documentclass`, such as `11pt`
+    pub fn option(mut self, option: &str) -> Self {
+        self.options.push(option.to_string());
+        self
+    }
+
+    /// Load a package, once
+    pub fn usepackage(mut self, package: &str) -> Self {
+        if !self.packages.iter().any(|other| other == package) {
+            self.packages.push(package.to_string());
+        }
+        self
+    }
+
+    /// Set `\title`
+    pub fn title(self, title: &str) -> Self {
+        self.preamble_command("title", title)
+    }
+
+    /// Set `\author`
+    pub fn author(self, author: &str) -> Self {
+        self.preamble_command("author", author)
+    }
+
+    /// Set `\date`
+    pub fn date(self, date: &str) -> Self {
+        self.preamble_command("date", date)
+    }
+
+    /// Add `\maketitle`
+    pub fn maketitle(mut self) -> Self {
+        let mut block = Chunks::default();
+        block.command("maketitle", Vec::new());
+        self.blocks.push(block.finish());
+        self
+    }
+
+    /// Add a `\chapter`
+    pub fn chapter(self, title: &str) -> Self {
+        self.heading("chapter", title)
+    }
+
+    /// Add a `\section`
+    pub fn section(self, title: &str) -> Self {
+        self.heading("section", title)
+    }
+
+    /// Add a `\subsection`
+    pub fn subsection(self, title: &str) -> Self {
+        self.heading("subsection", title)
+    }
+
+    /// Add a `\subsubsection`
+    pub fn subsubsection(self, title: &str) -> Self {
+        self.heading("subsubsection", title)
+    }
+
+    /// Add a paragraph of text
+    pub fn paragraph(mut self, text: &str) -> Self {
+        self.blocks.push(escape(text.trim()));
+        self
+    }
+
+    /// Add an `itemize` list
+    pub fn itemize<S: AsRef<str>>(self, items: impl IntoIterator<Item = S>) -> Self {
+        self.list("itemize", items)
+    }
+
+    /// Add an `enumerate` list
+    pub fn enumerate<S: AsRef<str>>(self, items: impl IntoIterator<Item = S>) -> Self {
+        self.list("enumerate", items)
+    }
+
+    /// Add chunks as a block, such as an environment
+    ///
+    /// The line numbers of the chunks are recalculated.
+    pub fn block(mut self, chunks: Vec<Chunk>) -> Self {
+        self.blocks.push(chunks);
+        self
+    }
+
+    /// Build the document
+    pub fn build(self) -> Document {
+        let mut class = vec![(
+            String::new(),
+            Scope::new_unchecked(
+                Chunks::from_text(&self.documentclass).finish(),
+                ScopeVariant::Curly,
+            ),
+        )];
+        if !self.options.is_empty() {
+            let options = Chunks::from_text(&self.options.join(","));
+            class.insert(
+                0,
+                (
+                    String::new(),
+                    Scope::new_unchecked(options.finish(), ScopeVariant::Square),
+                ),
+            );
+        }
+
+        let mut preamble = Chunks::default();
+        preamble.push(ChunkVariant::Command(Command::new_unchecked(
+            "documentclass".to_string(),
+            class,
+        )));
+
+        let documentoptions = self
+            .options
+            .iter()
+            .map(|option| match option.split_once('=') {
+                Some((key, value)) => (
+                    String::new(),
+                    key.to_string(),
+                    Some((String::new(), String::new(), value.to_string())),
+                    String::new(),
+                ),
+                None => (String::new(), option.to_string(), None, String::new()),
+            })
+            .collect::<Vec<_>>();
+
+        for package in &self.packages {
+            preamble.text("\n");
+            preamble.command("usepackage", vec![Chunks::from_text(package)]);
+        }
+
+        for (label, value) in self.preamble {
+            preamble.text("\n");
+            preamble.command(&label, vec![line(&value)]);
+        }
+
+        preamble.paragraph_break("");
+
+        let mut body = Chunks::default();
+        body.text("\n");
+
+        // an empty block would leave a blank line, which is read back as a paragraph break
+        let blocks = self.blocks.into_iter().filter(|block| !block.is_empty());
+
+        for (n, block) in blocks.enumerate() {
+            if n > 0 {
+                body.paragraph_break("");
+            }
+
+            for chunk in block {
+                body.push(chunk.variant_owned());
+            }
+        }
+
+        if body.0.len() > 1 {
+            body.text("\n");
+        }
+
+        let mut trailing = Chunks::default();
+        trailing.text("\n");
+
+        let mut document = Document::new_unchecked(
+            preamble.finish(),
+            Some(self.documentclass),
+            documentoptions,
+            body.finish(),
+            Vec::new(),
+            String::new(),
+            String::new(),
+            trailing.finish(),
+        );
+        document.renumber();
+        document
+    }
+
+    fn preamble_command(mut self, label: &str, value: &str) -> Self {
+        self.preamble.retain(|(other, _)| other != label);
+        self.preamble.push((label.to_string(), value.to_string()));
+        self
+    }
+
+    fn heading(mut self, label: &str, title: &str) -> Self {
+        let mut block = Chunks::default();
+        block.command(label, vec![line(title)]);
+        self.blocks.push(block.finish());
+        self
+    }
+
+    fn list<S: AsRef<str>>(mut self, label: &str, items: impl IntoIterator<Item = S>) -> Self {
+        let mut content = Chunks::default();
+
+        for item in items {
+            content.text("\n  ");
+            content.command("item", Vec::new());
+            content.text(" ");
+            escape(item.as_ref().trim())
+                .into_iter()
+                .for_each(|chunk| content.push(chunk.variant_owned()));
+        }
+        content.text("\n");
+
+        let mut block = Chunks::default();
+        block.environment(label, Vec::new(), content);
+        self.blocks.push(block.finish());
+        self
+    }
+}
+
+// escaped text on a single line
+fn line(text: &str) -> Chunks {
+    Chunks::escaped(
+        &text.split_whitespace().collect::<Vec<_>>().join(" "),
+        EscapeMode::Text,
+    )
+}
@@ -38,6 +38,11 @@ impl Chunk {
         self.line_no
     }
 
+    /// Returns the relative line number of current chunk (mut)
+    pub fn line_no_mut(&mut self) -> &mut u32 {
+        &mut self.line_no
+    }
+
     /// Returns the variant of current chunk
     pub fn variant(&self) -> &ChunkVariant {
         &self.variant
@@ -65,6 +70,18 @@ impl Validate for Chunk {
     }
 }
 
+impl Chunk {
+    // validate a chunk in a group, where a `\begin` or `\end` without its pair is a plain command
+    pub(crate) fn validate_in_group(&self) -> Result<(), crate::InternalError> {
+        match self.variant() {
+            ChunkVariant::Command(c) if matches!(c.label().as_str(), "begin" | "end") => {
+                c.arguments().iter().try_for_each(|(_, arg)| arg.validate())
+            }
+            _ => self.validate(),
+        }
+    }
+}
+
 impl Lines for Chunk {
     fn lines(&self) -> u32 {
         self.variant.lines()
@@ -100,6 +117,18 @@ impl IntoChunks for Chunk {
                 line_no,
                 ast2::ChunkVariant::Environment(env.into()),
             )],
+            ChunkVariant::Conditional(c) => vec![ast2::Chunk::new_unchecked(
+                line_no,
+                ast2::ChunkVariant::Conditional(c.into()),
+            )],
+            ChunkVariant::Parameter(p) => vec![ast2::Chunk::new_unchecked(
+                line_no,
+                ast2::ChunkVariant::Parameter(p.into()),
+            )],
+            ChunkVariant::MacroDefinition(d) => vec![ast2::Chunk::new_unchecked(
+                line_no,
+                ast2::ChunkVariant::MacroDefinition(d.into()),
+            )],
             ChunkVariant::ParagraphBreak(s) => vec![ast2::Chunk::new_unchecked(
                 line_no,
                 ast2::ChunkVariant::Text(s),
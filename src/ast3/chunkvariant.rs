@@ -5,7 +5,7 @@ use crate::{
     traits::{Lines, Validate},
 };
 
-use super::{Command, Environment, MathsBlock, Scope};
+use super::{Command, Conditional, Environment, MacroDefinition, MathsBlock, Parameter, Scope};
 
 /// Different types of things a chunk can be
 #[derive(Clone)]
@@ -24,6 +24,12 @@ pub enum ChunkVariant {
     Scope(Scope),
     /// An environmentnis a labelled scope with options
     Environment(Environment),
+    /// An `\if...` test with its branches
+    Conditional(Conditional),
+    /// A parameter token such as `#1`
+    Parameter(Parameter),
+    /// A `\def` and its parameter text and body
+    MacroDefinition(MacroDefinition),
 }
 
 impl Validate for ChunkVariant {
@@ -69,6 +75,9 @@ impl Validate for ChunkVariant {
             Self::Command(c) => return c.validate(),
             Self::Scope(sc) => return sc.validate(),
             Self::Environment(e) => return e.validate(),
+            Self::Conditional(c) => return c.validate(),
+            Self::Parameter(p) => return p.validate(),
+            Self::MacroDefinition(d) => return d.validate(),
         }
 
         Ok(())
@@ -85,6 +94,9 @@ impl Lines for ChunkVariant {
             Self::Scope(sc) => sc.lines(),
             Self::Environment(e) => e.lines(),
             Self::MathsBlock(mb) => mb.lines(),
+            Self::Conditional(c) => c.lines(),
+            Self::Parameter(p) => p.lines(),
+            Self::MacroDefinition(d) => d.lines(),
         }
     }
 }
@@ -97,6 +109,9 @@ impl Display for ChunkVariant {
             Self::Command(c) => c.fmt(f),
             Self::Scope(sc) => sc.fmt(f),
             Self::Environment(env) => env.fmt(f),
+            Self::Conditional(c) => c.fmt(f),
+            Self::Parameter(p) => p.fmt(f),
+            Self::MacroDefinition(d) => d.fmt(f),
         }
     }
 }
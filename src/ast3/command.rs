@@ -4,7 +4,7 @@ use std::fmt::Write;
 use crate::{
     ast1, ast2,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
 use super::Scope;
@@ -45,6 +45,11 @@ impl Command {
         &self.arguments
     }
 
+    /// Return mutable argument of the command
+    pub fn arguments_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.arguments
+    }
+
     /// Return owned label of the command
     pub fn label_owned(self) -> String {
         self.label
@@ -64,13 +69,23 @@ impl Command {
 impl TryFrom<ast2::Command> for Command {
     type Error = crate::Error;
 
+    /// Error positions are relative to the backslash of the command
     fn try_from(value: ast2::Command) -> Result<Self, Self::Error> {
         let (label, args) = value.decompose();
 
         let mut args_new = Vec::with_capacity(args.len());
 
         for (prec, scope) in args {
-            args_new.push((prec, scope.try_into()?));
+            // the position of the argument is only worked out for an error
+            let scope = scope.try_into().map_err(|e: crate::Error| {
+                e.offset(
+                    Position::start()
+                        .after(&format!("\\{label}"))
+                        .after_arguments(&args_new)
+                        .after(&prec),
+                )
+            })?;
+            args_new.push((prec, scope));
         }
 
         Ok(Self::new_unchecked(label, args_new))
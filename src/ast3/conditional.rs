@@ -0,0 +1,348 @@
+use std::fmt::Display;
+
+use crate::{
+    ast1, ast2,
+    traits::{Lines, Validate},
+    InternalError, Position,
+};
+
+use super::{Chunk, ChunkVariant, MathsBlock};
+
+/// A conditional is an `\if…` test and its branches up to the matching `\fi`
+///
+/// The content is read when the test is true, or for case 0 of `\ifcase`. Each `\or` starts
+/// another case, and `\else` starts the branch read when no other branch is.
+/// `\unless` right before the test is part of the conditional, and inverts the test.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Conditional {
+    // whitespace after `\unless`, when the test is inverted by it
+    unless: Option<String>,
+    label: String,
+    condition: Vec<Chunk>,
+    content: Vec<Chunk>,
+    cases: Vec<Vec<Chunk>>,
+    otherwise: Option<Vec<Chunk>>,
+}
+
+impl Conditional {
+    /// Constructs a new Conditional
+    pub fn new(
+        label: String,
+        condition: Vec<Chunk>,
+        content: Vec<Chunk>,
+        cases: Vec<Vec<Chunk>>,
+        otherwise: Option<Vec<Chunk>>,
+    ) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(label, condition, content, cases, otherwise);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new Conditional without checking
+    pub fn new_unchecked(
+        label: String,
+        condition: Vec<Chunk>,
+        content: Vec<Chunk>,
+        cases: Vec<Vec<Chunk>>,
+        otherwise: Option<Vec<Chunk>>,
+    ) -> Self {
+        Self {
+            unless: None,
+            label,
+            condition,
+            content,
+            cases,
+            otherwise,
+        }
+    }
+
+    /// Invert the test with `\unless` and the whitespace after it, or remove it with `None`
+    pub fn with_unless(mut self, unless: Option<String>) -> Self {
+        self.unless = unless;
+        self
+    }
+
+    /// Returns the whitespace between `\unless` and the test, if the test is inverted
+    pub fn unless(&self) -> Option<&String> {
+        self.unless.as_ref()
+    }
+
+    /// Returns the label of the test, such as `ifx`
+    pub fn label(&self) -> &String {
+        &self.label
+    }
+
+    /// Returns the test as it is written, such as `\ifx` or `\unless\ifx`
+    pub fn opening(&self) -> String {
+        match &self.unless {
+            Some(unless) => format!("\\unless{unless}\\{}", self.label),
+            None => format!("\\{}", self.label),
+        }
+    }
+
+    /// Returns the chunks read by the test, such as `\a\b` of `\ifx\a\b`
+    pub fn condition(&self) -> &Vec<Chunk> {
+        &self.condition
+    }
+
+    /// Returns the mutable chunks read by the test
+    pub fn condition_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.condition
+    }
+
+    /// Returns the chunks read when the test is true
+    pub fn content(&self) -> &Vec<Chunk> {
+        &self.content
+    }
+
+    /// Returns the mutable chunks read when the test is true
+    pub fn content_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.content
+    }
+
+    /// Returns the chunks after each `\or`
+    pub fn cases(&self) -> &Vec<Vec<Chunk>> {
+        &self.cases
+    }
+
+    /// Returns the mutable chunks after each `\or`
+    pub fn cases_mut(&mut self) -> &mut Vec<Vec<Chunk>> {
+        &mut self.cases
+    }
+
+    /// Returns the chunks after `\else`, if there is one
+    pub fn otherwise(&self) -> Option<&Vec<Chunk>> {
+        self.otherwise.as_ref()
+    }
+
+    /// Returns the mutable chunks after `\else`, if there is one
+    pub fn otherwise_mut(&mut self) -> Option<&mut Vec<Chunk>> {
+        self.otherwise.as_mut()
+    }
+
+    /// Returns the content of `Conditional`
+    #[allow(clippy::type_complexity)]
+    pub fn decompose(
+        self,
+    ) -> (
+        String,
+        Vec<Chunk>,
+        Vec<Chunk>,
+        Vec<Vec<Chunk>>,
+        Option<Vec<Chunk>>,
+    ) {
+        (
+            self.label,
+            self.condition,
+            self.content,
+            self.cases,
+            self.otherwise,
+        )
+    }
+
+    /// Whether the content is text as it is written, which is how the content of `\iffalse` is
+    /// kept when its braces do not balance, as TeX skips it without matching them
+    pub fn is_raw(&self) -> bool {
+        self.label == "iffalse"
+            && self.unless.is_none()
+            && self
+                .content
+                .iter()
+                .all(|chunk| matches!(chunk.variant(), ChunkVariant::Text(_)))
+            && self.content.iter().any(|chunk| match chunk.variant() {
+                ChunkVariant::Text(s) => s.chars().any(|c| {
+                    matches!(c, '\\' | '%')
+                        || ast1::ScopeVariant::is_opening(c)
+                        || ast1::ScopeVariant::is_closing(c)
+                }),
+                _ => false,
+            })
+    }
+
+    /// Returns the condition and every branch in order
+    pub fn parts(&self) -> impl Iterator<Item = &Vec<Chunk>> {
+        [&self.condition, &self.content]
+            .into_iter()
+            .chain(self.cases.iter())
+            .chain(self.otherwise.iter())
+    }
+
+    /// Returns every branch in order with the position it starts at, where `start` is the
+    /// position of the backslash of the test
+    pub fn branches(&self, start: Position) -> Vec<(Position, &Vec<Chunk>)> {
+        let mut position = start.after(&self.opening());
+        self.condition
+            .iter()
+            .for_each(|chunk| position.advance_str(&chunk.to_string()));
+
+        let keywords = std::iter::once("")
+            .chain(std::iter::repeat_n("\\or", self.cases.len()))
+            .chain(self.otherwise.as_ref().map(|_| "\\else"));
+
+        keywords
+            .zip(self.parts().skip(1))
+            .map(|(keyword, branch)| {
+                position.advance_str(keyword);
+                let start = position;
+                branch
+                    .iter()
+                    .for_each(|chunk| position.advance_str(&chunk.to_string()));
+                (start, branch)
+            })
+            .collect()
+    }
+
+    /// Returns the mutable condition and every branch in order
+    pub fn parts_mut(&mut self) -> impl Iterator<Item = &mut Vec<Chunk>> {
+        [&mut self.condition, &mut self.content]
+            .into_iter()
+            .chain(self.cases.iter_mut())
+            .chain(self.otherwise.iter_mut())
+    }
+}
+
+impl TryFrom<ast2::Conditional> for Conditional {
+    type Error = crate::Error;
+
+    /// Error positions are relative to the backslash of the `\if…` command, or of `\unless`
+    /// before it
+    fn try_from(value: ast2::Conditional) -> Result<Self, Self::Error> {
+        let unless = value.unless().cloned();
+        let opening = value.opening();
+        let raw = value.is_raw();
+        let (label, condition, content, cases, otherwise) = value.decompose();
+        let has_otherwise = otherwise.is_some();
+
+        let mut parts = Vec::with_capacity(cases.len() + 3);
+        for (index, chunks) in [condition, content]
+            .into_iter()
+            .chain(cases)
+            .chain(otherwise)
+            .enumerate()
+        {
+            if raw && index == 1 {
+                parts.push(chunks.into_iter().map(raw_text).collect());
+            } else {
+                Self::part(&opening, chunks, &mut parts, has_otherwise)?;
+            }
+        }
+
+        let otherwise = match has_otherwise {
+            true => parts.pop(),
+            false => None,
+        };
+        let mut parts = parts.into_iter();
+        let condition = parts.next().unwrap();
+        let content = parts.next().unwrap();
+        let cases = parts.collect();
+
+        Ok(Self::new_unchecked(label, condition, content, cases, otherwise).with_unless(unless))
+    }
+}
+
+impl Conditional {
+    // converts a part and adds it to `parts`, the parts before it, working out where it
+    // starts only for an error
+    fn part(
+        opening: &str,
+        chunks: Vec<ast2::Chunk>,
+        parts: &mut Vec<Vec<Chunk>>,
+        has_otherwise: bool,
+    ) -> Result<(), crate::Error> {
+        let chunks = MathsBlock::from_chunks(chunks)
+            .map_err(|e| e.offset(Self::part_start(opening, parts, has_otherwise)))?;
+        parts.push(chunks);
+        Ok(())
+    }
+
+    // where the part after `before` starts, relative to the start of the opening
+    fn part_start(opening: &str, before: &[Vec<Chunk>], has_otherwise: bool) -> Position {
+        // cases are after `\or` and the last part is after `\else` if there is one
+        let keyword = |index: usize, last: bool| match index {
+            0 | 1 => "",
+            _ if last && has_otherwise => "\\else",
+            _ => "\\or",
+        };
+
+        before
+            .iter()
+            .enumerate()
+            .fold(
+                Position::start().after(opening),
+                |position, (index, part)| position.after(keyword(index, false)).after_all(part),
+            )
+            .after(keyword(before.len(), true))
+    }
+}
+
+// a chunk of raw content, which is only text
+fn raw_text(chunk: ast2::Chunk) -> Chunk {
+    let (line_no, variant) = chunk.decompose();
+    let ast2::ChunkVariant::Text(s) = variant else {
+        unreachable!("raw content which is not text")
+    };
+    Chunk::new_unchecked(line_no, ChunkVariant::Text(s))
+}
+
+impl Validate for Conditional {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if !self.label.starts_with("if") {
+            return Err(InternalError::NotConditional);
+        }
+
+        for c in self.label.chars() {
+            if matches!(c, '\\' | '%')
+                || ast1::ScopeVariant::is_opening(c)
+                || ast1::ScopeVariant::is_closing(c)
+            {
+                return Err(InternalError::UnsanitisedCharInString(c));
+            }
+        }
+
+        // a conditional can be in a group, so its branches are checked as part of one
+        let raw = self.is_raw();
+        for (index, part) in self.parts().enumerate() {
+            if !(raw && index == 1) {
+                part.iter().try_for_each(Chunk::validate_in_group)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for Conditional {
+    fn lines(&self) -> u32 {
+        self.parts()
+            .flatten()
+            .map(|chunk| chunk.lines() - 1)
+            .sum::<u32>()
+            + self.unless.iter().flat_map(|s| s.matches('\n')).count() as u32
+            + 1
+    }
+}
+
+impl Display for Conditional {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let concat = |chunks: &[Chunk]| chunks.iter().map(ToString::to_string).collect::<String>();
+
+        f.write_fmt(format_args!(
+            "{}{}{}",
+            self.opening(),
+            concat(&self.condition),
+            concat(&self.content)
+        ))?;
+
+        for case in self.cases.iter() {
+            f.write_fmt(format_args!("\\or{}", concat(case)))?;
+        }
+
+        if let Some(otherwise) = &self.otherwise {
+            f.write_fmt(format_args!("\\else{}", concat(otherwise)))?;
+        }
+
+        f.write_str("\\fi")
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use super::{Chunk, ChunkVariant, Conditional, Document};
+
+/// Values of the tests which can be worked out without running TeX
+///
+/// `\iftrue` and `\iffalse` are always known, as are `\ifnum`, `\ifodd` and `\ifcase` with
+/// literal integers. Booleans created with `\newif\ifname` are known once they are given a
+/// value, either with [`Conditions::with_boolean`] or by reading a preamble.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Conditions {
+    booleans: BTreeMap<String, bool>,
+}
+
+// what the next commands of a preamble are read as
+enum Pending {
+    None,
+    // `\newif` followed by the boolean it creates
+    NewIf,
+    // `\let` followed by the boolean it sets, and the test it is set to
+    Let(Option<String>),
+}
+
+impl Conditions {
+    /// Create conditions where only tests of literals are known
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the booleans created and set in the preamble of a document
+    ///
+    /// `\newif\ifname` creates a boolean which is false, `\nametrue` and `\namefalse` set it,
+    /// as does `\let\ifname\iftrue`. Only the branches of conditionals which are known to be
+    /// read are followed, and settings inside scopes are ignored as they are local.
+    pub fn from_document(document: &Document) -> Self {
+        let mut out = Self::new();
+        out.read(document.chunks_preamable());
+        out
+    }
+
+    /// Set the value of a boolean, such as `draft` for `\ifdraft`
+    pub fn with_boolean(mut self, name: &str, value: bool) -> Self {
+        self.booleans.insert(name.to_string(), value);
+        self
+    }
+
+    /// Returns the value of a boolean, if known
+    pub fn boolean(&self, name: &str) -> Option<bool> {
+        self.booleans.get(name).copied()
+    }
+
+    /// Returns the branch of a conditional TeX reads, if the test is known
+    ///
+    /// The branch is empty when the test is false and there is no `\else`. A test inverted
+    /// by `\unless` reads the other branch.
+    pub fn evaluate<'a>(&self, conditional: &'a Conditional) -> Option<&'a [Chunk]> {
+        let case = match conditional.label().as_str() {
+            "ifcase" => integer(&text(conditional.condition())?)?,
+            "ifnum" => {
+                let condition = text(conditional.condition())?;
+                let (index, relation) = condition
+                    .char_indices()
+                    .find(|(_, c)| matches!(c, '<' | '=' | '>'))?;
+                let a = integer(&condition[..index])?;
+                let b = integer(&condition[index + relation.len_utf8()..])?;
+
+                let value = match relation {
+                    '<' => a < b,
+                    '=' => a == b,
+                    _ => a > b,
+                };
+                (!value).into()
+            }
+            "ifodd" => (integer(&text(conditional.condition())?)? % 2 == 0).into(),
+            label => (!self.test(label)?).into(),
+        };
+
+        // `\unless` inverts the test, which TeX does not allow for `\ifcase`
+        let case = match conditional.unless() {
+            Some(_) if conditional.label() == "ifcase" => return None,
+            Some(_) => 1 - case,
+            None => case,
+        };
+
+        let branch = match case {
+            0 => Some(conditional.content()),
+            case if case > 0 => conditional.cases().get(case as usize - 1),
+            _ => None,
+        };
+
+        Some(match branch.or(conditional.otherwise()) {
+            Some(branch) => branch,
+            None => &[],
+        })
+    }
+
+    /// Returns the branch TeX reads, or the content when the test is not known
+    pub fn evaluate_or_content<'a>(&self, conditional: &'a Conditional) -> &'a [Chunk] {
+        self.evaluate(conditional).unwrap_or(conditional.content())
+    }
+
+    // value of a test which does not read a condition
+    fn test(&self, label: &str) -> Option<bool> {
+        match label {
+            "iftrue" => Some(true),
+            "iffalse" => Some(false),
+            _ => self.boolean(label.strip_prefix("if")?),
+        }
+    }
+
+    // follow the settings of booleans in order
+    fn read(&mut self, chunks: &[Chunk]) {
+        let mut pending = Pending::None;
+
+        for chunk in chunks {
+            let command = match chunk.variant() {
+                ChunkVariant::Command(command) => command.label().as_str(),
+                ChunkVariant::Text(s) if s.chars().all(|c| c.is_whitespace() || c == '=') => {
+                    continue
+                }
+                ChunkVariant::Conditional(conditional) => {
+                    pending = Pending::None;
+                    if let Some(branch) = self.evaluate(conditional) {
+                        self.read(branch)
+                    }
+                    continue;
+                }
+                _ => {
+                    pending = Pending::None;
+                    continue;
+                }
+            };
+
+            pending = match (pending, command.strip_prefix("if")) {
+                (Pending::NewIf, Some(name)) => {
+                    self.booleans.insert(name.to_string(), false);
+                    Pending::None
+                }
+                (Pending::Let(None), Some(name)) => Pending::Let(Some(name.to_string())),
+                (Pending::Let(Some(name)), Some(_)) => {
+                    if let Some(value) = self.test(command) {
+                        self.booleans.insert(name, value);
+                    }
+                    Pending::None
+                }
+                _ => match command {
+                    "newif" => Pending::NewIf,
+                    "let" => Pending::Let(None),
+                    _ => {
+                        let setting = [("true", true), ("false", false)].into_iter().find_map(
+                            |(suffix, value)| Some((command.strip_suffix(suffix)?, value)),
+                        );
+
+                        if let Some((name, value)) = setting {
+                            if let Some(boolean) = self.booleans.get_mut(name) {
+                                *boolean = value
+                            }
+                        }
+                        Pending::None
+                    }
+                },
+            };
+        }
+    }
+}
+
+// the condition as text, if it is only text
+fn text(chunks: &[Chunk]) -> Option<String> {
+    chunks
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Text(s) => Some(s.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+// an integer with optional signs, in decimal, octal after `'` or hexadecimal after `"`
+fn integer(s: &str) -> Option<i64> {
+    let mut negative = false;
+    let mut rest = s.trim_start();
+
+    while let Some(sign) = rest.strip_prefix(['+', '-']) {
+        negative ^= rest.starts_with('-');
+        rest = sign.trim_start();
+    }
+
+    let rest = rest.trim_end();
+    let (digits, radix) = if let Some(octal) = rest.strip_prefix('\'') {
+        (octal, 8)
+    } else if let Some(hex) = rest.strip_prefix('"') {
+        (hex, 16)
+    } else {
+        (rest, 10)
+    };
+
+    // TeX only takes uppercase hexadecimal digits
+    if !digits
+        .chars()
+        .all(|c| c.is_digit(radix) && !c.is_ascii_lowercase())
+    {
+        return None;
+    }
+
+    let value = i64::from_str_radix(digits, radix).ok()?;
+
+    Some(if negative { -value } else { value })
+}
@@ -1,23 +1,27 @@
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+use std::{fmt::Display, str::FromStr};
 
 use crate::{
+    ast1::Catcodes,
     ast2,
     ast3::{Environment, MathsBlock, Paragraph},
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, ParseLimits, Position,
 };
 
-use super::{Chunk, ChunkVariant, Command, Scope};
+use super::{Chunk, ChunkVariant, Match, Node, Query, Scope};
 
 /// Main struct for stage 3 AST
 ///
-/// Display `{}` reconstructs the original document
+/// Display `{}` reconstructs the original document. A document without a `document`
+/// environment, such as a package, displays without one, and the body is closed with the
+/// whitespace found before `{document}` in its `\end`.
 #[derive(Default, Clone)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
 pub struct Document {
     documentclass: Option<String>,
     // prec, key, (equal prec, equal post, val), post
+    #[allow(clippy::type_complexity)]
     documentoptions: Vec<(String, String, Option<(String, String, String)>, String)>,
 
     preamable: Vec<Chunk>,
@@ -26,14 +30,21 @@ pub struct Document {
     body_args: Vec<(String, Scope)>,
     body_begin_prec: String,
     body_end_prec: String,
+    // whether the document has a `document` environment
+    has_body: bool,
 
     trailing: Vec<Chunk>,
 }
 
 impl Validate for Document {
     fn validate(&self) -> Result<(), crate::InternalError> {
-        for section in [&self.preamable, &self.body, &self.trailing] {
-            let mut expected_line = 1;
+        // trailing is numbered from the line it starts on, as it is parsed
+        for (section, first) in [
+            (&self.preamable, 1),
+            (&self.body, 1),
+            (&self.trailing, self.trailing_line_no()),
+        ] {
+            let mut expected_line = first;
 
             for chunk in section.iter() {
                 if chunk.line_no() != expected_line {
@@ -66,8 +77,23 @@ impl Lines for Document {
 }
 
 impl Document {
+    /// Parse a document, failing with [`ErrorType::LimitExceeded`](crate::ErrorType::LimitExceeded)
+    /// when it goes over any of the limits
+    pub fn parse_with_limits(s: &str, limits: ParseLimits) -> Result<Self, crate::Error> {
+        Self::parse_with_catcodes(s, limits, Catcodes::default())
+    }
+
+    /// Parse a document starting with the given catcodes, such as the source of a package
+    pub fn parse_with_catcodes(
+        s: &str,
+        limits: ParseLimits,
+        catcodes: Catcodes,
+    ) -> Result<Self, crate::Error> {
+        ast2::Document::parse_with_catcodes(s, limits, catcodes)?.try_into()
+    }
+
     /// Create new document
-    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
     pub fn new(
         preamable: Vec<Chunk>,
         documentclass: Option<String>,
@@ -93,7 +119,7 @@ impl Document {
     }
 
     /// Create new document without checking
-    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
     pub fn new_unchecked(
         preamable: Vec<Chunk>,
         documentclass: Option<String>,
@@ -112,6 +138,7 @@ impl Document {
             body_args,
             body_begin_prec,
             body_end_prec,
+            has_body: true,
             trailing,
         }
     }
@@ -131,6 +158,98 @@ impl Document {
         &self.trailing
     }
 
+    /// Returns the mutable chunks representing the preamable
+    pub fn chunks_preamable_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.preamable
+    }
+
+    /// Returns the mutable chunks representing the body
+    pub fn chunks_body_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.body
+    }
+
+    /// Returns the mutable chunks representing the trailing
+    pub fn chunks_trailing_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.trailing
+    }
+
+    /// Returns the document class, if declared
+    pub fn documentclass(&self) -> Option<&String> {
+        self.documentclass.as_ref()
+    }
+
+    /// Returns the options passed to `\documentclass`
+    #[allow(clippy::type_complexity)]
+    pub fn documentoptions(
+        &self,
+    ) -> &Vec<(String, String, Option<(String, String, String)>, String)> {
+        &self.documentoptions
+    }
+
+    /// Returns the arguments of `\begin{document}`, not including the label
+    pub fn body_args(&self) -> &Vec<(String, Scope)> {
+        &self.body_args
+    }
+
+    /// Returns the mutable arguments of `\begin{document}`, not including the label
+    pub fn body_args_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.body_args
+    }
+
+    /// Returns the string between `\begin` and `{document}`
+    pub fn body_begin_prec(&self) -> &String {
+        &self.body_begin_prec
+    }
+
+    /// Returns the string between `\end` and `{document}`
+    pub fn body_end_prec(&self) -> &String {
+        &self.body_end_prec
+    }
+
+    /// Index all nodes of the document for searching
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    /// Returns the path from the outermost to the innermost node containing the position
+    pub fn node_at(&self, position: Position) -> Vec<Match<'_>> {
+        self.query()
+            .node_at(position)
+            .into_iter()
+            .copied()
+            .collect()
+    }
+
+    /// Returns the start position and the position right after the end of a node
+    /// borrowed from this document
+    pub fn span_of(&self, node: Node) -> Option<(Position, Position)> {
+        self.query()
+            .locate(node)
+            .map(|found| (found.start(), found.end()))
+    }
+
+    // the line trailing starts on, after the preamable and the body
+    pub(super) fn trailing_line_no(&self) -> u32 {
+        let mut position = Position::start().after_all(&self.preamable);
+
+        if self.has_body {
+            position = position
+                .after(&format!("\\begin{}{{document}}", self.body_begin_prec))
+                .after_arguments(&self.body_args)
+                .after_all(&self.body)
+                .after(&format!("\\end{}{{document}}", self.body_end_prec));
+        }
+
+        position.line
+    }
+
+    /// Whether the document has a `document` environment
+    ///
+    /// Documents without one, such as packages, only have a preamable.
+    pub fn has_body(&self) -> bool {
+        self.has_body
+    }
+
     /// Push a Chunk to a `Vec<Chunk>` without checking
     fn push_chunk_vec_unchecked(vec: &mut Vec<Chunk>, chunk: Chunk) {
         if let ChunkVariant::Text(s) = chunk.variant() {
@@ -285,33 +404,52 @@ impl TryFrom<ast2::Document> for Document {
 
         let mut cursor = CursorState::Preamable;
 
+        // how many chunks of the preamble are before the first `\documentclass`
+        let mut documentclass_index = None;
+
+        // positions are only worked out for errors, from the chunks read so far, which are
+        // the preamble, the body in `construct` and the trailing chunks
+        macro_rules! here {
+            () => {
+                Position::start()
+                    .after_all(&preamable)
+                    .after_display(&construct)
+                    .after_all(&trailing)
+            };
+        }
+
         for chunk in value.chunks_owned() {
             let (line_no, variant) = chunk.decompose();
 
             match variant {
                 ast2::ChunkVariant::Environment(env) if env.label().as_str() == "document" => {
-                    let (_, args, content, begin, end) = Environment::try_from(env)?.decompose();
+                    let (_, args, content, begin, end) = Environment::try_from(env)
+                        .map_err(|e| e.offset(here!()))?
+                        .decompose();
                     construct.body_args = args;
                     construct.body = content;
                     construct.body_begin_prec = begin;
                     construct.body_end_prec = end;
+                    construct.has_body = true;
                     cursor = CursorState::Trailing;
                 }
                 ast2::ChunkVariant::Command(ref cmd)
                     if cmd.label().as_str() == "documentclass"
                         && cursor == CursorState::Preamable =>
                 {
-                    if construct.documentclass.is_some() {
+                    if let Some(first) = documentclass_index {
                         return Err(crate::Error::new(
-                            line_no,
+                            here!(),
                             crate::ErrorType::DoubleDocumentClass,
-                        ));
+                        )
+                        .with_related(Position::start().after_all(&preamable[..first])));
                     }
+                    documentclass_index = Some(preamable.len());
                     for (_, scope) in cmd.clone().decompose().1 {
                         match scope.variant() {
                             ast2::ScopeVariant::Curly if construct.documentclass.is_some() => {
                                 return Err(crate::Error::new(
-                                    line_no,
+                                    here!(),
                                     crate::ErrorType::TooManyArgsDocumentClass,
                                 ))
                             }
@@ -344,6 +482,7 @@ impl TryFrom<ast2::Document> for Document {
                                             BufferMode::Prec => {
                                                 prec.push_str(&buffer);
                                                 buffer.clear();
+                                                arg.push(c);
                                                 buffermode = BufferMode::Arg;
                                             }
                                             BufferMode::Arg if !c.is_whitespace() => arg.push(c),
@@ -396,11 +535,11 @@ impl TryFrom<ast2::Document> for Document {
                                         {
                                             let mut val_started = false;
 
-                                            for c in k.chars() {
+                                            for c in v.chars() {
                                                 if c.is_whitespace() && !val_started {
                                                     equal_post.push(c)
                                                 } else {
-                                                    if val_started {
+                                                    if !val_started {
                                                         val_started = true;
                                                     }
 
@@ -431,7 +570,11 @@ impl TryFrom<ast2::Document> for Document {
         }
 
         construct.preamable = Paragraph::from_chunks(MathsBlock::from_chunks(preamable)?);
-        construct.trailing = Paragraph::from_chunks(MathsBlock::from_chunks(trailing)?);
+        // the trailing chunks start after the preamble and the body
+        construct.trailing = Paragraph::from_chunks(
+            MathsBlock::from_chunks(trailing)
+                .map_err(|e| e.offset(Position::start().after_display(&construct)))?,
+        );
 
         Ok(construct)
     }
@@ -441,61 +584,23 @@ impl FromStr for Document {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        ast2::Document::from_str(s)?.try_into()
+        Self::parse_with_limits(s, ParseLimits::default())
     }
 }
 
 impl Display for Document {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(
-            self.preamable
+        self.preamable.iter().try_for_each(|chunk| chunk.fmt(f))?;
+
+        if self.has_body {
+            f.write_fmt(format_args!("\\begin{}{{document}}", self.body_begin_prec))?;
+            self.body_args
                 .iter()
-                .chain(
-                    [Chunk::new_unchecked(
-                        1,
-                        ChunkVariant::Command(Command::new_unchecked(
-                            "begin".to_string(),
-                            [(
-                                self.body_begin_prec.clone(),
-                                Scope::new_unchecked(
-                                    vec![Chunk::new_unchecked(
-                                        1,
-                                        ChunkVariant::Text("document".to_string()),
-                                    )],
-                                    super::ScopeVariant::Curly,
-                                ),
-                            )]
-                            .into_iter()
-                            .chain(self.body_args.clone())
-                            .collect(),
-                        )),
-                    )]
-                    .iter(),
-                )
-                .chain(self.body.iter())
-                .chain(
-                    [Chunk::new_unchecked(
-                        1,
-                        ChunkVariant::Command(Command::new_unchecked(
-                            "end".to_string(),
-                            vec![(
-                                self.body_begin_prec.clone(),
-                                Scope::new_unchecked(
-                                    vec![Chunk::new_unchecked(
-                                        1,
-                                        ChunkVariant::Text("document".to_string()),
-                                    )],
-                                    super::ScopeVariant::Curly,
-                                ),
-                            )],
-                        )),
-                    )]
-                    .iter(),
-                )
-                .chain(self.trailing.iter())
-                .map(ToString::to_string)
-                .collect::<String>()
-                .as_str(),
-        )
+                .try_for_each(|(prec, arg)| f.write_fmt(format_args!("{prec}{arg}")))?;
+            self.body.iter().try_for_each(|chunk| chunk.fmt(f))?;
+            f.write_fmt(format_args!("\\end{}{{document}}", self.body_end_prec))?;
+        }
+
+        self.trailing.iter().try_for_each(|chunk| chunk.fmt(f))
     }
 }
@@ -0,0 +1,439 @@
+//! Editing operations which keep line numbers consistent
+//!
+//! Each operation renumbers the chunks of the container being edited. Editing a nested
+//! container changes the number of lines of its parents, so nested containers are edited
+//! within `edit_chunk` of the document, which renumbers the chunk holding them and the chunks
+//! after it.
+//!
+//! Editing a document edits its body and shifts the trailing chunks after it, a document
+//! without `\begin{document}` has no body to edit. The preamable and trailing can be edited
+//! through their mutable chunks followed by `renumber`. Trailing is numbered from the line it
+//! starts on, as it is parsed.
+
+use std::ops::Range;
+
+use crate::{
+    traits::{Lines, Validate},
+    InternalError,
+};
+
+use super::{
+    Chunk, ChunkVariant, Command, Conditional, Document, Environment, MacroDefinition, Scope,
+};
+
+impl Document {
+    /// Insert a variant at an index of the body, shifting all chunks after it
+    ///
+    /// Fails if the document has no body, panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, variant: ChunkVariant) -> Result<(), InternalError> {
+        self.validate_body()?;
+        variant.validate()?;
+        self.insert_at_unchecked(index, variant);
+        Ok(())
+    }
+
+    /// Insert a variant at an index without checking
+    ///
+    /// Panics if the document has no body or `index > len`.
+    pub fn insert_at_unchecked(&mut self, index: usize, variant: ChunkVariant) {
+        self.edit_body(|body| insert(body, index, variant))
+    }
+
+    /// Remove and return the chunk at an index of the body
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Chunk {
+        self.edit_body(|body| remove(body, index))
+    }
+
+    /// Replace the chunk at an index of the body, returning the original chunk
+    ///
+    /// Panics if `index >= len`.
+    pub fn replace(&mut self, index: usize, variant: ChunkVariant) -> Result<Chunk, InternalError> {
+        variant.validate()?;
+        Ok(self.replace_unchecked(index, variant))
+    }
+
+    /// Replace the chunk at an index without checking
+    pub fn replace_unchecked(&mut self, index: usize, variant: ChunkVariant) -> Chunk {
+        self.edit_body(|body| replace(body, index, variant))
+    }
+
+    /// Edit the chunk at an index of the body and anything nested in it, then renumber the chunk and
+    /// the chunks after it, returning what `edit` returns
+    ///
+    /// Panics if `index >= len`.
+    pub fn edit_chunk<T>(&mut self, index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+        self.edit_body(|body| edit_chunk(body, index, edit))
+    }
+
+    /// Move a range of chunks in the body into a new environment
+    ///
+    /// Fails if the document has no body, panics if the range is out of bounds.
+    pub fn wrap_in_environment(
+        &mut self,
+        range: Range<usize>,
+        label: &str,
+    ) -> Result<(), InternalError> {
+        self.validate_body()?;
+        validate_label(label)?;
+        self.wrap_in_environment_unchecked(range, label);
+        Ok(())
+    }
+
+    /// Move a range of chunks into a new environment without checking
+    ///
+    /// Panics if the document has no body or the range is out of bounds.
+    pub fn wrap_in_environment_unchecked(&mut self, range: Range<usize>, label: &str) {
+        self.edit_body(|body| wrap(body, range, label))
+    }
+
+    /// Recalculate the line numbers of every chunk in the preamable, body and trailing
+    pub fn renumber(&mut self) {
+        renumber(self.chunks_preamable_mut());
+        self.body_args_mut()
+            .iter_mut()
+            .for_each(|(_, arg)| arg.renumber());
+        renumber(self.chunks_body_mut());
+
+        let line_no = self.trailing_line_no();
+        renumber_starting(self.chunks_trailing_mut(), line_no)
+    }
+
+    // a document without `\begin{document}` prints no body, so edits to it would be lost
+    fn validate_body(&self) -> Result<(), InternalError> {
+        if !self.has_body() {
+            return Err(InternalError::NoBody);
+        }
+        Ok(())
+    }
+
+    // edit the body, then shift trailing by the change in lines of the body
+    fn edit_body<T>(&mut self, edit: impl FnOnce(&mut Vec<Chunk>) -> T) -> T {
+        assert!(self.has_body(), "{}", InternalError::NoBody);
+        let before = lines(self.chunks_body());
+        let out = edit(self.chunks_body_mut());
+        let delta = lines(self.chunks_body()) as i64 - before as i64;
+
+        self.chunks_trailing_mut()
+            .iter_mut()
+            .for_each(|chunk| *chunk.line_no_mut() = (chunk.line_no() as i64 + delta) as u32);
+        out
+    }
+}
+
+impl Scope {
+    /// Insert a variant at an index, shifting all chunks after it
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, variant: ChunkVariant) -> Result<(), InternalError> {
+        variant.validate()?;
+        self.insert_at_unchecked(index, variant);
+        Ok(())
+    }
+
+    /// Insert a variant at an index without checking
+    pub fn insert_at_unchecked(&mut self, index: usize, variant: ChunkVariant) {
+        insert(self.chunks_mut(), index, variant)
+    }
+
+    /// Remove and return the chunk at an index
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Chunk {
+        remove(self.chunks_mut(), index)
+    }
+
+    /// Replace the chunk at an index, returning the original chunk
+    ///
+    /// Panics if `index >= len`.
+    pub fn replace(&mut self, index: usize, variant: ChunkVariant) -> Result<Chunk, InternalError> {
+        variant.validate()?;
+        Ok(self.replace_unchecked(index, variant))
+    }
+
+    /// Replace the chunk at an index without checking
+    pub fn replace_unchecked(&mut self, index: usize, variant: ChunkVariant) -> Chunk {
+        replace(self.chunks_mut(), index, variant)
+    }
+
+    /// Edit the chunk at an index and anything nested in it, then renumber the chunk and
+    /// the chunks after it, returning what `edit` returns
+    ///
+    /// Panics if `index >= len`.
+    pub fn edit_chunk<T>(&mut self, index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+        edit_chunk(self.chunks_mut(), index, edit)
+    }
+
+    /// Move a range of chunks into a new environment
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn wrap_in_environment(
+        &mut self,
+        range: Range<usize>,
+        label: &str,
+    ) -> Result<(), InternalError> {
+        validate_label(label)?;
+        self.wrap_in_environment_unchecked(range, label);
+        Ok(())
+    }
+
+    /// Move a range of chunks into a new environment without checking
+    pub fn wrap_in_environment_unchecked(&mut self, range: Range<usize>, label: &str) {
+        wrap(self.chunks_mut(), range, label)
+    }
+
+    /// Recalculate the line numbers of every chunk in the scope
+    pub fn renumber(&mut self) {
+        renumber(self.chunks_mut())
+    }
+}
+
+impl Environment {
+    /// Insert a variant at an index, shifting all chunks after it
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, variant: ChunkVariant) -> Result<(), InternalError> {
+        variant.validate()?;
+        self.insert_at_unchecked(index, variant);
+        Ok(())
+    }
+
+    /// Insert a variant at an index without checking
+    pub fn insert_at_unchecked(&mut self, index: usize, variant: ChunkVariant) {
+        insert(self.content_mut(), index, variant)
+    }
+
+    /// Remove and return the chunk at an index
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> Chunk {
+        remove(self.content_mut(), index)
+    }
+
+    /// Replace the chunk at an index, returning the original chunk
+    ///
+    /// Panics if `index >= len`.
+    pub fn replace(&mut self, index: usize, variant: ChunkVariant) -> Result<Chunk, InternalError> {
+        variant.validate()?;
+        Ok(self.replace_unchecked(index, variant))
+    }
+
+    /// Replace the chunk at an index without checking
+    pub fn replace_unchecked(&mut self, index: usize, variant: ChunkVariant) -> Chunk {
+        replace(self.content_mut(), index, variant)
+    }
+
+    /// Edit the chunk at an index and anything nested in it, then renumber the chunk and
+    /// the chunks after it, returning what `edit` returns
+    ///
+    /// Panics if `index >= len`.
+    pub fn edit_chunk<T>(&mut self, index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+        edit_chunk(self.content_mut(), index, edit)
+    }
+
+    /// Move a range of chunks into a new environment
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn wrap_in_environment(
+        &mut self,
+        range: Range<usize>,
+        label: &str,
+    ) -> Result<(), InternalError> {
+        validate_label(label)?;
+        self.wrap_in_environment_unchecked(range, label);
+        Ok(())
+    }
+
+    /// Move a range of chunks into a new environment without checking
+    pub fn wrap_in_environment_unchecked(&mut self, range: Range<usize>, label: &str) {
+        wrap(self.content_mut(), range, label)
+    }
+
+    /// Set the argument at an index, returning the original argument
+    ///
+    /// The argument is appended if `index == len`, panics if `index > len`.
+    pub fn set_argument(
+        &mut self,
+        index: usize,
+        argument: Scope,
+    ) -> Result<Option<Scope>, InternalError> {
+        argument.validate()?;
+        Ok(self.set_argument_unchecked(index, argument))
+    }
+
+    /// Set the argument at an index without checking
+    pub fn set_argument_unchecked(&mut self, index: usize, mut argument: Scope) -> Option<Scope> {
+        argument.renumber();
+        set_argument(self.arguments_mut(), index, argument)
+    }
+
+    /// Recalculate the line numbers of every chunk in the arguments and content
+    pub fn renumber(&mut self) {
+        self.arguments_mut()
+            .iter_mut()
+            .for_each(|(_, arg)| arg.renumber());
+        renumber(self.content_mut())
+    }
+}
+
+impl Command {
+    /// Set the argument at an index, returning the original argument
+    ///
+    /// The argument is appended if `index == len`, panics if `index > len`.
+    pub fn set_argument(
+        &mut self,
+        index: usize,
+        argument: Scope,
+    ) -> Result<Option<Scope>, InternalError> {
+        argument.validate()?;
+        Ok(self.set_argument_unchecked(index, argument))
+    }
+
+    /// Set the argument at an index without checking
+    pub fn set_argument_unchecked(&mut self, index: usize, mut argument: Scope) -> Option<Scope> {
+        argument.renumber();
+        set_argument(self.arguments_mut(), index, argument)
+    }
+
+    /// Recalculate the line numbers of every chunk in the arguments
+    pub fn renumber(&mut self) {
+        self.arguments_mut()
+            .iter_mut()
+            .for_each(|(_, arg)| arg.renumber())
+    }
+}
+
+impl Conditional {
+    /// Recalculate the line numbers of every chunk in the condition and branches
+    pub fn renumber(&mut self) {
+        self.parts_mut().for_each(|part| renumber(part))
+    }
+}
+
+impl MacroDefinition {
+    /// Recalculate the line numbers of every chunk in the parameter text and body
+    pub fn renumber(&mut self) {
+        renumber(self.parameters_mut());
+        renumber(self.body_mut())
+    }
+}
+
+impl Chunk {
+    /// Recalculate the line numbers of every chunk nested in this chunk
+    pub fn renumber(&mut self) {
+        match self.variant_mut() {
+            ChunkVariant::Text(_)
+            | ChunkVariant::ParagraphBreak(_)
+            | ChunkVariant::Parameter(_) => {}
+            ChunkVariant::MathsBlock(b) => renumber(b.content_mut()),
+            ChunkVariant::Command(c) => c.renumber(),
+            ChunkVariant::Scope(sc) => sc.renumber(),
+            ChunkVariant::Environment(env) => env.renumber(),
+            ChunkVariant::Conditional(c) => c.renumber(),
+            ChunkVariant::MacroDefinition(d) => d.renumber(),
+        }
+    }
+}
+
+fn insert(chunks: &mut Vec<Chunk>, index: usize, variant: ChunkVariant) {
+    let mut chunk = Chunk::new_unchecked(0, variant);
+    chunk.renumber();
+    chunks.insert(index, chunk);
+    renumber_from(chunks, index)
+}
+
+fn remove(chunks: &mut Vec<Chunk>, index: usize) -> Chunk {
+    let out = chunks.remove(index);
+    renumber_from(chunks, index);
+    out
+}
+
+fn replace(chunks: &mut [Chunk], index: usize, variant: ChunkVariant) -> Chunk {
+    let mut chunk = Chunk::new_unchecked(0, variant);
+    chunk.renumber();
+    let out = std::mem::replace(&mut chunks[index], chunk);
+    renumber_from(chunks, index);
+    out
+}
+
+fn edit_chunk<T>(chunks: &mut [Chunk], index: usize, edit: impl FnOnce(&mut Chunk) -> T) -> T {
+    let out = edit(&mut chunks[index]);
+    chunks[index].renumber();
+    renumber_from(chunks, index);
+    out
+}
+
+fn wrap(chunks: &mut Vec<Chunk>, range: Range<usize>, label: &str) {
+    let start = range.start;
+    let mut content = chunks.drain(range).collect::<Vec<_>>();
+    renumber_from(&mut content, 0);
+
+    let environment = Environment::new_unchecked(
+        label.to_string(),
+        Vec::new(),
+        content,
+        String::new(),
+        String::new(),
+    );
+    chunks.insert(
+        start,
+        Chunk::new_unchecked(0, ChunkVariant::Environment(environment)),
+    );
+    renumber_from(chunks, start)
+}
+
+fn set_argument(
+    arguments: &mut Vec<(String, Scope)>,
+    index: usize,
+    argument: Scope,
+) -> Option<Scope> {
+    if index == arguments.len() {
+        arguments.push((String::new(), argument));
+        None
+    } else {
+        Some(std::mem::replace(&mut arguments[index].1, argument))
+    }
+}
+
+fn validate_label(label: &str) -> Result<(), InternalError> {
+    Environment::new(
+        label.to_string(),
+        Vec::new(),
+        Vec::new(),
+        String::new(),
+        String::new(),
+    )
+    .map(|_| ())
+}
+
+// recalculates line numbers from an index, the chunks before it are assumed to be correct
+fn renumber_from(chunks: &mut [Chunk], index: usize) {
+    let mut line_no = match index.checked_sub(1).and_then(|last| chunks.get(last)) {
+        Some(last) => last.line_no() + last.lines() - 1,
+        None => 1,
+    };
+
+    for chunk in chunks[index..].iter_mut() {
+        *chunk.line_no_mut() = line_no;
+        line_no += chunk.lines() - 1;
+    }
+}
+
+fn renumber(chunks: &mut [Chunk]) {
+    renumber_starting(chunks, 1)
+}
+
+// recalculates line numbers of every chunk, the first one starting on `line_no`
+fn renumber_starting(chunks: &mut [Chunk], mut line_no: u32) {
+    for chunk in chunks.iter_mut() {
+        chunk.renumber();
+        *chunk.line_no_mut() = line_no;
+        line_no += chunk.lines() - 1;
+    }
+}
+
+// lines chunks span after the first
+fn lines(chunks: &[Chunk]) -> u32 {
+    chunks.iter().map(|chunk| chunk.lines() - 1).sum()
+}
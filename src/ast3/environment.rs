@@ -4,7 +4,7 @@ use std::fmt::Write;
 use crate::{
     ast1, ast2,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
 use super::{Chunk, MathsBlock, Paragraph, Scope};
@@ -70,6 +70,36 @@ impl Environment {
         &self.label
     }
 
+    /// Returns arguments of environment, not including the label
+    pub fn arguments(&self) -> &Vec<(String, Scope)> {
+        &self.arguments
+    }
+
+    /// Returns mutable arguments of environment, not including the label
+    pub fn arguments_mut(&mut self) -> &mut Vec<(String, Scope)> {
+        &mut self.arguments
+    }
+
+    /// Returns the chunks within the environment
+    pub fn content(&self) -> &Vec<Chunk> {
+        &self.content
+    }
+
+    /// Returns the mutable chunks within the environment
+    pub fn content_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.content
+    }
+
+    /// Returns the string between `\begin` and its label
+    pub fn prec_begin(&self) -> &String {
+        &self.prec_begin
+    }
+
+    /// Returns the string between `\end` and its label
+    pub fn prec_end(&self) -> &String {
+        &self.prec_end
+    }
+
     /// Returns the content of `Environment`
     pub fn decompose(self) -> (String, Vec<(String, Scope)>, Vec<Chunk>, String, String) {
         (
@@ -85,19 +115,29 @@ impl Environment {
 impl TryFrom<ast2::Environment> for Environment {
     type Error = crate::Error;
 
+    /// Error positions are relative to the backslash of `\begin`
     fn try_from(value: ast2::Environment) -> Result<Self, Self::Error> {
         let (label, args, content, prec_begin, prec_end) = value.decompose();
 
         let mut args_new = Vec::with_capacity(args.len());
 
+        // positions are only worked out for errors
+        let start = || Position::start().after(&format!("\\begin{prec_begin}{{{label}}}"));
+
         for (prec, scope) in args {
-            args_new.push((prec, scope.try_into()?));
+            let scope = scope.try_into().map_err(|e: crate::Error| {
+                e.offset(start().after_arguments(&args_new).after(&prec))
+            })?;
+            args_new.push((prec, scope));
         }
 
+        let content = MathsBlock::from_chunks(content)
+            .map_err(|e| e.offset(start().after_arguments(&args_new)))?;
+
         Ok(Self::new_unchecked(
             label,
             args_new,
-            Paragraph::from_chunks(MathsBlock::from_chunks(content)?),
+            Paragraph::from_chunks(content),
             prec_begin,
             prec_end,
         ))
@@ -0,0 +1,306 @@
+use crate::traits::Lines;
+
+use super::{Chunk, ChunkVariant, Command, Environment, Scope, ScopeVariant};
+
+/// Escapes text so that it is written as it is, as valid chunks
+///
+/// Special characters become commands, such as `\%` and `\textbackslash{}`, balanced
+/// brackets become scopes, and blank lines become paragraph breaks. Brackets which would
+/// be read as arguments of a command before them become `\lbrack{}` and `\textparenleft{}`.
+pub fn escape(text: &str) -> Vec<Chunk> {
+    let mut out = Chunks::default();
+    let mut rest = text;
+
+    // paragraph breaks are whitespace with at least two newlines
+    while let Some((start, end)) = paragraph_break(rest) {
+        out.extend(Chunks::escaped(&rest[..start], EscapeMode::Text));
+        out.push(ChunkVariant::ParagraphBreak(rest[start..end].to_string()));
+        rest = &rest[end..];
+    }
+
+    out.extend(Chunks::escaped(rest, EscapeMode::Text));
+    out.finish()
+}
+
+// the range of the first run of whitespace with two or more newlines, from its first newline
+fn paragraph_break(s: &str) -> Option<(usize, usize)> {
+    let mut start = None;
+    let mut newlines = 0;
+
+    for (i, c) in s.char_indices() {
+        if !c.is_whitespace() {
+            if newlines >= 2 {
+                return start.map(|start| (start, i));
+            }
+            start = None;
+            newlines = 0;
+            continue;
+        }
+
+        if c == '\n' {
+            start.get_or_insert(i);
+            newlines += 1;
+        }
+    }
+
+    (newlines >= 2).then(|| (start.unwrap_or(0), s.len()))
+}
+
+// what is escaped
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EscapeMode {
+    // prose, with straight double quotes turned into TeX quotes
+    Prose,
+    // everything, keeping quotes
+    Text,
+    // URLs in `\url` and `\href`
+    Url,
+    // `alltt`, where only backslashes, braces and `%` are special, and `$` is escaped for the parser
+    Verbatim,
+}
+
+// chunks which are built in order, keeping line numbers consistent
+#[derive(Default)]
+pub(crate) struct Chunks(pub(crate) Vec<Chunk>);
+
+impl Chunks {
+    pub(crate) fn from_text(s: &str) -> Self {
+        let mut out = Self::default();
+        out.text(s);
+        out
+    }
+
+    // text with special characters escaped
+    pub(crate) fn escaped(s: &str, mode: EscapeMode) -> Self {
+        // scopes which are still open, innermost last
+        let mut stack = vec![(None, Self::default())];
+        let mut previous = ' ';
+
+        for c in s.chars() {
+            let open = stack.last().unwrap().0;
+            let outermost = stack.len() == 1;
+            let out = &mut stack.last_mut().unwrap().1;
+            let specials: &[char] = match mode {
+                EscapeMode::Prose | EscapeMode::Text => &[
+                    '\\', '{', '}', '%', '$', '&', '#', '_', '~', '^', '<', '>', '|',
+                ],
+                EscapeMode::Url => &['\\', '{', '}', '%', '#'],
+                EscapeMode::Verbatim => &['\\', '{', '}', '%', '$'],
+            };
+
+            // a bracket at the start or after a command would be read as an argument of
+            // the command before it
+            let argument = out.after_command(outermost);
+
+            match c {
+                '(' | '[' if argument => out.symbol(c),
+                '(' | '[' => stack.push((Some(c), Self::default())),
+                ')' | ']' if open == Some(opening(c)) => {
+                    let (_, inner) = stack.pop().unwrap();
+                    let variant = match c {
+                        ')' => ScopeVariant::Round,
+                        _ => ScopeVariant::Square,
+                    };
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .1
+                        .push(ChunkVariant::Scope(Scope::new_unchecked(
+                            inner.finish(),
+                            variant,
+                        )));
+                }
+                ')' | ']' => out.symbol(c),
+                '"' if mode == EscapeMode::Prose => {
+                    match previous.is_whitespace() || "([{".contains(previous) {
+                        true => out.text("``"),
+                        false => out.text("''"),
+                    }
+                }
+                c if specials.contains(&c) => out.symbol(c),
+                c => out.text(&c.to_string()),
+            }
+
+            previous = c;
+        }
+
+        // brackets which were never closed
+        while stack.len() > 1 {
+            let (open, inner) = stack.pop().unwrap();
+            let out = &mut stack.last_mut().unwrap().1;
+            out.symbol(open.unwrap_or('('));
+            out.extend(inner);
+        }
+
+        stack.pop().unwrap().1
+    }
+
+    // a special character, as a command
+    fn symbol(&mut self, c: char) {
+        let label = match c {
+            '\\' => "textbackslash",
+            '~' => "textasciitilde",
+            '^' => "textasciicircum",
+            '<' => "textless",
+            '>' => "textgreater",
+            '|' => "textbar",
+            '(' => "textparenleft",
+            ')' => "textparenright",
+            '[' => "lbrack",
+            ']' => "rbrack",
+            c => {
+                self.command(&c.to_string(), Vec::new());
+                return;
+            }
+        };
+
+        self.command(label, vec![Self::default()])
+    }
+
+    // whether the next chunk follows a command, ignoring whitespace, or is the first one
+    // when `start` is set
+    fn after_command(&self, start: bool) -> bool {
+        let mut chunks = self.0.iter().rev().peekable();
+
+        if let Some(ChunkVariant::Text(s)) = chunks.peek().map(|chunk| chunk.variant()) {
+            if s.trim().is_empty() {
+                chunks.next();
+            }
+        }
+
+        match chunks.next().map(Chunk::variant) {
+            Some(ChunkVariant::Command(_)) => true,
+            Some(_) => false,
+            None => start,
+        }
+    }
+
+    pub(crate) fn text(&mut self, s: &str) {
+        if !s.is_empty() {
+            self.push(ChunkVariant::Text(s.to_string()))
+        }
+    }
+
+    pub(crate) fn command(&mut self, label: &str, arguments: Vec<Chunks>) {
+        let arguments = arguments
+            .into_iter()
+            .map(|arg| {
+                (
+                    String::new(),
+                    Scope::new_unchecked(arg.finish(), ScopeVariant::Curly),
+                )
+            })
+            .collect();
+
+        self.push(ChunkVariant::Command(Command::new_unchecked(
+            label.to_string(),
+            arguments,
+        )))
+    }
+
+    pub(crate) fn environment(
+        &mut self,
+        label: &str,
+        arguments: Vec<(String, Scope)>,
+        content: Chunks,
+    ) {
+        self.push(ChunkVariant::Environment(Environment::new_unchecked(
+            label.to_string(),
+            arguments,
+            content.finish(),
+            String::new(),
+            String::new(),
+        )))
+    }
+
+    pub(crate) fn hard_break(&mut self, indent: &str) {
+        self.command("\\", Vec::new());
+        self.text(&format!("\n{indent}"));
+    }
+
+    // a paragraph break, taking the whitespace before it
+    pub(crate) fn paragraph_break(&mut self, indent: &str) {
+        let mut whitespace = String::new();
+
+        if let Some(ChunkVariant::Text(s)) = self.0.last_mut().map(Chunk::variant_mut) {
+            let trimmed = s.trim_end().len();
+            whitespace = s.split_off(trimmed);
+
+            if s.is_empty() {
+                self.0.pop();
+            }
+        }
+
+        let newlines = whitespace.matches('\n').count();
+        let mut separator = whitespace.trim_end_matches(' ').to_string();
+        separator.push_str(&"\n".repeat(2usize.saturating_sub(newlines)));
+        separator.push_str(indent);
+
+        self.push(ChunkVariant::ParagraphBreak(separator))
+    }
+
+    pub(crate) fn extend(&mut self, other: Chunks) {
+        other
+            .0
+            .into_iter()
+            .for_each(|chunk| self.push(chunk.variant_owned()))
+    }
+
+    pub(crate) fn push(&mut self, variant: ChunkVariant) {
+        // the parser reads a round or square scope after a command, and whitespace, as an argument
+        if let ChunkVariant::Scope(scope) = &variant {
+            if scope.variant() != ScopeVariant::Curly {
+                let whitespace = match self.0.last().map(Chunk::variant) {
+                    Some(ChunkVariant::Text(s))
+                        if s.trim().is_empty() && s.matches('\n').count() < 2 =>
+                    {
+                        Some(s.clone())
+                    }
+                    _ => None,
+                };
+                let index = self.0.len().checked_sub(1 + whitespace.is_some() as usize);
+
+                if let Some(ChunkVariant::Command(command)) =
+                    index.map(|index| self.0[index].variant_mut())
+                {
+                    if command.label().chars().all(char::is_alphabetic) {
+                        let scope = scope.clone();
+                        command
+                            .arguments_mut()
+                            .push((whitespace.clone().unwrap_or_default(), scope));
+
+                        if whitespace.is_some() {
+                            self.0.pop();
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+
+        if let ChunkVariant::Text(s) = &variant {
+            if let Some(ChunkVariant::Text(last)) = self.0.last_mut().map(Chunk::variant_mut) {
+                last.push_str(s);
+                return;
+            }
+        }
+
+        let line_no = self
+            .0
+            .last()
+            .map(|last| last.line_no() + last.lines() - 1)
+            .unwrap_or(1);
+        self.0.push(Chunk::new_unchecked(line_no, variant))
+    }
+
+    pub(crate) fn finish(self) -> Vec<Chunk> {
+        self.0
+    }
+}
+
+fn opening(c: char) -> char {
+    match c {
+        ')' => '(',
+        _ => '[',
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt::Display;
+
+use crate::{
+    ast1, ast2,
+    traits::{Lines, Validate},
+    InternalError, Position,
+};
+
+use super::{Chunk, ChunkVariant, MathsBlock};
+
+/// A macro defined with `\def` or similar, such as `\def\foo#1.{#1}`
+///
+/// The parameter text is everything between the name and the body, which is the parameters
+/// and the delimiters after them.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct MacroDefinition {
+    command: String,
+    prec: String,
+    label: String,
+    parameters: Vec<Chunk>,
+    body: Vec<Chunk>,
+}
+
+impl MacroDefinition {
+    /// Constructs a new MacroDefinition
+    pub fn new(
+        command: String,
+        prec: String,
+        label: String,
+        parameters: Vec<Chunk>,
+        body: Vec<Chunk>,
+    ) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(command, prec, label, parameters, body);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new MacroDefinition without checking
+    pub fn new_unchecked(
+        command: String,
+        prec: String,
+        label: String,
+        parameters: Vec<Chunk>,
+        body: Vec<Chunk>,
+    ) -> Self {
+        Self {
+            command,
+            prec,
+            label,
+            parameters,
+            body,
+        }
+    }
+
+    /// Returns the defining command, such as `gdef`
+    pub fn command(&self) -> &String {
+        &self.command
+    }
+
+    /// Returns the whitespace between the defining command and the name
+    pub fn prec(&self) -> &String {
+        &self.prec
+    }
+
+    /// Returns the name of the macro, such as `foo` for `\foo`
+    pub fn label(&self) -> &String {
+        &self.label
+    }
+
+    /// Returns the chunks of the parameter text, such as `#1.` of `\def\foo#1.{#1}`
+    pub fn parameters(&self) -> &Vec<Chunk> {
+        &self.parameters
+    }
+
+    /// Returns the mutable chunks of the parameter text
+    pub fn parameters_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.parameters
+    }
+
+    /// Returns the chunks of the body, without its braces
+    pub fn body(&self) -> &Vec<Chunk> {
+        &self.body
+    }
+
+    /// Returns the mutable chunks of the body
+    pub fn body_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.body
+    }
+
+    /// Returns all fields of this struct
+    pub fn decompose(self) -> (String, String, String, Vec<Chunk>, Vec<Chunk>) {
+        (
+            self.command,
+            self.prec,
+            self.label,
+            self.parameters,
+            self.body,
+        )
+    }
+
+    /// Returns the number of parameters the macro takes
+    pub fn arity(&self) -> usize {
+        arity(&self.parameters)
+    }
+
+    /// Returns the position the body starts at, after its opening brace, where `start` is
+    /// the position of the backslash of the defining command
+    pub fn body_start(&self, start: Position) -> Position {
+        let mut position = start.after(&format!("\\{}{}\\{}", self.command, self.prec, self.label));
+        self.parameters
+            .iter()
+            .for_each(|chunk| position.advance_str(&chunk.to_string()));
+        position.advance('{');
+        position
+    }
+}
+
+impl TryFrom<ast2::MacroDefinition> for MacroDefinition {
+    type Error = crate::Error;
+
+    /// Error positions are relative to the backslash of the defining command
+    fn try_from(value: ast2::MacroDefinition) -> Result<Self, Self::Error> {
+        let (command, prec, label, parameters, body) = value.decompose();
+        let start = Position::start().after(&format!("\\{command}{prec}\\{label}"));
+
+        let parameters = MathsBlock::from_chunks(parameters).map_err(|e| e.offset(start))?;
+        // where the body starts is only worked out for an error
+        let body = MathsBlock::from_chunks(body)
+            .map_err(|e| e.offset(start.after_all(&parameters).after("{")))?;
+
+        Ok(Self::new_unchecked(command, prec, label, parameters, body))
+    }
+}
+
+impl Validate for MacroDefinition {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if !ast2::DEF_COMMANDS.contains(&self.command.as_str()) {
+            return Err(InternalError::NotMacroDefinition);
+        }
+
+        if self.label.len() != 1 {
+            for c in self.label.chars() {
+                if matches!(c, '\\' | '%')
+                    || ast1::ScopeVariant::is_opening(c)
+                    || ast1::ScopeVariant::is_closing(c)
+                {
+                    return Err(InternalError::UnsanitisedCharInString(c));
+                }
+            }
+        }
+
+        for chunk in self.parameters.iter() {
+            chunk.validate()?
+        }
+
+        for chunk in self.body.iter() {
+            chunk.validate_in_group()?
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for MacroDefinition {
+    fn lines(&self) -> u32 {
+        let newlines = |s: &str| s.matches('\n').count() as u32;
+
+        newlines(&self.prec)
+            + newlines(&self.label)
+            + self
+                .parameters
+                .iter()
+                .chain(self.body.iter())
+                .map(|chunk| chunk.lines() - 1)
+                .sum::<u32>()
+            + 1
+    }
+}
+
+impl Display for MacroDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let concat = |chunks: &[Chunk]| chunks.iter().map(ToString::to_string).collect::<String>();
+
+        f.write_fmt(format_args!(
+            "\\{}{}\\{}{}{{{}}}",
+            self.command,
+            self.prec,
+            self.label,
+            concat(&self.parameters),
+            concat(&self.body)
+        ))
+    }
+}
+
+// numbered parameters in the chunks, including those in delimiters such as `[#1]`
+fn arity(chunks: &[Chunk]) -> usize {
+    chunks
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Parameter(parameter) => parameter.number().is_some() as usize,
+            ChunkVariant::Scope(scope) => arity(scope.chunks()),
+            _ => 0,
+        })
+        .sum()
+}
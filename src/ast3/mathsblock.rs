@@ -4,7 +4,7 @@ use crate::{
     ast2::{self, IntoChunks},
     ast3::{ChunkVariant, MathsVariant, Paragraph},
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
 use super::{Chunk, MathsType};
@@ -39,6 +39,41 @@ impl MathsBlock {
             content,
         }
     }
+
+    /// Returns the delimiters used for the maths block
+    pub fn variant(&self) -> &MathsVariant {
+        &self.variant
+    }
+
+    /// Returns whether the maths block is inline or outline
+    pub fn r#type(&self) -> &MathsType {
+        &self.r#type
+    }
+
+    /// Returns the chunks within the maths block
+    pub fn content(&self) -> &Vec<Chunk> {
+        &self.content
+    }
+
+    /// Returns the mutable chunks within the maths block
+    pub fn content_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.content
+    }
+
+    /// Returns all fields of this struct
+    pub fn decompose(self) -> (MathsVariant, MathsType, Vec<Chunk>) {
+        (self.variant, self.r#type, self.content)
+    }
+
+    /// Returns the opening and closing delimiters
+    pub fn delimiters(&self) -> (&'static str, &'static str) {
+        match (&self.variant, &self.r#type) {
+            (MathsVariant::Brackets, MathsType::Outline) => ("\\[", "\\]"),
+            (MathsVariant::Brackets, MathsType::Inline) => ("\\(", "\\)"),
+            (MathsVariant::Dollars, MathsType::Outline) => ("$$", "$$"),
+            (MathsVariant::Dollars, MathsType::Inline) => ("$", "$"),
+        }
+    }
 }
 
 impl Validate for MathsBlock {
@@ -63,120 +98,131 @@ impl Lines for MathsBlock {
 
 impl MathsBlock {
     /// Maps a `Vec<ast2::Chunk>` to `Vec<Chunk>` with MathsBlocks
+    ///
+    /// Error positions are relative to the start of the first chunk
     pub fn from_chunks(chunks: Vec<ast2::Chunk>) -> Result<Vec<Chunk>, crate::Error> {
-        #[derive(PartialEq)]
-        enum MathsMode {
-            SingleDollar(MathsVariant),
-            DoubleDollar(MathsVariant),
-            DoubleClosing,
-            None,
-        }
-
         let mut out = Vec::new();
         let mut mode = MathsMode::None;
         let mut depth: u32 = 0;
 
+        // content of the current maths block, and the line its opening delimiter is in
         let mut buffer = Vec::new();
         let mut buffer_line = 0;
 
-        for chunk in chunks {
+        // wrap the buffer into a maths block
+        macro_rules! close {
+            ($variant:expr, $type:expr) => {
+                mode = MathsMode::None;
+                depth = 0;
+                out.push(Self::close(
+                    std::mem::take(&mut buffer),
+                    buffer_line,
+                    $variant,
+                    $type,
+                    &out,
+                )?)
+            };
+        }
+
+        // chunks left to read, in reverse order
+        let mut pending = chunks.into_iter().rev().collect::<Vec<_>>();
+
+        // positions are only worked out for errors, from the output so far, as doing it for
+        // every chunk would go over each subtree once per level of nesting
+        while let Some(chunk) = pending.pop() {
             let (line_no, variant) = chunk.decompose();
 
+            // a single `$` inside `$$` maths followed by something other than text is content,
+            // and stays before it
+            if mode == MathsMode::DoubleClosing && !matches!(variant, ast2::ChunkVariant::Text(_)) {
+                mode = MathsMode::DoubleDollar(MathsVariant::Dollars);
+                buffer.push(ast2::Chunk::new_unchecked(
+                    line_no,
+                    ast2::ChunkVariant::Text("$".to_string()),
+                ));
+            }
+
             match variant {
                 ast2::ChunkVariant::Command(cmd)
                     if mode == MathsMode::None && matches!(cmd.label().as_str(), ")" | "]") =>
                 {
-                    return Err(crate::Error::new(
-                        line_no,
+                    return Err(Self::offset(&out)(crate::Error::new(
+                        Position::start(),
                         crate::ErrorType::UnexpectedMathsEnd,
-                    ))
+                    )))
                 }
                 ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == "(" && mode == MathsMode::None =>
+                    if matches!(cmd.label().as_str(), "(" | "[") && mode == MathsMode::None =>
                 {
-                    mode = MathsMode::SingleDollar(MathsVariant::Brackets);
-                    buffer_line = line_no;
-                    depth = 1;
-                }
-                ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == "[" && mode == MathsMode::None =>
-                {
-                    mode = MathsMode::DoubleDollar(MathsVariant::Brackets);
+                    mode = if cmd.label() == "(" {
+                        MathsMode::SingleDollar(MathsVariant::Brackets)
+                    } else {
+                        MathsMode::DoubleDollar(MathsVariant::Brackets)
+                    };
                     buffer_line = line_no;
                     depth = 1;
                 }
-                ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == "("
-                        && mode == MathsMode::SingleDollar(MathsVariant::Brackets) =>
-                {
-                    depth += 1
-                }
-                ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == "["
-                        && mode == MathsMode::DoubleDollar(MathsVariant::Brackets) =>
-                {
-                    depth += 1
-                }
                 ast2::ChunkVariant::Command(cmd)
                     if cmd.label() == ")"
                         && mode == MathsMode::SingleDollar(MathsVariant::Brackets)
                         && depth == 1 =>
                 {
-                    mode = MathsMode::None;
-                    depth = 0;
-                    out.push(Chunk::new_unchecked(
-                        buffer_line,
-                        ChunkVariant::MathsBlock(Self::new_unchecked(
-                            MathsVariant::Brackets,
-                            MathsType::Inline,
-                            Self::from_chunks(std::mem::take(&mut buffer))?,
-                        )),
-                    ))
+                    close!(MathsVariant::Brackets, MathsType::Inline);
                 }
                 ast2::ChunkVariant::Command(cmd)
                     if cmd.label() == "]"
                         && mode == MathsMode::DoubleDollar(MathsVariant::Brackets)
                         && depth == 1 =>
                 {
-                    mode = MathsMode::None;
-                    depth = 0;
-                    out.push(Chunk::new_unchecked(
-                        buffer_line,
-                        ChunkVariant::MathsBlock(Self::new_unchecked(
-                            MathsVariant::Brackets,
-                            MathsType::Outline,
-                            Self::from_chunks(std::mem::take(&mut buffer))?,
-                        )),
-                    ))
+                    close!(MathsVariant::Brackets, MathsType::Outline);
                 }
                 ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == ")"
-                        && mode == MathsMode::SingleDollar(MathsVariant::Brackets) =>
+                    if (cmd.label() == "("
+                        && mode == MathsMode::SingleDollar(MathsVariant::Brackets))
+                        || (cmd.label() == "["
+                            && mode == MathsMode::DoubleDollar(MathsVariant::Brackets)) =>
                 {
-                    depth -= 1
+                    depth += 1;
+                    buffer.push(ast2::Chunk::new_unchecked(
+                        line_no,
+                        ast2::ChunkVariant::Command(cmd),
+                    ))
                 }
                 ast2::ChunkVariant::Command(cmd)
-                    if cmd.label() == "]"
-                        && mode == MathsMode::DoubleDollar(MathsVariant::Brackets) =>
+                    if (cmd.label() == ")"
+                        && mode == MathsMode::SingleDollar(MathsVariant::Brackets))
+                        || (cmd.label() == "]"
+                            && mode == MathsMode::DoubleDollar(MathsVariant::Brackets)) =>
                 {
-                    depth -= 1
+                    depth -= 1;
+                    buffer.push(ast2::Chunk::new_unchecked(
+                        line_no,
+                        ast2::ChunkVariant::Command(cmd),
+                    ))
                 }
                 ast2::ChunkVariant::Scope(s) if mode == MathsMode::None => {
                     out.push(Chunk::new_unchecked(
                         line_no,
-                        ChunkVariant::Scope(s.try_into()?),
+                        ChunkVariant::Scope(s.try_into().map_err(Self::offset(&out))?),
                     ));
                 }
                 ast2::ChunkVariant::Environment(env) if mode == MathsMode::None => {
                     out.push(Chunk::new_unchecked(
                         line_no,
-                        ChunkVariant::Environment(env.try_into()?),
+                        ChunkVariant::Environment(env.try_into().map_err(Self::offset(&out))?),
                     ));
                 }
+                variant @ (ast2::ChunkVariant::Conditional(_)
+                | ast2::ChunkVariant::MacroDefinition(_)
+                | ast2::ChunkVariant::Parameter(_))
+                    if mode == MathsMode::None =>
+                {
+                    Self::folded(variant, line_no, &mut pending, &mut out)?
+                }
                 ast2::ChunkVariant::Command(cmd) if mode == MathsMode::None => {
                     out.push(Chunk::new_unchecked(
                         line_no,
-                        ChunkVariant::Command(cmd.try_into()?),
+                        ChunkVariant::Command(cmd.try_into().map_err(Self::offset(&out))?),
                     ));
                 }
                 ast2::ChunkVariant::Text(s)
@@ -190,13 +236,31 @@ impl MathsBlock {
                     let mut text_buffer = String::new();
                     let mut text_buffer_line = cursor_line_no;
 
+                    // text outside of maths goes to the output,
+                    // text inside maths goes to the maths block buffer
                     macro_rules! push_str {
                         () => {
-                            if !text_buffer.is_empty() {
-                                out.push(Chunk::new_unchecked(
+                            if mode == MathsMode::None {
+                                Self::push_text(
+                                    &mut out,
+                                    &mut text_buffer,
+                                    text_buffer_line,
+                                    |line_no, s| {
+                                        Chunk::new_unchecked(line_no, ChunkVariant::Text(s))
+                                    },
+                                )
+                            } else {
+                                Self::push_text(
+                                    &mut buffer,
+                                    &mut text_buffer,
                                     text_buffer_line,
-                                    ChunkVariant::Text(std::mem::take(&mut text_buffer)),
-                                ))
+                                    |line_no, s| {
+                                        ast2::Chunk::new_unchecked(
+                                            line_no,
+                                            ast2::ChunkVariant::Text(s),
+                                        )
+                                    },
+                                )
                             }
                         };
                     }
@@ -206,83 +270,36 @@ impl MathsBlock {
                             cursor_line_no += 1
                         }
 
+                        // a single `$` inside `$$` maths is just content
+                        if mode == MathsMode::DoubleClosing && c != '$' {
+                            mode = MathsMode::DoubleDollar(MathsVariant::Dollars);
+                            text_buffer.push('$');
+                        }
+
                         if c == '$' {
                             match mode {
                                 MathsMode::None => {
                                     push_str!();
                                     mode = MathsMode::SingleDollar(MathsVariant::Dollars);
+                                    buffer_line = cursor_line_no;
                                     text_buffer_line = cursor_line_no;
                                 }
                                 MathsMode::SingleDollar(MathsVariant::Dollars)
                                     if text_buffer.is_empty() && buffer.is_empty() =>
                                 {
-                                    mode = MathsMode::DoubleDollar(MathsVariant::Dollars)
+                                    mode = MathsMode::DoubleDollar(MathsVariant::Dollars);
                                 }
                                 MathsMode::DoubleDollar(MathsVariant::Dollars) => {
                                     mode = MathsMode::DoubleClosing
                                 }
                                 MathsMode::DoubleClosing => {
-                                    mode = MathsMode::None;
-
-                                    if !text_buffer.is_empty() {
-                                        buffer.push(ast2::Chunk::new_unchecked(
-                                            text_buffer_line,
-                                            ast2::ChunkVariant::Text(std::mem::take(
-                                                &mut text_buffer,
-                                            )),
-                                        ));
-                                    }
-
-                                    out.push(Chunk::new_unchecked(
-                                        text_buffer_line,
-                                        ChunkVariant::MathsBlock(MathsBlock::new_unchecked(
-                                            MathsVariant::Dollars,
-                                            MathsType::Outline,
-                                            Self::from_chunks(
-                                                std::mem::take(&mut buffer)
-                                                    .into_iter()
-                                                    .map(|mut chunk| {
-                                                        *chunk.line_no_mut() -=
-                                                            text_buffer_line - 1;
-                                                        chunk
-                                                    })
-                                                    .collect(),
-                                            )?,
-                                        )),
-                                    ));
-
+                                    push_str!();
+                                    close!(MathsVariant::Dollars, MathsType::Outline);
                                     text_buffer_line = cursor_line_no;
                                 }
                                 MathsMode::SingleDollar(MathsVariant::Dollars) => {
-                                    mode = MathsMode::None;
-
-                                    if !text_buffer.is_empty() {
-                                        buffer.push(ast2::Chunk::new_unchecked(
-                                            text_buffer_line,
-                                            ast2::ChunkVariant::Text(std::mem::take(
-                                                &mut text_buffer,
-                                            )),
-                                        ));
-                                    }
-
-                                    out.push(Chunk::new_unchecked(
-                                        text_buffer_line,
-                                        ChunkVariant::MathsBlock(MathsBlock::new_unchecked(
-                                            MathsVariant::Dollars,
-                                            MathsType::Inline,
-                                            Self::from_chunks(
-                                                std::mem::take(&mut buffer)
-                                                    .into_iter()
-                                                    .map(|mut chunk| {
-                                                        *chunk.line_no_mut() -=
-                                                            text_buffer_line - 1;
-                                                        chunk
-                                                    })
-                                                    .collect(),
-                                            )?,
-                                        )),
-                                    ));
-
+                                    push_str!();
+                                    close!(MathsVariant::Dollars, MathsType::Inline);
                                     text_buffer_line = cursor_line_no;
                                 }
                                 MathsMode::SingleDollar(MathsVariant::Brackets)
@@ -300,16 +317,341 @@ impl MathsBlock {
         }
 
         if mode != MathsMode::None {
-            return Err(crate::Error::new(
-                buffer_line,
-                crate::ErrorType::UnclosedMaths,
-            ));
+            return Err(Self::unclosed(&out, &buffer, mode));
         }
 
         out = Paragraph::from_chunks(out);
 
         Ok(out)
     }
+
+    // move the text buffered so far to the output or maths block buffer as a chunk
+    fn push_text<T>(
+        chunks: &mut Vec<T>,
+        text: &mut String,
+        line_no: u32,
+        new: fn(u32, String) -> T,
+    ) {
+        if !text.is_empty() {
+            chunks.push(new(line_no, std::mem::take(text)))
+        }
+    }
+
+    // maths left open at the end of the chunks, where `out` is the chunks before it
+    fn unclosed(out: &[Chunk], buffer: &[ast2::Chunk], mode: MathsMode) -> crate::Error {
+        let open = Position::start().after_all(out);
+        let end = match mode {
+            MathsMode::SingleDollar(MathsVariant::Dollars) => open.after("$"),
+            MathsMode::SingleDollar(MathsVariant::Brackets) => open.after("\\("),
+            MathsMode::DoubleDollar(MathsVariant::Brackets) => open.after("\\["),
+            _ => open.after("$$"),
+        }
+        .after_all(buffer)
+        .after(if mode == MathsMode::DoubleClosing {
+            "$"
+        } else {
+            ""
+        });
+
+        crate::Error::new(open, crate::ErrorType::UnclosedMaths).with_related(end)
+    }
+
+    // maps an error in a chunk to the position after the chunks before it
+    fn offset(out: &[Chunk]) -> impl FnOnce(crate::Error) -> crate::Error + '_ {
+        move |e| e.offset(Position::start().after_all(out))
+    }
+
+    // a maths block of the buffered chunks, where `line_no` is the line its opening delimiter
+    // is in and `out` the chunks before it, kept out of `from_chunks` which recurses once per
+    // level of nesting
+    fn close(
+        buffer: Vec<ast2::Chunk>,
+        line_no: u32,
+        variant: MathsVariant,
+        r#type: MathsType,
+        out: &[Chunk],
+    ) -> Result<Chunk, crate::Error> {
+        let open = match (variant, &r#type) {
+            (MathsVariant::Dollars, MathsType::Inline) => "$",
+            (MathsVariant::Dollars, MathsType::Outline) => "$$",
+            (MathsVariant::Brackets, MathsType::Inline) => "\\(",
+            (MathsVariant::Brackets, MathsType::Outline) => "\\[",
+        };
+
+        let chunks = buffer
+            .into_iter()
+            .map(|mut chunk| {
+                *chunk.line_no_mut() -= line_no - 1;
+                chunk
+            })
+            .collect();
+
+        Ok(Chunk::new_unchecked(
+            line_no,
+            ChunkVariant::MathsBlock(Self::new_unchecked(
+                variant,
+                r#type,
+                Self::from_chunks(chunks)
+                    .map_err(|e| e.offset(Position::start().after_all(out).after(open)))?,
+            )),
+        ))
+    }
+
+    // reads a conditional, macro definition or parameter into the output, kept out of
+    // `from_chunks` which recurses once per level of nesting
+    fn folded(
+        variant: ast2::ChunkVariant,
+        line_no: u32,
+        pending: &mut Vec<ast2::Chunk>,
+        out: &mut Vec<Chunk>,
+    ) -> Result<(), crate::Error> {
+        match variant {
+            ast2::ChunkVariant::Conditional(conditional) => {
+                Self::conditional(conditional, line_no, pending, out)
+            }
+            ast2::ChunkVariant::MacroDefinition(definition) => {
+                Self::macro_definition(definition, line_no, pending, out)
+            }
+            ast2::ChunkVariant::Parameter(parameter) => {
+                out.push(Chunk::new_unchecked(
+                    line_no,
+                    ChunkVariant::Parameter(parameter.into()),
+                ));
+                Ok(())
+            }
+            _ => unreachable!("chunk which is not folded"),
+        }
+    }
+
+    fn conditional(
+        conditional: ast2::Conditional,
+        line_no: u32,
+        pending: &mut Vec<ast2::Chunk>,
+        out: &mut Vec<Chunk>,
+    ) -> Result<(), crate::Error> {
+        if !Reader::balanced_parts(&conditional) {
+            Self::unfold(conditional.flatten(), line_no, pending);
+            return Ok(());
+        }
+
+        out.push(Chunk::new_unchecked(
+            line_no,
+            ChunkVariant::Conditional(conditional.try_into().map_err(Self::offset(out))?),
+        ));
+        Ok(())
+    }
+
+    fn macro_definition(
+        definition: ast2::MacroDefinition,
+        line_no: u32,
+        pending: &mut Vec<ast2::Chunk>,
+        out: &mut Vec<Chunk>,
+    ) -> Result<(), crate::Error> {
+        if !(Reader::balanced(definition.parameters()) && Reader::balanced(definition.body())) {
+            Self::unfold(definition.flatten(), line_no, pending);
+            return Ok(());
+        }
+
+        out.push(Chunk::new_unchecked(
+            line_no,
+            ChunkVariant::MacroDefinition(definition.try_into().map_err(Self::offset(out))?),
+        ));
+        Ok(())
+    }
+
+    // maths opened in one branch of a conditional and closed after it, such as
+    // `\ifx\a\b$x\else$y\fi$`, or opened in the parameter text of a definition, is read with
+    // the keywords as commands, by putting the flattened chunks back to be read again
+    fn unfold(chunks: Vec<ast2::Chunk>, line_no: u32, pending: &mut Vec<ast2::Chunk>) {
+        pending.extend(chunks.into_iter().rev().map(|mut chunk| {
+            *chunk.line_no_mut() += line_no - 1;
+            chunk
+        }));
+    }
+}
+
+#[derive(PartialEq)]
+enum MathsMode {
+    SingleDollar(MathsVariant),
+    DoubleDollar(MathsVariant),
+    DoubleClosing,
+    None,
+}
+
+// follows the maths delimiters at the top level of chunks the way `from_chunks` reads them,
+// without converting anything, to tell whether a conditional or definition can be read on
+// its own before converting it
+struct Reader {
+    mode: MathsMode,
+    depth: u32,
+    // text of the current chunk not moved to the output or maths block yet
+    text: bool,
+    // the maths block has content
+    content: bool,
+}
+
+impl Reader {
+    // whether reading the chunks on their own does not fail at their top level
+    fn balanced(chunks: &[ast2::Chunk]) -> bool {
+        let mut reader = Self {
+            mode: MathsMode::None,
+            depth: 0,
+            text: false,
+            content: false,
+        };
+
+        reader.read(chunks) && reader.mode == MathsMode::None
+    }
+
+    // whether every part of a conditional can be read on its own, raw content is not read
+    fn balanced_parts(conditional: &ast2::Conditional) -> bool {
+        let raw = conditional.is_raw();
+        conditional
+            .parts()
+            .enumerate()
+            .all(|(index, part)| (raw && index == 1) || Self::balanced(part))
+    }
+
+    // false where `from_chunks` fails with an unexpected end of maths
+    fn read(&mut self, chunks: &[ast2::Chunk]) -> bool {
+        chunks.iter().all(|chunk| self.chunk(chunk.variant()))
+    }
+
+    fn chunk(&mut self, variant: &ast2::ChunkVariant) -> bool {
+        let none = self.mode == MathsMode::None;
+
+        match variant {
+            ast2::ChunkVariant::Command(cmd) => return self.command(cmd.label()),
+            ast2::ChunkVariant::Text(s)
+                if !matches!(
+                    self.mode,
+                    MathsMode::SingleDollar(MathsVariant::Brackets)
+                        | MathsMode::DoubleDollar(MathsVariant::Brackets)
+                ) =>
+            {
+                self.text(s)
+            }
+            ast2::ChunkVariant::Conditional(conditional) if none => {
+                if !Self::balanced_parts(conditional) {
+                    let keywords = ["", ""]
+                        .into_iter()
+                        .chain(std::iter::repeat_n("or", conditional.cases().len()))
+                        .chain(conditional.otherwise().as_ref().map(|_| "else"));
+
+                    return (conditional.unless().is_none() || self.command("unless"))
+                        && self.command(conditional.label())
+                        && keywords.zip(conditional.parts()).all(|(keyword, part)| {
+                            (keyword.is_empty() || self.command(keyword)) && self.read(part)
+                        })
+                        && self.command("fi");
+                }
+            }
+            ast2::ChunkVariant::MacroDefinition(definition) if none => {
+                if !(Self::balanced(definition.parameters()) && Self::balanced(definition.body())) {
+                    if !self.command(definition.command()) {
+                        return false;
+                    }
+                    if !definition.prec().is_empty() {
+                        self.text(definition.prec());
+                    }
+                    if !(self.command(definition.label()) && self.read(definition.parameters())) {
+                        return false;
+                    }
+
+                    // the body is a scope once flattened
+                    if self.mode != MathsMode::None {
+                        self.content = true;
+                    }
+                }
+            }
+            _ if none => {}
+            _ => self.content = true,
+        }
+
+        true
+    }
+
+    fn command(&mut self, label: &str) -> bool {
+        match (&self.mode, label) {
+            (MathsMode::None, ")" | "]") => return false,
+            (MathsMode::None, "(") => self.open(MathsMode::SingleDollar(MathsVariant::Brackets)),
+            (MathsMode::None, "[") => self.open(MathsMode::DoubleDollar(MathsVariant::Brackets)),
+            (MathsMode::SingleDollar(MathsVariant::Brackets), ")")
+            | (MathsMode::DoubleDollar(MathsVariant::Brackets), "]")
+                if self.depth == 1 =>
+            {
+                self.close()
+            }
+            (MathsMode::SingleDollar(MathsVariant::Brackets), "(")
+            | (MathsMode::DoubleDollar(MathsVariant::Brackets), "[") => {
+                self.depth += 1;
+                self.content = true;
+            }
+            (MathsMode::SingleDollar(MathsVariant::Brackets), ")")
+            | (MathsMode::DoubleDollar(MathsVariant::Brackets), "]") => {
+                self.depth -= 1;
+                self.content = true;
+            }
+            (MathsMode::None, _) => {}
+            _ => self.content = true,
+        }
+
+        true
+    }
+
+    fn text(&mut self, s: &str) {
+        for c in s.chars() {
+            if self.mode == MathsMode::DoubleClosing && c != '$' {
+                self.mode = MathsMode::DoubleDollar(MathsVariant::Dollars);
+                self.text = true;
+            }
+
+            if c != '$' {
+                self.text = true;
+                continue;
+            }
+
+            match self.mode {
+                MathsMode::None => {
+                    self.push();
+                    self.open(MathsMode::SingleDollar(MathsVariant::Dollars));
+                }
+                MathsMode::SingleDollar(MathsVariant::Dollars) if !self.text && !self.content => {
+                    self.mode = MathsMode::DoubleDollar(MathsVariant::Dollars)
+                }
+                MathsMode::DoubleDollar(MathsVariant::Dollars) => {
+                    self.mode = MathsMode::DoubleClosing
+                }
+                MathsMode::DoubleClosing | MathsMode::SingleDollar(MathsVariant::Dollars) => {
+                    self.push();
+                    self.close();
+                }
+                MathsMode::SingleDollar(MathsVariant::Brackets)
+                | MathsMode::DoubleDollar(MathsVariant::Brackets) => {}
+            }
+        }
+
+        self.push()
+    }
+
+    // text goes to the maths block if it is open
+    fn push(&mut self) {
+        if self.text && self.mode != MathsMode::None {
+            self.content = true;
+        }
+        self.text = false;
+    }
+
+    fn open(&mut self, mode: MathsMode) {
+        self.mode = mode;
+        self.depth = 1;
+    }
+
+    fn close(&mut self) {
+        self.mode = MathsMode::None;
+        self.depth = 0;
+        self.content = false;
+    }
 }
 
 impl IntoChunks for MathsBlock {
@@ -401,12 +743,7 @@ impl IntoChunks for MathsBlock {
 
 impl Display for MathsBlock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (open, close) = match (&self.variant, &self.r#type) {
-            (MathsVariant::Brackets, MathsType::Outline) => ("\\[", "\\]"),
-            (MathsVariant::Brackets, MathsType::Inline) => ("\\(", "\\)"),
-            (MathsVariant::Dollars, MathsType::Outline) => ("$$", "$$"),
-            (MathsVariant::Dollars, MathsType::Inline) => ("$", "$"),
-        };
+        let (open, close) = self.delimiters();
 
         f.write_fmt(format_args!(
             "{open}{}{close}",
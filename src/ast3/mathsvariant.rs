@@ -1,5 +1,5 @@
 /// Variant used to declare the maths block
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
 pub enum MathsVariant {
@@ -8,38 +8,71 @@
 //! |Scope|Scope to represent grouping of elements.|
 //! |Command|`\commandName` followed by multiple arguments.|
 //! |Environment|Content between a `\begin{label}{arguments...}` and a `\end{label}`.|
+//! |Conditional|`\if...` test and its branches up to the matching `\fi`.|
+//! |MacroDefinition|`\def\name` followed by a parameter text and a body.|
+//! |Parameter|`#1` and similar parameter tokens.|
 //! |MathsBlock|Content surrounded by `$`, `\[` or `\(`.|
 //! |InterParagraph|A paragraph break.|
 //!
 //! The stage 3 AST can be reconstructed a one-to-one copy
 //! of the stage 2 AST, and hence the original document with no loss of information.
 //!
+//! [`escape`] turns arbitrary text into valid chunks, and [`DocumentBuilder`] builds
+//! whole documents from text with correct line numbers.
+//!
+//! [`Conditions`] works out which branch of a conditional is read when the test does not
+//! depend on anything outside of the document, such as `\iffalse`.
+//!
+//! [`Document::reparse`] updates a document after a text edit by parsing only the part
+//! of the document containing the edit.
+//!
 //! ## Peformance
 //!
 //! Peformance is not known, current assuming it to be
 //! - Worst case O(n<sup>2</sup>)
 //! - Average case O(n)
 
+mod builder;
 mod chunk;
 mod chunkvariant;
 mod command;
+mod conditional;
+mod conditions;
 mod document;
+mod edit;
 mod environment;
+mod escape;
+mod macrodefinition;
 mod mathsblock;
 mod mathstype;
 mod mathsvariant;
 mod paragraph;
+mod parameter;
+mod query;
+mod reparse;
 mod scope;
 mod scopevariant;
+mod selector;
+pub mod visit;
 
+pub use builder::DocumentBuilder;
 pub use chunk::Chunk;
 pub use chunkvariant::ChunkVariant;
 pub use command::Command;
+pub use conditional::Conditional;
+pub use conditions::Conditions;
 pub use document::Document;
 pub use environment::Environment;
+pub use escape::escape;
+pub(crate) use escape::{Chunks, EscapeMode};
+pub use macrodefinition::MacroDefinition;
 pub use mathsblock::MathsBlock;
 pub use mathstype::MathsType;
 pub use mathsvariant::MathsVariant;
 pub use paragraph::Paragraph;
+pub use parameter::Parameter;
+pub use query::{Match, Node, Query};
 pub use scope::Scope;
 pub use scopevariant::ScopeVariant;
+pub use selector::{Selector, SelectorError};
+pub use visit::{Fold, Visit, VisitMut};
@@ -33,7 +33,9 @@ impl Paragraph {
                             buffer.push(c);
                         }
                         '\n' => {
-                            buffer_line = line_no;
+                            if consec_newlines == 0 {
+                                buffer_line = line_no;
+                            }
                             consec_newlines += 1;
                             line_no += 1;
 
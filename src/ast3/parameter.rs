@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+use crate::{
+    ast2,
+    traits::{Lines, Validate},
+    InternalError,
+};
+
+/// A parameter token, such as `#1` in the body of a macro
+///
+/// `##1` is a parameter of a macro defined by the body, `#` without a number is the `#{` at
+/// the end of a parameter text, or a stray `#`.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Parameter {
+    hashes: usize,
+    number: Option<u8>,
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&"#".repeat(self.hashes))?;
+
+        match self.number {
+            Some(number) => f.write_fmt(format_args!("{number}")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Parameter {
+    /// Constructs a new Parameter
+    pub fn new(hashes: usize, number: Option<u8>) -> Result<Self, InternalError> {
+        let out = Self::new_unchecked(hashes, number);
+        out.validate()?;
+        Ok(out)
+    }
+
+    /// Constructs a new Parameter without checking
+    pub fn new_unchecked(hashes: usize, number: Option<u8>) -> Self {
+        Self { hashes, number }
+    }
+
+    /// Returns the number of `#` characters
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the number of the parameter, from 1 to 9
+    pub fn number(&self) -> Option<u8> {
+        self.number
+    }
+
+    /// Returns all fields of this struct
+    pub fn decompose(self) -> (usize, Option<u8>) {
+        (self.hashes, self.number)
+    }
+}
+
+impl Validate for Parameter {
+    fn validate(&self) -> Result<(), crate::InternalError> {
+        if self.hashes == 0 || self.number.is_some_and(|number| !(1..=9).contains(&number)) {
+            return Err(InternalError::InvalidParameter);
+        }
+
+        Ok(())
+    }
+}
+
+impl Lines for Parameter {
+    fn lines(&self) -> u32 {
+        1
+    }
+}
+
+impl From<ast2::Parameter> for Parameter {
+    fn from(value: ast2::Parameter) -> Self {
+        let (hashes, number) = value.decompose();
+        Self::new_unchecked(hashes, number)
+    }
+}
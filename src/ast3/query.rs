@@ -0,0 +1,406 @@
+//! Searching the stage 3 AST
+//!
+//! A `Query` flattens a document into a list of nodes in source order, each
+//! with its position, depth and parent, so that searches do not need to recurse.
+//!
+//! ```
+//! use std::str::FromStr;
+//! use latex_ast::ast3::Document;
+//!
+//! let document = Document::from_str(
+//!     r"\begin{document}\begin{figure}\caption{See \cite{x}}\end{figure}\end{document}",
+//! )
+//! .unwrap();
+//! let query = document.query();
+//!
+//! let cites = query.select("environment[figure] command[cite]").unwrap();
+//! assert_eq!(cites.len(), 1);
+//! assert_eq!(cites[0].start().column, 44);
+//! ```
+
+use crate::Position;
+
+use super::{
+    Chunk, ChunkVariant, Command, Conditional, Document, Environment, MacroDefinition, MathsBlock,
+    Parameter, Scope, Selector,
+};
+
+/// A node of the stage 3 AST
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub enum Node<'a> {
+    /// Plain text
+    Text(&'a String),
+    /// A paragraph break
+    ParagraphBreak(&'a String),
+    /// A maths block
+    MathsBlock(&'a MathsBlock),
+    /// A command
+    Command(&'a Command),
+    /// A scope which is not an argument
+    Scope(&'a Scope),
+    /// An argument of a command or environment
+    Argument(&'a Scope),
+    /// An environment
+    Environment(&'a Environment),
+    /// The `document` environment
+    Body(&'a Document),
+    /// A conditional, its condition and branches are its children
+    Conditional(&'a Conditional),
+    /// A parameter token
+    Parameter(&'a Parameter),
+    /// A macro definition, its parameter text and body are its children
+    MacroDefinition(&'a MacroDefinition),
+}
+
+impl<'a> Node<'a> {
+    /// Returns the label of a command, environment, conditional or defined macro
+    pub fn label(&self) -> Option<&'a str> {
+        match self {
+            Self::Command(c) => Some(c.label()),
+            Self::Environment(env) => Some(env.label()),
+            Self::Conditional(c) => Some(c.label()),
+            Self::MacroDefinition(d) => Some(d.label()),
+            Self::Body(_) => Some("document"),
+            _ => None,
+        }
+    }
+
+    /// Whether both refer to the same node in the tree, rather than equal nodes
+    pub fn ptr_eq(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Self::Text(a), Node::Text(b)) => std::ptr::eq(*a, *b),
+            (Self::ParagraphBreak(a), Node::ParagraphBreak(b)) => std::ptr::eq(*a, *b),
+            (Self::MathsBlock(a), Node::MathsBlock(b)) => std::ptr::eq(*a, *b),
+            (Self::Command(a), Node::Command(b)) => std::ptr::eq(*a, *b),
+            (Self::Scope(a), Node::Scope(b)) => std::ptr::eq(*a, *b),
+            (Self::Argument(a), Node::Argument(b)) => std::ptr::eq(*a, *b),
+            (Self::Environment(a), Node::Environment(b)) => std::ptr::eq(*a, *b),
+            (Self::Body(a), Node::Body(b)) => std::ptr::eq(*a, *b),
+            (Self::Conditional(a), Node::Conditional(b)) => std::ptr::eq(*a, *b),
+            (Self::Parameter(a), Node::Parameter(b)) => std::ptr::eq(*a, *b),
+            (Self::MacroDefinition(a), Node::MacroDefinition(b)) => std::ptr::eq(*a, *b),
+            _ => false,
+        }
+    }
+
+    /// Whether the node is a command with the label
+    pub fn is_command(&self, label: &str) -> bool {
+        matches!(self, Self::Command(c) if c.label() == label)
+    }
+
+    /// Whether the node is an environment with the label, including `document`
+    pub fn is_environment(&self, label: &str) -> bool {
+        matches!(self, Self::Environment(_) | Self::Body(_) if self.label() == Some(label))
+    }
+}
+
+impl<'a> From<&'a Chunk> for Node<'a> {
+    fn from(value: &'a Chunk) -> Self {
+        match value.variant() {
+            ChunkVariant::Text(s) => Self::Text(s),
+            ChunkVariant::ParagraphBreak(s) => Self::ParagraphBreak(s),
+            ChunkVariant::MathsBlock(mb) => Self::MathsBlock(mb),
+            ChunkVariant::Command(c) => Self::Command(c),
+            ChunkVariant::Scope(sc) => Self::Scope(sc),
+            ChunkVariant::Environment(env) => Self::Environment(env),
+            ChunkVariant::Conditional(c) => Self::Conditional(c),
+            ChunkVariant::Parameter(p) => Self::Parameter(p),
+            ChunkVariant::MacroDefinition(d) => Self::MacroDefinition(d),
+        }
+    }
+}
+
+/// A node found in a document
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Match<'a> {
+    node: Node<'a>,
+    start: Position,
+    end: Position,
+    depth: usize,
+    index: usize,
+    parent: Option<usize>,
+}
+
+impl<'a> Match<'a> {
+    /// Returns the node
+    pub fn node(&self) -> Node<'a> {
+        self.node
+    }
+
+    /// Returns the position of the first character of the node
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Returns the position right after the last character of the node
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    /// Returns the number of ancestors of the node
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+/// All nodes of a document, in the order they appear in the source
+///
+/// Command and environment arguments come before the content they belong to.
+pub struct Query<'a> {
+    nodes: Vec<Match<'a>>,
+}
+
+impl<'a> Query<'a> {
+    /// Index all nodes of a document
+    pub fn new(document: &'a Document) -> Self {
+        let mut builder = Builder {
+            nodes: Vec::new(),
+            cursor: Position::start(),
+        };
+
+        builder.chunks(document.chunks_preamable(), None);
+
+        if document.has_body() {
+            let body = builder.open(Node::Body(document), None);
+            builder.cursor.advance_str("\\begin");
+            builder.cursor.advance_str(document.body_begin_prec());
+            builder.cursor.advance_str("{document}");
+            builder.arguments(document.body_args(), Some(body));
+            builder.chunks(document.chunks_body(), Some(body));
+            builder.cursor.advance_str("\\end");
+            builder.cursor.advance_str(document.body_end_prec());
+            builder.cursor.advance_str("{document}");
+            builder.close(body);
+        }
+
+        builder.chunks(document.chunks_trailing(), None);
+
+        Self {
+            nodes: builder.nodes,
+        }
+    }
+
+    /// Returns every node in the document
+    pub fn descendants(&self) -> std::slice::Iter<'_, Match<'a>> {
+        self.nodes.iter()
+    }
+
+    /// Returns every node within a node, not including itself
+    pub fn descendants_of(&self, node: &Match) -> std::slice::Iter<'_, Match<'a>> {
+        let end = self.nodes[node.index + 1..]
+            .iter()
+            .position(|other| other.depth <= node.depth)
+            .map(|offset| node.index + 1 + offset)
+            .unwrap_or(self.nodes.len());
+
+        self.nodes[node.index + 1..end].iter()
+    }
+
+    /// Returns the nodes directly within a node
+    pub fn children_of<'s>(&'s self, node: &Match) -> impl Iterator<Item = &'s Match<'a>> {
+        let depth = node.depth + 1;
+        self.descendants_of(node)
+            .filter(move |child| child.depth == depth)
+    }
+
+    /// Returns the node directly containing a node
+    pub fn parent_of(&self, node: &Match) -> Option<&Match<'a>> {
+        node.parent.map(|parent| &self.nodes[parent])
+    }
+
+    /// Returns all nodes containing a node, innermost first
+    pub fn ancestors_of<'s>(&'s self, node: &Match) -> impl Iterator<Item = &'s Match<'a>> {
+        let mut parent = node.parent;
+
+        std::iter::from_fn(move || {
+            let out = &self.nodes[parent?];
+            parent = out.parent;
+            Some(out)
+        })
+    }
+
+    /// Returns the path from the outermost to the innermost node containing the position
+    ///
+    /// The path is empty if the position is not within any node, such as past the end.
+    pub fn node_at(&self, position: Position) -> Vec<&Match<'a>> {
+        // children come right after their parent, so the last node containing
+        // the position is the innermost one
+        let innermost = match self
+            .nodes
+            .iter()
+            .rev()
+            .find(|node| node.start <= position && position < node.end)
+        {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+
+        let mut path = self.ancestors_of(innermost).collect::<Vec<_>>();
+        path.reverse();
+        path.push(innermost);
+        path
+    }
+
+    /// Returns the located node, the node must be borrowed from the queried document
+    pub fn locate(&self, node: Node) -> Option<&Match<'a>> {
+        self.nodes.iter().find(|other| other.node.ptr_eq(&node))
+    }
+
+    /// Returns every command with the label
+    pub fn commands_named<'s>(&'s self, label: &'s str) -> impl Iterator<Item = &'s Match<'a>> {
+        self.nodes
+            .iter()
+            .filter(move |node| node.node.is_command(label))
+    }
+
+    /// Returns every environment with the label
+    pub fn environments_named<'s>(&'s self, label: &'s str) -> impl Iterator<Item = &'s Match<'a>> {
+        self.nodes
+            .iter()
+            .filter(move |node| node.node.is_environment(label))
+    }
+
+    /// Returns every node matching a selector, see `Selector` for the syntax
+    pub fn select(&self, selector: &str) -> Result<Vec<&Match<'a>>, crate::ast3::SelectorError> {
+        Ok(self.select_parsed(&selector.parse()?))
+    }
+
+    /// Returns every node matching a parsed selector
+    pub fn select_parsed(&self, selector: &Selector) -> Vec<&Match<'a>> {
+        self.nodes
+            .iter()
+            .filter(|node| selector.matches(self, node))
+            .collect()
+    }
+}
+
+struct Builder<'a> {
+    nodes: Vec<Match<'a>>,
+    // position of the next character
+    cursor: Position,
+}
+
+impl<'a> Builder<'a> {
+    fn open(&mut self, node: Node<'a>, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+
+        self.nodes.push(Match {
+            node,
+            start: self.cursor,
+            end: self.cursor,
+            depth: parent
+                .map(|parent| self.nodes[parent].depth + 1)
+                .unwrap_or(0),
+            index,
+            parent,
+        });
+
+        index
+    }
+
+    fn close(&mut self, index: usize) {
+        self.nodes[index].end = self.cursor
+    }
+
+    fn chunks(&mut self, chunks: &'a [Chunk], parent: Option<usize>) {
+        chunks.iter().for_each(|chunk| self.chunk(chunk, parent))
+    }
+
+    fn arguments(&mut self, arguments: &'a [(String, Scope)], parent: Option<usize>) {
+        for (prec, arg) in arguments {
+            self.cursor.advance_str(prec);
+            self.scope(Node::Argument(arg), arg, parent);
+        }
+    }
+
+    fn scope(&mut self, node: Node<'a>, scope: &'a Scope, parent: Option<usize>) {
+        let index = self.open(node, parent);
+        self.cursor.advance(scope.variant().open());
+        self.chunks(scope.chunks(), Some(index));
+        self.cursor.advance(scope.variant().close());
+        self.close(index);
+    }
+
+    fn chunk(&mut self, chunk: &'a Chunk, parent: Option<usize>) {
+        match chunk.variant() {
+            ChunkVariant::Text(s) => {
+                let index = self.open(Node::Text(s), parent);
+                self.cursor.advance_str(s);
+                self.close(index);
+            }
+            ChunkVariant::ParagraphBreak(s) => {
+                let index = self.open(Node::ParagraphBreak(s), parent);
+                self.cursor.advance_str(s);
+                self.close(index);
+            }
+            ChunkVariant::MathsBlock(mb) => {
+                let index = self.open(Node::MathsBlock(mb), parent);
+                let (open, close) = mb.delimiters();
+                self.cursor.advance_str(open);
+                self.chunks(mb.content(), Some(index));
+                self.cursor.advance_str(close);
+                self.close(index);
+            }
+            ChunkVariant::Command(c) => {
+                let index = self.open(Node::Command(c), parent);
+                self.cursor.advance('\\');
+                self.cursor.advance_str(c.label());
+                self.arguments(c.arguments(), Some(index));
+                self.close(index);
+            }
+            ChunkVariant::Scope(sc) => self.scope(Node::Scope(sc), sc, parent),
+            ChunkVariant::Environment(env) => {
+                let index = self.open(Node::Environment(env), parent);
+                self.cursor.advance_str("\\begin");
+                self.cursor.advance_str(env.prec_begin());
+                self.cursor.advance('{');
+                self.cursor.advance_str(env.label());
+                self.cursor.advance('}');
+                self.arguments(env.arguments(), Some(index));
+                self.chunks(env.content(), Some(index));
+                self.cursor.advance_str("\\end");
+                self.cursor.advance_str(env.prec_end());
+                self.cursor.advance('{');
+                self.cursor.advance_str(env.label());
+                self.cursor.advance('}');
+                self.close(index);
+            }
+            ChunkVariant::Conditional(c) => {
+                let index = self.open(Node::Conditional(c), parent);
+                self.cursor.advance_str(&c.opening());
+                self.chunks(c.condition(), Some(index));
+                self.chunks(c.content(), Some(index));
+                for case in c.cases() {
+                    self.cursor.advance_str("\\or");
+                    self.chunks(case, Some(index));
+                }
+                if let Some(otherwise) = c.otherwise() {
+                    self.cursor.advance_str("\\else");
+                    self.chunks(otherwise, Some(index));
+                }
+                self.cursor.advance_str("\\fi");
+                self.close(index);
+            }
+            ChunkVariant::Parameter(p) => {
+                let index = self.open(Node::Parameter(p), parent);
+                self.cursor.advance_str(&p.to_string());
+                self.close(index);
+            }
+            ChunkVariant::MacroDefinition(d) => {
+                let index = self.open(Node::MacroDefinition(d), parent);
+                self.cursor.advance('\\');
+                self.cursor.advance_str(d.command());
+                self.cursor.advance_str(d.prec());
+                self.cursor.advance('\\');
+                self.cursor.advance_str(d.label());
+                self.chunks(d.parameters(), Some(index));
+                self.cursor.advance('{');
+                self.chunks(d.body(), Some(index));
+                self.cursor.advance('}');
+                self.close(index);
+            }
+        }
+    }
+}
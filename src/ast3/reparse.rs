@@ -0,0 +1,606 @@
+//! Incremental reparsing of a stage 3 document after a text edit
+//!
+//! Only the smallest scope, environment or paragraph containing the edit is parsed again,
+//! chunks around it are kept and the line numbers of chunks after it are shifted. When the
+//! edit could change how its surroundings parse, such as an unbalanced brace, a `\fi` or a
+//! `\def`, the whole document is parsed again instead.
+
+use std::str::FromStr;
+
+use crate::{
+    ast1::{self, Catcodes},
+    ast2,
+    traits::Lines,
+    ParseLimits, Position,
+};
+
+use super::{
+    visit::{walk_chunks, walk_command, Visit},
+    Chunk, ChunkVariant, Command, Conditional, Document, MacroDefinition, MathsBlock, Paragraph,
+    ScopeVariant,
+};
+
+impl Document {
+    /// Replace the text between two positions and update the document to match,
+    /// the result is equal to parsing the edited text from scratch
+    ///
+    /// Positions refer to the text the document displays as, which does not contain comments.
+    /// Positions past the end of a line are moved back to the end of the line. The document
+    /// is left unchanged if the edited text does not parse. The document is taken to be parsed
+    /// with the default catcodes.
+    pub fn reparse(
+        &mut self,
+        start: Position,
+        end: Position,
+        text: &str,
+    ) -> Result<(), crate::Error> {
+        let edit = Edit {
+            start,
+            end: end.max(start),
+            text,
+        };
+
+        // catcode changes affect how everything after them parses, and a comment takes out
+        // text after the edit
+        if edit.text.contains('%') || changes_catcodes(|visitor| visitor.visit_document(self)) {
+            return self.reparse_all(&edit);
+        }
+
+        if let Some(delta) = self.reparse_sections(&edit) {
+            self.chunks_trailing_mut()
+                .iter_mut()
+                .for_each(|chunk| *chunk.line_no_mut() = shift(chunk.line_no(), delta));
+            return Ok(());
+        }
+
+        self.reparse_all(&edit)
+    }
+
+    // parse the whole edited document
+    fn reparse_all(&mut self, edit: &Edit) -> Result<(), crate::Error> {
+        let source = self.to_string();
+        let from = byte_offset(&source, Position::start(), edit.start);
+        let to = byte_offset(&source, Position::start(), edit.end);
+
+        *self = Self::from_str(&format!(
+            "{}{}{}",
+            &source[..from],
+            edit.text,
+            &source[to..]
+        ))?;
+        Ok(())
+    }
+
+    // reparse within the preamable or the body, returning the change in lines
+    fn reparse_sections(&mut self, edit: &Edit) -> Option<i64> {
+        let preamable_end = Position::start().after(&concat(self.chunks_preamable()));
+
+        if edit.end <= preamable_end {
+            return reparse_chunks(
+                self.chunks_preamable_mut(),
+                Position::start(),
+                edit,
+                Container::Preamable,
+                &[],
+                0,
+            );
+        }
+
+        if !self.has_body() {
+            return None;
+        }
+
+        let mut body_start =
+            preamable_end.after(&format!("\\begin{}{{document}}", self.body_begin_prec()));
+        for (prec, arg) in self.body_args() {
+            body_start.advance_str(&format!("{prec}{arg}"));
+        }
+        let body_end = body_start.after(&concat(self.chunks_body()));
+
+        if body_start <= edit.start && edit.end <= body_end {
+            reparse_chunks(
+                self.chunks_body_mut(),
+                body_start,
+                edit,
+                Container::Environment,
+                &[],
+                1,
+            )
+        } else {
+            None
+        }
+    }
+}
+
+// a replacement of the text between two positions
+struct Edit<'a> {
+    start: Position,
+    end: Position,
+    text: &'a str,
+}
+
+impl Edit<'_> {
+    fn within(&self, start: Position, end: Position) -> bool {
+        start <= self.start && self.end <= end
+    }
+}
+
+// what the chunks being reparsed are the content of, and so what surrounds them
+#[derive(Clone, Copy, PartialEq)]
+enum Container {
+    Preamable,
+    Environment,
+    Scope,
+    // a branch of a conditional, the start of which could join the keyword before it
+    Branch,
+}
+
+// Reparse the smallest part of `chunks` containing the edit, where `base` is the position
+// of the first chunk, `scopes` are the variants of the scopes around them and `environments`
+// is the number of environments around them, returning the change in lines
+fn reparse_chunks(
+    chunks: &mut Vec<Chunk>,
+    base: Position,
+    edit: &Edit,
+    container: Container,
+    scopes: &[ScopeVariant],
+    environments: usize,
+) -> Option<i64> {
+    let strings = chunks.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let mut spans = Vec::with_capacity(chunks.len());
+    let mut position = base;
+
+    for string in strings.iter() {
+        let start = position;
+        position.advance_str(string);
+        spans.push((start, position));
+    }
+
+    let end = position;
+
+    // try the content of a scope or environment containing the whole edit first
+    if let Some(index) = spans.iter().position(|(s, e)| edit.within(*s, *e)) {
+        if container == Container::Preamable && takes_out(&chunks[index]) {
+            return None;
+        }
+
+        if let Some(delta) = reparse_nested(
+            &mut chunks[index],
+            spans[index].0,
+            edit,
+            scopes,
+            environments,
+        ) {
+            chunks[index + 1..]
+                .iter_mut()
+                .for_each(|chunk| *chunk.line_no_mut() = shift(chunk.line_no(), delta));
+            return Some(delta);
+        }
+    }
+
+    // otherwise the paragraphs the edit touches, which are folded into conditionals and
+    // macro definitions with the rest of the chunks
+    if chunks.iter().any(conditional) {
+        return None;
+    }
+
+    let is_break = |chunk: &Chunk| matches!(chunk.variant(), ChunkVariant::ParagraphBreak(_));
+    let touched = |index: usize| {
+        let (s, e) = spans[index];
+        s <= edit.end
+            && edit.start <= e
+            && !(is_break(&chunks[index]) && (e == edit.start || s == edit.end))
+    };
+
+    let (mut from, mut to) = match (0..chunks.len())
+        .filter(|i| touched(*i))
+        .fold(None, |range: Option<(usize, usize)>, i| {
+            Some(range.map_or((i, i + 1), |(a, _)| (a, i + 1)))
+        }) {
+        Some(range) => range,
+        None => {
+            let index = spans.iter().take_while(|(_, e)| *e <= edit.start).count();
+            (index, index)
+        }
+    };
+
+    while from > 0 && !is_break(&chunks[from - 1]) {
+        from -= 1;
+    }
+    while to < chunks.len() && !is_break(&chunks[to]) {
+        to += 1;
+    }
+
+    let region_start = spans.get(from).map_or(end, |(s, _)| *s);
+    let region_end = if to > from {
+        spans[to - 1].1
+    } else {
+        region_start
+    };
+
+    if !edit.within(region_start, region_end) || container == Container::Branch && from == 0 {
+        return None;
+    }
+
+    let old = strings[from..to].concat();
+    let start = byte_offset(&old, region_start, edit.start);
+    let end = byte_offset(&old, region_start, edit.end);
+    let fragment = format!("{}{}{}", &old[..start], edit.text, &old[end..]);
+
+    if !balanced(&fragment, scopes)
+        || container == Container::Environment
+            && from == 0
+            && fragment.starts_with(ast1::ScopeVariant::is_closing)
+    {
+        return None;
+    }
+
+    let mut new = parse(&fragment, scopes.len(), environments)?;
+
+    if changes_catcodes(|visitor| walk_chunks(visitor, &new))
+        || has_conditionals(|visitor| walk_chunks(visitor, &new))
+    {
+        return None;
+    }
+
+    let after_break = from > 0;
+    let before_break = to < chunks.len();
+
+    // paragraph breaks take all whitespace around them
+    if after_break && fragment.starts_with(char::is_whitespace)
+        || before_break && fragment[fragment.trim_end().len()..].contains('\n')
+        || after_break && before_break && fragment.is_empty()
+    {
+        return None;
+    }
+
+    let meaningful = |chunk: &&Chunk| match chunk.variant() {
+        ChunkVariant::Text(s) => !s.trim().is_empty(),
+        ChunkVariant::ParagraphBreak(_) => false,
+        _ => true,
+    };
+    let is_scope = |chunk: Option<&Chunk>| {
+        chunk.is_some_and(|chunk| matches!(chunk.variant(), ChunkVariant::Scope(_)))
+    };
+    let is_command = |chunk: Option<&Chunk>| {
+        chunk.is_some_and(|chunk| matches!(chunk.variant(), ChunkVariant::Command(_)))
+    };
+
+    // commands and environments take scopes as arguments across whitespace
+    if after_break
+        && is_scope(new.first())
+        && is_command(chunks[..from - 1].iter().rev().find(meaningful))
+        || before_break
+            && is_command(new.iter().rev().find(meaningful))
+            && is_scope(chunks.get(to + 1))
+        || container == Container::Environment
+            && from == 0
+            && match new.iter().find(meaningful) {
+                Some(chunk) => is_scope(Some(chunk)),
+                None => before_break,
+            }
+    {
+        return None;
+    }
+
+    // the document class and body are taken out of the preamable
+    if container == Container::Preamable && chunks[from..to].iter().chain(new.iter()).any(takes_out)
+    {
+        return None;
+    }
+
+    let line_no = match from.checked_sub(1) {
+        Some(last) => chunks[last].line_no() + chunks[last].lines() - 1,
+        None => 1,
+    };
+    new.iter_mut()
+        .for_each(|chunk| *chunk.line_no_mut() += line_no - 1);
+
+    let delta = newlines(&fragment) - newlines(&old);
+    let next = from + new.len();
+    chunks.splice(from..to, new);
+    chunks[next..]
+        .iter_mut()
+        .for_each(|chunk| *chunk.line_no_mut() = shift(chunk.line_no(), delta));
+
+    Some(delta)
+}
+
+// Reparse the content of a scope, or an environment or argument in a chunk, if it
+// contains the whole edit
+fn reparse_nested(
+    chunk: &mut Chunk,
+    start: Position,
+    edit: &Edit,
+    scopes: &[ScopeVariant],
+    environments: usize,
+) -> Option<i64> {
+    match chunk.variant_mut() {
+        ChunkVariant::Scope(scope) => {
+            let content = start.after(&scope.variant().open().to_string());
+            let end = content.after(&concat(scope.chunks()));
+
+            if edit.within(content, end) {
+                let scopes = [scopes, &[scope.variant()]].concat();
+                reparse_chunks(
+                    scope.chunks_mut(),
+                    content,
+                    edit,
+                    Container::Scope,
+                    &scopes,
+                    environments,
+                )
+            } else {
+                None
+            }
+        }
+        ChunkVariant::Command(command) => {
+            let mut position = start.after(&format!("\\{}", command.label()));
+
+            for (prec, arg) in command.arguments_mut() {
+                position.advance_str(prec);
+                let content = position.after(&arg.variant().open().to_string());
+                let end = content.after(&concat(arg.chunks()));
+                position.advance_str(&arg.to_string());
+
+                if edit.within(content, end) {
+                    let scopes = [scopes, &[arg.variant()]].concat();
+                    return reparse_chunks(
+                        arg.chunks_mut(),
+                        content,
+                        edit,
+                        Container::Scope,
+                        &scopes,
+                        environments,
+                    );
+                }
+            }
+
+            None
+        }
+        ChunkVariant::Environment(environment) => {
+            let mut position = start.after(&format!(
+                "\\begin{}{{{}}}",
+                environment.prec_begin(),
+                environment.label()
+            ));
+
+            for (prec, arg) in environment.arguments_mut() {
+                position.advance_str(prec);
+                let content = position.after(&arg.variant().open().to_string());
+                let end = content.after(&concat(arg.chunks()));
+                position.advance_str(&arg.to_string());
+
+                if edit.within(content, end) {
+                    let scopes = [scopes, &[arg.variant()]].concat();
+                    return reparse_chunks(
+                        arg.chunks_mut(),
+                        content,
+                        edit,
+                        Container::Scope,
+                        &scopes,
+                        environments + 1,
+                    );
+                }
+            }
+
+            let end = position.after(&concat(environment.content()));
+
+            if edit.within(position, end) {
+                reparse_chunks(
+                    environment.content_mut(),
+                    position,
+                    edit,
+                    Container::Environment,
+                    scopes,
+                    environments + 1,
+                )
+            } else {
+                None
+            }
+        }
+        ChunkVariant::Conditional(conditional) => {
+            // the condition is read from the start of the content
+            let mut position = start.after(&format!(
+                "{}{}",
+                conditional.opening(),
+                concat(conditional.condition())
+            ));
+            let keywords = std::iter::once("")
+                .chain(std::iter::repeat_n("\\or", conditional.cases().len()))
+                .chain(conditional.otherwise().map(|_| "\\else"))
+                .collect::<Vec<_>>();
+
+            for (keyword, part) in keywords.into_iter().zip(conditional.parts_mut().skip(1)) {
+                position.advance_str(keyword);
+                let end = position.after(&concat(part));
+
+                if edit.within(position, end) {
+                    return reparse_chunks(
+                        part,
+                        position,
+                        edit,
+                        Container::Branch,
+                        scopes,
+                        environments,
+                    );
+                }
+
+                position = end;
+            }
+
+            None
+        }
+        // the parameter text is reparsed with the definition around it
+        ChunkVariant::MacroDefinition(definition) => {
+            let content = definition.body_start(start);
+            let end = content.after(&concat(definition.body()));
+
+            if edit.within(content, end) {
+                let scopes = [scopes, &[ScopeVariant::Curly]].concat();
+                reparse_chunks(
+                    definition.body_mut(),
+                    content,
+                    edit,
+                    Container::Scope,
+                    &scopes,
+                    environments,
+                )
+            } else {
+                None
+            }
+        }
+        ChunkVariant::Text(_)
+        | ChunkVariant::ParagraphBreak(_)
+        | ChunkVariant::MathsBlock(_)
+        | ChunkVariant::Parameter(_) => None,
+    }
+}
+
+// whether any command visited changes the catcodes
+fn changes_catcodes(visit: impl FnOnce(&mut CatcodeChanges)) -> bool {
+    let mut visitor = CatcodeChanges(false);
+    visit(&mut visitor);
+    visitor.0
+}
+
+struct CatcodeChanges(bool);
+
+impl Visit for CatcodeChanges {
+    fn visit_command(&mut self, command: &Command) {
+        self.0 |= Catcodes::is_change(command.label());
+        walk_command(self, command)
+    }
+}
+
+// whether any conditional, macro definition or command which could be part of one is visited
+fn has_conditionals(visit: impl FnOnce(&mut Conditionals)) -> bool {
+    let mut visitor = Conditionals(false);
+    visit(&mut visitor);
+    visitor.0
+}
+
+struct Conditionals(bool);
+
+impl Visit for Conditionals {
+    fn visit_command(&mut self, command: &Command) {
+        self.0 |= folded(command.label());
+        walk_command(self, command)
+    }
+
+    fn visit_conditional(&mut self, _conditional: &Conditional) {
+        self.0 = true
+    }
+
+    fn visit_macro_definition(&mut self, _definition: &MacroDefinition) {
+        self.0 = true
+    }
+}
+
+// whether the chunk is a conditional or macro definition, or a command which could be part
+// of one
+fn conditional(chunk: &Chunk) -> bool {
+    match chunk.variant() {
+        ChunkVariant::Command(command) => folded(command.label()),
+        ChunkVariant::Conditional(_) | ChunkVariant::MacroDefinition(_) => true,
+        _ => false,
+    }
+}
+
+// whether a command could start or end a conditional or macro definition
+fn folded(label: &str) -> bool {
+    ast2::is_keyword(label) || ast2::DEF_COMMANDS.contains(&label)
+}
+
+// whether the chunk is taken out of the preamable into fields of the document
+fn takes_out(chunk: &Chunk) -> bool {
+    match chunk.variant() {
+        ChunkVariant::Command(command) => command.label() == "documentclass",
+        ChunkVariant::Environment(environment) => environment.label() == "document",
+        _ => false,
+    }
+}
+
+// parse text as the content of a scope or environment, nested in `depth` scopes and
+// `environments` environments, failing where parsing the whole document would
+fn parse(text: &str, depth: usize, environments: usize) -> Option<Vec<Chunk>> {
+    let limits = ParseLimits::default();
+    let limits = limits
+        .with_max_depth(limits.max_depth().checked_sub(depth)?)
+        .with_max_environment_depth(limits.max_environment_depth().checked_sub(environments)?);
+
+    let chunks = ast2::Document::parse_with_limits(text, limits)
+        .ok()?
+        .chunks_owned();
+    Some(Paragraph::from_chunks(
+        MathsBlock::from_chunks(chunks).ok()?,
+    ))
+}
+
+// whether text does not end escaped or in a comment, and does not close any of the scopes
+// it is in, so parsing it on its own gives the same chunks as parsing it in place
+fn balanced(text: &str, scopes: &[ScopeVariant]) -> bool {
+    let mut depths = vec![0; scopes.len()];
+    let mut escaped = false;
+    let mut comment = false;
+
+    for c in text.chars() {
+        match c {
+            '\n' => comment = false,
+            _ if comment => continue,
+            '%' if !escaped => {
+                comment = true;
+                continue;
+            }
+            '\\' if !escaped => {
+                escaped = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        if !escaped {
+            for (depth, variant) in depths.iter_mut().zip(scopes) {
+                if c == variant.open() {
+                    *depth += 1
+                } else if c == variant.close() {
+                    *depth -= 1
+                }
+
+                if *depth < 0 {
+                    return false;
+                }
+            }
+        }
+
+        escaped = false;
+    }
+
+    !escaped && !comment && depths.iter().all(|depth| *depth == 0)
+}
+
+fn concat(chunks: &[Chunk]) -> String {
+    chunks.iter().map(ToString::to_string).collect()
+}
+
+fn newlines(text: &str) -> i64 {
+    text.matches('\n').count() as i64
+}
+
+fn shift(line_no: u32, delta: i64) -> u32 {
+    (line_no as i64 + delta) as u32
+}
+
+// byte offset of a position in text starting at `base`, clamped to the end of its line
+fn byte_offset(text: &str, base: Position, position: Position) -> usize {
+    let mut current = base;
+
+    for (index, c) in text.char_indices() {
+        if current >= position || current.line == position.line && c == '\n' {
+            return index;
+        }
+        current.advance(c);
+    }
+
+    text.len()
+}
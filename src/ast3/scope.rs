@@ -3,7 +3,7 @@ use std::fmt::Display;
 use crate::{
     ast2,
     traits::{Lines, Validate},
-    InternalError,
+    InternalError, Position,
 };
 
 use super::{Chunk, MathsBlock, Paragraph, ScopeVariant};
@@ -38,6 +38,11 @@ impl Scope {
         &self.chunks
     }
 
+    /// Returns all mutable chunks within
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.chunks
+    }
+
     /// Returns all owned chunks within
     pub fn chunks_owned(self) -> Vec<Chunk> {
         self.chunks
@@ -57,11 +62,14 @@ impl Scope {
 impl TryFrom<ast2::Scope> for Scope {
     type Error = crate::Error;
 
+    /// Error positions are relative to the opening parenthesis of the scope
     fn try_from(value: ast2::Scope) -> Result<Self, Self::Error> {
         let (chunks, variant) = value.decompose();
 
         Ok(Self::new_unchecked(
-            Paragraph::from_chunks(MathsBlock::from_chunks(chunks)?),
+            Paragraph::from_chunks(
+                MathsBlock::from_chunks(chunks).map_err(|e| e.offset(Position::new(1, 2)))?,
+            ),
             variant.into(),
         ))
     }
@@ -70,7 +78,7 @@ impl TryFrom<ast2::Scope> for Scope {
 impl Validate for Scope {
     fn validate(&self) -> Result<(), crate::InternalError> {
         for chunk in self.chunks() {
-            chunk.validate()?
+            chunk.validate_in_group()?
         }
 
         Ok(())
@@ -0,0 +1,252 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::{Match, MathsType, Node, Query};
+
+/// A CSS-like pattern matching nodes of a `Query`
+///
+/// A selector is a list of steps, each written as a node type optionally followed
+/// by a label in square brackets, such as `command[caption]`.
+///
+/// |Type|Matches|
+/// |---|---|
+/// |`*`|Any node.|
+/// |`text`|Plain text.|
+/// |`paragraph`|Paragraph breaks.|
+/// |`maths`|Maths blocks, the label can be `inline` or `outline`.|
+/// |`command`|Commands, the label is the command name.|
+/// |`scope`|Scopes which are not arguments.|
+/// |`argument`|Arguments of commands and environments.|
+/// |`environment`|Environments including `document`, the label is the environment name.|
+/// |`conditional`|Conditionals, the label is the test such as `ifx`.|
+/// |`definition`|Macro definitions, the label is the name of the macro.|
+/// |`parameter`|Parameter tokens such as `#1`.|
+///
+/// Steps separated by whitespace match descendants, steps separated by `>` match
+/// direct children, and selectors separated by `,` match either.
+///
+/// - `environment[figure] > command[caption]` captions directly inside a figure
+/// - `maths command[frac]` fractions anywhere in maths
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Selector(Vec<Vec<(Combinator, Step)>>);
+
+/// How a step relates to the step before it
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+enum StepType {
+    Any,
+    Text,
+    Paragraph,
+    Maths,
+    Command,
+    Scope,
+    Argument,
+    Environment,
+    Conditional,
+    Definition,
+    Parameter,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+struct Step {
+    r#type: StepType,
+    label: Option<String>,
+}
+
+impl Step {
+    fn matches(&self, node: &Node) -> bool {
+        let type_matches = matches!(
+            (self.r#type, node),
+            (StepType::Any, _)
+                | (StepType::Text, Node::Text(_))
+                | (StepType::Paragraph, Node::ParagraphBreak(_))
+                | (StepType::Maths, Node::MathsBlock(_))
+                | (StepType::Command, Node::Command(_))
+                | (StepType::Scope, Node::Scope(_))
+                | (StepType::Argument, Node::Argument(_))
+                | (StepType::Environment, Node::Environment(_) | Node::Body(_))
+                | (StepType::Conditional, Node::Conditional(_))
+                | (StepType::Definition, Node::MacroDefinition(_))
+                | (StepType::Parameter, Node::Parameter(_))
+        );
+
+        let label = match &self.label {
+            Some(label) => label,
+            None => return type_matches,
+        };
+
+        type_matches
+            && match node {
+                Node::MathsBlock(mb) => match mb.r#type() {
+                    MathsType::Inline => label == "inline",
+                    MathsType::Outline => label == "outline",
+                },
+                _ => node.label() == Some(label),
+            }
+    }
+}
+
+impl Selector {
+    /// Whether a node of the query matches the selector
+    pub fn matches(&self, query: &Query, node: &Match) -> bool {
+        self.0
+            .iter()
+            .any(|steps| Self::matches_steps(query, steps, node))
+    }
+
+    // match the last step against the node, then the remaining steps against its ancestors
+    fn matches_steps(query: &Query, steps: &[(Combinator, Step)], node: &Match) -> bool {
+        let ((combinator, step), rest) = match steps.split_last() {
+            Some(split) => split,
+            None => return true,
+        };
+
+        if !step.matches(&node.node()) {
+            return false;
+        }
+
+        if rest.is_empty() {
+            return true;
+        }
+
+        match combinator {
+            Combinator::Child => query
+                .parent_of(node)
+                .is_some_and(|parent| Self::matches_steps(query, rest, parent)),
+            Combinator::Descendant => query
+                .ancestors_of(node)
+                .any(|ancestor| Self::matches_steps(query, rest, ancestor)),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut alternatives = Vec::new();
+        let mut steps = Vec::new();
+        let mut combinator = None;
+        let mut chars = s.chars().enumerate().peekable();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                c if c.is_whitespace() => {
+                    if !steps.is_empty() && combinator.is_none() {
+                        combinator = Some(Combinator::Descendant)
+                    }
+                }
+                '>' if steps.is_empty() || combinator == Some(Combinator::Child) => {
+                    return Err(SelectorError::MissingStep(index))
+                }
+                '>' => combinator = Some(Combinator::Child),
+                ',' if steps.is_empty() || combinator == Some(Combinator::Child) => {
+                    return Err(SelectorError::MissingStep(index))
+                }
+                ',' => {
+                    alternatives.push(std::mem::take(&mut steps));
+                    combinator = None;
+                }
+                c if c == '*' || c.is_ascii_alphabetic() => {
+                    if !steps.is_empty() && combinator.is_none() {
+                        return Err(SelectorError::UnexpectedChar(c, index));
+                    }
+
+                    let mut name = c.to_string();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                        name.push(c)
+                    }
+
+                    let r#type = match name.as_str() {
+                        "*" => StepType::Any,
+                        "text" => StepType::Text,
+                        "paragraph" => StepType::Paragraph,
+                        "maths" => StepType::Maths,
+                        "command" => StepType::Command,
+                        "scope" => StepType::Scope,
+                        "argument" => StepType::Argument,
+                        "environment" => StepType::Environment,
+                        "conditional" => StepType::Conditional,
+                        "definition" => StepType::Definition,
+                        "parameter" => StepType::Parameter,
+                        _ => return Err(SelectorError::UnknownType(name)),
+                    };
+
+                    let label = match chars.next_if(|(_, c)| c == &'[') {
+                        Some((open, _)) => {
+                            let mut label = String::new();
+                            loop {
+                                match chars.next() {
+                                    Some((_, ']')) => break,
+                                    Some((_, c)) => label.push(c),
+                                    None => return Err(SelectorError::UnclosedLabel(open)),
+                                }
+                            }
+                            Some(label.trim().to_string())
+                        }
+                        None => None,
+                    };
+
+                    steps.push((
+                        combinator.take().unwrap_or(Combinator::Descendant),
+                        Step { r#type, label },
+                    ));
+                }
+                c => return Err(SelectorError::UnexpectedChar(c, index)),
+            }
+        }
+
+        if combinator == Some(Combinator::Child) {
+            return Err(SelectorError::MissingStep(s.chars().count()));
+        }
+
+        if steps.is_empty() {
+            return Err(SelectorError::MissingStep(s.chars().count()));
+        }
+
+        alternatives.push(steps);
+        Ok(Self(alternatives))
+    }
+}
+
+/// Reasons a selector could not be parsed, positions are character indices starting from 0
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SelectorError {
+    /// A character which cannot appear here
+    UnexpectedChar(char, usize),
+    /// A node type which does not exist
+    UnknownType(String),
+    /// A label opened with `[` but never closed
+    UnclosedLabel(usize),
+    /// A `>` or `,` without a step on either side, or an empty selector
+    MissingStep(usize),
+}
+
+impl Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedChar(c, index) => {
+                f.write_fmt(format_args!("unexpected `{c}` at index {index}"))
+            }
+            Self::UnknownType(name) => f.write_fmt(format_args!("unknown node type `{name}`")),
+            Self::UnclosedLabel(index) => f.write_fmt(format_args!(
+                "label opened at index {index} is never closed"
+            )),
+            Self::MissingStep(index) => {
+                f.write_fmt(format_args!("expected a step at index {index}"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
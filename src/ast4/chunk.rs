@@ -11,7 +11,7 @@ pub struct Chunk {
 }
 
 impl From<ast3::Chunk> for Chunk {
-    fn from(value: ast3::Chunk) -> Self {
+    fn from(_value: ast3::Chunk) -> Self {
         todo!()
     }
 }
@@ -21,7 +21,7 @@ pub enum ChunkVariant {
 }
 
 impl From<ast3::ChunkVariant> for ChunkVariant {
-    fn from(value: ast3::ChunkVariant) -> Self {
+    fn from(_value: ast3::ChunkVariant) -> Self {
         todo!()
     }
 }
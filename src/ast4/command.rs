@@ -6,7 +6,7 @@ use crate::ast3;
 pub struct Command;
 
 impl From<ast3::Command> for Command {
-    fn from(value: ast3::Command) -> Self {
+    fn from(_value: ast3::Command) -> Self {
         todo!()
     }
 }
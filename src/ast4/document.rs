@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::ast3;
 
 use super::{documentoptions::DocumentOptions, Chunk, DocumentClass, Scope};
@@ -22,7 +20,7 @@ pub struct Document {
 }
 
 impl From<ast3::Document> for Document {
-    fn from(value: ast3::Document) -> Self {
+    fn from(_value: ast3::Document) -> Self {
         todo!()
     }
 }
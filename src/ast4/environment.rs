@@ -6,7 +6,7 @@ use crate::ast3;
 pub struct Environment;
 
 impl From<ast3::Environment> for Environment {
-    fn from(value: ast3::Environment) -> Self {
+    fn from(_value: ast3::Environment) -> Self {
         todo!()
     }
 }
@@ -12,7 +12,7 @@ pub struct MathsBlock {
 }
 
 impl From<ast3::MathsBlock> for MathsBlock {
-    fn from(value: ast3::MathsBlock) -> Self {
+    fn from(_value: ast3::MathsBlock) -> Self {
         todo!()
     }
 }
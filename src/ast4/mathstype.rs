@@ -12,7 +12,7 @@ pub enum MathsType {
 }
 
 impl From<ast3::MathsType> for MathsType {
-    fn from(value: ast3::MathsType) -> Self {
+    fn from(_value: ast3::MathsType) -> Self {
         todo!()
     }
 }
@@ -12,7 +12,7 @@ pub enum MathsVariant {
 }
 
 impl From<ast3::MathsVariant> for MathsVariant {
-    fn from(value: ast3::MathsVariant) -> Self {
+    fn from(_value: ast3::MathsVariant) -> Self {
         todo!()
     }
 }
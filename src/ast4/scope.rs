@@ -11,7 +11,7 @@ pub struct Scope {
 }
 
 impl From<ast3::Scope> for Scope {
-    fn from(value: ast3::Scope) -> Self {
+    fn from(_value: ast3::Scope) -> Self {
         todo!()
     }
 }
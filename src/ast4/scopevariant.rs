@@ -11,7 +11,7 @@ pub enum ScopeVariant {
 }
 
 impl From<ast3::ScopeVariant> for ScopeVariant {
-    fn from(value: ast3::ScopeVariant) -> Self {
+    fn from(_value: ast3::ScopeVariant) -> Self {
         todo!()
     }
 }
@@ -0,0 +1,24 @@
+//! Language server over stdio, see `latex_ast::lsp`
+
+use std::io;
+
+use latex_ast::lsp::{read_message, write_message, Server};
+
+fn main() -> io::Result<()> {
+    let mut reader = io::stdin().lock();
+    let mut writer = io::stdout().lock();
+    let mut server = Server::new();
+
+    while let Some(message) = read_message(&mut reader)? {
+        for reply in server.handle_str(&message) {
+            write_message(&mut writer, &reply)?;
+        }
+
+        if let Some(code) = server.exit_code() {
+            std::process::exit(code)
+        }
+    }
+
+    // the client went away without asking to exit
+    std::process::exit(1)
+}
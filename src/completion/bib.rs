@@ -0,0 +1,178 @@
+/// An entry of a `.bib` file
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct BibEntry {
+    key: String,
+    r#type: String,
+    title: Option<String>,
+}
+
+impl BibEntry {
+    /// Returns the citation key
+    pub fn key(&self) -> &String {
+        &self.key
+    }
+
+    /// Returns the entry type in lowercase, such as `article`
+    pub fn r#type(&self) -> &String {
+        &self.r#type
+    }
+
+    /// Returns the title field with braces removed, if there is one
+    pub fn title(&self) -> Option<&String> {
+        self.title.as_ref()
+    }
+
+    /// Read all entries of a `.bib` file, skipping anything malformed
+    ///
+    /// `@string`, `@preamble` and `@comment` blocks are not entries.
+    pub fn parse_all(source: &str) -> Vec<Self> {
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut entries = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            if chars[i] != '@' {
+                i += 1;
+                continue;
+            }
+            i += 1;
+
+            let r#type = take_while(&chars, &mut i, |c| c.is_ascii_alphabetic()).to_lowercase();
+            take_while(&chars, &mut i, char::is_whitespace);
+
+            let close = match chars.get(i) {
+                Some('{') => '}',
+                Some('(') => ')',
+                _ => continue,
+            };
+            i += 1;
+
+            if matches!(r#type.as_str(), "string" | "preamble" | "comment") {
+                skip_group(&chars, &mut i, close);
+                continue;
+            }
+
+            take_while(&chars, &mut i, char::is_whitespace);
+            let key = take_while(&chars, &mut i, |c| {
+                c != ',' && c != close && !c.is_whitespace()
+            });
+            let mut title = None;
+
+            // fields are `name = value` separated by commas
+            loop {
+                take_while(&chars, &mut i, |c| c.is_whitespace() || c == ',');
+
+                match chars.get(i) {
+                    Some(c) if *c == close => {
+                        i += 1;
+                        break;
+                    }
+                    None => break,
+                    _ => {}
+                }
+
+                let name = take_while(&chars, &mut i, |c| {
+                    c != '=' && c != ',' && c != close && !c.is_whitespace()
+                })
+                .to_lowercase();
+                take_while(&chars, &mut i, char::is_whitespace);
+
+                if chars.get(i) != Some(&'=') {
+                    // not a field, skip to the next comma
+                    take_while(&chars, &mut i, |c| c != ',' && c != close);
+                    continue;
+                }
+                i += 1;
+
+                let value = field_value(&chars, &mut i, close);
+
+                if name == "title" {
+                    title = Some(value.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+            }
+
+            if !key.is_empty() {
+                entries.push(Self { key, r#type, title })
+            }
+        }
+
+        entries
+    }
+}
+
+fn take_while(chars: &[char], i: &mut usize, f: impl Fn(char) -> bool) -> String {
+    let start = *i;
+    while chars.get(*i).is_some_and(|c| f(*c)) {
+        *i += 1
+    }
+    chars[start..*i].iter().collect()
+}
+
+// skip to after the closing character of a group, minding nested braces
+fn skip_group(chars: &[char], i: &mut usize, close: char) {
+    let mut depth = 0;
+
+    while let Some(c) = chars.get(*i) {
+        *i += 1;
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            c if *c == close && depth == 0 => return,
+            _ => {}
+        }
+    }
+}
+
+// reads a braced, quoted or bare value, values joined with `#` are concatenated
+fn field_value(chars: &[char], i: &mut usize, close: char) -> String {
+    let mut out = String::new();
+
+    loop {
+        take_while(chars, i, char::is_whitespace);
+
+        match chars.get(*i) {
+            Some('{') => {
+                *i += 1;
+                let mut depth = 0;
+
+                while let Some(c) = chars.get(*i) {
+                    *i += 1;
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => break,
+                        '}' => depth -= 1,
+                        c => out.push(*c),
+                    }
+                }
+            }
+            Some('"') => {
+                *i += 1;
+                let mut depth = 0;
+
+                while let Some(c) = chars.get(*i) {
+                    *i += 1;
+                    match c {
+                        '"' if depth == 0 => break,
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        c => out.push(*c),
+                    }
+                }
+            }
+            Some(_) => out.push_str(&take_while(chars, i, |c| {
+                c != ',' && c != close && c != '#' && !c.is_whitespace()
+            })),
+            None => return out,
+        }
+
+        take_while(chars, i, char::is_whitespace);
+
+        if chars.get(*i) == Some(&'#') {
+            *i += 1;
+        } else {
+            return out;
+        }
+    }
+}
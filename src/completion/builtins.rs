@@ -0,0 +1,148 @@
+/// Commands which define a new command named by their first argument
+pub const DEFINING_COMMANDS: [&str; 5] = [
+    "newcommand",
+    "renewcommand",
+    "providecommand",
+    "DeclareMathOperator",
+    "DeclareRobustCommand",
+];
+
+/// Commonly used commands from LaTeX and popular packages, besides the
+/// [sectioning commands](crate::sectioning::COMMANDS)
+pub const COMMANDS: &[&str] = &[
+    "addbibresource",
+    "alpha",
+    "appendix",
+    "author",
+    "autoref",
+    "begin",
+    "beta",
+    "bibitem",
+    "bibliography",
+    "bibliographystyle",
+    "bigskip",
+    "caption",
+    "centering",
+    "chi",
+    "cite",
+    "cref",
+    "date",
+    "ddots",
+    "delta",
+    "documentclass",
+    "dots",
+    "ell",
+    "emph",
+    "end",
+    "epsilon",
+    "eqref",
+    "eta",
+    "footnote",
+    "frac",
+    "gamma",
+    "geq",
+    "hfill",
+    "hline",
+    "href",
+    "hspace",
+    "includegraphics",
+    "infty",
+    "input",
+    "int",
+    "item",
+    "kappa",
+    "label",
+    "lambda",
+    "langle",
+    "ldots",
+    "left",
+    "leq",
+    "lim",
+    "linewidth",
+    "maketitle",
+    "mathbb",
+    "mathbf",
+    "mathcal",
+    "mathrm",
+    "medskip",
+    "mu",
+    "newcommand",
+    "newenvironment",
+    "newline",
+    "newpage",
+    "noindent",
+    "nu",
+    "omega",
+    "pageref",
+    "partial",
+    "phi",
+    "pi",
+    "prod",
+    "psi",
+    "qquad",
+    "quad",
+    "rangle",
+    "ref",
+    "renewcommand",
+    "rho",
+    "right",
+    "sigma",
+    "smallskip",
+    "sqrt",
+    "sum",
+    "tableofcontents",
+    "tau",
+    "textbf",
+    "textit",
+    "textsc",
+    "texttt",
+    "textwidth",
+    "theta",
+    "times",
+    "title",
+    "to",
+    "underline",
+    "url",
+    "usepackage",
+    "varepsilon",
+    "vdots",
+    "vspace",
+    "xi",
+    "zeta",
+];
+
+/// Commonly used environments from LaTeX and popular packages
+pub const ENVIRONMENTS: &[&str] = &[
+    "abstract",
+    "align",
+    "align*",
+    "array",
+    "bmatrix",
+    "cases",
+    "center",
+    "description",
+    "document",
+    "enumerate",
+    "equation",
+    "equation*",
+    "figure",
+    "figure*",
+    "flushleft",
+    "flushright",
+    "gather",
+    "gather*",
+    "itemize",
+    "lemma",
+    "minipage",
+    "multline",
+    "pmatrix",
+    "proof",
+    "quotation",
+    "quote",
+    "table",
+    "table*",
+    "tabular",
+    "thebibliography",
+    "theorem",
+    "verbatim",
+];
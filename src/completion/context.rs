@@ -0,0 +1,155 @@
+/// Commands whose arguments refer to labels
+pub(crate) const REF_COMMANDS: [&str; 9] = [
+    "ref",
+    "eqref",
+    "pageref",
+    "autoref",
+    "cref",
+    "Cref",
+    "vref",
+    "nameref",
+    "labelcref",
+];
+
+/// Commands whose arguments are citation keys
+pub(crate) const CITE_COMMANDS: [&str; 12] = [
+    "cite",
+    "citep",
+    "citet",
+    "citeauthor",
+    "citeyear",
+    "parencite",
+    "textcite",
+    "autocite",
+    "footcite",
+    "fullcite",
+    "nocite",
+    "Cite",
+];
+
+/// What is being typed at the cursor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Context {
+    /// A command name after `\`
+    Command(String),
+    /// An environment name inside `\begin{`
+    Begin(String),
+    /// An environment name inside `\end{`, with the innermost open environment
+    End(String, Option<String>),
+    /// A label inside `\ref{` and similar
+    Ref(String),
+    /// A citation key inside `\cite{` and similar
+    Cite(String),
+}
+
+impl Context {
+    /// Work out the context from the text before the cursor
+    pub fn detect(before: &str) -> Option<Self> {
+        let name_start = before
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+            .len();
+
+        if is_command_start(before, name_start) {
+            return Some(Self::Command(before[name_start..].to_string()));
+        }
+
+        // inside an argument which has not been closed yet
+        let open = before.rfind('{')?;
+        let typed = &before[open + 1..];
+
+        if typed.contains(['}', '{', '\\', '\n']) {
+            return None;
+        }
+
+        let mut head = before[..open].trim_end();
+
+        // skip optional arguments such as `\cite[p.~3]{`
+        while head.ends_with(']') {
+            head = head[..head.rfind('[')?].trim_end();
+        }
+
+        let label_start = head
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '*')
+            .len();
+
+        if !is_command_start(head, label_start) {
+            return None;
+        }
+
+        let label = head[label_start..].trim_end_matches('*');
+        let partial = typed
+            .rsplit(',')
+            .next()
+            .unwrap_or("")
+            .trim_start()
+            .to_string();
+
+        match label {
+            "begin" => Some(Self::Begin(partial)),
+            "end" => Some(Self::End(
+                partial,
+                innermost_open(&before[..label_start - 1]),
+            )),
+            _ if REF_COMMANDS.contains(&label) => Some(Self::Ref(partial)),
+            _ if CITE_COMMANDS.contains(&label) => Some(Self::Cite(partial)),
+            _ => None,
+        }
+    }
+}
+
+// whether a command name starts at `index`, after an unescaped backslash
+fn is_command_start(s: &str, index: usize) -> bool {
+    let backslashes = s[..index].chars().rev().take_while(|c| c == &'\\').count();
+    backslashes % 2 == 1
+}
+
+// the innermost environment opened but not yet closed
+fn innermost_open(s: &str) -> Option<String> {
+    let mut stack = Vec::new();
+
+    for line in s.split('\n') {
+        // ignore comments
+        let mut line = line;
+        let mut search = 0;
+        while let Some(found) = line[search..].find('%') {
+            let at = search + found;
+            if is_command_start(line, at) && at > 0 {
+                search = at + 1;
+            } else {
+                line = &line[..at];
+                break;
+            }
+        }
+
+        let mut rest = line;
+
+        while let Some(at) = rest.find('\\') {
+            rest = &rest[at + 1..];
+
+            let (is_begin, after) = if let Some(after) = rest.strip_prefix("begin") {
+                (true, after)
+            } else if let Some(after) = rest.strip_prefix("end") {
+                (false, after)
+            } else {
+                continue;
+            };
+
+            let after = after.trim_start();
+            let name = match after
+                .strip_prefix('{')
+                .and_then(|after| after.split_once('}'))
+            {
+                Some((name, _)) => name.to_string(),
+                None => continue,
+            };
+
+            if is_begin {
+                stack.push(name)
+            } else if let Some(index) = stack.iter().rposition(|open| open == &name) {
+                stack.truncate(index)
+            }
+        }
+    }
+
+    stack.pop()
+}
@@ -0,0 +1,327 @@
+//! # Completion
+//!
+//! Proposes completions for the text being typed at a cursor.
+//!
+//! |Context|Proposals|
+//! |---|---|
+//! |`\`|Built-in commands and commands defined with `\newcommand` and similar.|
+//! |`\begin{`|Built-in environments, environments used or defined in the document.|
+//! |`\end{`|The innermost environment which is still open.|
+//! |`\ref{`|Labels declared with `\label`.|
+//! |`\cite{`|Keys from `\bibitem` and loaded `.bib` files.|
+//!
+//! The context is read from the text before the cursor, so that unfinished input
+//! such as `\ref{` without a closing brace still gets proposals. Candidates are
+//! collected from a document, which can be an older version that still parsed.
+
+mod bib;
+mod builtins;
+mod context;
+
+pub use bib::BibEntry;
+pub use context::Context;
+pub(crate) use context::{CITE_COMMANDS, REF_COMMANDS};
+
+use crate::{
+    ast3::{self, Node},
+    sectioning, Position,
+};
+
+/// What a completion inserts
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompletionKind {
+    Command,
+    Environment,
+    Label,
+    Citation,
+}
+
+/// A proposed completion
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Completion {
+    label: String,
+    kind: CompletionKind,
+    detail: String,
+}
+
+impl Completion {
+    /// Create a new completion
+    pub fn new(label: String, kind: CompletionKind, detail: String) -> Self {
+        Self {
+            label,
+            kind,
+            detail,
+        }
+    }
+
+    /// Returns the text to insert, without the leading `\` of commands
+    pub fn label(&self) -> &String {
+        &self.label
+    }
+
+    /// Returns what is inserted
+    pub fn kind(&self) -> CompletionKind {
+        self.kind
+    }
+
+    /// Returns a short description
+    pub fn detail(&self) -> &String {
+        &self.detail
+    }
+}
+
+/// Proposes completions, with citation keys from any loaded `.bib` files
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Completer {
+    bib: Vec<BibEntry>,
+}
+
+impl Completer {
+    /// Create a completer without any `.bib` files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the entries of a `.bib` file
+    pub fn add_bib(&mut self, source: &str) {
+        self.bib.extend(BibEntry::parse_all(source))
+    }
+
+    /// Returns the loaded `.bib` entries
+    pub fn bib(&self) -> &Vec<BibEntry> {
+        &self.bib
+    }
+
+    /// Completions at a position in the document
+    pub fn complete(&self, document: &ast3::Document, position: Position) -> Vec<Completion> {
+        let text = document.to_string();
+        let mut cursor = Position::start();
+        let before = text
+            .char_indices()
+            .find(|(_, c)| {
+                let reached = cursor >= position;
+                cursor.advance(*c);
+                reached
+            })
+            .map(|(index, _)| &text[..index])
+            .unwrap_or(&text);
+
+        self.complete_after(document, before)
+    }
+
+    /// Completions for the text before the cursor, with candidates from the document
+    pub fn complete_after(&self, document: &ast3::Document, before: &str) -> Vec<Completion> {
+        let (partial, mut out) = match Context::detect(before) {
+            Some(Context::Command(partial)) => (partial, commands(document)),
+            Some(Context::Begin(partial)) => (partial, environments(document)),
+            Some(Context::End(partial, open)) => (
+                partial,
+                open.map(|open| {
+                    Completion::new(
+                        open,
+                        CompletionKind::Environment,
+                        "innermost open environment".to_string(),
+                    )
+                })
+                .into_iter()
+                .collect(),
+            ),
+            Some(Context::Ref(partial)) => (partial, labels(document)),
+            Some(Context::Cite(partial)) => (partial, self.citations(document)),
+            None => return Vec::new(),
+        };
+
+        out.retain(|completion| completion.label.starts_with(&partial));
+        out.sort_by(|a, b| a.label.cmp(&b.label));
+        out.dedup_by(|a, b| a.label == b.label);
+        out
+    }
+
+    fn citations(&self, document: &ast3::Document) -> Vec<Completion> {
+        let query = document.query();
+
+        query
+            .commands_named("bibitem")
+            .filter_map(|node| match node.node() {
+                Node::Command(c) => first_curly(c).map(|key| {
+                    Completion::new(
+                        key,
+                        CompletionKind::Citation,
+                        format!("bibitem on line {}", node.start().line),
+                    )
+                }),
+                _ => None,
+            })
+            .chain(self.bib.iter().map(|entry| {
+                Completion::new(
+                    entry.key().clone(),
+                    CompletionKind::Citation,
+                    match entry.title() {
+                        Some(title) => format!("@{} {title}", entry.r#type()),
+                        None => format!("@{}", entry.r#type()),
+                    },
+                )
+            }))
+            .collect()
+    }
+}
+
+// text of the first curly argument of a command
+fn first_curly(command: &ast3::Command) -> Option<String> {
+    command
+        .arguments()
+        .iter()
+        .find(|(_, arg)| arg.variant() == ast3::ScopeVariant::Curly)
+        .map(|(_, arg)| {
+            arg.chunks()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+}
+
+fn commands(document: &ast3::Document) -> Vec<Completion> {
+    let query = document.query();
+    let nodes = query.descendants().as_slice();
+    let mut out = builtins::COMMANDS
+        .iter()
+        .chain(sectioning::COMMANDS.iter())
+        .map(|name| {
+            Completion::new(
+                name.to_string(),
+                CompletionKind::Command,
+                "built-in command".to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for (index, node) in nodes.iter().enumerate() {
+        let command = match node.node() {
+            Node::Command(c) if builtins::DEFINING_COMMANDS.contains(&c.label().as_str()) => c,
+            _ => continue,
+        };
+
+        // `\newcommand{\name}` or `\newcommand\name`
+        let defined = match command.arguments().first() {
+            Some((_, arg)) => match arg.chunks().first().map(ast3::Chunk::variant) {
+                Some(ast3::ChunkVariant::Command(c)) => Some(c),
+                _ => None,
+            },
+            None => match nodes.get(index + 1).map(|next| next.node()) {
+                Some(Node::Command(c)) => Some(c),
+                _ => None,
+            },
+        };
+
+        let defined = match defined {
+            Some(defined) => defined,
+            None => continue,
+        };
+
+        // number of arguments in `\newcommand{\name}[2]`
+        let count = command
+            .arguments()
+            .get(1)
+            .filter(|(_, arg)| arg.variant() == ast3::ScopeVariant::Square)
+            .and_then(|(_, arg)| {
+                arg.chunks()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<String>()
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+            });
+
+        out.push(Completion::new(
+            defined.label().clone(),
+            CompletionKind::Command,
+            match count {
+                Some(count) => format!(
+                    "\\{} with {count} arguments, line {}",
+                    command.label(),
+                    node.start().line
+                ),
+                None => format!("\\{}, line {}", command.label(), node.start().line),
+            },
+        ));
+    }
+
+    out
+}
+
+fn environments(document: &ast3::Document) -> Vec<Completion> {
+    let query = document.query();
+    let mut out = builtins::ENVIRONMENTS
+        .iter()
+        .map(|name| {
+            Completion::new(
+                name.to_string(),
+                CompletionKind::Environment,
+                "built-in environment".to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for node in query.descendants() {
+        match node.node() {
+            Node::Command(c)
+                if matches!(c.label().as_str(), "newenvironment" | "renewenvironment") =>
+            {
+                if let Some(name) = first_curly(c) {
+                    out.push(Completion::new(
+                        name,
+                        CompletionKind::Environment,
+                        format!("\\{}, line {}", c.label(), node.start().line),
+                    ))
+                }
+            }
+            Node::Environment(env) => out.push(Completion::new(
+                env.label().clone(),
+                CompletionKind::Environment,
+                "used in document".to_string(),
+            )),
+            _ => {}
+        }
+    }
+
+    // built-in and defined environments come first, so they are kept when deduplicating
+    out
+}
+
+fn labels(document: &ast3::Document) -> Vec<Completion> {
+    let query = document.query();
+
+    query
+        .commands_named("label")
+        .filter_map(|node| {
+            let key = match node.node() {
+                Node::Command(c) => first_curly(c)?,
+                _ => return None,
+            };
+
+            let environment = query
+                .ancestors_of(node)
+                .find_map(|ancestor| match ancestor.node() {
+                    Node::Environment(env) => Some(env.label().clone()),
+                    _ => None,
+                });
+
+            Some(Completion::new(
+                key,
+                CompletionKind::Label,
+                match environment {
+                    Some(environment) => {
+                        format!("label in {environment}, line {}", node.start().line)
+                    }
+                    None => format!("label on line {}", node.start().line),
+                },
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,286 @@
+use std::fmt::{Display, Write};
+
+use crate::{Error, ErrorType};
+
+/// How serious a diagnostic is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// The document could not be parsed
+    Error,
+    /// The document was parsed, but something may not be as intended
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A message attached to a location in the source
+///
+/// Primary labels mark where the problem is, secondary labels
+/// point at related locations, such as the matching `\begin`.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Label {
+    line: u32,
+    column: Option<u32>,
+    message: String,
+    primary: bool,
+}
+
+impl Label {
+    /// Create a primary label, columns start from 1
+    pub fn primary(line: u32, column: Option<u32>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+            primary: true,
+        }
+    }
+
+    /// Create a secondary label, columns start from 1
+    pub fn secondary(line: u32, column: Option<u32>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+            primary: false,
+        }
+    }
+
+    /// Returns the line number of the label
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the column of the label, if known
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+
+    /// Returns the message of the label
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    /// Whether this label marks the main location of the diagnostic
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// A diagnostic that can be rendered with snippets of the original source
+///
+/// ```text
+/// error: environment `itemize` opened is never closed
+///   --> main.tex:12:5
+///    |
+/// 12 |     \begin{itemize}
+///    |     ^ environment opened here
+/// 13 |     \item x
+///    |            - input ends without `\end`
+/// ```
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Diagnostic {
+    severity: Severity,
+    message: String,
+    labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    /// Create an error diagnostic without labels
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Create a warning diagnostic without labels
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Attach a label to the diagnostic
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Returns the severity of the diagnostic
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the headline message
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    /// Returns all attached labels
+    pub fn labels(&self) -> &Vec<Label> {
+        &self.labels
+    }
+
+    /// Render the diagnostic against the source it was produced from
+    pub fn render(&self, source: &str) -> String {
+        self.render_inner(None, source)
+    }
+
+    /// Render the diagnostic, naming the source file in the location line
+    pub fn render_named(&self, name: &str, source: &str) -> String {
+        self.render_inner(Some(name), source)
+    }
+
+    fn render_inner(&self, name: Option<&str>, source: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message);
+
+        if self.labels.is_empty() {
+            return out;
+        }
+
+        let source_lines = source.split('\n').collect::<Vec<_>>();
+
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort_by_key(|label| (label.line, !label.primary, label.column));
+
+        let main = labels
+            .iter()
+            .find(|label| label.primary)
+            .unwrap_or(&labels[0]);
+        let width = labels
+            .iter()
+            .map(|label| label.line)
+            .max()
+            .unwrap()
+            .to_string()
+            .len();
+        let pad = " ".repeat(width);
+
+        let _ = match (name, main.column) {
+            (Some(name), Some(column)) => {
+                writeln!(out, "{pad}--> {name}:{}:{column}", main.line)
+            }
+            (Some(name), None) => writeln!(out, "{pad}--> {name}:{}", main.line),
+            (None, Some(column)) => {
+                writeln!(out, "{pad}--> line {}, column {column}", main.line)
+            }
+            (None, None) => writeln!(out, "{pad}--> line {}", main.line),
+        };
+        let _ = writeln!(out, "{pad} |");
+
+        let mut previous: Option<u32> = None;
+
+        for label in labels.iter() {
+            let text = source_lines
+                .get(label.line.saturating_sub(1) as usize)
+                .copied()
+                .unwrap_or("")
+                .trim_end_matches('\r');
+
+            if previous != Some(label.line) {
+                if previous.is_some_and(|previous| previous + 1 < label.line) {
+                    let _ = writeln!(out, "...");
+                }
+
+                let _ = writeln!(out, "{:>width$} | {text}", label.line);
+                previous = Some(label.line);
+            }
+
+            let marker = if label.primary { '^' } else { '-' };
+            let (start, len) = match label.column {
+                Some(column) => (column.saturating_sub(1) as usize, 1),
+                None => {
+                    let start = text.chars().take_while(|c| c.is_whitespace()).count();
+                    let len = text.trim().chars().count().max(1);
+                    (start, len)
+                }
+            };
+
+            // keep tabs so that markers line up with the source line
+            let indent = text
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .take(start)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+
+            let _ = writeln!(
+                out,
+                "{pad} | {indent}{} {}",
+                marker.to_string().repeat(len),
+                label.message
+            );
+        }
+
+        out
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}: {}", self.severity, self.message))
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(value: &Error) -> Self {
+        let (primary, related) = match &value.r#type {
+            ErrorType::UnexpectedClosing(_) => ("no matching opening parenthesis", ""),
+            ErrorType::UnclosedArgument(_) => ("argument opened here", "input ends here"),
+            ErrorType::UnclosedScope(_) => ("scope opened here", "input ends here"),
+            ErrorType::NoEnvironmentLabel => ("environment label missing after this command", ""),
+            ErrorType::UnexpectedEnd(_) => (
+                "no matching `\\begin`",
+                "innermost open environment begins here",
+            ),
+            ErrorType::UnclosedEnvironment(_) => {
+                ("environment opened here", "input ends without `\\end`")
+            }
+            ErrorType::TooManyArgsEnd => ("extra arguments given here", ""),
+            ErrorType::TooManyArgsDocumentClass => ("second class given here", ""),
+            ErrorType::DoubleDocumentClass => ("declared again here", "first declared here"),
+            ErrorType::UnexpectedMathsEnd => ("closed here", ""),
+            ErrorType::UnclosedMaths => ("opened here", "input ends here"),
+            ErrorType::InvalidUtf8 => ("invalid byte sequence here", ""),
+            ErrorType::Read(_) => ("reading stopped here", ""),
+            ErrorType::LimitExceeded(_) => ("limit reached here", ""),
+            ErrorType::Internal(_) => ("here", ""),
+        };
+
+        let out = Diagnostic::error(value.r#type.to_string()).with_label(Label::primary(
+            value.position.line,
+            Some(value.position.column),
+            primary,
+        ));
+
+        match value.related {
+            Some(position) => out.with_label(Label::secondary(
+                position.line,
+                Some(position.column),
+                related,
+            )),
+            None => out,
+        }
+    }
+}
+
+impl From<Error> for Diagnostic {
+    fn from(value: Error) -> Self {
+        Self::from(&value)
+    }
+}
@@ -1,20 +1,45 @@
 use std::fmt::Display;
 
-use crate::{ast1::ScopeVariant, InternalError};
+use crate::{ast1::ScopeVariant, Diagnostic, InternalError, Limit, Position};
 
 /// Main error struct
 ///
-/// Contains line number of where the error occurs
+/// Contains the position of where the error occurs, and optionally a related position,
+/// such as the `\begin` an unexpected `\end` conflicts with.
 #[derive(Debug)]
 #[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
 pub struct Error {
-    pub line: u32,
+    pub position: Position,
+    pub related: Option<Position>,
     pub r#type: ErrorType,
 }
 
 impl Error {
-    pub fn new(line: u32, r#type: ErrorType) -> Self {
-        Self { line, r#type }
+    pub fn new(position: Position, r#type: ErrorType) -> Self {
+        Self {
+            position,
+            related: None,
+            r#type,
+        }
+    }
+
+    /// Attach a related position to the error
+    pub fn with_related(mut self, related: Position) -> Self {
+        self.related = Some(related);
+        self
+    }
+
+    /// Maps positions relative to a nested input to absolute positions,
+    /// where `base` is the absolute position of the first character of the nested input
+    pub fn offset(mut self, base: Position) -> Self {
+        self.position = self.position.offset(base);
+        self.related = self.related.map(|related| related.offset(base));
+        self
+    }
+
+    /// Render the error with snippets of the source it was produced from
+    pub fn render(&self, source: &str) -> String {
+        Diagnostic::from(self).render(source)
     }
 }
 
@@ -44,13 +69,69 @@ pub enum ErrorType {
     UnexpectedMathsEnd,
     /// Maths block is unclosed
     UnclosedMaths,
+    /// The input is not valid UTF-8
+    InvalidUtf8,
+    /// The input could not be read
+    Read(std::io::ErrorKind),
+    /// The input goes over a parsing limit
+    LimitExceeded(Limit),
     /// Non LaTeX related error
     Internal(InternalError),
 }
 
+impl ErrorType {
+    /// Describe the error, with `at` inserted after the construct it refers to,
+    /// e.g. `" on line 12"`.
+    fn describe(&self, at: &str) -> String {
+        match self {
+            Self::UnexpectedClosing(variant) => {
+                format!("unexpected closing `{}`{at}", variant.close())
+            }
+            Self::UnclosedArgument(variant) => format!(
+                "command argument opened with `{}`{at} is never closed",
+                variant.open()
+            ),
+            Self::UnclosedScope(variant) => {
+                format!("scope opened with `{}`{at} is never closed", variant.open())
+            }
+            Self::NoEnvironmentLabel => {
+                format!("`\\begin` or `\\end`{at} is missing an environment label")
+            }
+            Self::UnexpectedEnd(label) => {
+                format!("`\\end{{{label}}}`{at} does not close any open environment")
+            }
+            Self::UnclosedEnvironment(label) => {
+                format!("environment `{label}` opened{at} is never closed")
+            }
+            Self::TooManyArgsEnd => format!("`\\end`{at} takes exactly one argument"),
+            Self::TooManyArgsDocumentClass => {
+                format!("`\\documentclass`{at} takes only one class argument")
+            }
+            Self::DoubleDocumentClass => {
+                format!("`\\documentclass`{at} is declared more than once")
+            }
+            Self::UnexpectedMathsEnd => format!("maths block closed{at} before it is opened"),
+            Self::UnclosedMaths => format!("maths block opened{at} is never closed"),
+            Self::InvalidUtf8 => format!("input is not valid UTF-8{at}"),
+            Self::Read(kind) => format!("failed to read input{at}: {kind}"),
+            Self::LimitExceeded(limit) => format!("{limit} goes over the limit{at}"),
+            Self::Internal(e) => format!("{e}{at}"),
+        }
+    }
+}
+
+impl Display for ErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.describe(""))
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{self:?}"))
+        f.write_str(&self.r#type.describe(&format!(
+            " on line {}, column {}",
+            self.position.line, self.position.column
+        )))
     }
 }
 
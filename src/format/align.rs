@@ -0,0 +1,211 @@
+use crate::ast3::{Chunk, ChunkVariant, Environment, MathsBlock, ScopeVariant, Visit};
+
+use super::{is_verb, verb_len, writer::Writer, Formatter};
+
+/// Commands drawing rules between rows, placed on their own lines
+const RULES: [&str; 9] = [
+    "hline",
+    "cline",
+    "toprule",
+    "midrule",
+    "bottomrule",
+    "cmidrule",
+    "addlinespace",
+    "hdashline",
+    "noalign",
+];
+
+/// A row of an alignment environment
+#[derive(Default)]
+pub(super) struct Row {
+    rules: Vec<String>,
+    cells: Vec<String>,
+    end: Option<String>,
+}
+
+impl Row {
+    fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.cells.is_empty() && self.end.is_none()
+    }
+}
+
+/// Split the content of an alignment environment into rows and cells
+///
+/// Returns `None` if the content cannot be laid out as a table,
+/// such as when it contains paragraph breaks or environments, even nested in a cell.
+pub(super) fn rows(formatter: &Formatter, content: &[Chunk]) -> Option<Vec<Row>> {
+    let mut rows = Vec::new();
+    let mut row = Row::default();
+    let mut cell = Writer::compact();
+    let mut i = 0;
+
+    while let Some(chunk) = content.get(i) {
+        let verb = i > 0 && is_verb(&content[i - 1]);
+        i += 1;
+
+        match chunk.variant() {
+            // `\verb|...|` is kept up to the closing delimiter
+            ChunkVariant::Text(s) if verb => {
+                let end = verb_len(s);
+                cell.raw(&s[..end]);
+                text(&mut row, &mut cell, &s[end..])
+            }
+            ChunkVariant::Text(s) => text(&mut row, &mut cell, s),
+            ChunkVariant::Command(c) if c.label() == "\\" => {
+                let mut end = String::from("\\\\");
+                let mut rest = None;
+
+                // `\\*` and `\\[2pt]`
+                if let Some(ChunkVariant::Text(s)) = content.get(i).map(Chunk::variant) {
+                    if let Some(after) = s.strip_prefix('*') {
+                        end.push('*');
+                        i += 1;
+
+                        if !after.trim().is_empty() {
+                            rest = Some(after)
+                        }
+                    }
+                }
+
+                if rest.is_none() {
+                    let skip = match content.get(i).map(Chunk::variant) {
+                        Some(ChunkVariant::Text(s)) if s.trim().is_empty() => 1,
+                        _ => 0,
+                    };
+
+                    if let Some(ChunkVariant::Scope(sc)) = content.get(i + skip).map(Chunk::variant)
+                    {
+                        if sc.variant() == ScopeVariant::Square {
+                            let mut option = Writer::compact();
+                            formatter.scope(&mut option, sc);
+                            end.push_str(&option.finish());
+                            i += skip + 1;
+                        }
+                    }
+                }
+
+                row.cells
+                    .push(std::mem::replace(&mut cell, Writer::compact()).finish());
+                row.end = Some(end);
+                rows.push(std::mem::take(&mut row));
+
+                if let Some(rest) = rest {
+                    text(&mut row, &mut cell, rest)
+                }
+            }
+            ChunkVariant::Command(c)
+                if RULES.contains(&c.label().as_str())
+                    && row.cells.is_empty()
+                    && cell.is_blank() =>
+            {
+                let mut rule = Writer::compact();
+                formatter.command(&mut rule, c);
+                row.rules.push(rule.finish());
+            }
+            // a conditional can hold part of a row or several rows
+            ChunkVariant::ParagraphBreak(_)
+            | ChunkVariant::Environment(_)
+            | ChunkVariant::Conditional(_) => return None,
+            // a cell is written on one line
+            _ if has_blocks(chunk) => return None,
+            _ => formatter.chunk(&mut cell, chunk),
+        }
+    }
+
+    let last = cell.finish();
+    if !last.is_empty() || !row.cells.is_empty() {
+        row.cells.push(last)
+    }
+
+    if !row.is_empty() {
+        rows.push(row)
+    }
+
+    Some(rows)
+}
+
+// text of a row, split into cells at `&`
+fn text(row: &mut Row, cell: &mut Writer, s: &str) {
+    for (i, part) in s.split('&').enumerate() {
+        if i > 0 {
+            row.cells
+                .push(std::mem::replace(cell, Writer::compact()).finish())
+        }
+        cell.text(part)
+    }
+}
+
+// whether a paragraph break or an environment is nested in the chunk
+fn has_blocks(chunk: &Chunk) -> bool {
+    let mut visitor = Blocks(false);
+    visitor.visit_chunk(chunk);
+    visitor.0
+}
+
+struct Blocks(bool);
+
+impl Visit for Blocks {
+    fn visit_paragraph_break(&mut self, _paragraph_break: &String) {
+        self.0 = true
+    }
+
+    fn visit_environment(&mut self, _environment: &Environment) {
+        self.0 = true
+    }
+
+    // maths is written as it is
+    fn visit_maths_block(&mut self, _maths_block: &MathsBlock) {}
+}
+
+/// Write the rows with the `&` of each column lined up
+pub(super) fn write(writer: &mut Writer, rows: &[Row]) {
+    let mut widths = Vec::new();
+
+    // rows with a single cell, such as `\multicolumn`, do not set the widths
+    for row in rows.iter().filter(|row| row.cells.len() > 1) {
+        for (i, cell) in row.cells.iter().enumerate() {
+            let width = cell.chars().count();
+
+            match widths.get_mut(i) {
+                Some(max) if *max < width => *max = width,
+                Some(_) => {}
+                None => widths.push(width),
+            }
+        }
+    }
+
+    for row in rows {
+        for rule in row.rules.iter() {
+            writer.line(rule)
+        }
+
+        if row.cells.is_empty() && row.end.is_none() {
+            continue;
+        }
+
+        let mut line = String::new();
+
+        for (i, cell) in row.cells.iter().enumerate() {
+            if i > 0 {
+                line.push_str(" & ")
+            }
+
+            if i + 1 < row.cells.len() {
+                let width = widths.get(i).copied().unwrap_or(0);
+                line.push_str(&format!("{cell:<width$}"))
+            } else {
+                line.push_str(cell)
+            }
+        }
+
+        if let Some(end) = &row.end {
+            line.truncate(line.trim_end().len());
+            if !line.is_empty() {
+                line.push(' ')
+            }
+            line.push_str(end)
+        }
+
+        writer.line(line.trim_end())
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    ast1::{EventKind, Events},
+    Position,
+};
+
+/// A comment of the source, placed after the content it follows
+pub(super) struct Comment {
+    // number of non-whitespace characters before the comment, not counting other comments
+    anchor: usize,
+    // the whitespace between the comment and the content before it on its line, `None` when
+    // the comment is on its own line
+    sep: Option<String>,
+    // line breaks before a comment on its own line
+    before: usize,
+    // line breaks between the comment and what follows it
+    after: usize,
+    text: String,
+}
+
+/// Returns the comments of a source, which must parse
+pub(super) fn comments(source: &str) -> Vec<Comment> {
+    let mut ranges = Events::new(source.as_bytes())
+        .filter(|event| matches!(event.kind, EventKind::Comment(_)))
+        .map(|event| (event.start, event.end))
+        .peekable();
+
+    let mut comments: Vec<Comment> = Vec::new();
+    let mut here = Position::start();
+    let mut anchor = 0;
+    let mut sep = String::new();
+    let mut breaks = 0;
+    // the comment being read, and the comments after the last content
+    let mut comment: Option<(String, Position)> = None;
+    let mut trailing = 0;
+    // the last character is a backslash escaping the next one
+    let mut escape = false;
+
+    for c in source.chars() {
+        if comment.is_none() && ranges.peek().is_some_and(|(start, _)| *start == here) {
+            let (_, end) = ranges.next().unwrap();
+            comment = Some((String::new(), end));
+        }
+
+        match &mut comment {
+            Some((text, end)) if here != *end => text.push(c),
+            _ => {
+                if let Some((text, _)) = comment.take() {
+                    comments.push(Comment {
+                        anchor,
+                        sep: (breaks == 0 && anchor != 0).then(|| std::mem::take(&mut sep)),
+                        before: breaks,
+                        after: 0,
+                        text,
+                    });
+                    trailing += 1;
+                    sep.clear();
+                    breaks = 0;
+                }
+
+                let escaping = std::mem::take(&mut escape);
+                match c {
+                    '\n' => {
+                        breaks += 1;
+                        sep.clear();
+                    }
+                    // the label of a control space, which is kept after its backslash
+                    ' ' | '\t' if escaping => {}
+                    c if c.is_whitespace() => sep.push(c),
+                    _ => {
+                        set_after(&mut comments, trailing, breaks);
+                        anchor += 1;
+                        sep.clear();
+                        breaks = 0;
+                        trailing = 0;
+                        escape = c == '\\' && !escaping;
+                    }
+                }
+            }
+        }
+
+        here.advance(c);
+    }
+
+    if let Some((text, _)) = comment {
+        comments.push(Comment {
+            anchor,
+            sep: (breaks == 0 && anchor != 0).then_some(sep),
+            before: breaks,
+            after: 0,
+            text,
+        });
+        trailing += 1;
+        breaks = 0;
+    }
+    set_after(&mut comments, trailing, breaks);
+
+    comments
+}
+
+// the line breaks after the last of the comments following the previous content
+fn set_after(comments: &mut [Comment], trailing: usize, breaks: usize) {
+    if trailing != 0 {
+        if let Some(last) = comments.last_mut() {
+            last.after = breaks
+        }
+    }
+}
+
+/// Puts the comments back into the formatted source, after the same content as in the
+/// source
+///
+/// Formatting only changes whitespace, so the content before a comment is found by counting
+/// the other characters. A comment keeps its line: it stays after the content before it or
+/// on its own line, and blank lines around it are kept.
+pub(super) fn restore(formatted: &str, comments: &[Comment]) -> String {
+    if comments.is_empty() {
+        return formatted.to_string();
+    }
+
+    let mut out = String::with_capacity(formatted.len());
+    let mut anchor = 0;
+    let mut rest = formatted;
+    let mut comments = comments.iter().peekable();
+
+    while let Some(target) = comments.peek().map(|comment| comment.anchor) {
+        // move to the end of the content before the comment
+        while anchor < target {
+            let Some((i, c)) = rest.char_indices().find(|(_, c)| !c.is_whitespace()) else {
+                break;
+            };
+            let end = i + c.len_utf8();
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            anchor += 1;
+        }
+
+        // the space after a backslash is the label of a control space, not whitespace
+        if escaped(&out) {
+            if let Some(c) = rest.chars().next().filter(|c| matches!(c, ' ' | '\t')) {
+                out.push(c);
+                rest = &rest[1..];
+            }
+        }
+
+        let space = &rest[..rest.len() - rest.trim_start().len()];
+        let indent = match space.rsplit_once('\n') {
+            Some((_, indent)) => indent.to_string(),
+            None => current_indent(&out).to_string(),
+        };
+
+        let mut last = None;
+        while let Some(comment) = comments.next_if(|comment| comment.anchor == target) {
+            match &comment.sep {
+                Some(sep) => out.push_str(sep),
+                None => {
+                    if !out.is_empty() {
+                        push_breaks(&mut out, comment.before);
+                    }
+                    out.push_str(&indent);
+                }
+            }
+            out.push_str(&comment.text);
+            last = Some(comment);
+        }
+
+        rest = &rest[space.len()..];
+        let after = last.map_or(0, |comment| comment.after);
+        if rest.is_empty() {
+            push_breaks(&mut out, after);
+        } else {
+            push_breaks(&mut out, after.max(1));
+            out.push_str(&indent);
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+// at most one blank line, keeping the space of a control space
+fn push_breaks(out: &mut String, breaks: usize) {
+    let mut trimmed = out.trim_end_matches([' ', '\t']).len();
+    if trimmed < out.len() && escaped(&out[..trimmed]) {
+        trimmed += 1
+    }
+    out.truncate(trimmed);
+
+    for _ in 0..breaks.min(2) {
+        out.push('\n')
+    }
+}
+
+// the indentation of the last line
+fn current_indent(out: &str) -> &str {
+    let line = match out.rsplit_once('\n') {
+        Some((_, line)) => line,
+        None => out,
+    };
+
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// whether text ends with a backslash escaping what follows it
+fn escaped(text: &str) -> bool {
+    text.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
@@ -0,0 +1,390 @@
+//! # Formatting
+//!
+//! Lays out a stage 3 document with consistent indentation and spacing.
+//!
+//! |Rule|Effect|
+//! |---|---|
+//! |Indentation|Environment bodies are indented by nesting depth, except for unindented environments such as `document`.|
+//! |Blocks|`\begin` and `\end` are put on their own lines when surrounded by whitespace, `\item` and sectioning commands start a line.|
+//! |Alignment|The `&` columns of `tabular`, `align` and similar environments are lined up, one row per line.|
+//! |Spacing|Whitespace between `\begin`, `\end` or a command and its arguments is removed.|
+//! |Wrapping|Prose is wrapped at the configured width, verbatim and maths are never wrapped.|
+//!
+//! Only whitespace is changed, so the document keeps its content and formatting twice
+//! gives the same result as formatting once. Line breaks only replace existing whitespace,
+//! except within alignment environments where spacing around `&` and `\\` is not significant.
+//! Comments are not part of the AST, so [`Formatter::format_str`] puts them back after the
+//! content they follow, on their own line when they were on their own line in the source.
+//!
+//! [`Sentences`] puts each sentence on its own line for cleaner diffs, keeping comments
+//! the same way with [`Sentences::apply_str`].
+
+mod align;
+mod comments;
+mod sentences;
+mod writer;
+
+use std::str::FromStr;
+
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Scope},
+    sectioning, Error,
+};
+
+pub use sentences::{Sentences, ABBREVIATIONS};
+use writer::Writer;
+
+/// Environments whose content is kept as it is
+pub const VERBATIM: [&str; 8] = [
+    "verbatim",
+    "verbatim*",
+    "Verbatim",
+    "lstlisting",
+    "minted",
+    "comment",
+    "alltt",
+    "filecontents",
+];
+
+/// Environments whose rows are aligned at `&`
+pub const ALIGNED: [&str; 24] = [
+    "tabular",
+    "tabular*",
+    "tabularx",
+    "tabulary",
+    "longtable",
+    "array",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "flalign",
+    "flalign*",
+    "eqnarray",
+    "eqnarray*",
+    "aligned",
+    "alignedat",
+    "split",
+    "cases",
+    "matrix",
+    "pmatrix",
+    "bmatrix",
+    "Bmatrix",
+    "vmatrix",
+    "Vmatrix",
+];
+
+/// Maths environments, which are indented but not wrapped
+pub const MATHS: [&str; 9] = [
+    "equation",
+    "equation*",
+    "gather",
+    "gather*",
+    "multline",
+    "multline*",
+    "displaymath",
+    "math",
+    "gathered",
+];
+
+/// Commands which start a new line when there is whitespace before them, along with the
+/// [sectioning commands](crate::sectioning::COMMANDS)
+pub const LINE_COMMANDS: [&str; 2] = ["item", "bibitem"];
+
+/// Formats documents with configurable rules
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Formatter {
+    indent: String,
+    width: Option<usize>,
+    verbatim: Vec<String>,
+    aligned: Vec<String>,
+    maths: Vec<String>,
+    unindented: Vec<String>,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self {
+            indent: "  ".to_string(),
+            width: None,
+            verbatim: VERBATIM.iter().map(ToString::to_string).collect(),
+            aligned: ALIGNED.iter().map(ToString::to_string).collect(),
+            maths: MATHS.iter().map(ToString::to_string).collect(),
+            unindented: vec!["document".to_string()],
+        }
+    }
+}
+
+impl Formatter {
+    /// Create a formatter indenting by two spaces without wrapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the string used for each level of indentation
+    pub fn with_indent(mut self, indent: &str) -> Self {
+        self.indent = indent.to_string();
+        self
+    }
+
+    /// Wrap prose so lines are no longer than `width` characters where possible
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Keep the content of an environment as it is
+    pub fn with_verbatim(mut self, label: &str) -> Self {
+        self.verbatim.push(label.to_string());
+        self
+    }
+
+    /// Align the rows of an environment at `&`
+    pub fn with_aligned(mut self, label: &str) -> Self {
+        self.aligned.push(label.to_string());
+        self
+    }
+
+    /// Indent an environment without wrapping its content
+    pub fn with_maths(mut self, label: &str) -> Self {
+        self.maths.push(label.to_string());
+        self
+    }
+
+    /// Do not indent the content of an environment
+    pub fn with_unindented(mut self, label: &str) -> Self {
+        self.unindented.push(label.to_string());
+        self
+    }
+
+    /// Returns the string used for each level of indentation
+    pub fn indent(&self) -> &String {
+        &self.indent
+    }
+
+    /// Returns the width prose is wrapped at, if wrapping
+    pub fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    /// Format a source, keeping its comments
+    pub fn format_str(&self, source: &str) -> Result<String, Error> {
+        let formatted = self.format(&ast3::Document::from_str(source)?);
+        Ok(comments::restore(&formatted, &comments::comments(source)))
+    }
+
+    /// Format a document, which has no comments as they are not part of the AST
+    pub fn format(&self, document: &ast3::Document) -> String {
+        let mut writer = Writer::new(&self.indent, self.width);
+
+        self.chunks(&mut writer, document.chunks_preamable());
+
+        if document.has_body() {
+            self.environment(
+                &mut writer,
+                "document",
+                document.body_begin_prec(),
+                document.body_args(),
+                document.chunks_body(),
+                document.body_end_prec(),
+            );
+        }
+
+        self.chunks(&mut writer, document.chunks_trailing());
+
+        writer.finish()
+    }
+
+    fn chunks(&self, writer: &mut Writer, chunks: &[Chunk]) {
+        let mut verb = false;
+
+        for chunk in chunks {
+            match chunk.variant() {
+                // `\verb|...|` is kept up to the closing delimiter
+                ChunkVariant::Text(s) if verb => {
+                    let end = verb_len(s);
+                    writer.raw(&s[..end]);
+                    writer.text(&s[end..]);
+                }
+                _ => self.chunk(writer, chunk),
+            }
+
+            verb = is_verb(chunk);
+        }
+    }
+
+    fn chunk(&self, writer: &mut Writer, chunk: &Chunk) {
+        match chunk.variant() {
+            ChunkVariant::Text(s) => writer.text(s),
+            ChunkVariant::ParagraphBreak(_) => writer.paragraph(),
+            ChunkVariant::MathsBlock(block) => writer.raw(&block.to_string()),
+            ChunkVariant::Command(command) => self.command(writer, command),
+            ChunkVariant::Scope(scope) => self.scope(writer, scope),
+            ChunkVariant::Environment(env) => self.environment(
+                writer,
+                env.label(),
+                env.prec_begin(),
+                env.arguments(),
+                env.content(),
+                env.prec_end(),
+            ),
+            ChunkVariant::Conditional(conditional) => self.conditional(writer, conditional),
+            ChunkVariant::Parameter(parameter) => writer.raw(&parameter.to_string()),
+            ChunkVariant::MacroDefinition(definition) => self.macro_definition(writer, definition),
+        }
+    }
+
+    // the parameter text is kept as it is, as its whitespace can be a delimiter
+    fn macro_definition(&self, writer: &mut Writer, definition: &ast3::MacroDefinition) {
+        writer.raw(&format!(
+            "\\{}{}\\{}",
+            definition.command(),
+            definition.prec(),
+            definition.label()
+        ));
+        for chunk in definition.parameters() {
+            writer.raw(&chunk.to_string())
+        }
+
+        writer.raw("{");
+        self.chunks(writer, definition.body());
+        writer.raw("}");
+    }
+
+    // the condition is kept as it is, as whitespace in it can end a number
+    fn conditional(&self, writer: &mut Writer, conditional: &ast3::Conditional) {
+        writer.raw(&conditional.opening());
+        for chunk in conditional.condition() {
+            writer.raw(&chunk.to_string())
+        }
+        self.chunks(writer, conditional.content());
+
+        for case in conditional.cases() {
+            writer.raw("\\or");
+            self.chunks(writer, case);
+        }
+
+        if let Some(otherwise) = conditional.otherwise() {
+            writer.raw("\\else");
+            self.chunks(writer, otherwise);
+        }
+
+        writer.raw("\\fi");
+    }
+
+    fn command(&self, writer: &mut Writer, command: &ast3::Command) {
+        let label = command.label().as_str();
+        if LINE_COMMANDS.contains(&label) || sectioning::level(label).is_some() {
+            writer.break_before()
+        }
+
+        writer.raw(&format!("\\{}", command.label()));
+        self.arguments(writer, command.arguments());
+    }
+
+    fn scope(&self, writer: &mut Writer, scope: &Scope) {
+        writer.raw(&scope.variant().open().to_string());
+        self.chunks(writer, scope.chunks());
+        writer.raw(&scope.variant().close().to_string());
+    }
+
+    fn arguments(&self, writer: &mut Writer, arguments: &[(String, Scope)]) {
+        for (prec, arg) in arguments {
+            writer.text(normalise(prec));
+            self.scope(writer, arg);
+        }
+    }
+
+    fn environment(
+        &self,
+        writer: &mut Writer,
+        label: &str,
+        prec_begin: &str,
+        arguments: &[(String, Scope)],
+        content: &[Chunk],
+        prec_end: &str,
+    ) {
+        let end = format!("\\end{}{{{label}}}", normalise(prec_end));
+        let is = |labels: &Vec<String>| labels.iter().any(|l| l == label);
+
+        writer.block();
+        writer.hold();
+        writer.raw(&format!("\\begin{}{{{label}}}", normalise(prec_begin)));
+        self.arguments(writer, arguments);
+        writer.release();
+
+        if is(&self.verbatim) {
+            writer.raw(&content.iter().map(ToString::to_string).collect::<String>());
+            writer.raw(&end);
+            writer.block();
+            return;
+        }
+
+        let indented = !is(&self.unindented);
+
+        if is(&self.aligned) {
+            if let Some(rows) = align::rows(self, content) {
+                if indented {
+                    writer.enter()
+                }
+                align::write(writer, &rows);
+                if indented {
+                    writer.leave()
+                }
+                writer.line(&end);
+                writer.block();
+                return;
+            }
+        }
+
+        let maths = is(&self.maths);
+
+        writer.block();
+        if indented {
+            writer.enter()
+        }
+        if maths {
+            writer.hold()
+        }
+
+        self.chunks(writer, content);
+
+        if maths {
+            writer.release()
+        }
+        writer.block();
+        if indented {
+            writer.leave()
+        }
+
+        writer.raw(&end);
+        writer.block();
+    }
+}
+
+// whether the chunk is `\verb`, its content is in the following text
+pub(crate) fn is_verb(chunk: &Chunk) -> bool {
+    matches!(chunk.variant(), ChunkVariant::Command(c) if c.label() == "verb" && c.arguments().is_empty())
+}
+
+// length of the text after `\verb` up to and including the closing delimiter
+pub(crate) fn verb_len(s: &str) -> usize {
+    let mut chars = s.char_indices();
+    let star = s.starts_with('*') as usize;
+
+    chars
+        .nth(star)
+        .and_then(|(_, delimiter)| chars.find(|(_, c)| *c == delimiter))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(s.len())
+}
+
+// whitespace before an argument is dropped unless it is a paragraph break
+fn normalise(prec: &str) -> &str {
+    if prec.matches('\n').count() > 1 {
+        prec
+    } else {
+        ""
+    }
+}
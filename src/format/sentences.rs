@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3::{self, Chunk, ChunkVariant},
+    traits::Lines,
+    Error,
+};
+
+use super::{comments, is_verb, verb_len, ALIGNED, MATHS, VERBATIM};
+
+/// Words ending with a full stop which do not end a sentence, compared in lowercase
+pub const ABBREVIATIONS: [&str; 35] = [
+    "e.g.", "i.e.", "cf.", "vs.", "viz.", "al.", "fig.", "figs.", "eq.", "eqs.", "sec.", "secs.",
+    "ch.", "tab.", "ref.", "refs.", "no.", "nos.", "dr.", "mr.", "mrs.", "ms.", "prof.", "st.",
+    "jr.", "sr.", "approx.", "resp.", "p.", "pp.", "vol.", "ed.", "eds.", "thm.", "def.",
+];
+
+/// Puts each sentence on its own line
+///
+/// Whitespace after the end of a sentence in `Text` chunks is replaced by a line break,
+/// indented as the line the sentence started on. Existing line breaks are kept,
+/// and `ParagraphBreak`s, maths, verbatim and alignment environments are left untouched.
+///
+/// A full stop is not the end of a sentence when it follows an abbreviation or a single
+/// capital letter such as an initial, when it is followed by `\ ` or `~`, or when the next
+/// sentence would start with a lowercase letter. `\@.` always ends a sentence.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Sentences {
+    abbreviations: Vec<String>,
+    skipped: Vec<String>,
+}
+
+impl Default for Sentences {
+    fn default() -> Self {
+        Self {
+            abbreviations: ABBREVIATIONS.iter().map(ToString::to_string).collect(),
+            skipped: VERBATIM
+                .iter()
+                .chain(ALIGNED.iter())
+                .chain(MATHS.iter())
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl Sentences {
+    /// Create with the default abbreviations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a word, including its full stop, which does not end a sentence
+    pub fn with_abbreviation(mut self, word: &str) -> Self {
+        self.abbreviations.push(word.to_lowercase());
+        self
+    }
+
+    /// Leave the content of an environment untouched
+    pub fn with_skipped(mut self, label: &str) -> Self {
+        self.skipped.push(label.to_string());
+        self
+    }
+
+    /// Put each sentence of a source on its own line, keeping its comments
+    pub fn apply_str(&self, source: &str) -> Result<String, Error> {
+        let mut document = ast3::Document::from_str(source)?;
+        self.apply(&mut document);
+        Ok(comments::restore(
+            &document.to_string(),
+            &comments::comments(source),
+        ))
+    }
+
+    /// Put each sentence of the document on its own line, which has no comments as they are
+    /// not part of the AST
+    pub fn apply(&self, document: &mut ast3::Document) {
+        self.chunks(document.chunks_preamable_mut());
+        self.chunks(document.chunks_body_mut());
+        self.chunks(document.chunks_trailing_mut());
+    }
+
+    fn chunks(&self, chunks: &mut [Chunk]) {
+        // indentation of the current line
+        let mut indent = String::new();
+
+        for i in 0..chunks.len() {
+            let next = chunks.get(i + 1).and_then(next_char);
+            let verb = i > 0 && is_verb(&chunks[i - 1]);
+
+            match chunks[i].variant_mut() {
+                ChunkVariant::Text(s) if verb => {
+                    let end = verb_len(s);
+                    *s = format!("{}{}", &s[..end], self.text(&s[end..], next, &indent))
+                }
+                ChunkVariant::Text(s) => *s = self.text(s, next, &indent),
+                ChunkVariant::Command(c) => {
+                    for (_, arg) in c.arguments_mut() {
+                        self.chunks(arg.chunks_mut())
+                    }
+                }
+                ChunkVariant::Scope(sc) => self.chunks(sc.chunks_mut()),
+                ChunkVariant::Conditional(c) => {
+                    for branch in c.parts_mut().skip(1) {
+                        self.chunks(branch)
+                    }
+                }
+                ChunkVariant::MacroDefinition(d) => self.chunks(d.body_mut()),
+                ChunkVariant::Environment(env) if !self.skipped.contains(env.label()) => {
+                    self.chunks(env.content_mut())
+                }
+                ChunkVariant::Environment(_)
+                | ChunkVariant::MathsBlock(_)
+                | ChunkVariant::ParagraphBreak(_)
+                | ChunkVariant::Parameter(_) => {}
+            }
+
+            let display = chunks[i].to_string();
+            if let Some((_, line)) = display.rsplit_once('\n') {
+                indent = leading_whitespace(line).to_string()
+            }
+        }
+
+        let mut line = 1;
+        for chunk in chunks.iter_mut() {
+            *chunk.line_no_mut() = line;
+            line += chunk.lines() - 1;
+        }
+    }
+
+    // `next` is the first character after the text
+    fn text(&self, s: &str, next: Option<char>, indent: &str) -> String {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        // start of the current line, if it starts within the text
+        let mut line_start = None;
+
+        while let Some(start) = rest.find(char::is_whitespace) {
+            let run = rest[start..]
+                .find(|c: char| !c.is_whitespace())
+                .map(|len| start + len)
+                .unwrap_or(rest.len());
+
+            let offset = s.len() - rest.len();
+            let before = &s[..offset + start];
+            let following = rest[run..].chars().next().or(next);
+
+            out.push_str(&rest[..start]);
+
+            if let Some(last) = rest[start..run].rfind('\n') {
+                out.push_str(&rest[start..run]);
+                line_start = Some(offset + start + last + 1);
+            } else if self.ends_sentence(before, following) {
+                let line = line_start.map(|line_start| &before[line_start..]);
+                out.push('\n');
+                out.push_str(line.map(leading_whitespace).unwrap_or(indent));
+            } else {
+                out.push_str(&rest[start..run])
+            }
+
+            rest = &rest[run..];
+        }
+
+        out.push_str(rest);
+        out
+    }
+
+    fn ends_sentence(&self, before: &str, following: Option<char>) -> bool {
+        let following = match following {
+            Some(c) => c,
+            None => return false,
+        };
+
+        if following.is_lowercase() {
+            return false;
+        }
+
+        let trimmed = before.trim_end_matches(['\'', '"']);
+
+        if trimmed.ends_with(['!', '?']) {
+            return true;
+        }
+
+        let word = match trimmed.strip_suffix('.') {
+            Some(word) => word
+                .rsplit(|c: char| c.is_whitespace() || c == '~')
+                .next()
+                .unwrap_or(""),
+            None => return false,
+        };
+
+        // full stops right after a command, maths or `\@`
+        if word.is_empty() {
+            return true;
+        }
+
+        let word = word.trim_start_matches(['`', '\'', '"']);
+        let mut chars = word.chars();
+
+        // initials such as `J. Smith`
+        if chars.next().is_some_and(char::is_uppercase) && chars.next().is_none() {
+            return false;
+        }
+
+        !self
+            .abbreviations
+            .contains(&format!("{}.", word.to_lowercase()))
+    }
+}
+
+// the first character of a chunk, if it can start a sentence
+fn next_char(chunk: &Chunk) -> Option<char> {
+    match chunk.variant() {
+        ChunkVariant::Text(s) => s.chars().next(),
+        ChunkVariant::Command(c) if c.label() == " " => None,
+        ChunkVariant::Command(_)
+        | ChunkVariant::Conditional(_)
+        | ChunkVariant::MacroDefinition(_) => Some('\\'),
+        ChunkVariant::Parameter(_) => Some('#'),
+        ChunkVariant::Scope(sc) => Some(sc.variant().open()),
+        ChunkVariant::MathsBlock(_) => Some('$'),
+        ChunkVariant::Environment(_) | ChunkVariant::ParagraphBreak(_) => None,
+    }
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
@@ -0,0 +1,232 @@
+/// Whitespace between two units of output
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(super) enum Gap {
+    None,
+    Space,
+    Line,
+    // a line break which is kept when wrapping
+    Break,
+    Paragraph,
+}
+
+/// Lays out text into indented lines
+///
+/// Text is collected into units which are never broken, units are separated
+/// by the whitespace found in the source so that no whitespace is added or removed
+/// between two tokens, only changed.
+pub(super) struct Writer {
+    indent: String,
+    width: Option<usize>,
+    // everything on one line, used for table cells
+    compact: bool,
+    // number of regions in which spaces are not broken
+    nowrap: usize,
+    depth: usize,
+
+    out: String,
+    line: String,
+    unit: String,
+    unit_gap: Gap,
+    gap: Gap,
+    // whether the next whitespace is a line break
+    broken: bool,
+}
+
+impl Writer {
+    /// A writer producing indented lines
+    pub fn new(indent: &str, width: Option<usize>) -> Self {
+        Self {
+            indent: indent.to_string(),
+            width,
+            compact: false,
+            nowrap: 0,
+            depth: 0,
+            out: String::new(),
+            line: String::new(),
+            unit: String::new(),
+            unit_gap: Gap::None,
+            gap: Gap::None,
+            broken: false,
+        }
+    }
+
+    /// A writer producing a single line with whitespace collapsed
+    pub fn compact() -> Self {
+        Self {
+            compact: true,
+            ..Self::new("", None)
+        }
+    }
+
+    /// Write text, whitespace is collapsed into gaps
+    pub fn text(&mut self, s: &str) {
+        for c in s.chars() {
+            match c {
+                '\n' if self.gap >= Gap::Line => self.gap = Gap::Paragraph,
+                '\n' => self.gap = Gap::Line,
+                c if c.is_whitespace() => self.gap = self.gap.max(Gap::Space),
+                c => {
+                    self.resolve();
+                    self.unit.push(c)
+                }
+            }
+        }
+    }
+
+    /// Write a string as it is, attached to the text before it unless separated by a gap
+    pub fn raw(&mut self, s: &str) {
+        self.resolve();
+        self.unit.push_str(s)
+    }
+
+    /// Start a new paragraph
+    pub fn paragraph(&mut self) {
+        self.gap = Gap::Paragraph
+    }
+
+    /// Whitespace around a block becomes a line break
+    pub fn block(&mut self) {
+        self.break_before();
+        self.broken = true
+    }
+
+    /// Whitespace before the next character becomes a line break
+    pub fn break_before(&mut self) {
+        self.place();
+        if self.gap != Gap::None {
+            self.gap = self.gap.max(Gap::Break)
+        }
+    }
+
+    /// Write a string on its own line, regardless of the whitespace around it
+    pub fn line(&mut self, s: &str) {
+        self.place();
+        self.gap = Gap::None;
+        self.broken = false;
+        self.unit_gap = Gap::Break;
+        self.unit.push_str(s);
+        self.place();
+    }
+
+    /// Lines after this are indented one level further
+    pub fn enter(&mut self) {
+        self.place();
+        self.depth += 1
+    }
+
+    /// Lines after this are indented one level less
+    pub fn leave(&mut self) {
+        self.place();
+        self.depth = self.depth.saturating_sub(1)
+    }
+
+    /// Spaces are not broken until [`Writer::release`], line breaks are kept
+    pub fn hold(&mut self) {
+        self.nowrap += 1
+    }
+
+    /// End a region started with [`Writer::hold`]
+    pub fn release(&mut self) {
+        self.nowrap = self.nowrap.saturating_sub(1)
+    }
+
+    /// Whether nothing has been written yet
+    pub fn is_blank(&self) -> bool {
+        self.out.is_empty() && self.line.is_empty() && self.unit.is_empty()
+    }
+
+    /// Returns the formatted text
+    pub fn finish(mut self) -> String {
+        self.place();
+
+        if self.compact {
+            return self.unit;
+        }
+
+        self.out.push_str(&self.line);
+        if self.gap >= Gap::Line {
+            self.out.push('\n')
+        }
+
+        self.out
+    }
+
+    // decide what the gap before the next character becomes
+    fn resolve(&mut self) {
+        let mut gap = std::mem::replace(&mut self.gap, Gap::None);
+        let broken = std::mem::take(&mut self.broken);
+
+        if gap == Gap::None {
+            return;
+        }
+
+        if broken {
+            gap = gap.max(Gap::Break)
+        }
+
+        if self.compact {
+            if !self.unit.is_empty() {
+                self.unit.push(' ')
+            }
+            return;
+        }
+
+        let held = self.nowrap > 0 || self.width.is_none();
+
+        match gap {
+            Gap::Space if held => self.unit.push(' '),
+            Gap::Space | Gap::Line if !held => {
+                self.place();
+                self.unit_gap = Gap::Space
+            }
+            gap => {
+                self.place();
+                self.unit_gap = gap
+            }
+        }
+    }
+
+    // put the current unit onto the current line
+    fn place(&mut self) {
+        if self.compact || self.unit.is_empty() {
+            return;
+        }
+
+        let gap = std::mem::replace(&mut self.unit_gap, Gap::None);
+        let unit = std::mem::take(&mut self.unit);
+
+        if !self.line.is_empty() {
+            match gap {
+                Gap::None => {}
+                Gap::Space => {
+                    let width = self.line.rsplit('\n').next().unwrap_or("").chars().count();
+                    let first = unit.split('\n').next().unwrap_or("").chars().count();
+
+                    if self.width.is_some_and(|max| width + 1 + first > max) {
+                        self.end_line()
+                    } else {
+                        self.line.push(' ')
+                    }
+                }
+                Gap::Line | Gap::Break => self.end_line(),
+                Gap::Paragraph => {
+                    self.end_line();
+                    self.out.push('\n')
+                }
+            }
+        } else if gap == Gap::Paragraph && !self.out.is_empty() {
+            self.out.push('\n')
+        }
+
+        if self.line.is_empty() {
+            self.line = self.indent.repeat(self.depth)
+        }
+
+        self.line.push_str(&unit)
+    }
+
+    fn end_line(&mut self) {
+        self.out.push_str(&std::mem::take(&mut self.line));
+        self.out.push('\n')
+    }
+}
@@ -0,0 +1,1117 @@
+//! # HTML
+//!
+//! Renders a stage 3 document as an HTML fragment.
+//!
+//! |Source|HTML|
+//! |---|---|
+//! |`\part` to `\subparagraph`|`<h1>` to `<h6>`, numbered unless starred.|
+//! |Paragraphs|`<p>`|
+//! |`itemize`, `enumerate` and `description`|`<ul>`, `<ol>` and `<dl>`|
+//! |`figure` and `table`|`<figure>`, with `\caption` as `<figcaption>`.|
+//! |`tabular` and similar|`<table>`, with `\multicolumn` spanning columns.|
+//! |`\emph`, `\textbf`, `\texttt` and similar|`<em>`, `<strong>`, `<code>` and similar.|
+//! |`\footnote`|A link to an endnote, listed at the end.|
+//! |`\label` and `\ref`|An anchor and a link to it, showing the number of the section, float or equation.|
+//! |`\cite`|Links to the entries of `thebibliography`.|
+//! |`\url` and `\href`|`<a>`, without an `href` unless the link is relative or uses `http`, `https`, `mailto` or `ftp`.|
+//! |Maths|TeX for MathJax or KaTeX, or MathML, see [`Maths`].|
+//! |Verbatim|`<pre><code>`, and `\verb` as `<code>`.|
+//! |`\def` and similar|Dropped, defined macros are not expanded.|
+//!
+//! Commands and environments which are not known are passed to a [`Handler`].
+//! By default, unknown commands are replaced by the content of their curly arguments
+//! and unknown environments by their content.
+//!
+//! ast4 is not rendered, as it does not yet convert from ast3.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Conditions, MathsType, Scope, ScopeVariant},
+    completion::{CITE_COMMANDS, REF_COMMANDS},
+    format::{verb_len, ALIGNED, MATHS, VERBATIM},
+    mathml,
+    plaintext::NON_PROSE,
+    sectioning::{self, starred},
+};
+
+/// Commands drawing rules in tables, which are dropped
+const RULES: [&str; 8] = [
+    "hline",
+    "cline",
+    "toprule",
+    "midrule",
+    "bottomrule",
+    "cmidrule",
+    "addlinespace",
+    "hdashline",
+];
+
+/// URL schemes which are linked, links with any other scheme are dropped
+const SCHEMES: [&str; 4] = ["http", "https", "mailto", "ftp"];
+
+/// Maths environments which number their equations
+const NUMBERED: [&str; 7] = [
+    "equation", "gather", "multline", "align", "alignat", "flalign", "eqnarray",
+];
+
+/// How maths is written
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub enum Maths {
+    /// `\(...\)` and `\[...\]`, which MathJax and KaTeX recognise by default
+    #[default]
+    Delimiters,
+    /// The delimiters used in the source
+    Source,
+    /// Presentation MathML, see [`crate::mathml`]
+    MathML,
+}
+
+/// Renders what the renderer does not know
+///
+/// Return `None` to use the default rendering.
+pub trait Handler {
+    /// Render a command as inline HTML
+    fn command(&self, command: &ast3::Command, context: &mut Context) -> Option<String> {
+        let _ = (command, context);
+        None
+    }
+
+    /// Render an environment as block HTML
+    fn environment(
+        &self,
+        environment: &ast3::Environment,
+        context: &mut Context,
+    ) -> Option<String> {
+        let _ = (environment, context);
+        None
+    }
+}
+
+impl Handler for () {}
+
+/// Renders documents as HTML
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Renderer {
+    maths: Maths,
+}
+
+impl Renderer {
+    /// Create a renderer writing maths with `\(...\)` and `\[...\]`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how maths is written
+    pub fn with_maths(mut self, maths: Maths) -> Self {
+        self.maths = maths;
+        self
+    }
+
+    /// Returns how maths is written
+    pub fn maths(&self) -> Maths {
+        self.maths
+    }
+
+    /// Render the body of a document, or the preamble if there is no body
+    pub fn render(&self, document: &ast3::Document) -> String {
+        self.render_with(document, &())
+    }
+
+    /// Render a document, passing unknown commands and environments to the handler
+    pub fn render_with(&self, document: &ast3::Document, handler: &dyn Handler) -> String {
+        let chunks = match document.has_body() {
+            true => document.chunks_body(),
+            false => document.chunks_preamable(),
+        };
+
+        let book = document.documentclass().is_some_and(|class| {
+            ["book", "report", "memoir", "scrbook", "scrreprt"].contains(&class.as_str())
+        });
+
+        let conditions = Conditions::from_document(document);
+
+        let mut collector = Collector {
+            conditions: &conditions,
+            numbering: Numbering::new(book),
+            current: String::new(),
+            float: None,
+            labels: BTreeMap::new(),
+            bibliography: Vec::new(),
+            title: Vec::new(),
+        };
+
+        if document.has_body() {
+            collector.chunks(document.chunks_preamable());
+        }
+        collector.chunks(chunks);
+
+        let mut context = Context {
+            renderer: self,
+            handler,
+            conditions: &conditions,
+            numbering: Numbering::new(book),
+            labels: collector.labels,
+            bibliography: collector.bibliography,
+            title: collector.title,
+            placed: Vec::new(),
+            float: None,
+            footnotes: Vec::new(),
+        };
+
+        let mut out = context.render_blocks(chunks);
+        out.push_str(&context.endnotes());
+        out
+    }
+}
+
+/// The state of rendering, passed to a [`Handler`]
+pub struct Context<'a> {
+    renderer: &'a Renderer,
+    handler: &'a dyn Handler,
+    conditions: &'a Conditions,
+    numbering: Numbering,
+    labels: BTreeMap<String, String>,
+    bibliography: Vec<String>,
+    title: Vec<(&'a str, &'a Scope)>,
+    placed: Vec<String>,
+    float: Option<&'static str>,
+    footnotes: Vec<String>,
+}
+
+impl Context<'_> {
+    /// Render chunks as inline HTML
+    pub fn render(&mut self, chunks: &[Chunk]) -> String {
+        let mut out = String::new();
+        let mut i = 0;
+
+        while i < chunks.len() {
+            match self.piece(chunks, &mut i) {
+                Piece::Inline(s) | Piece::Block(s) => out.push_str(&s),
+                Piece::Anchor(id) => out.push_str(&anchor(&id)),
+                Piece::Break => out.push(' '),
+            }
+        }
+
+        out
+    }
+
+    /// Render chunks as blocks, with prose in paragraphs
+    pub fn render_blocks(&mut self, chunks: &[Chunk]) -> String {
+        let mut blocks = Blocks::default();
+        let mut i = 0;
+
+        while i < chunks.len() {
+            let piece = self.piece(chunks, &mut i);
+            blocks.push(piece)
+        }
+
+        blocks.finish()
+    }
+
+    /// Returns the number a label refers to
+    pub fn reference(&self, key: &str) -> Option<&String> {
+        self.labels.get(key)
+    }
+
+    /// Returns the renderer
+    pub fn renderer(&self) -> &Renderer {
+        self.renderer
+    }
+
+    fn piece(&mut self, chunks: &[Chunk], i: &mut usize) -> Piece {
+        let chunk = &chunks[*i];
+        *i += 1;
+
+        match chunk.variant() {
+            ChunkVariant::Text(s) => Piece::Inline(text(s)),
+            ChunkVariant::Parameter(parameter) => Piece::Inline(text(&parameter.to_string())),
+            ChunkVariant::MacroDefinition(_) => Piece::Inline(String::new()),
+            ChunkVariant::ParagraphBreak(_) => Piece::Break,
+            ChunkVariant::MathsBlock(block) => Piece::Inline(self.maths(block)),
+            ChunkVariant::Scope(scope) => Piece::Inline(self.scope(scope)),
+            ChunkVariant::Command(command) => self.command(command, chunks, i),
+            ChunkVariant::Environment(env) => Piece::Block(self.environment(env)),
+            ChunkVariant::Conditional(conditional) => {
+                let branch = self.conditions.evaluate_or_content(conditional);
+
+                if branch
+                    .iter()
+                    .any(|chunk| matches!(chunk.variant(), ChunkVariant::ParagraphBreak(_)))
+                {
+                    Piece::Block(self.render_blocks(branch))
+                } else {
+                    Piece::Inline(self.render(branch))
+                }
+            }
+        }
+    }
+
+    fn scope(&mut self, scope: &Scope) -> String {
+        match scope.variant() {
+            ScopeVariant::Curly => self.render(scope.chunks()),
+            variant => format!(
+                "{}{}{}",
+                variant.open(),
+                self.render(scope.chunks()),
+                variant.close()
+            ),
+        }
+    }
+
+    fn maths(&self, block: &ast3::MathsBlock) -> String {
+        let (class, (open, close)) = match block.r#type() {
+            MathsType::Inline => ("inline", ("\\(", "\\)")),
+            MathsType::Outline => ("display", ("\\[", "\\]")),
+        };
+
+        let tex = match self.renderer.maths {
+            Maths::Delimiters => format!(
+                "{open}{}{close}",
+                block
+                    .content()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<String>()
+            ),
+            Maths::Source => block.to_string(),
+            Maths::MathML => return mathml::from_block(block),
+        };
+
+        format!("<span class=\"math {class}\">{}</span>", escape(&tex))
+    }
+
+    fn command(&mut self, command: &ast3::Command, chunks: &[Chunk], i: &mut usize) -> Piece {
+        let label = command.label().as_str();
+
+        if let Some(level) = sectioning::level(label) {
+            return self.heading(level, command, chunks, i);
+        }
+
+        let inline = match label {
+            "emph" | "textit" | "textsl" => self.wrap("em", command),
+            "textbf" => self.wrap("strong", command),
+            "texttt" => self.wrap("code", command),
+            "underline" | "uline" => self.wrap("u", command),
+            "textsuperscript" => self.wrap("sup", command),
+            "textsubscript" => self.wrap("sub", command),
+            "hl" => self.wrap("mark", command),
+            "textsc" => format!(
+                "<span class=\"smallcaps\">{}</span>",
+                self.argument(command)
+            ),
+            "textrm" | "textsf" | "textup" | "textmd" | "textnormal" | "mbox" | "hbox" | "text" => {
+                self.argument(command)
+            }
+            "enquote" => format!("\u{201c}{}\u{201d}", self.argument(command)),
+            "footnote" | "thanks" => self.footnote(command),
+            "label" => {
+                return match key(command) {
+                    Some(key) if !self.placed.contains(&key) => Piece::Anchor(key),
+                    _ => Piece::Inline(String::new()),
+                }
+            }
+            _ if REF_COMMANDS.contains(&label) => self.reference_link(command),
+            _ if CITE_COMMANDS.contains(&label) => self.citation(command),
+            "url" => {
+                let url = url(command, 0);
+                format!("<a{}>{}</a>", href(&url), escape(&url))
+            }
+            "href" => format!(
+                "<a{}>{}</a>",
+                href(&url(command, 0)),
+                curly(command)
+                    .get(1)
+                    .map(|text| self.render(text.chunks()))
+                    .unwrap_or_default()
+            ),
+            "includegraphics" => format!("<img src=\"{}\" alt=\"\">", escape(&url(command, 0))),
+            "caption" => return Piece::Block(self.caption(command)),
+            "maketitle" => return Piece::Block(self.maketitle()),
+            "par" => return Piece::Break,
+            "verb" if command.arguments().is_empty() => match chunks.get(*i).map(Chunk::variant) {
+                Some(ChunkVariant::Text(s)) => {
+                    *i += 1;
+                    verb(s)
+                }
+                _ => String::new(),
+            },
+            "item" | "bibitem" | "-" | "@" | "/" => String::new(),
+            "\\" | "newline" => "<br>".to_string(),
+            "and" => "<br>".to_string(),
+            " " | ";" | ":" => " ".to_string(),
+            "," => "\u{2009}".to_string(),
+            "LaTeX" | "TeX" => label.to_string(),
+            "LaTeXe" => "LaTeX2\u{3b5}".to_string(),
+            "ldots" | "dots" | "textellipsis" => "\u{2026}".to_string(),
+            "textendash" => "\u{2013}".to_string(),
+            "textemdash" => "\u{2014}".to_string(),
+            "textbackslash" => "\\".to_string(),
+            "%" | "&" | "$" | "#" | "_" | "{" | "}" => escape(label),
+            _ => {
+                let handler = self.handler;
+                match handler.command(command, self) {
+                    Some(s) => s,
+                    None => curly(command)
+                        .into_iter()
+                        .map(|arg| self.render(arg.chunks()))
+                        .collect(),
+                }
+            }
+        };
+
+        Piece::Inline(inline)
+    }
+
+    // the content of the last curly argument
+    fn argument(&mut self, command: &ast3::Command) -> String {
+        curly(command)
+            .last()
+            .map(|arg| self.render(arg.chunks()))
+            .unwrap_or_default()
+    }
+
+    fn wrap(&mut self, tag: &str, command: &ast3::Command) -> String {
+        format!("<{tag}>{}</{tag}>", self.argument(command))
+    }
+
+    fn heading(
+        &mut self,
+        level: usize,
+        command: &ast3::Command,
+        chunks: &[Chunk],
+        i: &mut usize,
+    ) -> Piece {
+        let (title, starred) = match curly(command).last() {
+            Some(title) => (*title, false),
+            None => match starred(chunks, *i) {
+                Some(title) => {
+                    *i += 2;
+                    (title, true)
+                }
+                None => return Piece::Inline(String::new()),
+            },
+        };
+
+        let id = match chunks[*i..]
+            .iter()
+            .find(|chunk| !matches!(chunk.variant(), ChunkVariant::Text(s) if s.trim().is_empty()))
+            .map(Chunk::variant)
+        {
+            Some(ChunkVariant::Command(c)) if c.label() == "label" => key(c),
+            _ => None,
+        };
+
+        let number = match starred {
+            true => String::new(),
+            false => format!(
+                "<span class=\"number\">{}</span> ",
+                self.numbering.section(level)
+            ),
+        };
+
+        let tag = format!("h{}", level.max(1));
+        let title = self.render(title.chunks());
+
+        Piece::Block(format!(
+            "<{tag}{}>{number}{}</{tag}>",
+            self.id(id),
+            title.trim()
+        ))
+    }
+
+    // an `id` attribute, marking the label as placed
+    fn id(&mut self, key: Option<String>) -> String {
+        match key {
+            Some(key) => {
+                let id = format!(" id=\"{}\"", escape(&key));
+                self.placed.push(key);
+                id
+            }
+            None => String::new(),
+        }
+    }
+
+    fn footnote(&mut self, command: &ast3::Command) -> String {
+        let content = self.argument(command);
+        self.footnotes.push(content);
+        let n = self.footnotes.len();
+
+        format!("<sup class=\"footnote-ref\"><a href=\"#fn{n}\" id=\"fnref{n}\">{n}</a></sup>")
+    }
+
+    fn endnotes(&self) -> String {
+        if self.footnotes.is_empty() {
+            return String::new();
+        }
+
+        let mut out = String::from("<section class=\"footnotes\">\n<ol>\n");
+
+        for (i, note) in self.footnotes.iter().enumerate() {
+            let n = i + 1;
+            out.push_str(&format!(
+                "<li id=\"fn{n}\">{} <a href=\"#fnref{n}\" class=\"footnote-back\">\u{21a9}</a></li>\n",
+                note.trim()
+            ));
+        }
+
+        out.push_str("</ol>\n</section>\n");
+        out
+    }
+
+    fn reference_link(&self, command: &ast3::Command) -> String {
+        keys(command)
+            .iter()
+            .map(|key| {
+                let number = self.labels.get(key).map(String::as_str).unwrap_or("??");
+                let number = match command.label().as_str() {
+                    "eqref" => format!("({number})"),
+                    _ => number.to_string(),
+                };
+
+                format!("<a href=\"#{}\">{}</a>", escape(key), escape(&number))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn citation(&mut self, command: &ast3::Command) -> String {
+        let links = keys(command)
+            .iter()
+            .map(|key| {
+                let text = match self.bibliography.iter().position(|entry| entry == key) {
+                    Some(i) => (i + 1).to_string(),
+                    None => key.clone(),
+                };
+
+                format!("<a href=\"#cite-{}\">{}</a>", escape(key), escape(&text))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let note = command
+            .arguments()
+            .iter()
+            .rfind(|(_, arg)| arg.variant() == ScopeVariant::Square)
+            .map(|(_, arg)| format!(", {}", self.render(arg.chunks())))
+            .unwrap_or_default();
+
+        format!("<cite>[{links}{note}]</cite>")
+    }
+
+    fn caption(&mut self, command: &ast3::Command) -> String {
+        let content = self.argument(command);
+
+        match self.float {
+            Some(kind) => {
+                let number = self.numbering.float(kind);
+                let name = match kind {
+                    "figure" => "Figure",
+                    _ => "Table",
+                };
+
+                format!(
+                    "<figcaption>{name} {number}: {}</figcaption>",
+                    content.trim()
+                )
+            }
+            None => format!("<p class=\"caption\">{}</p>", content.trim()),
+        }
+    }
+
+    fn maketitle(&mut self) -> String {
+        let mut out = String::from("<header>\n");
+
+        for field in ["title", "author", "date"] {
+            let scope = match self.title.iter().find(|(f, _)| f == &field) {
+                Some((_, scope)) => *scope,
+                None => continue,
+            };
+            let content = self.render(scope.chunks());
+
+            out.push_str(&match field {
+                "title" => format!("<h1 class=\"title\">{}</h1>\n", content.trim()),
+                _ => format!("<p class=\"{field}\">{}</p>\n", content.trim()),
+            });
+        }
+
+        out.push_str("</header>");
+        out
+    }
+
+    fn environment(&mut self, env: &ast3::Environment) -> String {
+        let label = env.label().as_str();
+
+        match label {
+            "itemize" => self.list("ul", env.content()),
+            "enumerate" => self.list("ol", env.content()),
+            "description" => self.description(env.content()),
+            "thebibliography" => self.bibliography(env.content()),
+            "figure" | "figure*" | "table" | "table*" => self.figure(env),
+            "tabular" | "tabular*" | "tabularx" | "tabulary" | "longtable" => {
+                self.table(env.content())
+            }
+            "center" | "flushleft" | "flushright" | "abstract" => format!(
+                "<div class=\"{label}\">\n{}</div>",
+                self.render_blocks(env.content())
+            ),
+            "quote" | "quotation" => format!(
+                "<blockquote>\n{}</blockquote>",
+                self.render_blocks(env.content())
+            ),
+            _ if VERBATIM.contains(&label) && !NON_PROSE.contains(&label) => format!(
+                "<pre><code>{}</code></pre>",
+                escape(source(env.content()).trim_start_matches('\n'))
+            ),
+            _ if MATHS.contains(&label) || ALIGNED[6..].contains(&label) => {
+                let key = env
+                    .content()
+                    .iter()
+                    .find_map(|chunk| match chunk.variant() {
+                        ChunkVariant::Command(c) if c.label() == "label" => key(c),
+                        _ => None,
+                    });
+
+                let maths = match self.renderer.maths {
+                    Maths::MathML => mathml::from_environment(env).unwrap_or_default(),
+                    _ => escape(&env.to_string()),
+                };
+
+                format!("<div class=\"math display\"{}>{maths}</div>", self.id(key))
+            }
+            _ => {
+                let handler = self.handler;
+                match handler.environment(env, self) {
+                    Some(s) => s,
+                    None if NON_PROSE.contains(&label) => String::new(),
+                    None => self.render_blocks(env.content()),
+                }
+            }
+        }
+    }
+
+    fn list(&mut self, tag: &str, content: &[Chunk]) -> String {
+        let mut out = format!("<{tag}>\n");
+
+        for (_, _, chunks) in items(content, "item") {
+            out.push_str(&format!(
+                "<li>{}</li>\n",
+                tight(&self.render_blocks(chunks))
+            ));
+        }
+
+        out.push_str(&format!("</{tag}>"));
+        out
+    }
+
+    fn description(&mut self, content: &[Chunk]) -> String {
+        let mut out = String::from("<dl>\n");
+
+        for (_, command, chunks) in items(content, "item") {
+            let term = command
+                .arguments()
+                .iter()
+                .find(|(_, arg)| arg.variant() == ScopeVariant::Square)
+                .map(|(_, arg)| self.render(arg.chunks()))
+                .unwrap_or_default();
+
+            out.push_str(&format!(
+                "<dt>{}</dt>\n<dd>{}</dd>\n",
+                term.trim(),
+                tight(&self.render_blocks(chunks))
+            ));
+        }
+
+        out.push_str("</dl>");
+        out
+    }
+
+    fn bibliography(&mut self, content: &[Chunk]) -> String {
+        let mut out = String::from("<ol class=\"bibliography\">\n");
+
+        for (_, command, chunks) in items(content, "bibitem") {
+            let id = key(command)
+                .map(|key| format!(" id=\"cite-{}\"", escape(&key)))
+                .unwrap_or_default();
+
+            out.push_str(&format!(
+                "<li{id}>{}</li>\n",
+                tight(&self.render_blocks(chunks))
+            ));
+        }
+
+        out.push_str("</ol>");
+        out
+    }
+
+    fn figure(&mut self, env: &ast3::Environment) -> String {
+        let kind = match env.label().starts_with("figure") {
+            true => "figure",
+            false => "table",
+        };
+
+        let key = env
+            .content()
+            .iter()
+            .find_map(|chunk| match chunk.variant() {
+                ChunkVariant::Command(c) if c.label() == "label" => key(c),
+                _ => None,
+            });
+        let id = self.id(key);
+
+        let outer = self.float.replace(kind);
+        let content = self.render_blocks(env.content());
+        self.float = outer;
+
+        format!("<figure class=\"{kind}\"{id}>\n{content}</figure>")
+    }
+
+    fn table(&mut self, content: &[Chunk]) -> String {
+        let mut rows: Vec<Vec<(String, Option<String>)>> = Vec::new();
+        let mut row = Vec::new();
+        let mut cell = (String::new(), None);
+        let mut i = 0;
+
+        while let Some(chunk) = content.get(i) {
+            match chunk.variant() {
+                ChunkVariant::Text(s) => {
+                    i += 1;
+
+                    for (n, part) in s.split('&').enumerate() {
+                        if n > 0 {
+                            row.push(std::mem::take(&mut cell))
+                        }
+                        cell.0.push_str(&text(part))
+                    }
+                }
+                ChunkVariant::Command(c) if c.label() == "\\" || c.label() == "tabularnewline" => {
+                    i += 1;
+                    row.push(std::mem::take(&mut cell));
+                    rows.push(std::mem::take(&mut row));
+
+                    // `\\*` and `\\[2pt]`
+                    if let Some(ChunkVariant::Text(s)) = content.get(i).map(Chunk::variant) {
+                        if s.trim() == "*" || s.trim().is_empty() {
+                            i += 1
+                        }
+                    }
+                    if let Some(ChunkVariant::Scope(sc)) = content.get(i).map(Chunk::variant) {
+                        if sc.variant() == ScopeVariant::Square {
+                            i += 1
+                        }
+                    }
+                }
+                ChunkVariant::Command(c) if RULES.contains(&c.label().as_str()) => i += 1,
+                ChunkVariant::Command(c) if c.label() == "multicolumn" => {
+                    i += 1;
+                    let args = curly(c);
+
+                    if let [span, _, body] = args[..] {
+                        cell.0.push_str(&self.render(body.chunks()));
+                        cell.1 = Some(source(span.chunks()).trim().to_string());
+                    }
+                }
+                ChunkVariant::ParagraphBreak(_) => i += 1,
+                _ => match self.piece(content, &mut i) {
+                    Piece::Inline(s) | Piece::Block(s) => cell.0.push_str(&s),
+                    Piece::Anchor(id) => cell.0.push_str(&anchor(&id)),
+                    Piece::Break => {}
+                },
+            }
+        }
+
+        if !cell.0.trim().is_empty() || !row.is_empty() {
+            row.push(cell);
+            rows.push(row);
+        }
+
+        let mut out = String::from("<table>\n");
+
+        for row in rows {
+            if row.iter().all(|(cell, _)| cell.trim().is_empty()) {
+                continue;
+            }
+
+            out.push_str("<tr>");
+            for (cell, span) in row {
+                let span = span
+                    .map(|span| format!(" colspan=\"{}\"", escape(&span)))
+                    .unwrap_or_default();
+                out.push_str(&format!("<td{span}>{}</td>", cell.trim()));
+            }
+            out.push_str("</tr>\n");
+        }
+
+        out.push_str("</table>");
+        out
+    }
+}
+
+// rendered output of a chunk
+enum Piece {
+    Inline(String),
+    Block(String),
+    Anchor(String),
+    Break,
+}
+
+// blocks of output, collecting inline output into paragraphs
+#[derive(Default)]
+struct Blocks {
+    out: String,
+    paragraph: String,
+}
+
+impl Blocks {
+    fn push(&mut self, piece: Piece) {
+        match piece {
+            Piece::Inline(s) => self.paragraph.push_str(&s),
+            Piece::Block(s) => {
+                self.flush();
+
+                if !s.is_empty() {
+                    self.out.push_str(&s);
+                    self.out.push('\n');
+                }
+            }
+            Piece::Anchor(id) if self.paragraph.trim().is_empty() => {
+                self.push(Piece::Block(anchor(&id)))
+            }
+            Piece::Anchor(id) => self.paragraph.push_str(&anchor(&id)),
+            Piece::Break => self.flush(),
+        }
+    }
+
+    fn flush(&mut self) {
+        let paragraph = std::mem::take(&mut self.paragraph);
+        let paragraph = paragraph.trim();
+
+        if !paragraph.is_empty() {
+            self.out.push_str(&format!("<p>{paragraph}</p>\n"));
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        self.out
+    }
+}
+
+// numbers of sections and floats
+struct Numbering {
+    top: usize,
+    sections: [u32; 7],
+    figures: u32,
+    tables: u32,
+    equations: u32,
+}
+
+impl Numbering {
+    fn new(book: bool) -> Self {
+        Self {
+            top: if book { 1 } else { 2 },
+            sections: [0; 7],
+            figures: 0,
+            tables: 0,
+            equations: 0,
+        }
+    }
+
+    fn section(&mut self, level: usize) -> String {
+        self.sections[level] += 1;
+
+        // parts do not reset chapters
+        if level == 0 {
+            return self.sections[0].to_string();
+        }
+
+        self.sections[level + 1..].iter_mut().for_each(|n| *n = 0);
+        self.sections[self.top.min(level)..=level]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn float(&mut self, kind: &str) -> String {
+        let n = match kind {
+            "figure" => &mut self.figures,
+            _ => &mut self.tables,
+        };
+
+        *n += 1;
+        n.to_string()
+    }
+}
+
+// finds what labels refer to before rendering, so references can go forwards
+struct Collector<'a> {
+    conditions: &'a Conditions,
+    numbering: Numbering,
+    current: String,
+    float: Option<&'static str>,
+    labels: BTreeMap<String, String>,
+    bibliography: Vec<String>,
+    title: Vec<(&'a str, &'a Scope)>,
+}
+
+impl<'a> Collector<'a> {
+    fn chunks(&mut self, chunks: &'a [Chunk]) {
+        for chunk in chunks {
+            match chunk.variant() {
+                ChunkVariant::Command(command) => self.command(command),
+                ChunkVariant::Scope(scope) => self.chunks(scope.chunks()),
+                ChunkVariant::Environment(env) => self.environment(env),
+                ChunkVariant::Conditional(conditional) => {
+                    let conditions = self.conditions;
+                    self.chunks(conditions.evaluate_or_content(conditional))
+                }
+                ChunkVariant::Text(_)
+                | ChunkVariant::MathsBlock(_)
+                | ChunkVariant::ParagraphBreak(_)
+                | ChunkVariant::Parameter(_)
+                | ChunkVariant::MacroDefinition(_) => {}
+            }
+        }
+    }
+
+    fn command(&mut self, command: &'a ast3::Command) {
+        let label = command.label().as_str();
+        let last = curly(command).last().copied();
+
+        match label {
+            _ if sectioning::level(label).is_some() && last.is_some() => {
+                let level = sectioning::level(label).unwrap_or(0);
+                self.current = self.numbering.section(level)
+            }
+            "caption" => {
+                if let Some(kind) = self.float {
+                    self.current = self.numbering.float(kind)
+                }
+            }
+            "label" => self.label(command),
+            "bibitem" => self.bibliography.extend(key(command)),
+            "title" | "author" | "date" => {
+                if let Some(scope) = last {
+                    self.title.retain(|(field, _)| field != &label);
+                    self.title.push((label, scope))
+                }
+            }
+            _ => {}
+        }
+
+        for (_, arg) in command.arguments() {
+            self.chunks(arg.chunks())
+        }
+    }
+
+    fn label(&mut self, command: &ast3::Command) {
+        if let Some(key) = key(command) {
+            self.labels.insert(key, self.current.clone());
+        }
+    }
+
+    fn environment(&mut self, env: &'a ast3::Environment) {
+        let label = env.label().as_str();
+
+        if MATHS.contains(&label) || ALIGNED[6..].contains(&label) {
+            return self.equations(env);
+        }
+
+        if VERBATIM.contains(&label) {
+            return;
+        }
+
+        let outer = self.float;
+        if label.starts_with("figure") {
+            self.float = Some("figure")
+        } else if label.starts_with("table") {
+            self.float = Some("table")
+        }
+
+        self.chunks(env.content());
+        self.float = outer;
+    }
+
+    // equations are numbered per environment, or per row where there are several
+    fn equations(&mut self, env: &ast3::Environment) {
+        let label = env.label().as_str();
+        let numbered = NUMBERED.contains(&label);
+        let rows = numbered && label != "equation" && label != "multline";
+
+        let next = |numbering: &mut Numbering| {
+            numbering.equations += 1;
+            numbering.equations.to_string()
+        };
+
+        if numbered {
+            self.current = next(&mut self.numbering);
+        }
+
+        for chunk in env.content() {
+            match chunk.variant() {
+                ChunkVariant::Command(c) if c.label() == "label" => self.label(c),
+                ChunkVariant::Command(c) if c.label() == "\\" && rows => {
+                    self.current = next(&mut self.numbering)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Escape text for HTML
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+// prose, with TeX ligatures and ties
+fn text(s: &str) -> String {
+    escape(s)
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("``", "\u{201c}")
+        .replace("''", "\u{201d}")
+        .replace('~', "&nbsp;")
+}
+
+fn anchor(id: &str) -> String {
+    format!("<a id=\"{}\"></a>", escape(id))
+}
+
+// the `href` attribute linking to a URL, empty unless the URL is relative or has a scheme
+// in `SCHEMES`
+//
+// Browsers ignore tabs, line breaks and leading control characters in a scheme, so these are
+// taken out before it is compared.
+fn href(url: &str) -> String {
+    let cleaned = url
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .skip_while(|c| c.is_ascii_control() || *c == ' ')
+        .collect::<String>();
+    let scheme = cleaned
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+
+    match scheme {
+        Some(scheme) if !SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) => String::new(),
+        _ => format!(" href=\"{}\"", escape(url)),
+    }
+}
+
+// `\verb|...|` from the text after `\verb`
+fn verb(s: &str) -> String {
+    let (code, rest) = split_verb(s);
+    format!("<code>{}</code>{}", escape(code), text(rest))
+}
+
+// the code of `\verb|...|` and the text after it, from the text after `\verb`
+pub(crate) fn split_verb(s: &str) -> (&str, &str) {
+    let end = verb_len(s);
+    let start = s.starts_with('*') as usize;
+    let delimiter = s[start..].chars().next().map(char::len_utf8).unwrap_or(0);
+    let code = s
+        .get(start + delimiter..end.saturating_sub(delimiter))
+        .unwrap_or("");
+
+    (code, &s[end..])
+}
+
+// a single paragraph without its `<p>`
+fn tight(s: &str) -> String {
+    let s = s.trim_end();
+
+    match s.strip_prefix("<p>").and_then(|s| s.strip_suffix("</p>")) {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => format!("\n{s}\n"),
+    }
+}
+
+// the chunks after each `\item`, with the command and its index
+pub(crate) fn items<'a>(
+    content: &'a [Chunk],
+    item: &str,
+) -> Vec<(usize, &'a ast3::Command, &'a [Chunk])> {
+    let starts = content
+        .iter()
+        .enumerate()
+        .filter_map(|(i, chunk)| match chunk.variant() {
+            ChunkVariant::Command(c) if c.label() == item => Some((i, c)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, (i, command))| {
+            let end = starts
+                .get(n + 1)
+                .map(|(end, _)| *end)
+                .unwrap_or(content.len());
+            (*i, *command, &content[i + 1..end])
+        })
+        .collect()
+}
+
+pub(crate) fn curly(command: &ast3::Command) -> Vec<&Scope> {
+    command
+        .arguments()
+        .iter()
+        .filter(|(_, arg)| arg.variant() == ScopeVariant::Curly)
+        .map(|(_, arg)| arg)
+        .collect()
+}
+
+pub(crate) fn source(chunks: &[Chunk]) -> String {
+    chunks.iter().map(ToString::to_string).collect()
+}
+
+// the first key of a `\label` or `\bibitem`
+pub(crate) fn key(command: &ast3::Command) -> Option<String> {
+    keys(command).into_iter().next()
+}
+
+pub(crate) fn keys(command: &ast3::Command) -> Vec<String> {
+    curly(command)
+        .last()
+        .map(|arg| {
+            source(arg.chunks())
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// a URL argument, with escaped characters restored
+pub(crate) fn url(command: &ast3::Command, n: usize) -> String {
+    curly(command)
+        .get(n)
+        .map(|arg| source(arg.chunks()))
+        .unwrap_or_default()
+        .replace("\\#", "#")
+        .replace("\\%", "%")
+        .replace("\\_", "_")
+        .replace("\\&", "&")
+        .trim()
+        .to_string()
+}
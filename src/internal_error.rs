@@ -12,17 +12,51 @@ pub enum InternalError {
     ParagraphBreakTooShort,
     /// Nonwhitespace in ParagraphBreak
     ParagraghBreakNonwhitespace,
-    /// `begin` is used as a command
+    /// `begin` is used as a command outside a group
     BeginCommand,
-    /// `end` is used as a command
+    /// `end` is used as a command outside a group
     EndCommand,
     /// ParagraphBreak is not added for Text
     UnbrokenParagraph,
+    /// Conditional label does not start with `if`
+    NotConditional,
+    /// Parameter has no `#` or a number outside 1 to 9
+    InvalidParameter,
+    /// Macro definition does not start with `\def` or similar
+    NotMacroDefinition,
+    /// Document has no `\begin{document}` body to edit
+    NoBody,
 }
 
 impl Display for InternalError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{self:?}"))
+        match self {
+            Self::UnsanitisedCharInString(c) => {
+                f.write_fmt(format_args!("string contains unescaped character `{c}`"))
+            }
+            Self::IncorrectChunkLineNumber { expected, got } => f.write_fmt(format_args!(
+                "chunk should start on line {expected}, but is numbered {got}"
+            )),
+            Self::ParagraphBreakTooShort => {
+                f.write_str("paragraph break contains fewer than two line breaks")
+            }
+            Self::ParagraghBreakNonwhitespace => {
+                f.write_str("paragraph break contains non-whitespace characters")
+            }
+            Self::BeginCommand => f.write_str("`\\begin` cannot be used as a plain command"),
+            Self::EndCommand => f.write_str("`\\end` cannot be used as a plain command"),
+            Self::UnbrokenParagraph => {
+                f.write_str("text contains a paragraph break that is not split into its own chunk")
+            }
+            Self::NotConditional => f.write_str("conditional label does not start with `if`"),
+            Self::InvalidParameter => {
+                f.write_str("parameter has no `#` or a number outside 1 to 9")
+            }
+            Self::NotMacroDefinition => {
+                f.write_str("macro definition does not start with `\\def` or similar")
+            }
+            Self::NoBody => f.write_str("document has no `\\begin{document}` body to edit"),
+        }
     }
 }
 
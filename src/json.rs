@@ -0,0 +1,415 @@
+//! Minimal JSON values for the language server and exporters
+//!
+//! Objects keep their keys in insertion order so output is stable.
+
+use std::{fmt::Display, str::FromStr};
+
+/// A JSON value
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum Json {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Create an object from key value pairs
+    pub fn object<const N: usize>(entries: [(&str, Json); N]) -> Self {
+        Self::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the value of a key if this is an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(other, _)| other == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Follows a path of keys through nested objects
+    pub fn pointer(&self, keys: &[&str]) -> Option<&Json> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    /// Sets a key if this is an object, replacing any existing value
+    pub fn insert(&mut self, key: &str, value: Json) {
+        if let Self::Object(entries) = self {
+            match entries.iter_mut().find(|(other, _)| other == key) {
+                Some((_, old)) => *old = value,
+                None => entries.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    /// Returns the string if this is a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if this is a non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean if this is a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the items if this is an array
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Whether this is null
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Self::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Self::Number(value as f64)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Self::Number(value as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(value: Vec<Json>) -> Self {
+        Self::Array(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => f.write_fmt(format_args!("\\u{:04x}", c as u32))?,
+            c => f.write_fmt(format_args!("{c}"))?,
+        }
+    }
+
+    f.write_str("\"")
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => f.write_fmt(format_args!("{b}")),
+            Self::Number(n) if n.is_finite() => f.write_fmt(format_args!("{n}")),
+            Self::Number(_) => f.write_str("null"),
+            Self::String(s) => write_string(f, s),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    item.fmt(f)?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    f.write_str(":")?;
+                    value.fmt(f)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Reasons a JSON text could not be parsed, with the byte offset it was found at
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JsonError {
+    /// The text ended in the middle of a value
+    UnexpectedEnd,
+    /// A character which cannot appear here
+    UnexpectedChar(usize),
+    /// A malformed number or escape sequence
+    Invalid(usize),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEnd => f.write_str("unexpected end of JSON"),
+            Self::UnexpectedChar(at) => f.write_fmt(format_args!("unexpected character at {at}")),
+            Self::Invalid(at) => f.write_fmt(format_args!("invalid value at {at}")),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl FromStr for Json {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            bytes: s.as_bytes(),
+            source: s,
+            index: 0,
+        };
+
+        let out = parser.value()?;
+        parser.whitespace();
+
+        if parser.index != parser.bytes.len() {
+            return Err(JsonError::UnexpectedChar(parser.index));
+        }
+
+        Ok(out)
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    source: &'a str,
+    index: usize,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.index)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.index += 1
+        }
+    }
+
+    fn peek(&self) -> Result<u8, JsonError> {
+        self.bytes
+            .get(self.index)
+            .copied()
+            .ok_or(JsonError::UnexpectedEnd)
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), JsonError> {
+        if self.source[self.index..].starts_with(literal) {
+            self.index += literal.len();
+            Ok(())
+        } else if self.source.len() - self.index < literal.len() {
+            Err(JsonError::UnexpectedEnd)
+        } else {
+            Err(JsonError::UnexpectedChar(self.index))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.whitespace();
+
+        match self.peek()? {
+            b'n' => self.expect("null").map(|_| Json::Null),
+            b't' => self.expect("true").map(|_| Json::Bool(true)),
+            b'f' => self.expect("false").map(|_| Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.index += 1;
+                let mut items = Vec::new();
+
+                self.whitespace();
+                if self.peek()? == b']' {
+                    self.index += 1;
+                    return Ok(Json::Array(items));
+                }
+
+                loop {
+                    items.push(self.value()?);
+                    self.whitespace();
+
+                    match self.peek()? {
+                        b',' => self.index += 1,
+                        b']' => {
+                            self.index += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(JsonError::UnexpectedChar(self.index)),
+                    }
+                }
+            }
+            b'{' => {
+                self.index += 1;
+                let mut entries = Vec::new();
+
+                self.whitespace();
+                if self.peek()? == b'}' {
+                    self.index += 1;
+                    return Ok(Json::Object(entries));
+                }
+
+                loop {
+                    self.whitespace();
+                    if self.peek()? != b'"' {
+                        return Err(JsonError::UnexpectedChar(self.index));
+                    }
+                    let key = self.string()?;
+
+                    self.whitespace();
+                    self.expect(":")?;
+                    entries.push((key, self.value()?));
+                    self.whitespace();
+
+                    match self.peek()? {
+                        b',' => self.index += 1,
+                        b'}' => {
+                            self.index += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return Err(JsonError::UnexpectedChar(self.index)),
+                    }
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.index;
+
+                while self
+                    .bytes
+                    .get(self.index)
+                    .is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+                {
+                    self.index += 1
+                }
+
+                self.source[start..self.index]
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|_| JsonError::Invalid(start))
+            }
+            _ => Err(JsonError::UnexpectedChar(self.index)),
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let start = self.index;
+        let digits = self
+            .source
+            .get(start..start + 4)
+            .ok_or(JsonError::UnexpectedEnd)?;
+        self.index += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| JsonError::Invalid(start))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        // skip the opening quote
+        self.index += 1;
+        let mut out = String::new();
+
+        loop {
+            let rest = &self.source[self.index..];
+            let c = rest.chars().next().ok_or(JsonError::UnexpectedEnd)?;
+            self.index += c.len_utf8();
+
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escape = self.peek()?;
+                    self.index += 1;
+
+                    match escape {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let start = self.index;
+                            let mut code = self.hex()?;
+
+                            // surrogate pairs are written as two escapes
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect("\\u")?;
+                                let low = self.hex()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(JsonError::Invalid(start));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+
+                            out.push(char::from_u32(code).ok_or(JsonError::Invalid(start))?)
+                        }
+                        _ => return Err(JsonError::Invalid(self.index - 1)),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
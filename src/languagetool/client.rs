@@ -0,0 +1,154 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::json::Json;
+
+use super::{AnnotatedText, Issue};
+
+impl AnnotatedText {
+    /// Check the text with a LanguageTool server, such as `localhost:8081`
+    ///
+    /// Sends a plain HTTP request to `/v2/check`, failing if connecting, sending or reading
+    /// the response takes longer than 30 seconds.
+    pub fn check(&self, address: &str, language: &str) -> io::Result<Vec<Issue>> {
+        self.check_with_timeout(address, language, Duration::from_secs(30))
+    }
+
+    /// Check the text with a LanguageTool server, failing if connecting, sending or waiting
+    /// for more of the response takes longer than `timeout`
+    pub fn check_with_timeout(
+        &self,
+        address: &str,
+        language: &str,
+        timeout: Duration,
+    ) -> io::Result<Vec<Issue>> {
+        let body = format!(
+            "language={}&data={}",
+            url_encode(language),
+            url_encode(&self.to_json().to_string())
+        );
+
+        let mut stream = connect(address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        write!(
+            stream,
+            "POST /v2/check HTTP/1.1\r\nHost: {address}\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Accept: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        let body = http_body(&response)?;
+        let json = Json::from_str(&body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        Ok(self.matches(&json))
+    }
+}
+
+fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b' ' => out.push('+'),
+            byte => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    out
+}
+
+// connect to the first address `address` resolves to which accepts within `timeout`
+fn connect(address: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last = None;
+
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = Some(e),
+        }
+    }
+
+    Err(last.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "address resolves to nothing")
+    }))
+}
+
+// the body of an HTTP response, which must have status 200
+//
+// Chunks are joined before the body is decoded, as a character can be split between them.
+fn http_body(response: &[u8]) -> io::Result<String> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let split = find(response, b"\r\n\r\n").ok_or_else(|| invalid("incomplete HTTP response"))?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| invalid("missing HTTP status"))?;
+
+    if status != "200" {
+        return Err(io::Error::other(format!(
+            "LanguageTool responded with {status}: {}",
+            String::from_utf8_lossy(body)
+        )));
+    }
+
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("transfer-encoding") && value.trim() == "chunked"
+        })
+    });
+
+    let body = match chunked {
+        true => dechunk(body).ok_or_else(|| invalid("invalid chunk"))?,
+        false => body.to_vec(),
+    };
+
+    String::from_utf8(body).map_err(|_| invalid("response is not valid UTF-8"))
+}
+
+// the bytes of a chunked body joined together
+fn dechunk(body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = body;
+
+    while let Some(line) = find(rest, b"\r\n") {
+        let size = std::str::from_utf8(&rest[..line]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+
+        if size == 0 {
+            break;
+        }
+
+        let after = &rest[line + 2..];
+        out.extend_from_slice(after.get(..size)?);
+        rest = after.get(size + 2..).unwrap_or_default();
+    }
+
+    Some(out)
+}
+
+// index of the first occurrence of `needle`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,373 @@
+//! # LanguageTool
+//!
+//! Exports a stage 3 document as LanguageTool `annotatedText`, and maps the
+//! matches of a check back to positions in the document.
+//!
+//! |Source|Segment|
+//! |---|---|
+//! |Prose|`{"text": ...}`|
+//! |Commands, maths and environments|`{"markup": ..., "interpretAs": ...}`, interpreted as their placeholder, if any.|
+//! |Whitespace and paragraph breaks|Markup interpreted as a single space or a blank line.|
+//!
+//! The segments cover the whole document as displayed, so the offsets of matches
+//! count UTF-16 code units from its start.
+//!
+//! ```
+//! use std::str::FromStr;
+//! use latex_ast::{ast3, json::Json, languagetool::AnnotatedText};
+//!
+//! let document = ast3::Document::from_str(r"A \emph{tpyo}.").unwrap();
+//! let annotated = AnnotatedText::new(&document);
+//!
+//! let response = Json::from_str(r#"{"matches": [{
+//!     "message": "Possible spelling mistake found.",
+//!     "offset": 8, "length": 4,
+//!     "replacements": [{"value": "typo"}],
+//!     "rule": {"id": "MORFOLOGIK_RULE_EN_US"}
+//! }]}"#).unwrap();
+//!
+//! let issue = &annotated.matches(&response)[0];
+//! assert_eq!(issue.start().to_string(), "1:9");
+//! assert_eq!(issue.replacements(), &vec!["typo".to_string()]);
+//! ```
+//!
+//! With the `languagetool-client` feature, `AnnotatedText::check` sends the text to a
+//! LanguageTool server over HTTP.
+
+#[cfg(feature = "languagetool-client")]
+mod client;
+
+use crate::{
+    ast3,
+    json::Json,
+    plaintext::{Extractor, PlainText},
+    Diagnostic, Label, Position,
+};
+
+/// A segment of annotated text
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub enum Segment {
+    /// Prose to check
+    Text(String),
+    /// Source which is not checked, read as `interpret_as`
+    Markup {
+        markup: String,
+        interpret_as: String,
+    },
+}
+
+/// A document as LanguageTool `annotatedText`
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct AnnotatedText {
+    segments: Vec<Segment>,
+}
+
+impl AnnotatedText {
+    /// Annotate a document, with the default placeholders
+    pub fn new(document: &ast3::Document) -> Self {
+        Self::with_extractor(document, &Extractor::new())
+    }
+
+    /// Annotate a document, with placeholders from the extractor
+    pub fn with_extractor(document: &ast3::Document, extractor: &Extractor) -> Self {
+        Self::from_plain(&document.to_string(), &extractor.extract(document))
+    }
+
+    /// Annotate a source from the text extracted from it
+    pub fn from_plain(source: &str, plain: &PlainText) -> Self {
+        let mut mappings = plain.mappings().iter().collect::<Vec<_>>();
+        mappings.sort_by_key(|mapping| (mapping.start(), mapping.end()));
+
+        let offsets = byte_offsets(
+            source,
+            mappings
+                .iter()
+                .flat_map(|mapping| [mapping.start(), mapping.end()])
+                .collect(),
+        );
+        let offset = |position: Position| lookup(&offsets, position);
+
+        let mut out = Self {
+            segments: Vec::new(),
+        };
+        let mut cursor = 0;
+
+        for mapping in mappings {
+            let (start, end) = (offset(mapping.start()), offset(mapping.end()));
+
+            if start > cursor {
+                out.markup(&source[cursor..start], "");
+            }
+
+            let output = &plain.text()[mapping.output().clone()];
+            let markup = &source[start.max(cursor)..end.max(cursor)];
+
+            if mapping.is_exact() {
+                out.text(markup)
+            } else {
+                out.markup(markup, output)
+            }
+
+            cursor = cursor.max(end);
+        }
+
+        if cursor < source.len() {
+            out.markup(&source[cursor..], "");
+        }
+
+        out
+    }
+
+    /// Returns the segments, in source order
+    pub fn segments(&self) -> &Vec<Segment> {
+        &self.segments
+    }
+
+    /// Returns the source the segments were made from
+    pub fn source(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.as_str(),
+                Segment::Markup { markup, .. } => markup.as_str(),
+            })
+            .collect()
+    }
+
+    /// Returns the `annotatedText` JSON
+    pub fn to_json(&self) -> Json {
+        Json::object([(
+            "annotation",
+            Json::Array(
+                self.segments
+                    .iter()
+                    .map(|segment| match segment {
+                        Segment::Text(text) => Json::object([("text", text.as_str().into())]),
+                        Segment::Markup {
+                            markup,
+                            interpret_as,
+                        } if interpret_as.is_empty() => {
+                            Json::object([("markup", markup.as_str().into())])
+                        }
+                        Segment::Markup {
+                            markup,
+                            interpret_as,
+                        } => Json::object([
+                            ("markup", markup.as_str().into()),
+                            ("interpretAs", interpret_as.as_str().into()),
+                        ]),
+                    })
+                    .collect(),
+            ),
+        )])
+    }
+
+    /// Returns the position of an offset in UTF-16 code units, as used by LanguageTool
+    pub fn position(&self, offset: usize) -> Position {
+        lookup(&self.positions(vec![offset]), offset)
+    }
+
+    // the positions of offsets in UTF-16 code units, found in one pass over the segments
+    fn positions(&self, mut offsets: Vec<usize>) -> Vec<(usize, Position)> {
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let mut out = Vec::with_capacity(offsets.len());
+        let mut offsets = offsets.into_iter().peekable();
+        let mut position = Position::start();
+        let mut units = 0;
+
+        for c in self.segments.iter().flat_map(|segment| match segment {
+            Segment::Text(text) => text.chars(),
+            Segment::Markup { markup, .. } => markup.chars(),
+        }) {
+            while let Some(offset) = offsets.next_if(|offset| *offset <= units) {
+                out.push((offset, position))
+            }
+            if offsets.peek().is_none() {
+                break;
+            }
+
+            units += c.len_utf16();
+            position.advance(c);
+        }
+
+        out.extend(offsets.map(|offset| (offset, position)));
+        out
+    }
+
+    /// Read the matches of a `/v2/check` response, skipping malformed ones
+    pub fn matches(&self, response: &Json) -> Vec<Issue> {
+        let matches = match response.get("matches").and_then(Json::as_array) {
+            Some(matches) => matches,
+            None => return Vec::new(),
+        };
+
+        let ranges = matches
+            .iter()
+            .map(|found| {
+                let offset = found.get("offset").and_then(Json::as_u64)? as usize;
+                let length = found.get("length").and_then(Json::as_u64)? as usize;
+                Some((offset, offset + length))
+            })
+            .collect::<Vec<_>>();
+        let positions = self.positions(
+            ranges
+                .iter()
+                .flatten()
+                .flat_map(|(start, end)| [*start, *end])
+                .collect(),
+        );
+
+        matches
+            .iter()
+            .zip(ranges)
+            .filter_map(|(found, range)| {
+                let (start, end) = range?;
+
+                Some(Issue {
+                    message: found.get("message").and_then(Json::as_str)?.to_string(),
+                    rule: found
+                        .pointer(&["rule", "id"])
+                        .and_then(Json::as_str)
+                        .unwrap_or("")
+                        .to_string(),
+                    replacements: found
+                        .get("replacements")
+                        .and_then(Json::as_array)
+                        .map(|replacements| {
+                            replacements
+                                .iter()
+                                .filter_map(|r| r.get("value").and_then(Json::as_str))
+                                .map(ToString::to_string)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    start: lookup(&positions, start),
+                    end: lookup(&positions, end),
+                })
+            })
+            .collect()
+    }
+
+    fn text(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+
+        match self.segments.last_mut() {
+            Some(Segment::Text(text)) => text.push_str(s),
+            _ => self.segments.push(Segment::Text(s.to_string())),
+        }
+    }
+
+    fn markup(&mut self, s: &str, interpret: &str) {
+        if s.is_empty() && interpret.is_empty() {
+            return;
+        }
+
+        match self.segments.last_mut() {
+            Some(Segment::Markup {
+                markup,
+                interpret_as,
+            }) => {
+                markup.push_str(s);
+                interpret_as.push_str(interpret);
+            }
+            _ => self.segments.push(Segment::Markup {
+                markup: s.to_string(),
+                interpret_as: interpret.to_string(),
+            }),
+        }
+    }
+}
+
+/// A problem found by LanguageTool
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Issue {
+    message: String,
+    rule: String,
+    replacements: Vec<String>,
+    start: Position,
+    end: Position,
+}
+
+impl Issue {
+    /// Returns the message explaining the problem
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    /// Returns the id of the rule which matched
+    pub fn rule(&self) -> &String {
+        &self.rule
+    }
+
+    /// Returns the suggested replacements
+    pub fn replacements(&self) -> &Vec<String> {
+        &self.replacements
+    }
+
+    /// Returns the position of the first character
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Returns the position right after the last character
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    /// Convert to a warning labelled at the start of the problem
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let label = match self.replacements.first() {
+            Some(replacement) => format!("did you mean `{replacement}`?"),
+            None => self.rule.clone(),
+        };
+
+        Diagnostic::warning(self.message.clone()).with_label(Label::primary(
+            self.start.line,
+            Some(self.start.column),
+            label,
+        ))
+    }
+}
+
+// byte offsets of positions in the source, found in one pass over it, positions past the
+// end are at its end
+fn byte_offsets(source: &str, mut positions: Vec<Position>) -> Vec<(Position, usize)> {
+    positions.sort_unstable();
+    positions.dedup();
+
+    let mut out = Vec::with_capacity(positions.len());
+    let mut positions = positions.into_iter().peekable();
+    let mut position = Position::start();
+
+    for (offset, c) in source.char_indices() {
+        while let Some(found) = positions.next_if(|target| *target <= position) {
+            out.push((found, offset))
+        }
+        if positions.peek().is_none() {
+            break;
+        }
+
+        position.advance(c);
+    }
+
+    out.extend(positions.map(|position| (position, source.len())));
+    out
+}
+
+// the value found for a key by `byte_offsets` or `AnnotatedText::positions`
+fn lookup<K: Ord, V: Copy>(found: &[(K, V)], key: K) -> V {
+    let index = found
+        .binary_search_by(|(other, _)| other.cmp(&key))
+        .expect("key which was not looked up");
+    found[index].1
+}
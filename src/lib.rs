@@ -18,10 +18,35 @@ pub mod ast4;
 
 pub mod traits;
 
+pub mod completion;
+pub mod format;
+pub mod html;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json")]
+pub mod languagetool;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod markdown;
+pub mod mathml;
+pub mod plaintext;
+pub mod sectioning;
+pub mod statistics;
+pub mod unicode;
+
 mod error;
 pub use error::*;
 
+mod position;
+pub use position::Position;
+
+mod diagnostic;
+pub use diagnostic::{Diagnostic, Label, Severity};
+
 mod internal_error;
 pub use internal_error::InternalError;
 
+mod limits;
+pub use limits::{Limit, ParseLimits};
+
 mod tests;
@@ -0,0 +1,109 @@
+use std::fmt::Display;
+
+/// A limit on the resources used by parsing
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Limit {
+    /// Scopes, arguments and conditionals nested inside each other
+    Depth,
+    /// Bytes of input
+    InputLen,
+    /// Stage 1 chunks and arguments in the whole document
+    Nodes,
+    /// Environments nested inside each other
+    EnvironmentDepth,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Depth => "nesting depth",
+            Self::InputLen => "input size",
+            Self::Nodes => "number of nodes",
+            Self::EnvironmentDepth => "environment depth",
+        })
+    }
+}
+
+/// Limits for parsing untrusted input
+///
+/// The default limits nesting to the deepest every later stage can handle, and does not limit
+/// the size of the input or the number of nodes. Stages 2 and 3 recurse into nested scopes and
+/// environments, so the depths can only be lowered from the default, and larger depths are
+/// clamped to [`ParseLimits::MAX_DEPTH`] and [`ParseLimits::MAX_ENVIRONMENT_DEPTH`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseLimits {
+    max_depth: usize,
+    max_input_len: Option<usize>,
+    max_nodes: Option<usize>,
+    max_environment_depth: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: Self::MAX_DEPTH,
+            max_input_len: None,
+            max_nodes: None,
+            max_environment_depth: Self::MAX_ENVIRONMENT_DEPTH,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// The deepest scopes and arguments can be nested, stage 2 counts conditionals too
+    pub const MAX_DEPTH: usize = 128;
+
+    /// The deepest environments can be nested
+    pub const MAX_ENVIRONMENT_DEPTH: usize = 32;
+
+    /// Create the default limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of scopes and arguments which can be nested inside each other, up to
+    /// [`ParseLimits::MAX_DEPTH`]
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth.min(Self::MAX_DEPTH);
+        self
+    }
+
+    /// Set the number of bytes of input
+    pub fn with_max_input_len(mut self, len: usize) -> Self {
+        self.max_input_len = Some(len);
+        self
+    }
+
+    /// Set the number of stage 1 chunks and arguments
+    pub fn with_max_nodes(mut self, nodes: usize) -> Self {
+        self.max_nodes = Some(nodes);
+        self
+    }
+
+    /// Set the number of environments which can be nested inside each other, up to
+    /// [`ParseLimits::MAX_ENVIRONMENT_DEPTH`]
+    pub fn with_max_environment_depth(mut self, depth: usize) -> Self {
+        self.max_environment_depth = depth.min(Self::MAX_ENVIRONMENT_DEPTH);
+        self
+    }
+
+    /// Returns the number of scopes and arguments which can be nested inside each other
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the number of bytes of input, if limited
+    pub fn max_input_len(&self) -> Option<usize> {
+        self.max_input_len
+    }
+
+    /// Returns the number of stage 1 chunks and arguments, if limited
+    pub fn max_nodes(&self) -> Option<usize> {
+        self.max_nodes
+    }
+
+    /// Returns the number of environments which can be nested inside each other
+    pub fn max_environment_depth(&self) -> usize {
+        self.max_environment_depth
+    }
+}
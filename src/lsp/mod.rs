@@ -0,0 +1,21 @@
+//! # Language server
+//!
+//! A Language Server Protocol server built on the stage 3 AST, the `latex-ast-lsp`
+//! binary runs it over stdio.
+//!
+//! |Feature|Description|
+//! |---|---|
+//! |Diagnostics|Parse errors are published whenever a document changes.|
+//! |Document symbols|Sectioning commands and environments.|
+//! |Go to definition|From a `\ref` to its `\label`, across open documents.|
+//! |Find references|Every `\ref` of a `\label`, across open documents.|
+//! |Folding ranges|Environments, scopes and maths blocks spanning multiple lines.|
+//! |Incremental sync|Documents are patched with each change instead of resent.|
+
+mod server;
+mod source;
+mod transport;
+
+pub use server::Server;
+pub use source::Source;
+pub use transport::{read_message, write_message};
@@ -0,0 +1,706 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{
+    ast1::Catcodes,
+    ast3::{self, Match, Node, Query},
+    completion::{Completer, CompletionKind, REF_COMMANDS},
+    json::Json,
+    sectioning, Diagnostic, Position,
+};
+
+use super::Source;
+
+/// Commands whose first argument declares a label
+const LABEL_COMMANDS: [&str; 1] = ["label"];
+
+// LSP error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// LSP symbol kinds
+const SYMBOL_MODULE: u32 = 2;
+const SYMBOL_STRUCT: u32 = 23;
+
+// LSP completion item kinds
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_CLASS: u32 = 7;
+const COMPLETION_REFERENCE: u32 = 18;
+const COMPLETION_CONSTANT: u32 = 21;
+
+/// A language server working on `ast3::Document`s
+///
+/// The server does not do any IO, each incoming message is passed to `handle`
+/// and the returned messages are sent back to the client.
+#[derive(Default)]
+pub struct Server {
+    documents: BTreeMap<String, Source>,
+    shutdown: bool,
+    exit_code: Option<i32>,
+}
+
+type Response = Result<Json, (i64, String)>;
+
+impl Server {
+    /// Create a server with no open documents
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the exit code once the client has sent `exit`
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Returns an open document
+    pub fn source(&self, uri: &str) -> Option<&Source> {
+        self.documents.get(uri)
+    }
+
+    /// Handle the body of a message, replying with a parse error if it is not JSON
+    pub fn handle_str(&mut self, message: &str) -> Vec<Json> {
+        match Json::from_str(message) {
+            Ok(message) => self.handle(&message),
+            Err(e) => vec![Self::error(Json::Null, PARSE_ERROR, e.to_string())],
+        }
+    }
+
+    /// Handle a message, returning the responses and notifications to send
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = match message.get("method").and_then(Json::as_str) {
+            Some(method) => method,
+            // responses to requests we never send
+            None => return Vec::new(),
+        };
+        let params = message.get("params").cloned().unwrap_or_default();
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, &params),
+        };
+
+        let response = if self.shutdown {
+            Err((INVALID_REQUEST, "server is shutting down".to_string()))
+        } else {
+            self.request(method, &params)
+        };
+
+        vec![match response {
+            Ok(result) => Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+            Err((code, message)) => Self::error(id, code, message),
+        }]
+    }
+
+    fn error(id: Json, code: i64, message: String) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                Json::object([("code", code.into()), ("message", message.into())]),
+            ),
+        ])
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Response {
+        match method {
+            "initialize" => Ok(Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        (
+                            "textDocumentSync",
+                            Json::object([("openClose", true.into()), ("change", 2_u32.into())]),
+                        ),
+                        ("documentSymbolProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("referencesProvider", true.into()),
+                        ("foldingRangeProvider", true.into()),
+                        (
+                            "completionProvider",
+                            Json::object([(
+                                "triggerCharacters",
+                                Json::Array(vec!["\\".into(), "{".into(), ",".into()]),
+                            )]),
+                        ),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object([
+                        ("name", env!("CARGO_PKG_NAME").into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/documentSymbol" => self.with_document(params, |source, document| {
+                Self::symbols(source, &document.query())
+            }),
+            "textDocument/foldingRange" => self.with_document(params, |_, document| {
+                Self::folding_ranges(&document.query())
+            }),
+            "textDocument/definition" => {
+                let keys = self.label_at(params)?;
+                Ok(self.find_commands(&LABEL_COMMANDS, &keys))
+            }
+            "textDocument/references" => {
+                let keys = self.label_at(params)?;
+                let mut out = self.find_commands(&REF_COMMANDS, &keys);
+
+                if params
+                    .pointer(&["context", "includeDeclaration"])
+                    .and_then(Json::as_bool)
+                    .unwrap_or(false)
+                {
+                    if let Json::Array(declarations) = self.find_commands(&LABEL_COMMANDS, &keys) {
+                        if let Json::Array(items) = &mut out {
+                            items.splice(0..0, declarations);
+                        }
+                    }
+                }
+
+                Ok(out)
+            }
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params
+            .pointer(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .map(ToString::to_string);
+        let version =
+            params
+                .pointer(&["textDocument", "version"])
+                .and_then(|version| match version {
+                    Json::Number(n) => Some(*n as i64),
+                    _ => None,
+                });
+
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                Vec::new()
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params
+                    .pointer(&["textDocument", "text"])
+                    .and_then(Json::as_str)
+                    .unwrap_or("")
+                    .to_string();
+                let catcodes = Catcodes::for_path(&uri);
+                self.documents
+                    .insert(uri.clone(), Source::with_catcodes(text, version, catcodes));
+                vec![self.publish_diagnostics(&uri)]
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                let changes = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .cloned()
+                    .unwrap_or_default();
+
+                match self.documents.get_mut(&uri) {
+                    Some(source) => {
+                        source.apply(&changes, version);
+                        vec![self.publish_diagnostics(&uri)]
+                    }
+                    None => Vec::new(),
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                vec![Json::object([
+                    ("jsonrpc", "2.0".into()),
+                    ("method", "textDocument/publishDiagnostics".into()),
+                    (
+                        "params",
+                        Json::object([
+                            ("uri", uri.into()),
+                            ("diagnostics", Json::Array(Vec::new())),
+                        ]),
+                    ),
+                ])]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Json {
+        let source = &self.documents[uri];
+
+        let diagnostics = match source.document() {
+            Ok(_) => Vec::new(),
+            // bibliographies are only opened for their citation keys
+            Err(_) if uri.ends_with(".bib") => Vec::new(),
+            Err(error) => {
+                let diagnostic = Diagnostic::from(error);
+                let related = diagnostic
+                    .labels()
+                    .iter()
+                    .filter(|label| !label.is_primary())
+                    .map(|label| {
+                        let at = Position::new(label.line(), label.column().unwrap_or(1));
+                        Json::object([
+                            (
+                                "location",
+                                Json::object([
+                                    ("uri", uri.into()),
+                                    ("range", source.lsp_range(at, Self::next_char(source, at))),
+                                ]),
+                            ),
+                            ("message", label.message().as_str().into()),
+                        ])
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut out = Json::object([
+                    (
+                        "range",
+                        source.lsp_range(error.position, Self::next_char(source, error.position)),
+                    ),
+                    ("severity", 1_u32.into()),
+                    ("source", env!("CARGO_PKG_NAME").into()),
+                    ("message", diagnostic.message().as_str().into()),
+                ]);
+
+                if !related.is_empty() {
+                    out.insert("relatedInformation", Json::Array(related));
+                }
+
+                vec![out]
+            }
+        };
+
+        let mut params = Json::object([
+            ("uri", uri.into()),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]);
+
+        if let Some(version) = source.version() {
+            params.insert("version", version.into());
+        }
+
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            ("params", params),
+        ])
+    }
+
+    // the position after the character at `at`, used to give errors a width of one character
+    fn next_char(source: &Source, at: Position) -> Position {
+        match source
+            .text()
+            .split('\n')
+            .nth(at.line.saturating_sub(1) as usize)
+            .and_then(|line| line.chars().nth(at.column.saturating_sub(1) as usize))
+        {
+            Some(c) if c != '\r' => at.after(&c.to_string()),
+            _ => at,
+        }
+    }
+
+    fn with_document(
+        &self,
+        params: &Json,
+        f: impl FnOnce(&Source, &ast3::Document) -> Json,
+    ) -> Response {
+        let source = self.source_of(params)?;
+
+        Ok(match source.document() {
+            Ok(document) => f(source, document),
+            // nothing can be offered until the document parses again
+            Err(_) => Json::Null,
+        })
+    }
+
+    fn source_of(&self, params: &Json) -> Result<&Source, (i64, String)> {
+        let uri = params
+            .pointer(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, "missing textDocument.uri".to_string()))?;
+
+        self.documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("document `{uri}` is not open")))
+    }
+
+    fn symbols(source: &Source, query: &Query) -> Json {
+        struct Open {
+            symbol: Option<Json>,
+            children: Vec<Json>,
+            // sections are closed by the next section of the same or a higher level
+            level: Option<usize>,
+            start: Position,
+            // where the symbol ends at the latest
+            limit: Position,
+        }
+
+        fn close(source: &Source, stack: &mut Vec<Open>, roots: &mut Vec<Json>, end: Position) {
+            let open = stack.pop().unwrap();
+            let end = if open.level.is_some() {
+                end
+            } else {
+                open.limit
+            };
+
+            let items = match open.symbol {
+                Some(mut symbol) => {
+                    symbol.insert("range", source.lsp_range(open.start, end));
+                    symbol.insert("children", Json::Array(open.children));
+                    vec![symbol]
+                }
+                // the body is not a symbol itself
+                None => open.children,
+            };
+
+            match stack.last_mut() {
+                Some(parent) => parent.children.extend(items),
+                None => roots.extend(items),
+            }
+        }
+
+        let document_end = query
+            .descendants()
+            .filter(|node| node.depth() == 0)
+            .map(Match::end)
+            .max()
+            .unwrap_or(Position::start());
+
+        let mut stack: Vec<Open> = Vec::new();
+        let mut roots = Vec::new();
+
+        let nodes = query.descendants().as_slice();
+
+        for (i, node) in nodes.iter().enumerate() {
+            // the name of a starred section is after the command, in the selection
+            let mut selection_end = node.end();
+
+            let (name, kind, level) = match node.node() {
+                Node::Body(_) => (None, SYMBOL_STRUCT, None),
+                Node::Environment(env) => (Some(env.label().clone()), SYMBOL_STRUCT, None),
+                Node::Command(c) => match sectioning::level(c.label()) {
+                    Some(level) => {
+                        let title = c
+                            .arguments()
+                            .iter()
+                            .find(|(_, arg)| arg.variant() == ast3::ScopeVariant::Curly)
+                            .map(|(_, arg)| arg)
+                            .or_else(|| {
+                                let title = starred(nodes, i)?;
+                                selection_end = nodes[i + 2].end();
+                                Some(title)
+                            });
+
+                        (
+                            Some(
+                                title
+                                    .map(|arg| argument_text(arg).trim().to_string())
+                                    .unwrap_or_else(|| c.label().clone()),
+                            ),
+                            SYMBOL_MODULE,
+                            Some(level),
+                        )
+                    }
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            while stack.last().is_some_and(|open| open.limit <= node.start()) {
+                let limit = stack.last().unwrap().limit;
+                close(source, &mut stack, &mut roots, limit);
+            }
+
+            if let Some(level) = level {
+                while stack
+                    .last()
+                    .is_some_and(|open| open.level.is_some_and(|other| other >= level))
+                {
+                    close(source, &mut stack, &mut roots, node.start());
+                }
+            }
+
+            let limit = match (level, node.node()) {
+                (Some(_), _) => stack
+                    .iter()
+                    .rev()
+                    .find(|open| open.level.is_none())
+                    .map(|open| open.limit)
+                    .unwrap_or(document_end),
+                // sections in the body end before `\end{document}`
+                (None, Node::Body(document)) => node
+                    .start()
+                    .after(&format!(
+                        "\\begin{}{{document}}",
+                        document.body_begin_prec()
+                    ))
+                    .after_arguments(document.body_args())
+                    .after_all(document.chunks_body()),
+                (None, _) => node.end(),
+            };
+
+            let symbol = name.map(|name| {
+                Json::object([
+                    ("name", name.into()),
+                    ("kind", kind.into()),
+                    ("range", Json::Null),
+                    (
+                        "selectionRange",
+                        source.lsp_range(node.start(), selection_end),
+                    ),
+                ])
+            });
+
+            stack.push(Open {
+                symbol,
+                children: Vec::new(),
+                level,
+                start: node.start(),
+                limit,
+            });
+        }
+
+        while let Some(open) = stack.last() {
+            let limit = open.limit;
+            close(source, &mut stack, &mut roots, limit);
+        }
+
+        Json::Array(roots)
+    }
+
+    fn folding_ranges(query: &Query) -> Json {
+        Json::Array(
+            query
+                .descendants()
+                .filter(|node| {
+                    matches!(
+                        node.node(),
+                        Node::Environment(_)
+                            | Node::Body(_)
+                            | Node::Scope(_)
+                            | Node::Argument(_)
+                            | Node::MathsBlock(_)
+                    ) && node.end().line > node.start().line
+                })
+                .map(|node| {
+                    Json::object([
+                        ("startLine", (node.start().line - 1).into()),
+                        ("endLine", (node.end().line - 1).into()),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    // the label keys under the cursor of a definition or references request
+    fn label_at(&self, params: &Json) -> Result<Vec<String>, (i64, String)> {
+        let source = self.source_of(params)?;
+        let document = match source.document() {
+            Ok(document) => document,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let position = source.position(params.get("position").unwrap_or(&Json::Null));
+        let query = document.query();
+        let path = query.node_at(position);
+
+        let command = match path.iter().rev().find_map(|node| match node.node() {
+            Node::Command(c)
+                if LABEL_COMMANDS.contains(&c.label().as_str())
+                    || REF_COMMANDS.contains(&c.label().as_str()) =>
+            {
+                Some((node, c))
+            }
+            _ => None,
+        }) {
+            Some(command) => command,
+            None => return Ok(Vec::new()),
+        };
+
+        // when the cursor is on one key of a list, only use that key
+        let argument = path.iter().find(|node| {
+            matches!(node.node(), Node::Argument(_)) && node.depth() == command.0.depth() + 1
+        });
+
+        if let Some(argument) = argument {
+            if let Node::Argument(scope) = argument.node() {
+                let text = argument_text(scope);
+                let mut at = argument.start().after("{");
+
+                for key in text.split(',') {
+                    let end = at.after(key);
+                    if at <= position && position <= end && !key.trim().is_empty() {
+                        return Ok(vec![key.trim().to_string()]);
+                    }
+                    at = end.after(",");
+                }
+            }
+        }
+
+        Ok(keys(command.1))
+    }
+
+    fn completion(&self, params: &Json) -> Response {
+        let source = self.source_of(params)?;
+        let document = match source.last_parsed() {
+            Some(document) => document,
+            None => return Ok(Json::Array(Vec::new())),
+        };
+
+        let uri = params
+            .pointer(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .unwrap_or("");
+        let offset = source.byte_offset(params.get("position").unwrap_or(&Json::Null));
+
+        let mut completer = Completer::new();
+        self.bibliographies(uri, document)
+            .iter()
+            .for_each(|bib| completer.add_bib(bib));
+
+        Ok(Json::Array(
+            completer
+                .complete_after(document, &source.text()[..offset])
+                .into_iter()
+                .map(|completion| {
+                    let kind = match completion.kind() {
+                        CompletionKind::Command => COMPLETION_FUNCTION,
+                        CompletionKind::Environment => COMPLETION_CLASS,
+                        CompletionKind::Label => COMPLETION_REFERENCE,
+                        CompletionKind::Citation => COMPLETION_CONSTANT,
+                    };
+
+                    Json::object([
+                        ("label", completion.label().as_str().into()),
+                        ("kind", kind.into()),
+                        ("detail", completion.detail().as_str().into()),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    // text of open `.bib` documents, and of `.bib` files loaded by the document
+    // which are read from disk relative to the document
+    fn bibliographies(&self, uri: &str, document: &ast3::Document) -> Vec<String> {
+        let mut out = self
+            .documents
+            .iter()
+            .filter(|(uri, _)| uri.ends_with(".bib"))
+            .map(|(_, source)| source.text().to_string())
+            .collect::<Vec<_>>();
+
+        let directory = match uri
+            .strip_prefix("file://")
+            .and_then(|path| path.rsplit_once('/'))
+        {
+            Some((directory, _)) => directory,
+            None => return out,
+        };
+
+        let query = document.query();
+        let names = query
+            .commands_named("bibliography")
+            .chain(query.commands_named("addbibresource"))
+            .filter_map(|node| match node.node() {
+                Node::Command(c) => Some(keys(c)),
+                _ => None,
+            })
+            .flatten();
+
+        for name in names {
+            let name = if name.ends_with(".bib") {
+                name
+            } else {
+                format!("{name}.bib")
+            };
+            let path = format!("{directory}/{name}");
+
+            if self.documents.contains_key(&format!("file://{path}")) {
+                continue;
+            }
+
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                out.push(text)
+            }
+        }
+
+        out
+    }
+
+    // locations of commands in any open document with one of the keys
+    fn find_commands(&self, labels: &[&str], wanted: &[String]) -> Json {
+        let mut out = Vec::new();
+
+        for (uri, source) in self.documents.iter() {
+            let document = match source.document() {
+                Ok(document) => document,
+                Err(_) => continue,
+            };
+
+            for node in document.query().descendants() {
+                if let Node::Command(c) = node.node() {
+                    if labels.contains(&c.label().as_str())
+                        && keys(c).iter().any(|key| wanted.contains(key))
+                    {
+                        out.push(Json::object([
+                            ("uri", uri.as_str().into()),
+                            ("range", source.lsp_range(node.start(), node.end())),
+                        ]));
+                    }
+                }
+            }
+        }
+
+        Json::Array(out)
+    }
+}
+
+// the title of a starred heading, the `*` and curly scope right after the command at `i`, as
+// in `\section*{Title}`
+fn starred<'a>(nodes: &[Match<'a>], i: usize) -> Option<&'a ast3::Scope> {
+    let siblings = nodes
+        .get(i + 1..i + 3)?
+        .iter()
+        .all(|node| node.depth() == nodes[i].depth());
+
+    match (siblings, nodes[i + 1].node(), nodes[i + 2].node()) {
+        (true, Node::Text(star), Node::Scope(title))
+            if star == "*" && title.variant() == ast3::ScopeVariant::Curly =>
+        {
+            Some(title)
+        }
+        _ => None,
+    }
+}
+
+// the source text inside an argument
+fn argument_text(scope: &ast3::Scope) -> String {
+    scope.chunks().iter().map(ToString::to_string).collect()
+}
+
+// the comma separated keys in the first curly argument of a command
+fn keys(command: &ast3::Command) -> Vec<String> {
+    command
+        .arguments()
+        .iter()
+        .find(|(_, arg)| arg.variant() == ast3::ScopeVariant::Curly)
+        .map(|(_, arg)| {
+            argument_text(arg)
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
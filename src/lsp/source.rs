@@ -0,0 +1,236 @@
+use crate::{ast1::Catcodes, ast3, json::Json, ParseLimits, Position};
+
+/// An open text document and its parse result
+pub struct Source {
+    text: String,
+    version: Option<i64>,
+    catcodes: Catcodes,
+    document: Result<ast3::Document, crate::Error>,
+    // the last version which parsed, kept while the current one does not
+    fallback: Option<ast3::Document>,
+}
+
+impl Source {
+    /// Parse a new document
+    pub fn new(text: String, version: Option<i64>) -> Self {
+        Self::with_catcodes(text, version, Catcodes::default())
+    }
+
+    /// Parse a new document starting with the given catcodes, such as the source of a package
+    pub fn with_catcodes(text: String, version: Option<i64>, catcodes: Catcodes) -> Self {
+        Self {
+            document: parse(&text, catcodes),
+            fallback: None,
+            text,
+            version,
+            catcodes,
+        }
+    }
+
+    /// Returns the current text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the version given by the client
+    pub fn version(&self) -> Option<i64> {
+        self.version
+    }
+
+    /// Returns the parsed document, or why it could not be parsed
+    pub fn document(&self) -> Result<&ast3::Document, &crate::Error> {
+        self.document.as_ref()
+    }
+
+    /// Returns the current document if it parses, otherwise the last version which did
+    pub fn last_parsed(&self) -> Option<&ast3::Document> {
+        self.document.as_ref().ok().or(self.fallback.as_ref())
+    }
+
+    /// Apply `contentChanges` from a `didChange` notification, then parse again
+    ///
+    /// Ranged changes are reparsed incrementally while the text starts with the default
+    /// catcodes and parses, otherwise the whole text is parsed again.
+    pub fn apply(&mut self, changes: &[Json], version: Option<i64>) {
+        let mut incremental = self.catcodes == Catcodes::default() && self.document.is_ok();
+
+        for change in changes {
+            let text = change.get("text").and_then(Json::as_str).unwrap_or("");
+
+            match change.get("range") {
+                Some(range) => {
+                    let start = range.get("start").map(|at| self.byte_offset(at));
+                    let end = range.get("end").map(|at| self.byte_offset(at));
+
+                    if let (Some(start), Some(end)) = (start, end) {
+                        let end = end.max(start);
+
+                        incremental = incremental && self.reparse(start, end, text);
+                        self.text.replace_range(start..end, text)
+                    }
+                }
+                None => {
+                    self.text = text.to_string();
+                    incremental = false;
+                }
+            }
+        }
+
+        self.version = version;
+
+        if incremental {
+            self.fallback = None;
+            return;
+        }
+
+        let document = parse(&self.text, self.catcodes);
+
+        match std::mem::replace(&mut self.document, document) {
+            Ok(previous) if self.document.is_err() => self.fallback = Some(previous),
+            _ if self.document.is_ok() => self.fallback = None,
+            _ => {}
+        }
+    }
+
+    // reparse the document for replacing the text between two byte offsets, returning whether
+    // it succeeded
+    //
+    // The document displays as the text without comments, which end at the end of their
+    // line, so the lines the change touches are compared with their comments taken out and
+    // only the part which differs is replaced.
+    fn reparse(&mut self, start: usize, end: usize, text: &str) -> bool {
+        let Ok(document) = self.document.as_mut() else {
+            return false;
+        };
+
+        let line_start = self.text[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = self.text[end..]
+            .find('\n')
+            .map_or(self.text.len(), |index| end + index);
+
+        let old = uncommented(&self.text[line_start..line_end]);
+        let new = uncommented(&format!(
+            "{}{text}{}",
+            &self.text[line_start..start],
+            &self.text[end..line_end]
+        ));
+
+        let prefix = old
+            .char_indices()
+            .zip(new.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(old.len().min(new.len()), |((index, _), _)| index);
+        let suffix = old[prefix..]
+            .chars()
+            .rev()
+            .zip(new[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>();
+
+        let line = self.text[..line_start].matches('\n').count() as u32 + 1;
+        let from = Position::new(line, 1).after(&old[..prefix]);
+        let to = from.after(&old[prefix..old.len() - suffix]);
+
+        document
+            .reparse(from, to, &new[prefix..new.len() - suffix])
+            .is_ok()
+    }
+
+    /// Convert an LSP position to a byte offset into the text
+    ///
+    /// LSP lines start from 0 and characters are counted in UTF-16 code units,
+    /// positions past the end of a line are moved back to the end of the line.
+    pub fn byte_offset(&self, position: &Json) -> usize {
+        let line = position.get("line").and_then(Json::as_u64).unwrap_or(0) as usize;
+        let character = position
+            .get("character")
+            .and_then(Json::as_u64)
+            .unwrap_or(0) as usize;
+
+        let mut offset = 0;
+
+        for (i, text) in self.text.split('\n').enumerate() {
+            if i == line {
+                let mut units = 0;
+
+                for (index, c) in text.char_indices() {
+                    if units >= character || c == '\r' {
+                        return offset + index;
+                    }
+                    units += c.len_utf16();
+                }
+
+                return offset + text.len();
+            }
+
+            offset += text.len() + 1;
+        }
+
+        self.text.len()
+    }
+
+    /// Convert an LSP position to a position in the document
+    pub fn position(&self, position: &Json) -> Position {
+        let offset = self.byte_offset(position);
+        Position::start().after(&self.text[..offset])
+    }
+
+    /// Convert a position in the document to an LSP position
+    pub fn lsp_position(&self, position: Position) -> Json {
+        let text = self
+            .text
+            .split('\n')
+            .nth(position.line.saturating_sub(1) as usize)
+            .unwrap_or("");
+
+        Json::object([
+            ("line", position.line.saturating_sub(1).into()),
+            (
+                "character",
+                text.chars()
+                    .take(position.column.saturating_sub(1) as usize)
+                    .map(char::len_utf16)
+                    .sum::<usize>()
+                    .into(),
+            ),
+        ])
+    }
+
+    /// Convert a span in the document to an LSP range
+    pub fn lsp_range(&self, start: Position, end: Position) -> Json {
+        Json::object([
+            ("start", self.lsp_position(start)),
+            ("end", self.lsp_position(end)),
+        ])
+    }
+}
+
+fn parse(text: &str, catcodes: Catcodes) -> Result<ast3::Document, crate::Error> {
+    ast3::Document::parse_with_catcodes(text, ParseLimits::default(), catcodes)
+}
+
+// the text as the parser reads it, with comments taken out and the line breaks ending them
+// kept
+fn uncommented(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut escaped = false;
+    let mut comment = false;
+
+    for c in text.chars() {
+        match c {
+            '\n' => comment = false,
+            _ if comment => continue,
+            '%' if !escaped => {
+                comment = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        escaped = c == '\\' && !escaped;
+        out.push(c);
+    }
+
+    out
+}
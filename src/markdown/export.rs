@@ -0,0 +1,897 @@
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Conditions, MathsType, Scope, ScopeVariant},
+    completion::{CITE_COMMANDS, REF_COMMANDS},
+    format::{ALIGNED, MATHS, VERBATIM},
+    html::{curly, items, keys, source, split_verb, url},
+    plaintext::NON_PROSE,
+    sectioning::{self, starred},
+    Diagnostic, Label, Position,
+};
+
+/// Commands which are dropped without a warning
+pub const IGNORED: [&str; 20] = [
+    "label",
+    "centering",
+    "raggedright",
+    "raggedleft",
+    "noindent",
+    "indent",
+    "newpage",
+    "clearpage",
+    "cleardoublepage",
+    "pagebreak",
+    "smallskip",
+    "medskip",
+    "bigskip",
+    "vspace",
+    "hspace",
+    "vfill",
+    "hfill",
+    "protect",
+    "phantomsection",
+    "nocite",
+];
+
+/// Commands drawing rules in tables
+const RULES: [&str; 8] = [
+    "hline",
+    "cline",
+    "toprule",
+    "midrule",
+    "bottomrule",
+    "cmidrule",
+    "addlinespace",
+    "hdashline",
+];
+
+/// Exports documents as Markdown
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Exporter {
+    ignored: Vec<String>,
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Self {
+            ignored: IGNORED.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl Exporter {
+    /// Create an exporter ignoring the default commands
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop a command without a warning
+    pub fn with_ignored(mut self, command: &str) -> Self {
+        self.ignored.push(command.to_string());
+        self
+    }
+
+    /// Export the body of a document, or the preamble if there is no body
+    pub fn export(&self, document: &ast3::Document) -> Markdown {
+        let book = document.documentclass().is_some_and(|class| {
+            ["book", "report", "memoir", "scrbook", "scrreprt"].contains(&class.as_str())
+        });
+
+        let conditions = Conditions::from_document(document);
+
+        let mut state = State {
+            exporter: self,
+            conditions: &conditions,
+            position: Position::start(),
+            top: if book { 1 } else { 2 },
+            alt: None,
+            footnotes: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        let mut blocks = match document.has_body() {
+            true => {
+                let mut start = Position::start();
+                document
+                    .chunks_preamable()
+                    .iter()
+                    .for_each(|chunk| start.advance_str(&chunk.to_string()));
+                start.advance_str(&format!(
+                    "\\begin{}{{document}}",
+                    document.body_begin_prec()
+                ));
+                document
+                    .body_args()
+                    .iter()
+                    .for_each(|(prec, arg)| start.advance_str(&format!("{prec}{arg}")));
+
+                state.blocks(document.chunks_body(), start)
+            }
+            false => state.blocks(document.chunks_preamable(), Position::start()),
+        };
+
+        if !state.footnotes.is_empty() {
+            blocks.push(
+                state
+                    .footnotes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, note)| format!("[^{}]: {}", i + 1, indent(note, 4)))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        }
+
+        let mut text = blocks.join("\n\n");
+        if !text.is_empty() {
+            text.push('\n')
+        }
+
+        Markdown {
+            text,
+            warnings: state.warnings,
+        }
+    }
+}
+
+/// A document exported as Markdown
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Markdown {
+    text: String,
+    warnings: Vec<Warning>,
+}
+
+impl Markdown {
+    /// Returns the Markdown
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    /// Returns what could not be converted, in document order
+    pub fn warnings(&self) -> &Vec<Warning> {
+        &self.warnings
+    }
+}
+
+/// Source which could not be converted to Markdown
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Warning {
+    message: String,
+    start: Position,
+    end: Position,
+}
+
+impl Warning {
+    /// Returns the message explaining what was not converted
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    /// Returns the position of the first character of the source
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Returns the position right after the source
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    /// Convert to a warning labelled at the start of the source
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::warning(self.message.clone()).with_label(Label::primary(
+            self.start.line,
+            Some(self.start.column),
+            "not converted to Markdown",
+        ))
+    }
+}
+
+// rendered output of a chunk
+enum Piece {
+    Inline(String),
+    Block(String),
+    Break,
+}
+
+struct State<'a> {
+    exporter: &'a Exporter,
+    conditions: &'a Conditions,
+    // position of the chunk being exported
+    position: Position,
+    top: usize,
+    // caption of the current figure
+    alt: Option<String>,
+    footnotes: Vec<String>,
+    warnings: Vec<Warning>,
+}
+
+impl State<'_> {
+    fn warn(&mut self, message: String, start: Position, source: &str) {
+        self.warnings.push(Warning {
+            message,
+            start,
+            end: start.after(source),
+        })
+    }
+
+    fn blocks(&mut self, chunks: &[Chunk], start: Position) -> Vec<String> {
+        self.position = start;
+
+        let mut blocks = Vec::new();
+        let mut paragraph = String::new();
+        let mut i = 0;
+
+        while i < chunks.len() {
+            match self.piece(chunks, &mut i) {
+                Piece::Inline(s) => paragraph.push_str(&s),
+                Piece::Block(s) => {
+                    blocks.extend(flush(&mut paragraph));
+                    if !s.is_empty() {
+                        blocks.push(s)
+                    }
+                }
+                Piece::Break => blocks.extend(flush(&mut paragraph)),
+            }
+        }
+
+        blocks.extend(flush(&mut paragraph));
+        blocks
+    }
+
+    fn inline(&mut self, chunks: &[Chunk], start: Position) -> String {
+        self.position = start;
+
+        let mut out = String::new();
+        let mut i = 0;
+
+        while i < chunks.len() {
+            match self.piece(chunks, &mut i) {
+                Piece::Inline(s) => out.push_str(&s),
+                Piece::Block(s) => {
+                    out.push('\n');
+                    out.push_str(&s);
+                    out.push('\n');
+                }
+                Piece::Break => out.push(' '),
+            }
+        }
+
+        out
+    }
+
+    fn piece(&mut self, chunks: &[Chunk], i: &mut usize) -> Piece {
+        let start = self.position;
+        let first = *i;
+        let chunk = &chunks[*i];
+        *i += 1;
+
+        let piece = match chunk.variant() {
+            ChunkVariant::Text(s) => Piece::Inline(text(s)),
+            ChunkVariant::Parameter(parameter) => Piece::Inline(text(&parameter.to_string())),
+            ChunkVariant::MacroDefinition(definition) => {
+                self.warn(
+                    format!("unsupported macro definition `\\{}`", definition.label()),
+                    start,
+                    &definition.to_string(),
+                );
+                Piece::Inline(String::new())
+            }
+            ChunkVariant::ParagraphBreak(_) => Piece::Break,
+            ChunkVariant::MathsBlock(block) => {
+                let content = source(block.content());
+
+                Piece::Inline(match block.r#type() {
+                    MathsType::Inline => format!("${}$", content.trim()),
+                    MathsType::Outline => format!("\n$$\n{}\n$$\n", content.trim()),
+                })
+            }
+            ChunkVariant::Scope(scope) => {
+                let content = self.inline(scope.chunks(), start.after("{"));
+
+                Piece::Inline(match scope.variant() {
+                    ScopeVariant::Curly => content,
+                    ScopeVariant::Round => format!("({content})"),
+                    ScopeVariant::Square => format!("\\[{content}\\]"),
+                })
+            }
+            ChunkVariant::Command(command) => self.command(command, chunks, i, start),
+            ChunkVariant::Environment(env) => Piece::Block(self.environment(env, start)),
+            ChunkVariant::Conditional(conditional) => {
+                let branch = self.conditions.evaluate_or_content(conditional);
+                let branch_start = conditional
+                    .branches(start)
+                    .into_iter()
+                    .find(|(_, other)| std::ptr::eq(other.as_slice(), branch))
+                    .map_or(start, |(position, _)| position);
+
+                if branch
+                    .iter()
+                    .any(|chunk| matches!(chunk.variant(), ChunkVariant::ParagraphBreak(_)))
+                {
+                    Piece::Block(self.blocks(branch, branch_start).join("\n\n"))
+                } else {
+                    Piece::Inline(self.inline(branch, branch_start))
+                }
+            }
+        };
+
+        self.position = chunks[first..*i]
+            .iter()
+            .fold(start, |position, chunk| position.after(&chunk.to_string()));
+
+        piece
+    }
+
+    // the content of the `n`th argument, exported inline
+    fn argument(&mut self, command: &ast3::Command, start: Position, n: usize) -> String {
+        let mut position = start.after(&format!("\\{}", command.label()));
+
+        for (prec, arg) in &command.arguments()[..n] {
+            position.advance_str(&format!("{prec}{arg}"));
+        }
+
+        match command.arguments().get(n) {
+            Some((prec, arg)) => self.inline(arg.chunks(), position.after(prec).after("{")),
+            None => String::new(),
+        }
+    }
+
+    // the content of the last argument of a variant
+    fn last(&mut self, command: &ast3::Command, start: Position) -> String {
+        match last_of(command, ScopeVariant::Curly) {
+            Some(n) => self.argument(command, start, n),
+            None => String::new(),
+        }
+    }
+
+    fn command(
+        &mut self,
+        command: &ast3::Command,
+        chunks: &[Chunk],
+        i: &mut usize,
+        start: Position,
+    ) -> Piece {
+        let label = command.label().as_str();
+
+        if let Some(level) = sectioning::level(label) {
+            let title = match last_of(command, ScopeVariant::Curly) {
+                Some(n) => self.argument(command, start, n),
+                None => match starred(chunks, *i) {
+                    Some(title) => {
+                        *i += 2;
+                        let position = chunks[*i - 2..*i - 1]
+                            .iter()
+                            .fold(start.after(&command.to_string()), |position, chunk| {
+                                position.after(&chunk.to_string())
+                            });
+                        self.inline(title.chunks(), position.after("{"))
+                    }
+                    None => return Piece::Inline(String::new()),
+                },
+            };
+
+            let depth = match level {
+                0 => 1,
+                level => (level.max(self.top) - self.top + 1).min(6),
+            };
+
+            return Piece::Block(format!("{} {}", "#".repeat(depth), single_line(&title)));
+        }
+
+        let inline = match label {
+            "emph" | "textit" | "textsl" => format!("*{}*", self.last(command, start)),
+            "textbf" => format!("**{}**", self.last(command, start)),
+            "texttt" => code(&plain(&self.last(command, start))),
+            "textrm" | "textsf" | "textup" | "textmd" | "textnormal" | "textsc" | "mbox"
+            | "hbox" | "text" | "underline" | "uline" => self.last(command, start),
+            "enquote" => format!("\u{201c}{}\u{201d}", self.last(command, start)),
+            "footnote" => {
+                let note = self.last(command, start);
+                self.footnotes.push(single_line(&note));
+                format!("[^{}]", self.footnotes.len())
+            }
+            _ if CITE_COMMANDS.contains(&label) => {
+                let keys = keys(command)
+                    .iter()
+                    .map(|key| format!("@{key}"))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+
+                let note = match last_of(command, ScopeVariant::Square) {
+                    Some(n) => format!(", {}", self.argument(command, start, n)),
+                    None => String::new(),
+                };
+
+                format!("[{keys}{note}]")
+            }
+            _ if REF_COMMANDS.contains(&label) => {
+                let source = command.to_string();
+                self.warn(
+                    format!("reference `{source}` has no Markdown equivalent"),
+                    start,
+                    &source,
+                );
+                source
+            }
+            "url" => format!("<{}>", url(command, 0)),
+            "href" => {
+                let text = match last_of(command, ScopeVariant::Curly) {
+                    Some(n) if n > 0 => self.argument(command, start, n),
+                    _ => String::new(),
+                };
+                format!("[{}]({})", text, destination(&url(command, 0)))
+            }
+            "includegraphics" => format!(
+                "![{}]({})",
+                self.alt.clone().unwrap_or_default(),
+                destination(&url(command, 0))
+            ),
+            "caption" => {
+                return Piece::Block(format!("*{}*", single_line(&self.last(command, start))))
+            }
+            "par" => return Piece::Break,
+            "verb" if command.arguments().is_empty() => match chunks.get(*i).map(Chunk::variant) {
+                Some(ChunkVariant::Text(s)) => {
+                    *i += 1;
+                    let (verb, rest) = split_verb(s);
+                    format!("{}{}", code(verb), text(rest))
+                }
+                _ => String::new(),
+            },
+            "item" | "bibitem" | "-" | "@" | "/" => String::new(),
+            "\\" | "newline" => "\\\n".to_string(),
+            " " | ";" | ":" | "," => " ".to_string(),
+            "LaTeX" | "TeX" => label.to_string(),
+            "LaTeXe" => "LaTeX2\u{3b5}".to_string(),
+            "ldots" | "dots" | "textellipsis" => "\u{2026}".to_string(),
+            "textendash" => "\u{2013}".to_string(),
+            "textemdash" => "\u{2014}".to_string(),
+            "textbackslash" => "\\\\".to_string(),
+            "_" | "#" | "$" => format!("\\{label}"),
+            "%" | "&" | "{" | "}" => label.to_string(),
+            _ if self.exporter.ignored.contains(command.label()) => String::new(),
+            _ => {
+                self.warn(
+                    format!("unsupported command `\\{label}`"),
+                    start,
+                    &command.to_string(),
+                );
+
+                (0..command.arguments().len())
+                    .filter(|n| command.arguments()[*n].1.variant() == ScopeVariant::Curly)
+                    .map(|n| self.argument(command, start, n))
+                    .collect()
+            }
+        };
+
+        Piece::Inline(inline)
+    }
+
+    fn environment(&mut self, env: &ast3::Environment, start: Position) -> String {
+        let label = env.label().as_str();
+        let content = env_content(env, start);
+
+        match label {
+            "itemize" => self.list(env, content, false),
+            "enumerate" => self.list(env, content, true),
+            "description" => self.description(env, content),
+            "thebibliography" => {
+                let mut out = Vec::new();
+                for (i, _, chunks) in items(env.content(), "bibitem") {
+                    let position = position_of(env.content(), i + 1, content);
+                    let text = self.blocks(chunks, position).join("\n\n");
+                    out.push(format!("1. {}", indent(&text, 3)))
+                }
+                out.join("\n")
+            }
+            "figure" | "figure*" => {
+                let alt = env
+                    .content()
+                    .iter()
+                    .find_map(|chunk| match chunk.variant() {
+                        ChunkVariant::Command(c) if c.label() == "caption" => curly(c)
+                            .last()
+                            .map(|arg| single_line(&plain(&source(arg.chunks())))),
+                        _ => None,
+                    });
+
+                let outer = std::mem::replace(&mut self.alt, alt);
+                let out = self.blocks(env.content(), content).join("\n\n");
+                self.alt = outer;
+                out
+            }
+            "table" | "table*" | "center" | "flushleft" | "flushright" | "abstract"
+            | "minipage" => self.blocks(env.content(), content).join("\n\n"),
+            "quote" | "quotation" => self
+                .blocks(env.content(), content)
+                .join("\n\n")
+                .lines()
+                .map(|line| match line {
+                    "" => ">".to_string(),
+                    line => format!("> {line}"),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "tabular" | "tabular*" | "tabularx" | "tabulary" | "longtable" => {
+                match self.table(env.content(), content) {
+                    Some(table) => table,
+                    None => {
+                        let source = env.to_string();
+                        self.warn(
+                            format!("`{label}` is not simple enough for a pipe table"),
+                            start,
+                            &source,
+                        );
+                        fence(&source, "latex")
+                    }
+                }
+            }
+            "comment" => String::new(),
+            _ if VERBATIM.contains(&label) && !NON_PROSE.contains(&label) => {
+                let language = match label {
+                    "minted" => curly_text(env.arguments()),
+                    "lstlisting" => env
+                        .arguments()
+                        .iter()
+                        .find(|(_, arg)| arg.variant() == ScopeVariant::Square)
+                        .and_then(|(_, arg)| {
+                            source(arg.chunks()).split(',').find_map(|option| {
+                                option
+                                    .split_once('=')
+                                    .filter(|(key, _)| key.trim() == "language")
+                                    .map(|(_, value)| value.trim().to_lowercase())
+                            })
+                        })
+                        .unwrap_or_default(),
+                    _ => String::new(),
+                };
+
+                let code = source(env.content());
+                let code = code.strip_prefix('\n').unwrap_or(&code);
+                let code = code.trim_end_matches([' ', '\t']).trim_end_matches('\n');
+                fence(code, &language)
+            }
+            "math" => format!("${}$", source(env.content()).trim()),
+            _ if MATHS.contains(&label) => {
+                let maths = source(env.content());
+                let maths = match label {
+                    "gather" | "gather*" => format!("\\begin{{gathered}}{maths}\\end{{gathered}}"),
+                    _ => maths,
+                };
+                format!("$$\n{}\n$$", maths.trim())
+            }
+            _ if ALIGNED[6..].contains(&label) => {
+                let maths = match label.trim_end_matches('*') {
+                    "align" | "flalign" | "eqnarray" => {
+                        format!(
+                            "\\begin{{aligned}}{}\\end{{aligned}}",
+                            source(env.content())
+                        )
+                    }
+                    "alignat" => format!(
+                        "\\begin{{alignedat}}{}{}\\end{{alignedat}}",
+                        env.arguments()
+                            .iter()
+                            .map(|(prec, arg)| format!("{prec}{arg}"))
+                            .collect::<String>(),
+                        source(env.content())
+                    ),
+                    _ => env.to_string(),
+                };
+                format!("$$\n{}\n$$", maths.trim())
+            }
+            _ => {
+                let source = env.to_string();
+                self.warn(format!("unsupported environment `{label}`"), start, &source);
+
+                match NON_PROSE.contains(&label) {
+                    true => String::new(),
+                    false => self.blocks(env.content(), content).join("\n\n"),
+                }
+            }
+        }
+    }
+
+    fn list(&mut self, env: &ast3::Environment, start: Position, ordered: bool) -> String {
+        let mut out = Vec::new();
+
+        for (n, (i, _, chunks)) in items(env.content(), "item").into_iter().enumerate() {
+            let position = position_of(env.content(), i + 1, start);
+            let text = self.blocks(chunks, position).join("\n\n");
+            let marker = match ordered {
+                true => format!("{}. ", n + 1),
+                false => "- ".to_string(),
+            };
+
+            out.push(format!("{marker}{}", indent(&text, marker.len())))
+        }
+
+        out.join("\n")
+    }
+
+    fn description(&mut self, env: &ast3::Environment, start: Position) -> String {
+        let mut out = Vec::new();
+
+        for (i, command, chunks) in items(env.content(), "item") {
+            let term = match last_of(command, ScopeVariant::Square) {
+                Some(n) => self.argument(command, position_of(env.content(), i, start), n),
+                None => String::new(),
+            };
+            let position = position_of(env.content(), i + 1, start);
+
+            let text = self.blocks(chunks, position).join("\n\n");
+            out.push(format!("- **{}** {}", term.trim(), indent(&text, 2)))
+        }
+
+        out.join("\n")
+    }
+
+    // a pipe table, or `None` if the table is not simple enough
+    fn table(&mut self, content: &[Chunk], start: Position) -> Option<String> {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut row = Vec::new();
+        let mut cell = String::new();
+        let mut i = 0;
+
+        self.position = start;
+
+        while let Some(chunk) = content.get(i) {
+            match chunk.variant() {
+                ChunkVariant::Text(s) => {
+                    for (n, part) in s.split('&').enumerate() {
+                        if n > 0 {
+                            row.push(std::mem::take(&mut cell))
+                        }
+                        cell.push_str(&text(part))
+                    }
+                }
+                ChunkVariant::Command(c) if c.label() == "\\" || c.label() == "tabularnewline" => {
+                    row.push(std::mem::take(&mut cell));
+                    rows.push(std::mem::take(&mut row));
+
+                    // `\\*` and `\\[2pt]`
+                    if let Some(ChunkVariant::Text(s)) = content.get(i + 1).map(Chunk::variant) {
+                        if s.trim() == "*" {
+                            self.position.advance_str(&chunk.to_string());
+                            i += 1;
+                        }
+                    }
+                    if let Some(ChunkVariant::Scope(sc)) = content.get(i + 1).map(Chunk::variant) {
+                        if sc.variant() == ScopeVariant::Square {
+                            self.position.advance_str(&content[i].to_string());
+                            i += 1;
+                        }
+                    }
+                }
+                ChunkVariant::Command(c) if RULES.contains(&c.label().as_str()) => {}
+                ChunkVariant::Command(c)
+                    if c.label() == "multicolumn" || c.label() == "multirow" =>
+                {
+                    return None
+                }
+                ChunkVariant::ParagraphBreak(_) | ChunkVariant::Environment(_) => return None,
+                _ => {
+                    let mut j = i;
+                    match self.piece(content, &mut j) {
+                        Piece::Inline(s) => cell.push_str(&s),
+                        Piece::Block(_) | Piece::Break => return None,
+                    }
+                    i = j;
+                    continue;
+                }
+            }
+
+            self.position.advance_str(&content[i].to_string());
+            i += 1;
+        }
+
+        if !cell.trim().is_empty() || !row.is_empty() {
+            row.push(cell);
+            rows.push(row);
+        }
+
+        rows.retain(|row| row.iter().any(|cell| !cell.trim().is_empty()));
+
+        let columns = rows.iter().map(Vec::len).max()?;
+        let mut out = Vec::new();
+
+        for (n, row) in rows.iter().enumerate() {
+            let cells = (0..columns)
+                .map(|column| {
+                    let cell = row
+                        .get(column)
+                        .map(|cell| single_line(cell))
+                        .unwrap_or_default();
+                    pipes(&cell)
+                })
+                .collect::<Vec<_>>();
+
+            out.push(format!("| {} |", cells.join(" | ")));
+
+            if n == 0 {
+                out.push(format!("|{}", " --- |".repeat(columns)));
+            }
+        }
+
+        Some(out.join("\n"))
+    }
+}
+
+// the paragraph so far, with lines trimmed so they are not read as code blocks
+fn flush(paragraph: &mut String) -> Option<String> {
+    // lines of display maths are kept as they are
+    let mut maths = false;
+
+    let text = std::mem::take(paragraph)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let out = match maths {
+                true => line.to_string(),
+                false => line_start(line),
+            };
+            maths ^= line == "$$";
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (!text.is_empty()).then_some(text)
+}
+
+// a line with a marker at its start escaped, so it is not read as a heading, list item or rule
+fn line_start(line: &str) -> String {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = match line[digits..].chars().next() {
+        Some('.' | ')') if digits > 0 => Some(digits),
+        Some('#' | '-' | '+' | '=') if digits == 0 => Some(0),
+        _ => None,
+    };
+
+    match marker {
+        Some(n) => format!("{}\\{}", &line[..n], &line[n..]),
+        None => line.to_string(),
+    }
+}
+
+// a table cell with `|` escaped, those already escaped by `text` are kept
+fn pipes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                out.extend(chars.next());
+            }
+            '|' => out.push_str("\\|"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+// a link destination, in angle brackets when it has brackets or whitespace, which would end it
+fn destination(url: &str) -> String {
+    let url = url.replace('<', "%3C").replace('>', "%3E");
+
+    match url.contains(|c: char| c.is_whitespace() || matches!(c, '(' | ')')) {
+        true => format!("<{url}>"),
+        false => url,
+    }
+}
+
+// prose, with TeX ligatures and Markdown characters escaped
+fn text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("``", "\u{201c}")
+        .replace("''", "\u{201d}")
+        .replace('~', "\u{a0}")
+        .chars()
+    {
+        if matches!(c, '*' | '`' | '<' | '>' | '[' | ']' | '|') {
+            out.push('\\')
+        }
+        out.push(c)
+    }
+
+    out
+}
+
+// exported text without Markdown escapes
+fn plain(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn single_line(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn code(s: &str) -> String {
+    let ticks = "`".repeat(longest_run(s, '`') + 1);
+    let padding = match s.starts_with('`') || s.ends_with('`') {
+        true => " ",
+        false => "",
+    };
+
+    format!("{ticks}{padding}{s}{padding}{ticks}")
+}
+
+fn fence(code: &str, language: &str) -> String {
+    let ticks = "`".repeat((longest_run(code, '`') + 1).max(3));
+    format!("{ticks}{language}\n{code}\n{ticks}")
+}
+
+fn longest_run(s: &str, c: char) -> usize {
+    s.split(|other| other != c).map(str::len).max().unwrap_or(0)
+}
+
+// indent every line but the first
+fn indent(s: &str, width: usize) -> String {
+    let padding = " ".repeat(width);
+
+    s.lines()
+        .enumerate()
+        .map(|(i, line)| match (i, line.is_empty()) {
+            (0, _) | (_, true) => line.to_string(),
+            _ => format!("{padding}{line}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn curly_text(arguments: &[(String, Scope)]) -> String {
+    arguments
+        .iter()
+        .find(|(_, arg)| arg.variant() == ScopeVariant::Curly)
+        .map(|(_, arg)| source(arg.chunks()).trim().to_string())
+        .unwrap_or_default()
+}
+
+// position of the content of an environment
+fn env_content(env: &ast3::Environment, start: Position) -> Position {
+    let mut position = start.after(&format!("\\begin{}{{{}}}", env.prec_begin(), env.label()));
+
+    env.arguments()
+        .iter()
+        .for_each(|(prec, arg)| position.advance_str(&format!("{prec}{arg}")));
+
+    position
+}
+
+// position of the `n`th chunk, given where the chunks start
+fn position_of(chunks: &[Chunk], n: usize, start: Position) -> Position {
+    chunks[..n]
+        .iter()
+        .fold(start, |position, chunk| position.after(&chunk.to_string()))
+}
+
+// index of the last argument of a variant
+fn last_of(command: &ast3::Command, variant: ScopeVariant) -> Option<usize> {
+    command
+        .arguments()
+        .iter()
+        .rposition(|(_, arg)| arg.variant() == variant)
+}
@@ -0,0 +1,1058 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3::{
+        self, Chunk, ChunkVariant, Chunks, DocumentBuilder, EscapeMode, MathsBlock, MathsType,
+        MathsVariant, Scope, ScopeVariant,
+    },
+    sectioning,
+    traits::Validate,
+};
+
+/// Imports Markdown as a LaTeX document
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Importer {
+    documentclass: String,
+}
+
+impl Default for Importer {
+    fn default() -> Self {
+        Self {
+            documentclass: "article".to_string(),
+        }
+    }
+}
+
+impl Importer {
+    /// Create an importer for an `article`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the document class
+    pub fn with_documentclass(mut self, class: &str) -> Self {
+        self.documentclass = class.to_string();
+        self
+    }
+
+    /// Import Markdown, under `\documentclass` and with the packages it needs
+    pub fn import(&self, markdown: &str) -> ast3::Document {
+        let lines = markdown
+            .lines()
+            .map(|line| line.replace('\t', "    "))
+            .collect::<Vec<_>>();
+        let blocks = blocks(&lines);
+
+        let chapters = ["book", "report", "memoir", "scrbook", "scrreprt"]
+            .contains(&self.documentclass.as_str());
+        let mut state = State {
+            headings: match chapters {
+                true => &sectioning::COMMANDS[1..],
+                false => &sectioning::COMMANDS[2..],
+            },
+            packages: Vec::new(),
+        };
+
+        let mut builder = DocumentBuilder::new(&self.documentclass);
+
+        for block in &blocks {
+            let mut chunks = Chunks::default();
+            state.block(&mut chunks, block, "");
+            builder = builder.block(chunks.finish());
+        }
+
+        for package in ["graphicx", "listings", "alltt", "hyperref"] {
+            if state.packages.contains(&package) {
+                builder = builder.usepackage(package);
+            }
+        }
+
+        builder.build()
+    }
+}
+
+// Markdown blocks
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    Code(String, String),
+    Maths(String),
+    Quote(Vec<Block>),
+    List {
+        ordered: bool,
+        tight: bool,
+        items: Vec<Vec<Block>>,
+    },
+    Table(Vec<char>, Vec<Vec<String>>),
+    Rule,
+}
+
+// splits lines into blocks
+fn blocks(lines: &[String]) -> Vec<Block> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = &lines[i];
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.is_empty() {
+            i += 1;
+        } else if indent >= 4 {
+            // indented code, up to the last indented line
+            let mut end = i;
+            let mut code = Vec::new();
+            while end < lines.len() && (lines[end].trim().is_empty() || indent_of(&lines[end]) >= 4)
+            {
+                code.push(lines[end].get(4..).unwrap_or(""));
+                end += 1;
+            }
+            while code.last().is_some_and(|line| line.trim().is_empty()) {
+                code.pop();
+                end -= 1;
+            }
+            out.push(Block::Code(String::new(), code.join("\n")));
+            i = end;
+        } else if let Some((fence, language)) = fence(trimmed) {
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !closes(&lines[i], &fence) {
+                code.push(strip_indent(&lines[i], indent));
+                i += 1;
+            }
+            out.push(Block::Code(language, code.join("\n")));
+            i += 1;
+        } else if let Some(rest) = trimmed.strip_prefix("$$") {
+            // display maths, on one line or up to a line ending with `$$`
+            let mut maths = Vec::new();
+            let mut rest = rest.to_string();
+            loop {
+                if let Some(content) = rest.trim_end().strip_suffix("$$") {
+                    maths.push(content.to_string());
+                    i += 1;
+                    break;
+                }
+                maths.push(rest);
+                i += 1;
+                match lines.get(i) {
+                    Some(line) => rest = line.clone(),
+                    None => break,
+                }
+            }
+            out.push(Block::Maths(maths.join("\n").trim().to_string()));
+        } else if let Some(heading) = heading(trimmed) {
+            out.push(heading);
+            i += 1;
+        } else if is_rule(trimmed) {
+            out.push(Block::Rule);
+            i += 1;
+        } else if trimmed.starts_with('>') {
+            let mut quoted = Vec::new();
+            while let Some(rest) = lines
+                .get(i)
+                .and_then(|line| line.trim_start().strip_prefix('>'))
+            {
+                quoted.push(rest.strip_prefix(' ').unwrap_or(rest).to_string());
+                i += 1;
+            }
+            out.push(Block::Quote(blocks(&quoted)));
+        } else if let Some(marker) = marker(line) {
+            let (list, end) = list(lines, i, marker);
+            out.push(list);
+            i = end;
+        } else if let Some(aligns) = lines.get(i + 1).and_then(|next| delimiter_row(next, line)) {
+            let mut rows = vec![cells(line)];
+            i += 2;
+            while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+                rows.push(cells(&lines[i]));
+                i += 1;
+            }
+            out.push(Block::Table(aligns, rows));
+        } else {
+            let mut paragraph = vec![trimmed.to_string()];
+            i += 1;
+
+            while let Some(line) = lines.get(i) {
+                let trimmed = line.trim();
+
+                // setext headings
+                if !trimmed.is_empty() && trimmed.chars().all(|c| c == '=') {
+                    out.push(Block::Heading(1, paragraph.join("\n")));
+                    paragraph.clear();
+                    i += 1;
+                    break;
+                }
+                if trimmed.len() >= 2 && trimmed.chars().all(|c| c == '-') {
+                    out.push(Block::Heading(2, paragraph.join("\n")));
+                    paragraph.clear();
+                    i += 1;
+                    break;
+                }
+
+                if trimmed.is_empty() || interrupts(line) {
+                    break;
+                }
+
+                paragraph.push(line.trim_start().to_string());
+                i += 1;
+            }
+
+            if !paragraph.is_empty() {
+                out.push(Block::Paragraph(
+                    paragraph.join("\n").trim_end().to_string(),
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+// a list starting at line `start`, and the line after it
+fn list(lines: &[String], start: usize, first: Marker) -> (Block, usize) {
+    let mut items = Vec::new();
+    let mut tight = true;
+    let mut i = start;
+
+    while let Some(current) = lines.get(i).and_then(|line| marker(line)) {
+        if current.ordered != first.ordered || current.indent != first.indent {
+            break;
+        }
+
+        let mut item = vec![lines[i][current.width..].to_string()];
+        let mut blank = false;
+        i += 1;
+
+        while let Some(line) = lines.get(i) {
+            if line.trim().is_empty() {
+                blank = true;
+                item.push(String::new());
+            } else if indent_of(line) < current.width && marker(line).is_some() {
+                break;
+            } else if indent_of(line) >= current.width {
+                if blank {
+                    tight = false;
+                }
+                blank = false;
+                item.push(line[current.width..].to_string());
+            } else if !blank && !interrupts(line) {
+                // lazy continuation of a paragraph
+                item.push(line.trim_start().to_string());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+
+        while item.last().is_some_and(String::is_empty) {
+            item.pop();
+        }
+
+        // a blank line between items makes a loose list
+        if blank && lines.get(i).and_then(|line| marker(line)).is_some() {
+            tight = false;
+        }
+
+        items.push(blocks(&item));
+    }
+
+    (
+        Block::List {
+            ordered: first.ordered,
+            tight,
+            items,
+        },
+        i,
+    )
+}
+
+#[derive(Clone, Copy)]
+struct Marker {
+    ordered: bool,
+    indent: usize,
+    // width of the indentation, marker and spacing
+    width: usize,
+}
+
+fn marker(line: &str) -> Option<Marker> {
+    let indent = indent_of(line);
+    if indent > 3 {
+        return None;
+    }
+
+    let rest = &line[indent..];
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+
+    let (ordered, len) = match rest.chars().next()? {
+        '-' | '*' | '+' => (false, 1),
+        _ if (1..10).contains(&digits)
+            && matches!(rest[digits..].chars().next(), Some('.' | ')')) =>
+        {
+            (true, digits + 1)
+        }
+        _ => return None,
+    };
+
+    let after = &rest[len..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+
+    match (spaces, after.trim().is_empty()) {
+        (0, false) => None,
+        (_, true) => Some(Marker {
+            ordered,
+            indent,
+            width: indent + len + 1,
+        }),
+        (1..=4, false) => Some(Marker {
+            ordered,
+            indent,
+            width: indent + len + spaces,
+        }),
+        _ => Some(Marker {
+            ordered,
+            indent,
+            width: indent + len + 1,
+        }),
+    }
+}
+
+// whether a line starts a block which ends a paragraph
+fn interrupts(line: &str) -> bool {
+    let trimmed = line.trim_start();
+
+    indent_of(line) < 4
+        && (heading(trimmed).is_some()
+            || fence(trimmed).is_some()
+            || trimmed.starts_with('>')
+            || trimmed.starts_with("$$")
+            || is_rule(trimmed)
+            || marker(line).is_some_and(|marker| !marker.ordered || trimmed.starts_with("1")))
+}
+
+fn heading(line: &str) -> Option<Block> {
+    let level = line.chars().take_while(|c| c == &'#').count();
+    let rest = &line[level..];
+
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+
+    let text = rest.trim();
+    let text = match text.trim_end_matches('#') {
+        stripped if stripped.is_empty() || stripped.ends_with(' ') => stripped.trim_end(),
+        _ => text,
+    };
+
+    Some(Block::Heading(level, text.to_string()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let chars = line
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+
+    chars.len() >= 3 && matches!(chars[0], '-' | '*' | '_') && chars.iter().all(|c| c == &chars[0])
+}
+
+// the fence of a fenced code block, and its language
+fn fence(line: &str) -> Option<(String, String)> {
+    let c = line.chars().next().filter(|c| c == &'`' || c == &'~')?;
+    let len = line.chars().take_while(|other| other == &c).count();
+    let info = line[len..].trim();
+
+    if len < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+
+    Some((
+        line[..len].to_string(),
+        info.split_whitespace().next().unwrap_or("").to_string(),
+    ))
+}
+
+fn closes(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim();
+    let c = fence.chars().next().unwrap_or('`');
+
+    indent_of(line) < 4 && trimmed.len() >= fence.len() && trimmed.chars().all(|other| other == c)
+}
+
+// the alignment of each column, if `line` is the delimiter row of a table
+fn delimiter_row(line: &str, header: &str) -> Option<Vec<char>> {
+    if !header.contains('|') {
+        return None;
+    }
+
+    let aligns = cells(line)
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => 'c',
+                (false, true) => 'r',
+                _ => 'l',
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    (aligns.len() == cells(header).len()).then_some(aligns)
+}
+
+// cells of a table row, split at unescaped pipes
+fn cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('|') => cells.last_mut().unwrap().push('|'),
+                Some(next) => {
+                    cells.last_mut().unwrap().push('\\');
+                    cells.last_mut().unwrap().push(next);
+                }
+                None => cells.last_mut().unwrap().push('\\'),
+            },
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn strip_indent(line: &str, indent: usize) -> &str {
+    &line[indent_of(line).min(indent)..]
+}
+
+// builds the LaTeX for blocks
+struct State {
+    headings: &'static [&'static str],
+    packages: Vec<&'static str>,
+}
+
+impl State {
+    fn uses(&mut self, package: &'static str) {
+        if !self.packages.contains(&package) {
+            self.packages.push(package)
+        }
+    }
+
+    fn blocks(&mut self, out: &mut Chunks, blocks: &[Block], indent: &str, tight: bool) {
+        for (n, block) in blocks.iter().enumerate() {
+            if n > 0 {
+                match tight {
+                    true => out.text(&format!("\n{indent}")),
+                    false => out.paragraph_break(indent),
+                }
+            }
+
+            self.block(out, block, indent)
+        }
+    }
+
+    fn block(&mut self, out: &mut Chunks, block: &Block, indent: &str) {
+        let inner = format!("{indent}  ");
+
+        match block {
+            Block::Heading(level, text) => {
+                let label = self.headings[(level - 1).min(self.headings.len() - 1)];
+                let title = self.inline(text, indent);
+                out.command(label, vec![title]);
+            }
+            Block::Paragraph(text) => match image(text) {
+                Some((alt, src)) => {
+                    self.uses("graphicx");
+
+                    let mut content = Chunks::default();
+                    content.text(&format!("\n{inner}"));
+                    content.command("centering", Vec::new());
+                    content.text(&format!("\n{inner}"));
+                    content.command(
+                        "includegraphics",
+                        vec![Chunks::escaped(&src, EscapeMode::Url)],
+                    );
+                    if !alt.is_empty() {
+                        content.text(&format!("\n{inner}"));
+                        content.command("caption", vec![self.inline(&alt, &inner)]);
+                    }
+                    content.text(&format!("\n{indent}"));
+
+                    out.environment("figure", Vec::new(), content);
+                }
+                None => out.extend(self.inline(text, indent)),
+            },
+            Block::Code(language, code) => {
+                let source = format!("\n{code}\n");
+
+                match verbatim(&source) {
+                    Some(content) if language.is_empty() => {
+                        out.environment("verbatim", Vec::new(), content)
+                    }
+                    Some(content) => {
+                        self.uses("listings");
+
+                        let mut option = Chunks::default();
+                        option.text(&format!("language={language}"));
+                        out.environment(
+                            "lstlisting",
+                            vec![(
+                                "".to_string(),
+                                Scope::new_unchecked(option.finish(), ScopeVariant::Square),
+                            )],
+                            content,
+                        )
+                    }
+                    None => {
+                        self.uses("alltt");
+                        out.environment(
+                            "alltt",
+                            Vec::new(),
+                            Chunks::escaped(&source, EscapeMode::Verbatim),
+                        )
+                    }
+                }
+            }
+            Block::Maths(maths) => {
+                let source = format!(
+                    "\n{}\n{indent}",
+                    maths
+                        .lines()
+                        .map(|line| format!("{inner}{}", line.trim()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                );
+
+                match parse(&source) {
+                    Some(content) => out.push(ChunkVariant::MathsBlock(MathsBlock::new_unchecked(
+                        MathsVariant::Brackets,
+                        MathsType::Outline,
+                        content,
+                    ))),
+                    None => out.extend(Chunks::escaped(&format!("$${maths}$$"), EscapeMode::Prose)),
+                }
+            }
+            Block::Quote(blocks) => {
+                let mut content = Chunks::default();
+                content.text(&format!("\n{inner}"));
+                self.blocks(&mut content, blocks, &inner, false);
+                content.text(&format!("\n{indent}"));
+                out.environment("quote", Vec::new(), content);
+            }
+            Block::List {
+                ordered,
+                tight,
+                items,
+            } => {
+                let item_indent = format!("{inner}  ");
+                let mut content = Chunks::default();
+
+                for item in items {
+                    content.text(&format!("\n{inner}"));
+                    content.command("item", Vec::new());
+                    content.text(" ");
+                    self.blocks(&mut content, item, &item_indent, *tight);
+                }
+                content.text(&format!("\n{indent}"));
+
+                let label = match ordered {
+                    true => "enumerate",
+                    false => "itemize",
+                };
+                out.environment(label, Vec::new(), content);
+            }
+            Block::Table(aligns, rows) => {
+                let mut content = Chunks::default();
+
+                for (n, row) in rows.iter().enumerate() {
+                    content.text(&format!("\n{inner}"));
+
+                    for column in 0..aligns.len() {
+                        if column > 0 {
+                            content.text(" & ");
+                        }
+                        let cell = row.get(column).map(String::as_str).unwrap_or("");
+                        content.extend(self.inline(cell, &inner));
+                    }
+
+                    content.text(" ");
+                    content.command("\\", Vec::new());
+
+                    if n == 0 {
+                        content.text(&format!("\n{inner}"));
+                        content.command("hline", Vec::new());
+                    }
+                }
+                content.text(&format!("\n{indent}"));
+
+                out.environment(
+                    "tabular",
+                    vec![(
+                        String::new(),
+                        Scope::new_unchecked(
+                            Chunks::from_text(&aligns.iter().collect::<String>()).finish(),
+                            ScopeVariant::Curly,
+                        ),
+                    )],
+                    content,
+                );
+            }
+            Block::Rule => {
+                out.command("noindent", Vec::new());
+
+                let mut width = Chunks::default();
+                width.command("linewidth", Vec::new());
+                out.command("rule", vec![width, Chunks::from_text("0.4pt")]);
+            }
+        }
+    }
+
+    // inline Markdown, with line breaks followed by `indent`
+    fn inline(&mut self, s: &str, indent: &str) -> Chunks {
+        let chars = s.chars().collect::<Vec<_>>();
+        let mut out = Chunks::default();
+        let mut literal = String::new();
+        let mut i = 0;
+
+        macro_rules! flush {
+            () => {
+                out.extend(Chunks::escaped(
+                    &std::mem::take(&mut literal),
+                    EscapeMode::Prose,
+                ))
+            };
+        }
+
+        while let Some(&c) = chars.get(i) {
+            match c {
+                '\\' if chars.get(i + 1).is_some_and(char::is_ascii_punctuation) => {
+                    literal.push(chars[i + 1]);
+                    i += 2;
+                }
+                '\\' if chars.get(i + 1) == Some(&'\n') => {
+                    flush!();
+                    out.hard_break(indent);
+                    i += 2;
+                }
+                '\n' => {
+                    if literal.ends_with("  ") {
+                        literal.truncate(literal.trim_end().len());
+                        flush!();
+                        out.hard_break(indent);
+                    } else {
+                        literal.truncate(literal.trim_end_matches(' ').len());
+                        literal.push('\n');
+                        literal.push_str(indent);
+                    }
+                    i += 1;
+                }
+                '`' => {
+                    let run = run_length(&chars, i);
+
+                    match find_run(&chars, i + run, '`', run) {
+                        Some(end) => {
+                            flush!();
+                            let code = chars[i + run..end]
+                                .iter()
+                                .collect::<String>()
+                                .replace('\n', " ");
+                            let code = match code.starts_with(' ')
+                                && code.ends_with(' ')
+                                && code.trim() != ""
+                            {
+                                true => &code[1..code.len() - 1],
+                                false => &code,
+                            };
+                            out.command("texttt", vec![Chunks::escaped(code, EscapeMode::Text)]);
+                            i = end + run;
+                        }
+                        None => {
+                            literal.extend(&chars[i..i + run]);
+                            i += run;
+                        }
+                    }
+                }
+                '!' if chars.get(i + 1) == Some(&'[') => match link(&chars, i + 1) {
+                    Some((_, src, end)) => {
+                        flush!();
+                        self.uses("graphicx");
+                        out.command(
+                            "includegraphics",
+                            vec![Chunks::escaped(&src, EscapeMode::Url)],
+                        );
+                        i = end;
+                    }
+                    None => {
+                        literal.push(c);
+                        i += 1;
+                    }
+                },
+                '[' => match link(&chars, i) {
+                    Some((text, url, end)) => {
+                        flush!();
+                        self.uses("hyperref");
+                        match text == url {
+                            true => {
+                                out.command("url", vec![Chunks::escaped(&url, EscapeMode::Url)])
+                            }
+                            false => {
+                                let text = self.inline(&text, indent);
+                                out.command(
+                                    "href",
+                                    vec![Chunks::escaped(&url, EscapeMode::Url), text],
+                                )
+                            }
+                        }
+                        i = end;
+                    }
+                    None => {
+                        literal.push(c);
+                        i += 1;
+                    }
+                },
+                '<' => match autolink(&chars, i) {
+                    Some((url, end)) => {
+                        flush!();
+                        self.uses("hyperref");
+                        match url.contains(':') {
+                            true => {
+                                out.command("url", vec![Chunks::escaped(&url, EscapeMode::Url)])
+                            }
+                            false => out.command(
+                                "href",
+                                vec![
+                                    Chunks::escaped(&format!("mailto:{url}"), EscapeMode::Url),
+                                    Chunks::escaped(&url, EscapeMode::Prose),
+                                ],
+                            ),
+                        }
+                        i = end;
+                    }
+                    None => {
+                        literal.push(c);
+                        i += 1;
+                    }
+                },
+                '$' => match maths(&chars, i) {
+                    Some((content, r#type, end)) => {
+                        flush!();
+                        let variant = match r#type {
+                            MathsType::Inline => MathsVariant::Dollars,
+                            MathsType::Outline => MathsVariant::Brackets,
+                        };
+                        out.push(ChunkVariant::MathsBlock(MathsBlock::new_unchecked(
+                            variant, r#type, content,
+                        )));
+                        i = end;
+                    }
+                    None => {
+                        literal.push(c);
+                        i += 1;
+                    }
+                },
+                '*' | '_' => {
+                    let run = run_length(&chars, i);
+
+                    match emphasis(&chars, i, run) {
+                        Some(end) => {
+                            flush!();
+                            let content = chars[i + run..end].iter().collect::<String>();
+                            let content = self.inline(&content, indent);
+
+                            match run {
+                                1 => out.command("emph", vec![content]),
+                                2 => out.command("textbf", vec![content]),
+                                _ => {
+                                    let mut emph = Chunks::default();
+                                    emph.command("emph", vec![content]);
+                                    out.command("textbf", vec![emph])
+                                }
+                            }
+                            i = end + run;
+                        }
+                        None => {
+                            literal.extend(&chars[i..i + run]);
+                            i += run;
+                        }
+                    }
+                }
+                '&' => match entity(&chars, i) {
+                    Some(('\u{a0}', end)) => {
+                        flush!();
+                        out.text("~");
+                        i = end;
+                    }
+                    Some((c, end)) => {
+                        literal.push(c);
+                        i = end;
+                    }
+                    None => {
+                        literal.push(c);
+                        i += 1;
+                    }
+                },
+                c => {
+                    literal.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        flush!();
+        out
+    }
+}
+
+// a paragraph which is only an image, as alt text and source
+fn image(text: &str) -> Option<(String, String)> {
+    let chars = text.trim().chars().collect::<Vec<_>>();
+
+    if chars.first() != Some(&'!') {
+        return None;
+    }
+
+    link(&chars, 1)
+        .filter(|(_, _, end)| *end == chars.len())
+        .map(|(alt, src, _)| (alt, src))
+}
+
+fn run_length(chars: &[char], i: usize) -> usize {
+    chars[i..].iter().take_while(|c| c == &&chars[i]).count()
+}
+
+// start of the next run of exactly `len` of `c`, from `from`
+fn find_run(chars: &[char], from: usize, c: char, len: usize) -> Option<usize> {
+    let mut i = from;
+
+    while i < chars.len() {
+        if chars[i] == c {
+            let run = run_length(chars, i);
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+
+    None
+}
+
+// start of the closing delimiter of emphasis opened at `i`
+fn emphasis(chars: &[char], i: usize, run: usize) -> Option<usize> {
+    let c = chars[i];
+    let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+
+    if chars.get(i + run).is_none_or(|c| c.is_whitespace()) {
+        return None;
+    }
+    if c == '_' && word(i.checked_sub(1).and_then(|before| chars.get(before))) {
+        return None;
+    }
+
+    let mut j = i + run;
+
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 2,
+            '`' => {
+                let len = run_length(chars, j);
+                j = find_run(chars, j + len, '`', len)
+                    .map(|end| end + len)
+                    .unwrap_or(j + len);
+            }
+            other if other == c => {
+                let len = run_length(chars, j);
+
+                if len == run
+                    && !chars[j - 1].is_whitespace()
+                    && !(c == '_' && word(chars.get(j + len)))
+                {
+                    return Some(j);
+                }
+                j += len;
+            }
+            _ => j += 1,
+        }
+    }
+
+    None
+}
+
+// text, destination and end of a link starting with `[` at `i`
+fn link(chars: &[char], i: usize) -> Option<(String, String, usize)> {
+    let mut depth = 0;
+    let mut j = i;
+
+    let close = loop {
+        match chars.get(j)? {
+            '\\' => j += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break j;
+                }
+            }
+            _ => {}
+        }
+        j += 1;
+    };
+
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+
+    let mut j = close + 2;
+    let mut url = String::new();
+
+    if chars.get(j) == Some(&'<') {
+        j += 1;
+        while let Some(&c) = chars.get(j) {
+            j += 1;
+            match c {
+                '>' => break,
+                '\n' => return None,
+                c => url.push(c),
+            }
+        }
+    } else {
+        let mut depth = 0;
+        while let Some(&c) = chars.get(j) {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                c if c.is_whitespace() => break,
+                _ => {}
+            }
+            url.push(c);
+            j += 1;
+        }
+    }
+
+    // an optional title, which is dropped
+    while chars.get(j).is_some_and(|c| c.is_whitespace()) {
+        j += 1;
+    }
+    if let Some(&quote) = chars.get(j).filter(|c| matches!(c, '"' | '\'')) {
+        j += 1 + chars[j + 1..].iter().position(|c| c == &quote)? + 1;
+        while chars.get(j).is_some_and(|c| c.is_whitespace()) {
+            j += 1;
+        }
+    }
+
+    if chars.get(j) != Some(&')') {
+        return None;
+    }
+
+    Some((chars[i + 1..close].iter().collect(), url, j + 1))
+}
+
+// destination and end of an autolink starting with `<` at `i`
+fn autolink(chars: &[char], i: usize) -> Option<(String, usize)> {
+    let end = i + chars[i..].iter().position(|c| c == &'>')?;
+    let url = chars[i + 1..end].iter().collect::<String>();
+
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme).unwrap_or("");
+    let is_url = (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'));
+    let is_email = url.split_once('@').is_some_and(|(user, domain)| {
+        !user.is_empty() && domain.contains('.') && !url.contains(char::is_whitespace)
+    });
+
+    ((is_url || is_email) && !url.contains(|c: char| c.is_whitespace() || c == '<'))
+        .then_some((url, end + 1))
+}
+
+// content, type and end of maths starting with `$` at `i`
+fn maths(chars: &[char], i: usize) -> Option<(Vec<Chunk>, MathsType, usize)> {
+    if chars.get(i + 1) == Some(&'$') {
+        let content = chars[i + 2..].iter().collect::<String>();
+        let end = content.find("$$")?;
+        let source = &content[..end];
+        let len = source.chars().count();
+
+        return Some((parse(source)?, MathsType::Outline, i + 2 + len + 2));
+    }
+
+    if chars.get(i + 1).is_none_or(|c| c.is_whitespace()) {
+        return None;
+    }
+
+    let mut j = i + 1;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 1,
+            '$' if !chars[j - 1].is_whitespace()
+                && !chars.get(j + 1).is_some_and(char::is_ascii_digit) =>
+            {
+                let source = chars[i + 1..j].iter().collect::<String>();
+                return Some((parse(&source)?, MathsType::Inline, j + 1));
+            }
+            _ => {}
+        }
+        j += 1;
+    }
+
+    None
+}
+
+// a character reference, and its end
+fn entity(chars: &[char], i: usize) -> Option<(char, usize)> {
+    let end = i + chars[i..].iter().take(12).position(|c| c == &';')?;
+    let name = chars[i + 1..end].iter().collect::<String>();
+
+    let c = match name.as_str() {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => match name.strip_prefix('#') {
+            Some(hex) if hex.starts_with(['x', 'X']) => {
+                char::from_u32(u32::from_str_radix(&hex[1..], 16).ok()?)?
+            }
+            Some(decimal) => char::from_u32(decimal.parse().ok()?)?,
+            None => return None,
+        },
+    };
+
+    Some((c, end + 1))
+}
+
+// LaTeX source as chunks, if it parses without a `document` environment
+fn parse(source: &str) -> Option<Vec<Chunk>> {
+    let document = ast3::Document::from_str(source).ok()?;
+
+    (!document.has_body() && document.to_string() == source && document.validate().is_ok())
+        .then(|| document.chunks_preamable().clone())
+}
+
+// the content of a `verbatim` environment, if the code can be written in one
+fn verbatim(source: &str) -> Option<Chunks> {
+    parse(source).map(Chunks)
+}
@@ -0,0 +1,39 @@
+//! # Markdown
+//!
+//! Exports a stage 3 document as CommonMark, with `$...$` and `$$...$$` for maths.
+//!
+//! |Source|Markdown|
+//! |---|---|
+//! |`\part` to `\subparagraph`|`#` headings, from the top level of the document class.|
+//! |`itemize`, `enumerate` and `description`|`-` and `1.` lists, with description terms in bold.|
+//! |`\emph`, `\textbf` and `\texttt`|`*...*`, `**...**` and `` `...` ``|
+//! |Verbatim environments and `\verb`|Fenced code blocks, with the language of `lstlisting` and `minted`, and code spans.|
+//! |`\href` and `\url`|Links|
+//! |`\includegraphics`|Images, described by the caption of their figure.|
+//! |Simple `tabular`s|Pipe tables, with the first row as the header.|
+//! |`\footnote`|Footnotes, `[^1]`.|
+//! |`\cite`|Citations, `[@key]`.|
+//!
+//! Anything which cannot be converted, such as references, unknown commands and
+//! tables with merged cells, is kept as close to the source as possible and reported
+//! as a [`Warning`] with its position in the document.
+//!
+//! [`Importer`] goes the other way, reading CommonMark with pipe tables and `$` maths
+//! into a document under a chosen `\documentclass`. Special characters are escaped,
+//! and packages such as `graphicx` and `hyperref` are loaded when they are used.
+//!
+//! |Markdown|LaTeX|
+//! |---|---|
+//! |`#` headings|`\section` and below, or `\chapter` for classes with chapters.|
+//! |`*...*`, `**...**` and code spans|`\emph`, `\textbf` and `\texttt`|
+//! |Lists|`itemize` and `enumerate`|
+//! |Code blocks|`verbatim`, or `lstlisting` with a language.|
+//! |Links and images|`\href`, `\url` and `\includegraphics`, in a `figure` when on their own.|
+//! |Tables|`tabular`, with a `\hline` under the header.|
+//! |`$...$` and `$$...$$`|Inline maths and `\[...\]`|
+
+mod export;
+mod import;
+
+pub use export::{Exporter, Markdown, Warning, IGNORED};
+pub use import::Importer;
@@ -0,0 +1,990 @@
+//! # MathML
+//!
+//! Converts maths to Presentation MathML, for accessible HTML.
+//!
+//! |TeX|MathML|
+//! |---|---|
+//! |Letters, digits and operators|`<mi>`, `<mn>` and `<mo>`|
+//! |`^` and `_`|`<msup>`, `<msub>` and `<msubsup>`, or `<munderover>` for large operators in display maths.|
+//! |`\frac`, `\binom` and `\sqrt`|`<mfrac>`, `<msqrt>` and `<mroot>`|
+//! |`\left` and `\right`|An `<mrow>` with fences.|
+//! |Greek letters and symbols, see [`GREEK`] and [`SYMBOLS`]|Their Unicode characters.|
+//! |`\mathbb`, `\mathbf` and similar|`mathvariant`|
+//! |`\text`|`<mtext>`|
+//! |Matrices, `cases`, `align` rows and `\\`|`<mtable>`|
+//! |Unknown commands and environments|`<merror>` with their source.|
+//!
+//! [`MathsType::Outline`] maths is written with `display="block"`.
+
+use crate::{
+    ast3::{self, MathsType},
+    format::{ALIGNED, MATHS},
+    html::escape,
+};
+
+/// Greek letters
+pub const GREEK: [(&str, char); 40] = [
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ϵ'),
+    ("varepsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("vartheta", 'ϑ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("pi", 'π'),
+    ("varpi", 'ϖ'),
+    ("rho", 'ρ'),
+    ("varrho", 'ϱ'),
+    ("sigma", 'σ'),
+    ("varsigma", 'ς'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'ϕ'),
+    ("varphi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("Gamma", 'Γ'),
+    ("Delta", 'Δ'),
+    ("Theta", 'Θ'),
+    ("Lambda", 'Λ'),
+    ("Xi", 'Ξ'),
+    ("Pi", 'Π'),
+    ("Sigma", 'Σ'),
+    ("Upsilon", 'Υ'),
+    ("Phi", 'Φ'),
+    ("Psi", 'Ψ'),
+    ("Omega", 'Ω'),
+];
+
+/// Operators, relations and other symbols
+pub const SYMBOLS: [(&str, char); 88] = [
+    ("times", '×'),
+    ("cdot", '⋅'),
+    ("div", '÷'),
+    ("pm", '±'),
+    ("mp", '∓'),
+    ("ast", '∗'),
+    ("star", '⋆'),
+    ("circ", '∘'),
+    ("bullet", '∙'),
+    ("oplus", '⊕'),
+    ("ominus", '⊖'),
+    ("otimes", '⊗'),
+    ("odot", '⊙'),
+    ("cup", '∪'),
+    ("cap", '∩'),
+    ("setminus", '∖'),
+    ("wedge", '∧'),
+    ("land", '∧'),
+    ("vee", '∨'),
+    ("lor", '∨'),
+    ("neg", '¬'),
+    ("lnot", '¬'),
+    ("leq", '≤'),
+    ("le", '≤'),
+    ("geq", '≥'),
+    ("ge", '≥'),
+    ("neq", '≠'),
+    ("ne", '≠'),
+    ("ll", '≪'),
+    ("gg", '≫'),
+    ("approx", '≈'),
+    ("equiv", '≡'),
+    ("sim", '∼'),
+    ("simeq", '≃'),
+    ("cong", '≅'),
+    ("propto", '∝'),
+    ("perp", '⊥'),
+    ("parallel", '∥'),
+    ("mid", '∣'),
+    ("in", '∈'),
+    ("notin", '∉'),
+    ("ni", '∋'),
+    ("subset", '⊂'),
+    ("subseteq", '⊆'),
+    ("supset", '⊃'),
+    ("supseteq", '⊇'),
+    ("to", '→'),
+    ("rightarrow", '→'),
+    ("leftarrow", '←'),
+    ("gets", '←'),
+    ("leftrightarrow", '↔'),
+    ("Rightarrow", '⇒'),
+    ("Leftarrow", '⇐'),
+    ("Leftrightarrow", '⇔'),
+    ("implies", '⟹'),
+    ("iff", '⟺'),
+    ("mapsto", '↦'),
+    ("uparrow", '↑'),
+    ("downarrow", '↓'),
+    ("forall", '∀'),
+    ("exists", '∃'),
+    ("nexists", '∄'),
+    ("partial", '∂'),
+    ("nabla", '∇'),
+    ("infty", '∞'),
+    ("emptyset", '∅'),
+    ("varnothing", '∅'),
+    ("hbar", 'ℏ'),
+    ("ell", 'ℓ'),
+    ("aleph", 'ℵ'),
+    ("Re", 'ℜ'),
+    ("Im", 'ℑ'),
+    ("wp", '℘'),
+    ("angle", '∠'),
+    ("triangle", '△'),
+    ("prime", '′'),
+    ("ldots", '…'),
+    ("dots", '…'),
+    ("cdots", '⋯'),
+    ("vdots", '⋮'),
+    ("ddots", '⋱'),
+    ("langle", '⟨'),
+    ("rangle", '⟩'),
+    ("lfloor", '⌊'),
+    ("rfloor", '⌋'),
+    ("lceil", '⌈'),
+    ("rceil", '⌉'),
+    ("colon", ':'),
+];
+
+/// Large operators, whose scripts go above and below in display maths
+pub const LARGE_OPERATORS: [(&str, char); 15] = [
+    ("sum", '∑'),
+    ("prod", '∏'),
+    ("coprod", '∐'),
+    ("int", '∫'),
+    ("iint", '∬'),
+    ("iiint", '∭'),
+    ("oint", '∮'),
+    ("bigcup", '⋃'),
+    ("bigcap", '⋂'),
+    ("bigoplus", '⨁'),
+    ("bigotimes", '⨂'),
+    ("bigodot", '⨀'),
+    ("bigvee", '⋁'),
+    ("bigwedge", '⋀'),
+    ("bigsqcup", '⨆'),
+];
+
+/// Named functions, written upright
+pub const FUNCTIONS: [&str; 32] = [
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "coth", "log", "ln", "lg", "exp", "det", "dim", "ker", "deg", "gcd", "arg", "hom", "Pr", "lim",
+    "limsup", "liminf", "max", "min", "sup", "inf",
+];
+
+// functions whose scripts go below in display maths
+const LIMITS: [&str; 8] = ["lim", "limsup", "liminf", "max", "min", "sup", "inf", "Pr"];
+
+// commands setting the variant of their argument
+const VARIANTS: [(&str, &str); 9] = [
+    ("mathbb", "double-struck"),
+    ("mathbf", "bold"),
+    ("boldsymbol", "bold-italic"),
+    ("mathit", "italic"),
+    ("mathrm", "normal"),
+    ("mathcal", "script"),
+    ("mathfrak", "fraktur"),
+    ("mathsf", "sans-serif"),
+    ("mathtt", "monospace"),
+];
+
+// accents over and under their argument, and whether they stretch
+const ACCENTS: [(&str, char, bool); 12] = [
+    ("hat", '^', false),
+    ("widehat", '^', true),
+    ("bar", '¯', false),
+    ("overline", '¯', true),
+    ("vec", '→', false),
+    ("overrightarrow", '→', true),
+    ("dot", '˙', false),
+    ("ddot", '¨', false),
+    ("tilde", '~', false),
+    ("widetilde", '~', true),
+    ("underline", '_', true),
+    ("underbrace", '⏟', true),
+];
+
+// spacing commands and their widths
+const SPACES: [(&str, &str); 8] = [
+    (",", "0.1667em"),
+    (":", "0.2222em"),
+    (">", "0.2222em"),
+    (";", "0.2778em"),
+    ("!", "-0.1667em"),
+    (" ", "0.25em"),
+    ("quad", "1em"),
+    ("qquad", "2em"),
+];
+
+// commands which change sizes or spacing and are dropped
+const IGNORED: [&str; 18] = [
+    "displaystyle",
+    "textstyle",
+    "scriptstyle",
+    "limits",
+    "nolimits",
+    "nonumber",
+    "notag",
+    "big",
+    "Big",
+    "bigg",
+    "Bigg",
+    "bigl",
+    "bigr",
+    "Bigl",
+    "Bigr",
+    "biggl",
+    "biggr",
+    "middle",
+];
+
+// matrix environments and their fences
+const MATRICES: [(&str, &str, &str); 7] = [
+    ("matrix", "", ""),
+    ("smallmatrix", "", ""),
+    ("pmatrix", "(", ")"),
+    ("bmatrix", "[", "]"),
+    ("Bmatrix", "{", "}"),
+    ("vmatrix", "|", "|"),
+    ("Vmatrix", "‖", "‖"),
+];
+
+/// Convert a maths block, as `<math>`
+pub fn from_block(block: &ast3::MathsBlock) -> String {
+    let source = block
+        .content()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<String>();
+
+    from_source(&source, matches!(block.r#type(), MathsType::Outline))
+}
+
+/// Convert a maths environment such as `equation` or `align`, as `<math display="block">`
+///
+/// Returns `None` if the environment is not maths.
+pub fn from_environment(env: &ast3::Environment) -> Option<String> {
+    let label = env.label().trim_end_matches('*');
+
+    if !MATHS.contains(&env.label().as_str()) && !ALIGNED[6..].contains(&env.label().as_str()) {
+        return None;
+    }
+
+    let content = env
+        .content()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<String>();
+
+    let mut parser = Parser::new(&content);
+    let node = match label {
+        "equation" | "displaymath" | "math" | "gather" | "multline" | "gathered" => {
+            parser.rows(None)
+        }
+        _ => parser.environment(label),
+    };
+
+    Some(math(&node, true))
+}
+
+/// Convert TeX maths source, as `<math>`
+pub fn from_source(source: &str, display: bool) -> String {
+    math(&Parser::new(source).rows(None), display)
+}
+
+fn math(node: &Node, display: bool) -> String {
+    let content = node.render(display);
+    let content = content
+        .strip_prefix("<mrow>")
+        .and_then(|s| s.strip_suffix("</mrow>"))
+        .filter(|_| matches!(node, Node::Row(_)))
+        .unwrap_or(&content);
+
+    match display {
+        true => format!("<math display=\"block\">{content}</math>"),
+        false => format!("<math>{content}</math>"),
+    }
+}
+
+// a maths tree
+enum Node {
+    Identifier(String, Option<&'static str>),
+    Number(String, Option<&'static str>),
+    Operator(String),
+    LargeOperator(String),
+    Fence(String),
+    Text(String),
+    Space(&'static str),
+    Row(Vec<Node>),
+    Fraction(Box<Node>, Box<Node>, bool),
+    Sqrt(Box<Node>),
+    Root(Box<Node>, Box<Node>),
+    Scripts {
+        base: Box<Node>,
+        sub: Option<Box<Node>>,
+        sup: Option<Box<Node>>,
+    },
+    Accent(Box<Node>, char, bool),
+    Table(Vec<Vec<Node>>, &'static str),
+    Error(String),
+}
+
+impl Node {
+    fn render(&self, display: bool) -> String {
+        let variant = |variant: &Option<&str>| match variant {
+            Some(variant) => format!(" mathvariant=\"{variant}\""),
+            None => String::new(),
+        };
+
+        match self {
+            Self::Identifier(s, v) => format!("<mi{}>{}</mi>", variant(v), escape(s)),
+            Self::Number(s, v) => format!("<mn{}>{}</mn>", variant(v), escape(s)),
+            Self::Operator(s) => format!("<mo>{}</mo>", escape(s)),
+            Self::LargeOperator(s) => format!("<mo largeop=\"true\">{}</mo>", escape(s)),
+            Self::Fence(s) => format!("<mo fence=\"true\" stretchy=\"true\">{}</mo>", escape(s)),
+            Self::Text(s) => format!("<mtext>{}</mtext>", escape(s)),
+            Self::Space(width) => format!("<mspace width=\"{width}\"/>"),
+            Self::Row(nodes) if nodes.len() == 1 => nodes[0].render(display),
+            Self::Row(nodes) => format!(
+                "<mrow>{}</mrow>",
+                nodes.iter().map(|n| n.render(display)).collect::<String>()
+            ),
+            Self::Fraction(num, den, true) => format!(
+                "<mfrac>{}{}</mfrac>",
+                num.render(display),
+                den.render(display)
+            ),
+            Self::Fraction(num, den, false) => format!(
+                "<mfrac linethickness=\"0\">{}{}</mfrac>",
+                num.render(display),
+                den.render(display)
+            ),
+            Self::Sqrt(node) => format!("<msqrt>{}</msqrt>", node.render(display)),
+            Self::Root(index, node) => format!(
+                "<mroot>{}{}</mroot>",
+                node.render(display),
+                index.render(display)
+            ),
+            Self::Scripts { base, sub, sup } => {
+                let under = display && base.has_limits();
+                let (tags, sub_tag, sup_tag) = match under {
+                    true => ("munderover", "munder", "mover"),
+                    false => ("msubsup", "msub", "msup"),
+                };
+
+                match (sub, sup) {
+                    (Some(sub), Some(sup)) => format!(
+                        "<{tags}>{}{}{}</{tags}>",
+                        base.render(display),
+                        sub.render(display),
+                        sup.render(display)
+                    ),
+                    (Some(sub), None) => format!(
+                        "<{sub_tag}>{}{}</{sub_tag}>",
+                        base.render(display),
+                        sub.render(display)
+                    ),
+                    (None, Some(sup)) => format!(
+                        "<{sup_tag}>{}{}</{sup_tag}>",
+                        base.render(display),
+                        sup.render(display)
+                    ),
+                    (None, None) => base.render(display),
+                }
+            }
+            Self::Accent(node, c, stretchy) => {
+                let (tag, attribute) = match c {
+                    '_' | '⏟' => ("munder", "accentunder"),
+                    _ => ("mover", "accent"),
+                };
+
+                format!(
+                    "<{tag} {attribute}=\"true\">{}<mo stretchy=\"{stretchy}\">{}</mo></{tag}>",
+                    node.render(display),
+                    escape(&c.to_string())
+                )
+            }
+            Self::Table(rows, columnalign) => {
+                let mut out = match columnalign.is_empty() {
+                    true => String::from("<mtable>"),
+                    false => format!("<mtable columnalign=\"{columnalign}\">"),
+                };
+
+                for row in rows {
+                    out.push_str("<mtr>");
+                    for cell in row {
+                        out.push_str(&format!("<mtd>{}</mtd>", cell.render(display)));
+                    }
+                    out.push_str("</mtr>");
+                }
+
+                out.push_str("</mtable>");
+                out
+            }
+            Self::Error(source) => format!("<merror><mtext>{}</mtext></merror>", escape(source)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Self::Row(nodes) if nodes.is_empty())
+    }
+
+    // whether scripts go above and below in display maths
+    fn has_limits(&self) -> bool {
+        match self {
+            Self::LargeOperator(s) => !["∫", "∬", "∭", "∮"].contains(&s.as_str()),
+            Self::Identifier(s, _) => LIMITS.contains(&s.as_str()),
+            _ => false,
+        }
+    }
+}
+
+// where a row stops
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Brace,
+    Right,
+    Ampersand,
+    Newline,
+    End,
+    Bracket,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        Self {
+            chars: source.chars().collect(),
+            i: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.i).is_some_and(|c| c.is_whitespace()) {
+            self.i += 1
+        }
+    }
+
+    // the command name at the current position, without consuming it
+    fn peek_command(&self) -> Option<String> {
+        if self.chars.get(self.i) != Some(&'\\') {
+            return None;
+        }
+
+        let letters = self.chars[self.i + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect::<String>();
+
+        match letters.is_empty() {
+            true => self.chars.get(self.i + 1).map(|c| c.to_string()),
+            false => Some(letters),
+        }
+    }
+
+    fn take_command(&mut self) -> Option<String> {
+        let name = self.peek_command()?;
+        self.i += 1 + name.chars().count();
+        Some(name)
+    }
+
+    // what ends the current row, if anything
+    fn stop(&self) -> Option<Stop> {
+        match self.chars.get(self.i)? {
+            '}' => Some(Stop::Brace),
+            ']' => Some(Stop::Bracket),
+            '&' => Some(Stop::Ampersand),
+            '\\' => match self.peek_command()?.as_str() {
+                "right" => Some(Stop::Right),
+                "\\" | "cr" => Some(Stop::Newline),
+                "end" => Some(Stop::End),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // rows and cells up to `stop`, as a table if there is more than one cell
+    fn rows(&mut self, stop: Option<Stop>) -> Node {
+        self.table(stop, "")
+    }
+
+    fn table(&mut self, stop: Option<Stop>, columnalign: &'static str) -> Node {
+        let mut rows = vec![vec![]];
+        let mut cell = Vec::new();
+
+        loop {
+            cell.extend(self.nodes(stop));
+
+            match self.stop() {
+                Some(Stop::Ampersand) => {
+                    self.i += 1;
+                    rows.last_mut()
+                        .unwrap()
+                        .push(Node::Row(std::mem::take(&mut cell)));
+                }
+                Some(Stop::Newline) => {
+                    self.take_command();
+                    self.skip_whitespace();
+                    // spacing after `\\`
+                    if self.chars.get(self.i) == Some(&'[') {
+                        self.group('[', ']');
+                    }
+                    rows.last_mut()
+                        .unwrap()
+                        .push(Node::Row(std::mem::take(&mut cell)));
+                    rows.push(vec![]);
+                }
+                // a closing brace, `\right` or `\end` which was not opened
+                Some(_) if stop.is_none() => {
+                    let source = match self.take_command() {
+                        Some(name) => format!("\\{name}"),
+                        None => {
+                            self.i += 1;
+                            self.chars[self.i - 1].to_string()
+                        }
+                    };
+                    cell.push(Node::Error(source));
+                }
+                _ => {
+                    rows.last_mut().unwrap().push(Node::Row(cell));
+                    break;
+                }
+            }
+        }
+
+        // a trailing `\\`
+        if rows.len() > 1
+            && rows
+                .last()
+                .is_some_and(|row| row.iter().all(Node::is_empty))
+        {
+            rows.pop();
+        }
+
+        if rows.len() == 1 && rows[0].len() == 1 && columnalign.is_empty() {
+            return rows.pop().unwrap().pop().unwrap();
+        }
+
+        Node::Table(rows, columnalign)
+    }
+
+    fn row(&mut self, stop: Option<Stop>) -> Node {
+        Node::Row(self.nodes(stop))
+    }
+
+    // atoms up to the next stop
+    fn nodes(&mut self, stop: Option<Stop>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.stop() {
+                None if self.i >= self.chars.len() => break,
+                // brackets only end optional arguments
+                Some(Stop::Bracket) if stop != Some(Stop::Bracket) => {
+                    self.i += 1;
+                    nodes.push(Node::Operator("]".to_string()));
+                    continue;
+                }
+                Some(_) => break,
+                None => {}
+            }
+
+            if let Some(node) = self.atom(None) {
+                let node = self.scripts(node);
+                nodes.push(node);
+            }
+        }
+
+        nodes
+    }
+
+    // `^`, `_` and primes after a base
+    fn scripts(&mut self, base: Node) -> Node {
+        let mut sub = None;
+        let mut sup: Option<Node> = None;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.chars.get(self.i) {
+                Some('_') if sub.is_none() => {
+                    self.i += 1;
+                    sub = Some(self.argument(None));
+                }
+                Some('^') if sup.is_none() => {
+                    self.i += 1;
+                    sup = Some(self.argument(None));
+                }
+                Some('\'') => {
+                    self.i += 1;
+                    let prime = Node::Operator("′".to_string());
+                    sup = Some(match sup {
+                        Some(Node::Row(mut nodes)) => {
+                            nodes.push(prime);
+                            Node::Row(nodes)
+                        }
+                        Some(other) => Node::Row(vec![other, prime]),
+                        None => prime,
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        match (&sub, &sup) {
+            (None, None) => base,
+            _ => Node::Scripts {
+                base: Box::new(base),
+                sub: sub.map(Box::new),
+                sup: sup.map(Box::new),
+            },
+        }
+    }
+
+    // a single atom or group
+    fn argument(&mut self, variant: Option<&'static str>) -> Node {
+        self.skip_whitespace();
+
+        match self.chars.get(self.i) {
+            Some('{') => {
+                self.i += 1;
+                self.group_row(variant)
+            }
+            Some(_) => self.atom(variant).unwrap_or(Node::Row(Vec::new())),
+            None => Node::Row(Vec::new()),
+        }
+    }
+
+    // the rest of a `{...}` group, after the opening brace
+    fn group_row(&mut self, variant: Option<&'static str>) -> Node {
+        let mut nodes = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.chars.get(self.i) {
+                None => break,
+                Some('}') => {
+                    self.i += 1;
+                    break;
+                }
+                Some('&') => {
+                    self.i += 1;
+                    nodes.push(Node::Error("&".to_string()));
+                }
+                _ => {
+                    if matches!(self.stop(), Some(Stop::Right | Stop::End | Stop::Newline)) {
+                        let source = self.take_command().unwrap_or_default();
+                        nodes.push(Node::Error(format!("\\{source}")));
+                        continue;
+                    }
+
+                    if let Some(node) = self.atom(variant) {
+                        let node = self.scripts(node);
+                        nodes.push(node);
+                    }
+                }
+            }
+        }
+
+        Node::Row(nodes)
+    }
+
+    // the raw content of a group, with the cursor on its opening delimiter
+    fn group(&mut self, open: char, close: char) -> String {
+        let mut depth = 0;
+        let start = self.i + 1;
+
+        while let Some(&c) = self.chars.get(self.i) {
+            self.i += 1;
+            match c {
+                '\\' => self.i += 1,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.chars[start..self.i - 1].iter().collect();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.chars[start.min(self.chars.len())..].iter().collect()
+    }
+
+    // the raw content of a curly argument, or a single character
+    fn raw_argument(&mut self) -> String {
+        self.skip_whitespace();
+
+        match self.chars.get(self.i) {
+            Some('{') => self.group('{', '}'),
+            Some(_) => match self.take_command() {
+                Some(name) => format!("\\{name}"),
+                None => {
+                    self.i += 1;
+                    self.chars[self.i - 1].to_string()
+                }
+            },
+            None => String::new(),
+        }
+    }
+
+    fn atom(&mut self, variant: Option<&'static str>) -> Option<Node> {
+        let c = *self.chars.get(self.i)?;
+
+        if c == '\\' {
+            return self.command(variant);
+        }
+
+        self.i += 1;
+
+        Some(match c {
+            '{' => self.group_row(variant),
+            c if c.is_ascii_digit()
+                || (c == '.' && self.chars.get(self.i).is_some_and(char::is_ascii_digit)) =>
+            {
+                let mut number = c.to_string();
+                while let Some(&next) = self.chars.get(self.i) {
+                    let decimal =
+                        next == '.' && self.chars.get(self.i + 1).is_some_and(char::is_ascii_digit);
+                    if !next.is_ascii_digit() && !decimal {
+                        break;
+                    }
+                    number.push(next);
+                    self.i += 1;
+                }
+                Node::Number(number, variant)
+            }
+            c if c.is_alphabetic() => Node::Identifier(c.to_string(), variant),
+            '~' => Node::Space("0.25em"),
+            '-' => Node::Operator("−".to_string()),
+            '*' => Node::Operator("∗".to_string()),
+            '\'' => Node::Operator("′".to_string()),
+            '}' => Node::Error("}".to_string()),
+            '#' | '%' | '$' => Node::Error(c.to_string()),
+            c => Node::Operator(c.to_string()),
+        })
+    }
+
+    fn command(&mut self, variant: Option<&'static str>) -> Option<Node> {
+        let name = self.take_command()?;
+        let lookup = |table: &[(&str, char)]| {
+            table
+                .iter()
+                .find(|(label, _)| label == &name)
+                .map(|(_, c)| c.to_string())
+        };
+
+        if let Some(c) = lookup(&GREEK) {
+            // capital Greek letters are upright
+            let variant = match name.starts_with(char::is_uppercase) {
+                true => variant.or(Some("normal")),
+                false => variant,
+            };
+            return Some(Node::Identifier(c, variant));
+        }
+        if let Some(c) = lookup(&SYMBOLS) {
+            return Some(match name.as_str() {
+                "infty" | "emptyset" | "varnothing" | "hbar" | "ell" | "aleph" | "Re" | "Im"
+                | "wp" | "partial" | "nabla" => Node::Identifier(c, variant),
+                _ => Node::Operator(c),
+            });
+        }
+        if let Some(c) = lookup(&LARGE_OPERATORS) {
+            return Some(Node::LargeOperator(c));
+        }
+        if FUNCTIONS.contains(&name.as_str()) {
+            return Some(Node::Identifier(name, None));
+        }
+        if let Some((_, variant)) = VARIANTS.iter().find(|(label, _)| label == &name) {
+            return Some(self.argument(Some(variant)));
+        }
+        if let Some((_, c, stretchy)) = ACCENTS.iter().find(|(label, _, _)| label == &name) {
+            return Some(Node::Accent(
+                Box::new(self.argument(variant)),
+                *c,
+                *stretchy,
+            ));
+        }
+        if let Some((_, width)) = SPACES.iter().find(|(label, _)| label == &name) {
+            return Some(Node::Space(width));
+        }
+        if IGNORED.contains(&name.as_str()) {
+            return None;
+        }
+
+        Some(match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let num = self.argument(variant);
+                let den = self.argument(variant);
+                Node::Fraction(Box::new(num), Box::new(den), true)
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let top = self.argument(variant);
+                let bottom = self.argument(variant);
+                Node::Row(vec![
+                    Node::Operator("(".to_string()),
+                    Node::Fraction(Box::new(top), Box::new(bottom), false),
+                    Node::Operator(")".to_string()),
+                ])
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                match self.chars.get(self.i) {
+                    Some('[') => {
+                        self.i += 1;
+                        let index = self.row(Some(Stop::Bracket));
+                        if self.chars.get(self.i) == Some(&']') {
+                            self.i += 1;
+                        }
+                        let node = self.argument(variant);
+                        Node::Root(Box::new(index), Box::new(node))
+                    }
+                    _ => Node::Sqrt(Box::new(self.argument(variant))),
+                }
+            }
+            "left" => {
+                let open = self.delimiter();
+                let content = self.row(Some(Stop::Right));
+                let close = match self.peek_command().as_deref() {
+                    Some("right") => {
+                        self.take_command();
+                        self.delimiter()
+                    }
+                    _ => None,
+                };
+
+                let mut nodes = Vec::new();
+                nodes.extend(open.map(Node::Fence));
+                nodes.push(content);
+                nodes.extend(close.map(Node::Fence));
+                Node::Row(nodes)
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" | "textnormal" => {
+                Node::Text(self.raw_argument())
+            }
+            "operatorname" => Node::Identifier(self.raw_argument(), None),
+            "{" | "}" | "%" | "&" | "#" | "$" | "_" => Node::Operator(name),
+            "|" | "Vert" => Node::Operator("‖".to_string()),
+            "vert" | "lvert" | "rvert" => Node::Operator("|".to_string()),
+            "lbrace" => Node::Operator("{".to_string()),
+            "rbrace" => Node::Operator("}".to_string()),
+            "label" | "tag" => {
+                self.raw_argument();
+                return None;
+            }
+            "begin" => {
+                let label = self.raw_argument();
+                self.environment(label.trim_end_matches('*'))
+            }
+            _ => {
+                // the source of an unknown command, with its arguments
+                let mut source = format!("\\{name}");
+                loop {
+                    self.skip_whitespace();
+                    match self.chars.get(self.i) {
+                        Some('{') => source.push_str(&format!("{{{}}}", self.group('{', '}'))),
+                        _ => break,
+                    }
+                }
+                Node::Error(source)
+            }
+        })
+    }
+
+    // the content of an environment after `\begin{label}`, up to its `\end`
+    fn environment(&mut self, label: &str) -> Node {
+        let matrix = MATRICES.iter().find(|(name, _, _)| name == &label);
+
+        let node = match label {
+            _ if matrix.is_some() => self.table(Some(Stop::End), "center"),
+            "cases" => self.table(Some(Stop::End), "left left"),
+            "array" | "subarray" => {
+                self.raw_argument();
+                self.table(Some(Stop::End), "center")
+            }
+            "align" | "aligned" | "split" | "flalign" | "eqnarray" => {
+                self.table(Some(Stop::End), "right left")
+            }
+            "alignat" | "alignedat" => {
+                self.raw_argument();
+                self.table(Some(Stop::End), "right left")
+            }
+            "gather" | "gathered" | "multline" | "equation" => {
+                self.table(Some(Stop::End), "center")
+            }
+            _ => {
+                let start = self.i;
+                self.table(Some(Stop::End), "");
+                let source = self.chars[start..self.i].iter().collect::<String>();
+                self.end();
+                return Node::Error(format!("\\begin{{{label}}}{source}\\end{{{label}}}"));
+            }
+        };
+
+        self.end();
+
+        match matrix {
+            Some((_, "", "")) => node,
+            Some((_, open, close)) => Node::Row(vec![
+                Node::Fence(open.to_string()),
+                node,
+                Node::Fence(close.to_string()),
+            ]),
+            None if label == "cases" => Node::Row(vec![Node::Fence("{".to_string()), node]),
+            None => node,
+        }
+    }
+
+    // `\end{...}`, if it is next
+    fn end(&mut self) {
+        if self.peek_command().as_deref() == Some("end") {
+            self.take_command();
+            self.raw_argument();
+        }
+    }
+
+    // the delimiter after `\left` or `\right`, `None` for `.`
+    fn delimiter(&mut self) -> Option<String> {
+        self.skip_whitespace();
+
+        let delimiter = self.raw_argument();
+        let delimiter = match delimiter.strip_prefix('\\') {
+            Some(name) => GREEK
+                .iter()
+                .chain(SYMBOLS.iter())
+                .find(|(label, _)| label == &name)
+                .map(|(_, c)| c.to_string())
+                .unwrap_or_else(|| match name {
+                    "|" | "Vert" => "‖".to_string(),
+                    "vert" | "lvert" | "rvert" => "|".to_string(),
+                    "lbrace" => "{".to_string(),
+                    "rbrace" => "}".to_string(),
+                    other => other.to_string(),
+                }),
+            None => delimiter,
+        };
+
+        (delimiter != ".").then_some(delimiter)
+    }
+}
@@ -0,0 +1,600 @@
+//! # Plain text extraction
+//!
+//! Extracts the prose of a stage 3 document for spelling and grammar checking,
+//! with a map from the extracted text back to the source.
+//!
+//! |Source|Extracted as|
+//! |---|---|
+//! |Text|The text, with runs of whitespace and `~` collapsed to a single space.|
+//! |`\emph{x}` and similar|`x`|
+//! |`\section{x}`, `\caption{x}` and similar|`x` as its own paragraph.|
+//! |`\footnote{x}`|`x` as its own paragraph, after the paragraph it is in.|
+//! |`\cite{x}` and `\ref{x}`|Placeholder tokens, `[0]` and `0` by default.|
+//! |Maths|A placeholder, `X` by default, including maths environments.|
+//! |Prose environments|Their content as its own paragraph.|
+//! |Non-prose environments|Dropped, such as `verbatim` and `tabular`.|
+//! |Other commands|Dropped with their arguments.|
+//! |Conditionals|The branch TeX reads if known, otherwise the content before `\else` or `\or`.|
+//! |`\def` and similar, parameters such as `#1`|Dropped.|
+//!
+//! The preamble is dropped, unless the document has no `document` environment.
+
+use std::ops::Range;
+
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Conditions, Scope, ScopeVariant},
+    completion::{CITE_COMMANDS, REF_COMMANDS},
+    format::{ALIGNED, MATHS, VERBATIM},
+    sectioning, Position,
+};
+
+/// Commands replaced by the content of their last curly argument
+pub const INLINE_COMMANDS: [&str; 18] = [
+    "emph",
+    "textbf",
+    "textit",
+    "texttt",
+    "textsc",
+    "textsf",
+    "textrm",
+    "textup",
+    "textsl",
+    "textmd",
+    "textnormal",
+    "underline",
+    "uline",
+    "mbox",
+    "hbox",
+    "text",
+    "enquote",
+    "hl",
+];
+
+/// Commands whose last curly argument is extracted as its own paragraph, along with the
+/// [sectioning commands](crate::sectioning::COMMANDS)
+pub const BLOCK_COMMANDS: [&str; 2] = ["caption", "title"];
+
+/// Commands whose last curly argument is extracted after the current paragraph
+pub const DEFERRED_COMMANDS: [&str; 3] = ["footnote", "footnotetext", "marginpar"];
+
+/// Environments which are dropped, in addition to verbatim environments
+pub const NON_PROSE: [&str; 11] = [
+    "tabular",
+    "tabular*",
+    "tabularx",
+    "tabulary",
+    "longtable",
+    "array",
+    "tikzpicture",
+    "picture",
+    "thebibliography",
+    "filecontents",
+    "comment",
+];
+
+/// Extracts plain text with configurable placeholders
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Extractor {
+    maths: String,
+    citation: String,
+    reference: String,
+    skipped: Vec<String>,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Self {
+            maths: "X".to_string(),
+            citation: "[0]".to_string(),
+            reference: "0".to_string(),
+            skipped: VERBATIM
+                .iter()
+                .chain(NON_PROSE.iter())
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl Extractor {
+    /// Create an extractor with the default placeholders
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the placeholder for maths
+    pub fn with_maths(mut self, placeholder: &str) -> Self {
+        self.maths = placeholder.to_string();
+        self
+    }
+
+    /// Set the placeholder for `\cite` and similar
+    pub fn with_citation(mut self, placeholder: &str) -> Self {
+        self.citation = placeholder.to_string();
+        self
+    }
+
+    /// Set the placeholder for `\ref` and similar
+    pub fn with_reference(mut self, placeholder: &str) -> Self {
+        self.reference = placeholder.to_string();
+        self
+    }
+
+    /// Drop the content of an environment
+    pub fn with_skipped(mut self, label: &str) -> Self {
+        self.skipped.push(label.to_string());
+        self
+    }
+
+    /// Extract the prose of a document
+    pub fn extract(&self, document: &ast3::Document) -> PlainText {
+        let mut out = Output::new(Conditions::from_document(document));
+
+        if document.has_body() {
+            out.skip(
+                &document
+                    .chunks_preamable()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<String>(),
+            );
+            out.skip(&format!(
+                "\\begin{}{{document}}",
+                document.body_begin_prec()
+            ));
+            self.skip_arguments(&mut out, document.body_args());
+            self.chunks(&mut out, document.chunks_body());
+        } else {
+            self.chunks(&mut out, document.chunks_preamable());
+        }
+
+        self.block(&mut out);
+
+        PlainText {
+            text: out.text,
+            mappings: out.mappings,
+        }
+    }
+
+    // a paragraph break, after which deferred text is written
+    fn block<'a>(&self, out: &mut Output<'a>) {
+        out.block();
+
+        while !out.deferred.is_empty() {
+            for (position, chunks) in std::mem::take(&mut out.deferred) {
+                let current = std::mem::replace(&mut out.position, position);
+                self.chunks(out, chunks);
+                out.position = current;
+                out.block();
+            }
+        }
+    }
+
+    fn chunks<'a>(&self, out: &mut Output<'a>, chunks: &'a [Chunk]) {
+        let mut i = 0;
+
+        while let Some(chunk) = chunks.get(i) {
+            i += 1;
+
+            match chunk.variant() {
+                ChunkVariant::Text(s) => out.text(s),
+                ChunkVariant::ParagraphBreak(s) => {
+                    out.skip(s);
+                    self.block(out);
+                }
+                ChunkVariant::MathsBlock(block) => out.replace(&block.to_string(), &self.maths),
+                ChunkVariant::Command(command) => match starred(command, chunks, i) {
+                    Some(title) => {
+                        self.heading(out, command, title);
+                        i += 2;
+                    }
+                    None => self.command(out, command),
+                },
+                ChunkVariant::Scope(scope) => self.scope(out, scope),
+                ChunkVariant::Environment(env) => self.environment(out, env),
+                ChunkVariant::Conditional(conditional) => self.conditional(out, conditional),
+                ChunkVariant::Parameter(parameter) => out.skip(&parameter.to_string()),
+                ChunkVariant::MacroDefinition(definition) => out.skip(&definition.to_string()),
+            }
+        }
+    }
+
+    fn conditional<'a>(&self, out: &mut Output<'a>, conditional: &'a ast3::Conditional) {
+        let start = out.position;
+        let branch = out.conditions.evaluate_or_content(conditional);
+
+        let chosen = conditional
+            .branches(start)
+            .into_iter()
+            .find(|(_, chunks)| std::ptr::eq(chunks.as_slice(), branch));
+        if let Some((position, chunks)) = chosen {
+            out.position = position;
+            self.chunks(out, chunks);
+        }
+
+        out.position = start.after(&conditional.to_string());
+    }
+
+    // a starred heading, the command followed by `*` and its title
+    fn heading<'a>(&self, out: &mut Output<'a>, command: &'a ast3::Command, title: &'a Scope) {
+        self.block(out);
+        out.skip(&format!("{command}*{{"));
+        self.chunks(out, title.chunks());
+        out.skip("}");
+        self.block(out);
+    }
+
+    fn command<'a>(&self, out: &mut Output<'a>, command: &'a ast3::Command) {
+        let label = command.label().as_str();
+
+        let inline = INLINE_COMMANDS.contains(&label);
+        let deferred = DEFERRED_COMMANDS.contains(&label);
+        let block = BLOCK_COMMANDS.contains(&label) || sectioning::level(label).is_some();
+        if inline || deferred || block {
+            let last = command
+                .arguments()
+                .iter()
+                .rposition(|(_, arg)| arg.variant() == ScopeVariant::Curly);
+
+            if let Some(last) = last {
+                let (before, after) = command.arguments().split_at(last);
+
+                let block = !inline && !deferred;
+
+                if block {
+                    self.block(out)
+                }
+                out.skip(&format!("\\{label}"));
+                self.skip_arguments(out, before);
+                out.skip(&after[0].0);
+                out.skip("{");
+                if deferred {
+                    out.deferred.push((out.position, after[0].1.chunks()));
+                    out.skip(
+                        &after[0]
+                            .1
+                            .chunks()
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<String>(),
+                    );
+                } else {
+                    self.chunks(out, after[0].1.chunks());
+                }
+                out.skip("}");
+                self.skip_arguments(out, &after[1..]);
+                if block {
+                    self.block(out)
+                }
+
+                return;
+            }
+        }
+
+        let source = command.to_string();
+
+        match label {
+            _ if CITE_COMMANDS.contains(&label) => out.replace(&source, &self.citation),
+            _ if REF_COMMANDS.contains(&label) => out.replace(&source, &self.reference),
+            "item" | "par" => {
+                self.block(out);
+                out.skip(&source)
+            }
+            _ if !command.arguments().is_empty() => out.skip(&source),
+            " " | "," | ";" | ":" | "\\" => out.space(&source),
+            "LaTeX" | "TeX" | "LaTeXe" => out.replace(&source, label),
+            "ldots" | "dots" | "textellipsis" => out.replace(&source, "..."),
+            "textendash" => out.replace(&source, "\u{2013}"),
+            "textemdash" => out.replace(&source, "\u{2014}"),
+            "%" | "&" | "$" | "#" | "_" | "{" | "}" => out.replace(&source, label),
+            _ => out.skip(&source),
+        }
+    }
+
+    fn scope<'a>(&self, out: &mut Output<'a>, scope: &'a Scope) {
+        match scope.variant() {
+            ScopeVariant::Curly => {
+                out.skip("{");
+                self.chunks(out, scope.chunks());
+                out.skip("}");
+            }
+            variant => {
+                out.exact(&variant.open().to_string());
+                self.chunks(out, scope.chunks());
+                out.exact(&variant.close().to_string());
+            }
+        }
+    }
+
+    fn environment<'a>(&self, out: &mut Output<'a>, env: &'a ast3::Environment) {
+        let label = env.label();
+
+        if self.skipped.contains(label) {
+            out.skip(&env.to_string());
+            return;
+        }
+
+        if MATHS.contains(&label.as_str()) || ALIGNED.contains(&label.as_str()) {
+            out.replace(&env.to_string(), &self.maths);
+            return;
+        }
+
+        self.block(out);
+        out.skip(&format!("\\begin{}{{{label}}}", env.prec_begin()));
+        self.skip_arguments(out, env.arguments());
+        self.chunks(out, env.content());
+        out.skip(&format!("\\end{}{{{label}}}", env.prec_end()));
+        self.block(out);
+    }
+
+    fn skip_arguments(&self, out: &mut Output, arguments: &[(String, Scope)]) {
+        for (prec, arg) in arguments {
+            out.skip(prec);
+            out.skip(&arg.to_string());
+        }
+    }
+}
+
+/// A range of extracted text and the source it came from
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Mapping {
+    output: Range<usize>,
+    start: Position,
+    end: Position,
+    exact: bool,
+}
+
+impl Mapping {
+    /// Returns the byte range in the extracted text
+    pub fn output(&self) -> &Range<usize> {
+        &self.output
+    }
+
+    /// Returns the position of the first character in the source
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Returns the position right after the last character in the source
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    /// Whether the text is copied from the source character by character,
+    /// rather than replacing it such as a placeholder
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+/// Extracted text with a map back to the source
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct PlainText {
+    text: String,
+    mappings: Vec<Mapping>,
+}
+
+impl PlainText {
+    /// Returns the extracted text
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    /// Returns the mappings, ordered by output range
+    pub fn mappings(&self) -> &Vec<Mapping> {
+        &self.mappings
+    }
+
+    /// Returns the mapping containing a byte offset of the text
+    pub fn mapping_at(&self, offset: usize) -> Option<&Mapping> {
+        let index = self
+            .mappings
+            .partition_point(|mapping| mapping.output.end <= offset);
+        self.mappings
+            .get(index)
+            .filter(|mapping| mapping.output.start <= offset)
+    }
+
+    /// Returns the source position of a byte offset of the text
+    ///
+    /// Offsets within a replacement map to the start of what it replaced.
+    pub fn source_position(&self, offset: usize) -> Option<Position> {
+        let mapping = self.mapping_at(offset)?;
+
+        if mapping.exact {
+            Some(
+                mapping
+                    .start
+                    .after(self.text.get(mapping.output.start..offset)?),
+            )
+        } else {
+            Some(mapping.start)
+        }
+    }
+
+    /// Returns the source range of a byte range of the text
+    ///
+    /// Ranges are widened to whole replacements.
+    pub fn source_range(&self, range: Range<usize>) -> Option<(Position, Position)> {
+        let start = self.source_position(range.start)?;
+
+        if range.is_empty() {
+            return Some((start, start));
+        }
+
+        let last = self.text.get(..range.end)?.chars().next_back()?;
+        let mapping = self.mapping_at(range.end - last.len_utf8())?;
+
+        let end = if mapping.exact {
+            mapping
+                .start
+                .after(self.text.get(mapping.output.start..range.end)?)
+        } else {
+            mapping.end
+        };
+
+        Some((start, end))
+    }
+}
+
+// extracted text being built, along with the position in the source
+struct Output<'a> {
+    text: String,
+    mappings: Vec<Mapping>,
+    position: Position,
+    // whitespace not yet written
+    space: Option<(Position, Position)>,
+    // a paragraph break not yet written
+    block: Option<Position>,
+    // text to write after the current paragraph, and where it starts
+    deferred: Vec<(Position, &'a [Chunk])>,
+    // values of the tests of conditionals
+    conditions: Conditions,
+}
+
+impl Output<'_> {
+    fn new(conditions: Conditions) -> Self {
+        Self {
+            text: String::new(),
+            mappings: Vec::new(),
+            position: Position::start(),
+            space: None,
+            block: None,
+            deferred: Vec::new(),
+            conditions,
+        }
+    }
+
+    // source which is not extracted
+    fn skip(&mut self, source: &str) {
+        self.position.advance_str(source)
+    }
+
+    // text, with whitespace collapsed
+    fn text(&mut self, s: &str) {
+        let mut run = 0;
+        let mut iter = s.char_indices().peekable();
+
+        while let Some((i, c)) = iter.next() {
+            let quote = (c == '`' || c == '\'') && iter.peek().is_some_and(|(_, next)| *next == c);
+
+            if !(c.is_whitespace() || c == '~' || quote) {
+                continue;
+            }
+
+            self.exact(&s[run..i]);
+
+            if quote {
+                iter.next();
+                self.replace(&s[i..i + 2], "\"");
+                run = i + 2;
+            } else {
+                self.space(&s[i..i + c.len_utf8()]);
+                run = i + c.len_utf8();
+            }
+        }
+
+        self.exact(&s[run..])
+    }
+
+    // whitespace, written as a single space before the next text
+    fn space(&mut self, source: &str) {
+        let start = self.position;
+        self.skip(source);
+
+        self.space = match self.space {
+            Some((first, _)) => Some((first, self.position)),
+            None => Some((start, self.position)),
+        }
+    }
+
+    // a paragraph break before the next text
+    fn block(&mut self) {
+        if self.block.is_none() {
+            self.block = Some(self.position)
+        }
+    }
+
+    // text which is the same as the source
+    fn exact(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+
+        self.separate();
+
+        let start = self.position;
+        self.skip(s);
+
+        match self.mappings.last_mut() {
+            Some(last) if last.exact && last.end == start && last.output.end == self.text.len() => {
+                last.output.end += s.len();
+                last.end = self.position
+            }
+            _ => self.push(start, s, true),
+        }
+
+        self.text.push_str(s)
+    }
+
+    // source replaced by some other text
+    fn replace(&mut self, source: &str, with: &str) {
+        self.separate();
+
+        let start = self.position;
+        self.skip(source);
+        self.push(start, with, false);
+        self.text.push_str(with)
+    }
+
+    // write any pending paragraph break or space
+    fn separate(&mut self) {
+        let space = self.space.take();
+
+        if let Some(at) = self.block.take() {
+            if !self.text.is_empty() {
+                self.mappings.push(Mapping {
+                    output: self.text.len()..self.text.len() + 2,
+                    start: at,
+                    end: at,
+                    exact: false,
+                });
+                self.text.push_str("\n\n");
+            }
+        } else if let Some((start, end)) = space {
+            if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+                self.mappings.push(Mapping {
+                    output: self.text.len()..self.text.len() + 1,
+                    start,
+                    end,
+                    exact: false,
+                });
+                self.text.push(' ');
+            }
+        }
+    }
+
+    fn push(&mut self, start: Position, s: &str, exact: bool) {
+        self.mappings.push(Mapping {
+            output: self.text.len()..self.text.len() + s.len(),
+            start,
+            end: self.position,
+            exact,
+        })
+    }
+}
+
+// the title of a heading, when the command is starred as in `\section*{Title}`, with `i` the
+// index of the chunk after the command
+fn starred<'a>(command: &ast3::Command, chunks: &'a [Chunk], i: usize) -> Option<&'a Scope> {
+    if command.arguments().is_empty() && sectioning::level(command.label()).is_some() {
+        sectioning::starred(chunks, i)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::Display;
+
+/// Location of a character in the source
+///
+/// Both line and column start from 1, columns are counted in `char`s.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    /// Create a new position
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+
+    /// Position of the first character of a document
+    pub fn start() -> Self {
+        Self::new(1, 1)
+    }
+
+    /// Move the position past a character
+    pub fn advance(&mut self, c: char) {
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    /// Move the position past a string
+    pub fn advance_str(&mut self, s: &str) {
+        s.chars().for_each(|c| self.advance(c))
+    }
+
+    /// Returns the position after `s`, given `s` starts at the current position
+    pub fn after(mut self, s: &str) -> Self {
+        self.advance_str(s);
+        self
+    }
+
+    /// Returns the position after the text `value` displays as, without allocating it
+    pub(crate) fn after_display(self, value: impl Display) -> Self {
+        let mut advance = Advance(self);
+        let _ = std::fmt::Write::write_fmt(&mut advance, format_args!("{value}"));
+        advance.0
+    }
+
+    /// Returns the position after the text of each of `values` in order
+    pub(crate) fn after_all<T: Display>(self, values: impl IntoIterator<Item = T>) -> Self {
+        values
+            .into_iter()
+            .fold(self, |position, value| position.after_display(value))
+    }
+
+    /// Returns the position after command arguments and the text before each of them
+    pub(crate) fn after_arguments<T: Display>(self, arguments: &[(String, T)]) -> Self {
+        arguments.iter().fold(self, |position, (prec, arg)| {
+            position.after(prec).after_display(arg)
+        })
+    }
+
+    /// Maps a position relative to a nested input to an absolute position,
+    /// where `base` is the absolute position of the first character of the nested input
+    pub fn offset(self, base: Self) -> Self {
+        Self {
+            line: base.line + self.line - 1,
+            column: if self.line == 1 {
+                base.column + self.column - 1
+            } else {
+                self.column
+            },
+        }
+    }
+}
+
+// advances a position past everything written to it
+struct Advance(Position);
+
+impl std::fmt::Write for Advance {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.advance_str(s);
+        Ok(())
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}:{}", self.line, self.column))
+    }
+}
@@ -0,0 +1,41 @@
+//! # Sectioning
+//!
+//! The sectioning commands, from `\part` down to `\subparagraph`, shared by the outputs and
+//! tools which treat headings apart from other commands.
+//!
+//! A starred heading such as `\section*{Title}` is not a command with an argument in
+//! stage 3, but the command followed by the text `*` and a curly scope holding the title.
+
+use crate::ast3::{Chunk, ChunkVariant, Scope, ScopeVariant};
+
+/// Sectioning commands, from the highest level
+pub const COMMANDS: [&str; 7] = [
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+/// Returns the level of a sectioning command, 0 for `\part`
+pub fn level(label: &str) -> Option<usize> {
+    COMMANDS.iter().position(|command| *command == label)
+}
+
+/// Returns the title of a starred heading, when the chunks from `i` are the `*` and title
+/// following the command, as in `\section*{Title}`
+pub fn starred(chunks: &[Chunk], i: usize) -> Option<&Scope> {
+    match (
+        chunks.get(i).map(Chunk::variant),
+        chunks.get(i + 1).map(Chunk::variant),
+    ) {
+        (Some(ChunkVariant::Text(star)), Some(ChunkVariant::Scope(title)))
+            if star == "*" && title.variant() == ScopeVariant::Curly =>
+        {
+            Some(title)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,542 @@
+//! # Statistics
+//!
+//! Counts words and other elements of a stage 3 document, in the style of texcount.
+//!
+//! |Count|Source|
+//! |---|---|
+//! |Words in text|Prose, including `\emph` and similar, and footnotes.|
+//! |Words in headers|Titles of `\section` and similar.|
+//! |Words outside text|Captions.|
+//! |Headers|`\part` down to `\subparagraph`.|
+//! |Figures and tables|`figure` and `table` environments, including starred forms.|
+//! |Maths|Inline and display `MathsBlock`s, and maths environments as display maths.|
+//! |Citations|Each key of `\cite` and similar.|
+//! |Footnotes|`\footnote`.|
+//!
+//! Only the branch of a conditional TeX reads is counted, or the content when the test is
+//! not known. Macro definitions are not counted.
+//!
+//! Counts are broken down per section. The preamble is not counted, unless the document
+//! has no `document` environment. Comments are not part of the AST, so `%TC:ignore`
+//! and `%TC:endignore` are read from the source with [`Counter::with_source`].
+
+use std::{fmt::Write, ops::Range};
+
+#[cfg(feature = "json")]
+use crate::json::Json;
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Conditions, MathsType, ScopeVariant},
+    completion::CITE_COMMANDS,
+    format::{ALIGNED, MATHS, VERBATIM},
+    plaintext::{INLINE_COMMANDS, NON_PROSE},
+    sectioning, Position,
+};
+
+/// Counts of one section or a whole document
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Counts {
+    pub words_text: u32,
+    pub words_headers: u32,
+    pub words_captions: u32,
+    pub headers: u32,
+    pub figures: u32,
+    pub tables: u32,
+    pub maths_inline: u32,
+    pub maths_display: u32,
+    pub citations: u32,
+    pub footnotes: u32,
+}
+
+impl Counts {
+    /// Add the counts of another section
+    pub fn add(&mut self, other: &Self) {
+        self.words_text += other.words_text;
+        self.words_headers += other.words_headers;
+        self.words_captions += other.words_captions;
+        self.headers += other.headers;
+        self.figures += other.figures;
+        self.tables += other.tables;
+        self.maths_inline += other.maths_inline;
+        self.maths_display += other.maths_display;
+        self.citations += other.citations;
+        self.footnotes += other.footnotes;
+    }
+
+    /// Returns the counts as a JSON object
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("wordsText", self.words_text.into()),
+            ("wordsHeaders", self.words_headers.into()),
+            ("wordsCaptions", self.words_captions.into()),
+            ("headers", self.headers.into()),
+            ("figures", self.figures.into()),
+            ("tables", self.tables.into()),
+            ("mathsInline", self.maths_inline.into()),
+            ("mathsDisplay", self.maths_display.into()),
+            ("citations", self.citations.into()),
+            ("footnotes", self.footnotes.into()),
+        ])
+    }
+}
+
+/// A section and its counts, not including its subsections
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Section {
+    command: Option<String>,
+    title: String,
+    line: u32,
+    counts: Counts,
+}
+
+impl Section {
+    /// Returns the sectioning command, or `None` for the text before the first section
+    pub fn command(&self) -> Option<&String> {
+        self.command.as_ref()
+    }
+
+    /// Returns the title as written in the source
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+
+    /// Returns the line the section starts on
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the counts within the section
+    pub fn counts(&self) -> &Counts {
+        &self.counts
+    }
+}
+
+/// Counts of a document, per section
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "eq", derive(PartialEq, Eq))]
+pub struct Statistics {
+    sections: Vec<Section>,
+}
+
+impl Statistics {
+    /// Returns the sections in document order, starting with the text before the first section
+    pub fn sections(&self) -> &Vec<Section> {
+        &self.sections
+    }
+
+    /// Returns the counts of the whole document
+    pub fn total(&self) -> Counts {
+        let mut total = Counts::default();
+        self.sections
+            .iter()
+            .for_each(|section| total.add(&section.counts));
+        total
+    }
+
+    /// Returns the statistics as a JSON object
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("total", self.total().to_json()),
+            (
+                "sections",
+                Json::Array(
+                    self.sections
+                        .iter()
+                        .map(|section| {
+                            Json::object([
+                                ("command", section.command.clone().into()),
+                                ("title", section.title.as_str().into()),
+                                ("line", section.line.into()),
+                                ("counts", section.counts.to_json()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    /// Returns a report in the style of texcount
+    pub fn report(&self) -> String {
+        let total = self.total();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "Words in text: {}", total.words_text);
+        let _ = writeln!(out, "Words in headers: {}", total.words_headers);
+        let _ = writeln!(
+            out,
+            "Words outside text (captions, etc.): {}",
+            total.words_captions
+        );
+        let _ = writeln!(out, "Number of headers: {}", total.headers);
+        let _ = writeln!(
+            out,
+            "Number of floats/tables/figures: {} (figures {}, tables {})",
+            total.figures + total.tables,
+            total.figures,
+            total.tables
+        );
+        let _ = writeln!(out, "Number of math inlines: {}", total.maths_inline);
+        let _ = writeln!(out, "Number of math displayed: {}", total.maths_display);
+        let _ = writeln!(out, "Number of citations: {}", total.citations);
+        let _ = writeln!(out, "Number of footnotes: {}", total.footnotes);
+        let _ = writeln!(out, "Subcounts:");
+        let _ = writeln!(
+            out,
+            "  text+headers+captions (#headers/#floats/#inlines/#displayed)"
+        );
+
+        for section in self.sections.iter() {
+            let counts = &section.counts;
+            let _ = write!(
+                out,
+                "  {}+{}+{} ({}/{}/{}/{})",
+                counts.words_text,
+                counts.words_headers,
+                counts.words_captions,
+                counts.headers,
+                counts.figures + counts.tables,
+                counts.maths_inline,
+                counts.maths_display
+            );
+
+            let _ = match &section.command {
+                Some(command) => writeln!(out, " {command}: {}", section.title),
+                None => writeln!(out, " _top_"),
+            };
+        }
+
+        out
+    }
+}
+
+/// Counts the statistics of documents
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Counter {
+    ignored: Vec<Range<u32>>,
+}
+
+impl Counter {
+    /// Create a counter which counts everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a counter which skips the lines between `%TC:ignore` and `%TC:endignore`
+    /// in the source the document was parsed from
+    pub fn with_source(source: &str) -> Self {
+        let mut ignored = Vec::new();
+        let mut start = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let line_no = i as u32 + 1;
+            let magic = match line.split_once('%') {
+                Some((_, comment)) => comment.trim(),
+                None => continue,
+            };
+
+            match magic {
+                "TC:ignore" if start.is_none() => start = Some(line_no),
+                "TC:endignore" => {
+                    if let Some(start) = start.take() {
+                        ignored.push(start..line_no + 1)
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(start) = start {
+            ignored.push(start..u32::MAX)
+        }
+
+        Self { ignored }
+    }
+
+    /// Count a document
+    pub fn count(&self, document: &ast3::Document) -> Statistics {
+        let mut walker = Walker {
+            counter: self,
+            sections: vec![Section {
+                command: None,
+                title: String::new(),
+                line: 1,
+                counts: Counts::default(),
+            }],
+            line: 1,
+            category: Category::Text,
+            conditions: Conditions::from_document(document),
+        };
+
+        if document.has_body() {
+            walker.skip(
+                &document
+                    .chunks_preamable()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<String>(),
+            );
+            walker.skip(&format!(
+                "\\begin{}{{document}}",
+                document.body_begin_prec()
+            ));
+            document
+                .body_args()
+                .iter()
+                .for_each(|(prec, arg)| walker.skip(&format!("{prec}{arg}")));
+            walker.chunks(document.chunks_body());
+        } else {
+            walker.chunks(document.chunks_preamable());
+        }
+
+        Statistics {
+            sections: walker.sections,
+        }
+    }
+
+    fn is_ignored(&self, line: u32) -> bool {
+        self.ignored.iter().any(|range| range.contains(&line))
+    }
+}
+
+// what words are counted as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Text,
+    Header,
+    Caption,
+}
+
+struct Walker<'a> {
+    counter: &'a Counter,
+    sections: Vec<Section>,
+    line: u32,
+    category: Category,
+    conditions: Conditions,
+}
+
+impl Walker<'_> {
+    fn counts(&mut self) -> Option<&mut Counts> {
+        if self.counter.is_ignored(self.line) {
+            return None;
+        }
+
+        self.sections.last_mut().map(|section| &mut section.counts)
+    }
+
+    fn skip(&mut self, s: &str) {
+        self.line += s.matches('\n').count() as u32
+    }
+
+    fn chunks(&mut self, chunks: &[Chunk]) {
+        let mut i = 0;
+
+        while let Some(chunk) = chunks.get(i) {
+            i += 1;
+
+            match chunk.variant() {
+                ChunkVariant::Text(s) => self.text(s),
+                ChunkVariant::MathsBlock(block) => {
+                    if let Some(counts) = self.counts() {
+                        match block.r#type() {
+                            MathsType::Inline => counts.maths_inline += 1,
+                            MathsType::Outline => counts.maths_display += 1,
+                        }
+                    }
+                    self.skip(&block.to_string())
+                }
+                ChunkVariant::Command(command) => self.command(command, chunks, &mut i),
+                ChunkVariant::Scope(scope) => self.curly(scope),
+                ChunkVariant::Environment(env) => self.environment(env),
+                ChunkVariant::ParagraphBreak(s) => self.skip(s),
+                ChunkVariant::Conditional(conditional) => self.conditional(conditional),
+                ChunkVariant::Parameter(_) => {}
+                ChunkVariant::MacroDefinition(definition) => self.skip(&definition.to_string()),
+            }
+        }
+    }
+
+    // only the branch TeX reads is counted
+    fn conditional(&mut self, conditional: &ast3::Conditional) {
+        let start = Position::new(self.line, 1);
+        let branch = self.conditions.evaluate_or_content(conditional);
+
+        let chosen = conditional
+            .branches(start)
+            .into_iter()
+            .find(|(_, chunks)| std::ptr::eq(chunks.as_slice(), branch));
+        if let Some((position, chunks)) = chosen {
+            self.line = position.line;
+            self.chunks(chunks);
+        }
+
+        self.line = start.after(&conditional.to_string()).line;
+    }
+
+    fn text(&mut self, s: &str) {
+        for line in s.split_inclusive('\n') {
+            let words = line
+                .split(|c: char| c.is_whitespace() || c == '~')
+                .filter(|word| word.chars().any(char::is_alphanumeric))
+                .count() as u32;
+            let category = self.category;
+
+            if let Some(counts) = self.counts() {
+                match category {
+                    Category::Text => counts.words_text += words,
+                    Category::Header => counts.words_headers += words,
+                    Category::Caption => counts.words_captions += words,
+                }
+            }
+
+            self.skip(line)
+        }
+    }
+
+    // content of a scope, counted in the current category
+    fn curly(&mut self, scope: &ast3::Scope) {
+        self.chunks(scope.chunks())
+    }
+
+    // `i` is the index of the chunk after the command, and is moved past the title of a
+    // starred heading
+    fn command(&mut self, command: &ast3::Command, chunks: &[Chunk], i: &mut usize) {
+        let label = command.label().as_str();
+        let source = command.to_string();
+
+        if let Some(level) = sectioning::level(label) {
+            let starred = match last_curly(command) {
+                Some(_) => None,
+                None => sectioning::starred(chunks, *i),
+            };
+            let title = last_curly(command)
+                .or(starred)
+                .map(|arg| {
+                    arg.chunks()
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<String>()
+                })
+                .unwrap_or_default();
+
+            if !self.counter.is_ignored(self.line) {
+                self.sections.push(Section {
+                    command: Some(sectioning::COMMANDS[level].to_string()),
+                    title: title.trim().to_string(),
+                    line: self.line,
+                    counts: Counts {
+                        headers: 1,
+                        ..Counts::default()
+                    },
+                });
+            }
+
+            if let Some(title) = starred {
+                *i += 2;
+                self.skip(&source);
+
+                let outer = std::mem::replace(&mut self.category, Category::Header);
+                self.curly(title);
+                self.category = outer;
+                return;
+            }
+
+            return self.argument(command, Category::Header);
+        }
+
+        match label {
+            "caption" => self.argument(command, Category::Caption),
+            "footnote" => {
+                if let Some(counts) = self.counts() {
+                    counts.footnotes += 1
+                }
+                self.argument(command, self.category)
+            }
+            _ if INLINE_COMMANDS.contains(&label) => self.argument(command, self.category),
+            _ if CITE_COMMANDS.contains(&label) => {
+                let keys = last_curly(command)
+                    .map(|arg| {
+                        arg.chunks()
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<String>()
+                            .split(',')
+                            .filter(|key| !key.trim().is_empty())
+                            .count() as u32
+                    })
+                    .unwrap_or(0);
+
+                if let Some(counts) = self.counts() {
+                    counts.citations += keys
+                }
+                self.skip(&source)
+            }
+            _ => self.skip(&source),
+        }
+    }
+
+    // count the last curly argument of a command, skipping the rest
+    fn argument(&mut self, command: &ast3::Command, category: Category) {
+        let last = command
+            .arguments()
+            .iter()
+            .rposition(|(_, arg)| arg.variant() == ScopeVariant::Curly);
+
+        for (i, (prec, arg)) in command.arguments().iter().enumerate() {
+            self.skip(prec);
+
+            if Some(i) == last {
+                let outer = std::mem::replace(&mut self.category, category);
+                self.curly(arg);
+                self.category = outer;
+            } else {
+                self.skip(&arg.to_string())
+            }
+        }
+    }
+
+    fn environment(&mut self, env: &ast3::Environment) {
+        let label = env.label().as_str();
+
+        // the alignment environments after `array` are maths
+        if MATHS.contains(&label) || ALIGNED[6..].contains(&label) {
+            if let Some(counts) = self.counts() {
+                counts.maths_display += 1
+            }
+            return self.skip(&env.to_string());
+        }
+
+        if VERBATIM.contains(&label) || NON_PROSE.contains(&label) {
+            return self.skip(&env.to_string());
+        }
+
+        if let Some(counts) = self.counts() {
+            match label {
+                "figure" | "figure*" => counts.figures += 1,
+                "table" | "table*" => counts.tables += 1,
+                _ => {}
+            }
+        }
+
+        self.skip(&format!("\\begin{}{{{label}}}", env.prec_begin()));
+        env.arguments()
+            .iter()
+            .for_each(|(prec, arg)| self.skip(&format!("{prec}{arg}")));
+        self.chunks(env.content());
+        self.skip(&format!("\\end{}{{{label}}}", env.prec_end()));
+    }
+}
+
+fn last_curly(command: &ast3::Command) -> Option<&ast3::Scope> {
+    command
+        .arguments()
+        .iter()
+        .rev()
+        .find(|(_, arg)| arg.variant() == ScopeVariant::Curly)
+        .map(|(_, arg)| arg)
+}
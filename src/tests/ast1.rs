@@ -3,7 +3,7 @@ use std::str::FromStr;
 use crate::{
     ast1::{Chunk, ChunkVariant, Command, Document, Scope, ScopeVariant},
     traits::Lines,
-    InternalError,
+    InternalError, Position,
 };
 
 #[test]
@@ -27,7 +27,7 @@ Test
     assert_eq!(
         ast,
         Err(crate::Error::new(
-            7,
+            Position::new(7, 21),
             crate::ErrorType::UnexpectedClosing(ScopeVariant::Square)
         ))
     )
@@ -54,9 +54,10 @@ test
     assert_eq!(
         ast,
         Err(crate::Error::new(
-            8,
+            Position::new(8, 17),
             crate::ErrorType::UnclosedArgument(ScopeVariant::Square)
-        ))
+        )
+        .with_related(Position::new(9, 5)))
     )
 }
 
@@ -79,9 +80,10 @@ fn unclosed_scope() {
     assert_eq!(
         ast,
         Err(crate::Error::new(
-            6,
+            Position::new(6, 9),
             crate::ErrorType::UnclosedScope(ScopeVariant::Square)
-        ))
+        )
+        .with_related(Position::new(7, 5)))
     )
 }
 
@@ -304,3 +306,150 @@ Bye!
     assert_eq!(dbg!(ast.chunks()), dbg!(&expected));
     assert_eq!(dbg!(ast.to_string()), content.to_string());
 }
+
+#[test]
+fn nested_position() {
+    let content = "text {a {b [c} d}";
+
+    let ast = Document::from_str(content);
+
+    assert_eq!(
+        ast,
+        Err(crate::Error::new(
+            Position::new(1, 12),
+            crate::ErrorType::UnclosedScope(ScopeVariant::Square)
+        )
+        .with_related(Position::new(1, 14)))
+    )
+}
+
+#[test]
+fn round_trip() {
+    for content in [
+        r"\textbf{a{b}c} d",
+        r"\alpha, \beta$$ (round) [square]",
+        r"\cmd[opt]{a {b} c}(d)",
+        "\\cmd\n\\[ x \\]",
+        r"\cmd \{a\}",
+    ] {
+        assert_eq!(Document::from_str(content).unwrap().to_string(), content);
+    }
+}
+
+#[test]
+fn nested_arguments() {
+    let content = r"\cmd(a (b) c)[d [e] f] (g)";
+
+    let ast = Document::from_str(content).unwrap();
+
+    assert_eq!(
+        ast.chunks(),
+        &vec![Chunk::new(
+            1,
+            ChunkVariant::Command(Command::new_unchecked(
+                "cmd".to_string(),
+                vec![
+                    (
+                        String::new(),
+                        Scope::new_unchecked(
+                            vec![
+                                Chunk::new_unchecked(1, ChunkVariant::Text("a ".to_string())),
+                                Chunk::new_unchecked(
+                                    1,
+                                    ChunkVariant::Scope(Scope::new_unchecked(
+                                        vec![Chunk::new_unchecked(
+                                            1,
+                                            ChunkVariant::Text("b".to_string())
+                                        )],
+                                        ScopeVariant::Round
+                                    ))
+                                ),
+                                Chunk::new_unchecked(1, ChunkVariant::Text(" c".to_string())),
+                            ],
+                            ScopeVariant::Round
+                        )
+                    ),
+                    (
+                        String::new(),
+                        Scope::new_unchecked(
+                            vec![
+                                Chunk::new_unchecked(1, ChunkVariant::Text("d ".to_string())),
+                                Chunk::new_unchecked(
+                                    1,
+                                    ChunkVariant::Scope(Scope::new_unchecked(
+                                        vec![Chunk::new_unchecked(
+                                            1,
+                                            ChunkVariant::Text("e".to_string())
+                                        )],
+                                        ScopeVariant::Square
+                                    ))
+                                ),
+                                Chunk::new_unchecked(1, ChunkVariant::Text(" f".to_string())),
+                            ],
+                            ScopeVariant::Square
+                        )
+                    ),
+                    (
+                        " ".to_string(),
+                        Scope::new_unchecked(
+                            vec![Chunk::new_unchecked(1, ChunkVariant::Text("g".to_string()))],
+                            ScopeVariant::Round
+                        )
+                    ),
+                ]
+            ))
+        )
+        .unwrap()]
+    );
+    assert_eq!(ast.to_string(), content);
+}
+
+#[test]
+fn label_termination() {
+    let content = r"\alpha, \beta2 \\x";
+
+    let ast = Document::from_str(content).unwrap();
+
+    assert_eq!(
+        ast.chunks(),
+        &vec![
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Command(Command::new_unchecked("alpha".to_string(), Vec::new()))
+            ),
+            Chunk::new_unchecked(1, ChunkVariant::Text(", ".to_string())),
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Command(Command::new_unchecked("beta".to_string(), Vec::new()))
+            ),
+            Chunk::new_unchecked(1, ChunkVariant::Text("2 ".to_string())),
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Command(Command::new_unchecked("\\".to_string(), Vec::new()))
+            ),
+            Chunk::new_unchecked(1, ChunkVariant::Text("x".to_string())),
+        ]
+    );
+    assert_eq!(ast.to_string(), content);
+}
+
+#[test]
+fn escaped_bracket() {
+    let content = "\\cmd\n\\[ x \\]";
+
+    let ast = Document::from_str(content).unwrap();
+
+    let command =
+        |label: &str| ChunkVariant::Command(Command::new_unchecked(label.to_string(), Vec::new()));
+    assert_eq!(
+        ast.chunks(),
+        &vec![
+            Chunk::new_unchecked(1, command("cmd")),
+            Chunk::new_unchecked(1, ChunkVariant::Text("\n".to_string())),
+            Chunk::new_unchecked(2, command("[")),
+            Chunk::new_unchecked(2, ChunkVariant::Text(" x ".to_string())),
+            Chunk::new_unchecked(2, command("]")),
+        ]
+    );
+    assert_eq!(ast.to_string(), content);
+}
@@ -3,8 +3,8 @@ use std::str::FromStr;
 use crate::{
     ast1,
     ast2::{Chunk, ChunkVariant, Command, Document, Environment, Scope, ScopeVariant},
-    traits::Lines,
-    InternalError,
+    traits::{Lines, Validate},
+    InternalError, Position,
 };
 
 #[test]
@@ -25,7 +25,10 @@ fn no_environment_label() {
 
     assert_eq!(
         ast,
-        Err(crate::Error::new(5, crate::ErrorType::NoEnvironmentLabel))
+        Err(crate::Error::new(
+            Position::new(5, 5),
+            crate::ErrorType::NoEnvironmentLabel
+        ))
     )
 }
 
@@ -47,12 +50,67 @@ fn unexpected_end() {
     assert_eq!(
         ast,
         Err(crate::Error::new(
-            6,
+            Position::new(6, 5),
             crate::ErrorType::UnexpectedEnd("itemize".to_string())
-        ))
+        )
+        .with_related(Position::new(3, 1)))
     )
 }
 
+#[test]
+fn unpaired_in_group() {
+    // a `\begin` or `\end` without its pair in an argument or macro body is a plain command
+    for content in [
+        r"\newenvironment{x}{\begin{itemize}}{\end{itemize}}",
+        r"\newcommand{\bi}{\begin{itemize}}",
+        r"\def\x{\begin{itemize}}",
+    ] {
+        let ast = Document::from_str(content).unwrap();
+        assert_eq!(ast.to_string(), content);
+        ast.validate().unwrap();
+
+        let ast = crate::ast3::Document::from_str(content).unwrap();
+        assert_eq!(ast.to_string(), content);
+        ast.validate().unwrap();
+    }
+
+    let ast = Document::from_str(r"\newcommand{\bi}{\begin{itemize}}").unwrap();
+    let ChunkVariant::Command(command) = ast.chunks()[0].variant() else {
+        panic!("not a command")
+    };
+    assert_eq!(
+        command.arguments()[1].1.chunks(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Command(Command::new_unchecked(
+                "begin".to_string(),
+                vec![(
+                    String::new(),
+                    Scope::new_unchecked(
+                        vec![Chunk::new_unchecked(
+                            1,
+                            ChunkVariant::Text("itemize".to_string())
+                        )],
+                        ScopeVariant::Curly
+                    )
+                )]
+            ))
+        )]
+    );
+
+    // environments are not paired across a group
+    let content = r"\begin{document}{\begin{itemize}} \end{itemize}\end{document}";
+
+    assert_eq!(
+        Document::from_str(content),
+        Err(crate::Error::new(
+            Position::new(1, 35),
+            crate::ErrorType::UnexpectedEnd("itemize".to_string())
+        )
+        .with_related(Position::new(1, 1)))
+    );
+}
+
 #[test]
 fn too_many_args_end() {
     let content = r#"
@@ -71,7 +129,10 @@ fn too_many_args_end() {
 
     assert_eq!(
         ast,
-        Err(crate::Error::new(7, crate::ErrorType::TooManyArgsEnd))
+        Err(crate::Error::new(
+            Position::new(7, 5),
+            crate::ErrorType::TooManyArgsEnd
+        ))
     )
 }
 
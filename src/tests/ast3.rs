@@ -1,4 +1,4 @@
-use std::{collections::HashMap, str::FromStr};
+use std::str::FromStr;
 
 use ast3::{Chunk, Command, Environment, MathsBlock, Scope};
 
@@ -206,3 +206,178 @@ fn paragraphbreak_nonwhitespace() {
         ast3::Chunk::new(1, ast3::ChunkVariant::Text("\n\n".to_string()))
     );
 }
+
+#[test]
+fn unclosed_maths() {
+    let content = r#"
+\begin{document}
+    \begin{itemize}
+        \item $\alpha$ and $\beta
+    \end{itemize}
+\end{document}
+"#
+    .trim();
+
+    assert_eq!(
+        ast3::Document::from_str(content),
+        Err(
+            crate::Error::new(Position::new(3, 28), crate::ErrorType::UnclosedMaths)
+                .with_related(Position::new(4, 5))
+        )
+    );
+}
+
+#[test]
+fn unexpected_maths_end() {
+    let content = "\\begin{document}\n  a {b \\) c}\n\\end{document}";
+
+    assert_eq!(
+        ast3::Document::from_str(content),
+        Err(crate::Error::new(
+            Position::new(2, 8),
+            crate::ErrorType::UnexpectedMathsEnd
+        ))
+    );
+}
+
+#[test]
+fn double_documentclass() {
+    let content = "\\documentclass{article}\n  \\documentclass{book}";
+
+    assert_eq!(
+        ast3::Document::from_str(content),
+        Err(
+            crate::Error::new(Position::new(2, 3), crate::ErrorType::DoubleDocumentClass)
+                .with_related(Position::new(1, 1))
+        )
+    );
+}
+
+#[test]
+fn inline_maths() {
+    let content = "\\begin{document}a $b \\alpha c$ d\\end{document}";
+
+    let ast = ast3::Document::from_str(content).unwrap();
+
+    assert_eq!(
+        ast.chunks_body(),
+        &vec![
+            Chunk::new(1, ast3::ChunkVariant::Text("a ".to_string())).unwrap(),
+            Chunk::new(
+                1,
+                ast3::ChunkVariant::MathsBlock(
+                    MathsBlock::new(
+                        ast3::MathsVariant::Dollars,
+                        ast3::MathsType::Inline,
+                        vec![
+                            Chunk::new(1, ast3::ChunkVariant::Text("b ".to_string())).unwrap(),
+                            Chunk::new(
+                                1,
+                                ast3::ChunkVariant::Command(
+                                    Command::new("alpha".to_string(), Vec::new()).unwrap()
+                                )
+                            )
+                            .unwrap(),
+                            Chunk::new(1, ast3::ChunkVariant::Text(" c".to_string())).unwrap(),
+                        ]
+                    )
+                    .unwrap()
+                )
+            )
+            .unwrap(),
+            Chunk::new(1, ast3::ChunkVariant::Text(" d".to_string())).unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn maths_leading_text() {
+    let content = "\\begin{document}x $a$ y $$b$$ z\\end{document}";
+
+    let ast = ast3::Document::from_str(content).unwrap();
+
+    let maths = |variant: ast3::MathsType, text: &str| {
+        Chunk::new(
+            1,
+            ast3::ChunkVariant::MathsBlock(
+                MathsBlock::new(
+                    ast3::MathsVariant::Dollars,
+                    variant,
+                    vec![Chunk::new(1, ast3::ChunkVariant::Text(text.to_string())).unwrap()],
+                )
+                .unwrap(),
+            ),
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        ast.chunks_body(),
+        &vec![
+            Chunk::new(1, ast3::ChunkVariant::Text("x ".to_string())).unwrap(),
+            maths(ast3::MathsType::Inline, "a"),
+            Chunk::new(1, ast3::ChunkVariant::Text(" y ".to_string())).unwrap(),
+            maths(ast3::MathsType::Outline, "b"),
+            Chunk::new(1, ast3::ChunkVariant::Text(" z".to_string())).unwrap(),
+        ]
+    );
+    assert_eq!(ast.to_string(), content);
+}
+
+#[test]
+fn maths_single_dollar_in_double() {
+    // a `$` inside `$$` maths stays before a command or scope right after it
+    for content in [
+        "$$a $\\alpha$ b$$",
+        "#1$$$\\section*{x}$~$$",
+        "$$a ${b}$ c$$",
+        "$$a $b$ c$$",
+    ] {
+        let ast = ast3::Document::from_str(content).unwrap();
+        assert_eq!(ast.to_string(), content);
+    }
+}
+
+#[test]
+fn document_options() {
+    let content = r#"\documentclass[ a4paper , font = 11pt,draft]{article}"#;
+
+    let ast = ast3::Document::from_str(content).unwrap();
+
+    assert_eq!(
+        ast.documentoptions(),
+        &vec![
+            (
+                " ".to_string(),
+                "a4paper".to_string(),
+                None,
+                " ".to_string()
+            ),
+            (
+                " ".to_string(),
+                "font".to_string(),
+                Some((" ".to_string(), " ".to_string(), "11pt".to_string())),
+                String::new()
+            ),
+            (String::new(), "draft".to_string(), None, String::new()),
+        ]
+    );
+    assert_eq!(ast.to_string(), content);
+}
+
+#[test]
+fn display_body() {
+    // the body is closed with the whitespace of its own `\end`
+    let content = "\\documentclass{article}\n\\begin {document}x\\end  {document}\n";
+    let ast = ast3::Document::from_str(content).unwrap();
+    assert!(ast.has_body());
+    assert_eq!(ast.body_begin_prec(), " ");
+    assert_eq!(ast.body_end_prec(), "  ");
+    assert_eq!(ast.to_string(), content);
+
+    // a document without a body is not given one
+    let content = "\\documentclass{article}\ntext";
+    let ast = ast3::Document::from_str(content).unwrap();
+    assert!(!ast.has_body());
+    assert_eq!(ast.to_string(), content);
+}
@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3::{self, escape, ChunkVariant, DocumentBuilder},
+    traits::Validate,
+};
+
+#[test]
+fn escaping() {
+    let text = "50% & $5 #1 a_b {x} ~ ^ \\ <a|b> so (ok) [ok] (open [x) y]\n\nnext";
+    let chunks = escape(text);
+    let latex = chunks.iter().map(ToString::to_string).collect::<String>();
+
+    assert_eq!(
+        latex,
+        "50\\% \\& \\$5 \\#1 a\\_b \\{x\\} \\textasciitilde{} \\textasciicircum{} \\textbackslash{} \
+         \\textless{}a\\textbar{}b\\textgreater{} so (ok) [ok] \\textparenleft{}open [x\\textparenright{} y]\n\nnext"
+    );
+
+    for chunk in &chunks {
+        assert!(chunk.validate().is_ok());
+    }
+    assert!(matches!(
+        chunks[chunks.len() - 2].variant(),
+        ChunkVariant::ParagraphBreak(_)
+    ));
+    assert_eq!(chunks.last().unwrap().line_no(), 3);
+
+    // the chunks are what the parser reads from their source
+    let document = ast3::Document::from_str(&latex).unwrap();
+    assert_eq!(document.chunks_preamable(), &chunks);
+
+    // brackets after a command are not its arguments
+    let chunks = escape("[a] 5% (b)");
+    let latex = chunks.iter().map(ToString::to_string).collect::<String>();
+    assert_eq!(
+        latex,
+        "\\lbrack{}a\\rbrack{} 5\\% \\textparenleft{}b\\textparenright{}"
+    );
+    assert_eq!(
+        ast3::Document::from_str(&latex).unwrap().chunks_preamable(),
+        &chunks
+    );
+}
+
+#[test]
+fn document_builder() {
+    let document = DocumentBuilder::article()
+        .option("11pt")
+        .usepackage("amsmath")
+        .usepackage("amsmath")
+        .title("Costs & {Sales}")
+        .maketitle()
+        .section("Intro_1")
+        .paragraph("Up 5%\nthis year.\n\nAnd more.")
+        .itemize(["a & b", "c"])
+        .build();
+
+    let latex = document.to_string();
+    assert_eq!(
+        latex,
+        "\\documentclass[11pt]{article}\n\\usepackage{amsmath}\n\\title{Costs \\& \\{Sales\\}}\n\n\
+         \\begin{document}\n\\maketitle\n\n\\section{Intro\\_1}\n\n\
+         Up 5\\%\nthis year.\n\nAnd more.\n\n\
+         \\begin{itemize}\n  \\item a \\& b\n  \\item c\n\\end{itemize}\n\
+         \\end{document}\n"
+    );
+    assert!(document.validate().is_ok());
+    assert_eq!(
+        document.documentoptions(),
+        &vec![(String::new(), "11pt".to_string(), None, String::new())]
+    );
+
+    let reparsed = ast3::Document::from_str(&latex).unwrap();
+    assert_eq!(reparsed.chunks_preamable(), document.chunks_preamable());
+    assert_eq!(reparsed.chunks_body(), document.chunks_body());
+    assert_eq!(reparsed.chunks_trailing(), document.chunks_trailing());
+    assert_eq!(reparsed.documentclass(), document.documentclass());
+
+    let document = DocumentBuilder::article().paragraph("x").build();
+    assert_eq!(
+        ast3::Document::from_str(&document.to_string()).unwrap(),
+        document
+    );
+
+    // options parse back to the same keys and values
+    let document = DocumentBuilder::article()
+        .option("a4paper")
+        .option("font=11pt")
+        .paragraph("x")
+        .build();
+    assert_eq!(
+        ast3::Document::from_str(&document.to_string()).unwrap(),
+        document
+    );
+
+    // brackets at the start of a block are not read as arguments of the command before
+    let documents = [
+        DocumentBuilder::article().section("x").paragraph("[a] b"),
+        DocumentBuilder::article().itemize(["[a] b", "(c) d"]),
+        DocumentBuilder::article().paragraph("50% [x]"),
+        DocumentBuilder::article().paragraph(""),
+        DocumentBuilder::article().section("a\n\nb").paragraph(""),
+        DocumentBuilder::article().paragraph("a\r\n\r\nb"),
+        DocumentBuilder::article().paragraph("a \n\n[b] c"),
+    ];
+
+    for builder in documents {
+        let document = builder.build();
+        assert_eq!(
+            ast3::Document::from_str(&document.to_string()).unwrap(),
+            document
+        );
+    }
+
+    let document = DocumentBuilder::article()
+        .section("x")
+        .paragraph("[a] b")
+        .build();
+    assert!(document
+        .to_string()
+        .contains("\\section{x}\n\n\\lbrack{}a\\rbrack{} b"));
+}
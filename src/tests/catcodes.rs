@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use crate::{
+    ast1::{self, Catcodes, ChunkVariant, EventKind, Events},
+    ast3, ParseLimits, Position,
+};
+
+// labels of the commands and the text at the top level
+fn chunks(document: &ast1::Document) -> Vec<String> {
+    document
+        .chunks()
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Command(command) => format!("\\{}", command.label()),
+            ChunkVariant::Text(text) => text.clone(),
+            ChunkVariant::Scope(_) => "{}".to_string(),
+        })
+        .collect()
+}
+
+fn parse(source: &str, catcodes: Catcodes) -> ast1::Document {
+    ast1::Document::parse_with_catcodes(source, ParseLimits::default(), catcodes).unwrap()
+}
+
+#[test]
+fn at_letter() {
+    let document = ast1::Document::from_str("\\makeatletter\\my@macro\\makeatother\\a@b").unwrap();
+    assert_eq!(
+        chunks(&document),
+        vec!["\\makeatletter", "\\my@macro", "\\makeatother", "\\a", "@b"]
+    );
+
+    // changes are not undone at the end of a group
+    let document = ast1::Document::from_str("{\\makeatletter}\\a@b").unwrap();
+    assert_eq!(chunks(&document), vec!["{}", "\\a@b"]);
+}
+
+#[test]
+fn for_path() {
+    assert!(Catcodes::for_path("x.sty").at_letter());
+    assert!(Catcodes::for_path("/a/b.cls").at_letter());
+    assert_eq!(Catcodes::for_path("x.tex"), Catcodes::default());
+
+    let source = "\\@ifnextchar*\\a\\b";
+    assert_eq!(
+        chunks(&parse(source, Catcodes::for_path("x.sty"))),
+        vec!["\\@ifnextchar", "*", "\\a", "\\b"]
+    );
+    assert_eq!(
+        chunks(&parse(source, Catcodes::for_path("x.tex"))),
+        vec!["\\@", "ifnextchar*", "\\a", "\\b"]
+    );
+}
+
+#[test]
+fn expl_syntax() {
+    let document = ast1::Document::from_str(
+        "\\tl_set:Nn\\ExplSyntaxOn\\tl_set:Nn \\l_tmpa_tl\\ExplSyntaxOff\\a_b",
+    )
+    .unwrap();
+    assert_eq!(
+        chunks(&document),
+        vec![
+            "\\tl",
+            "_set:Nn",
+            "\\ExplSyntaxOn",
+            "\\tl_set:Nn",
+            " ",
+            "\\l_tmpa_tl",
+            "\\ExplSyntaxOff",
+            "\\a",
+            "_b"
+        ]
+    );
+
+    let catcodes = Catcodes::new().with_expl_syntax(true);
+    assert!(catcodes.is_letter(':') && !catcodes.is_letter('@'));
+    assert_eq!(chunks(&parse("\\a:b", catcodes)), vec!["\\a:b"]);
+}
+
+#[test]
+fn events() {
+    let labels = |events: Events<&[u8]>| {
+        events
+            .filter_map(|event| match event.kind {
+                EventKind::CommandStart(label) => Some(label),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let source = "\\a@b\\makeatother\\c@d";
+    assert_eq!(
+        labels(Events::new(source.as_bytes()).with_catcodes(Catcodes::for_path("x.sty"))),
+        vec!["a@b", "makeatother", "c"]
+    );
+    assert_eq!(
+        labels(Events::new(source.as_bytes())),
+        vec!["a", "makeatother", "c"]
+    );
+}
+
+#[test]
+fn reparse() {
+    let source = "\\begin{document}\n\\makeatletter\n\\a@b\n\\end{document}";
+
+    // an edit after a change in catcodes
+    let mut document = ast3::Document::from_str(source).unwrap();
+    document
+        .reparse(Position::new(3, 5), Position::new(3, 5), "\\c@d")
+        .unwrap();
+    assert_eq!(
+        document,
+        ast3::Document::from_str(&document.to_string()).unwrap()
+    );
+
+    // an edit adding a change in catcodes
+    let source = "\\begin{document}\n\\a@b\n\n\\c@d\n\\end{document}";
+    let mut document = ast3::Document::from_str(source).unwrap();
+    document
+        .reparse(Position::new(2, 1), Position::new(2, 1), "\\makeatletter")
+        .unwrap();
+    assert_eq!(
+        document,
+        ast3::Document::from_str(&document.to_string()).unwrap()
+    );
+}
@@ -0,0 +1,140 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3,
+    completion::{BibEntry, Completer, CompletionKind, Context},
+    Position,
+};
+
+fn labels(completions: &[crate::completion::Completion]) -> Vec<&str> {
+    completions.iter().map(|c| c.label().as_str()).collect()
+}
+
+#[test]
+fn context() {
+    assert_eq!(
+        Context::detect("text \\sec"),
+        Some(Context::Command("sec".to_string()))
+    );
+    assert_eq!(Context::detect("line\\\\"), None);
+    assert_eq!(
+        Context::detect("\\begin{fig"),
+        Some(Context::Begin("fig".to_string()))
+    );
+    assert_eq!(
+        Context::detect("\\begin{a}\\begin{b}\\end{b}\n% \\begin{c}\n\\end{"),
+        Some(Context::End(String::new(), Some("a".to_string())))
+    );
+    assert_eq!(
+        Context::detect("\\cref{sec:a, fi"),
+        Some(Context::Ref("fi".to_string()))
+    );
+    assert_eq!(
+        Context::detect("\\citep[p.~3]{kn"),
+        Some(Context::Cite("kn".to_string()))
+    );
+    assert_eq!(Context::detect("\\textbf{x"), None);
+}
+
+#[test]
+fn complete() {
+    let content = r#"
+\documentclass{article}
+\newcommand{\vect}[1]{\mathbf{#1}}
+\newcommand\half{\frac{1}{2}}
+\newenvironment{note}{}{}
+\begin{document}
+\section{Intro}\label{sec:intro}
+\begin{figure}
+    \caption{A}\label{fig:a}
+\end{figure}
+\begin{itemize}
+    \item \ref{}
+\end{itemize}
+\begin{thebibliography}{9}
+\bibitem{knuth} Knuth
+\end{thebibliography}
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let completer = Completer::new();
+
+    let commands = completer.complete_after(&document, "\\ve");
+    assert_eq!(labels(&commands), vec!["vect"]);
+    assert_eq!(commands[0].kind(), CompletionKind::Command);
+    assert_eq!(
+        commands[0].detail(),
+        "\\newcommand with 1 arguments, line 2"
+    );
+    assert_eq!(
+        labels(&completer.complete_after(&document, "\\hal")),
+        vec!["half"]
+    );
+
+    let environments = completer.complete_after(&document, "\\begin{no");
+    assert_eq!(labels(&environments), vec!["note"]);
+    assert_eq!(environments[0].detail(), "\\newenvironment, line 4");
+
+    // the cursor inside `\ref{}`
+    let references = completer.complete(&document, Position::new(11, 16));
+    assert_eq!(labels(&references), vec!["fig:a", "sec:intro"]);
+    assert_eq!(references[0].detail(), "label in figure, line 8");
+    assert_eq!(references[1].detail(), "label on line 6");
+
+    let end = completer.complete_after(&document, "\\begin{itemize}\n\\end{");
+    assert_eq!(labels(&end), vec!["itemize"]);
+}
+
+#[test]
+fn citations() {
+    let content = r#"
+\documentclass{article}
+\newcommand{\vect}[1]{\mathbf{#1}}
+\newcommand\half{\frac{1}{2}}
+\newenvironment{note}{}{}
+\begin{document}
+\section{Intro}\label{sec:intro}
+\begin{figure}
+    \caption{A}\label{fig:a}
+\end{figure}
+\begin{itemize}
+    \item \ref{}
+\end{itemize}
+\begin{thebibliography}{9}
+\bibitem{knuth} Knuth
+\end{thebibliography}
+\end{document}
+"#
+    .trim();
+
+    let bib = r#"
+@string{j = "Journal"}
+@article{lamport94,
+    author = {Leslie Lamport},
+    title = {{LaTeX}: A Document
+             Preparation System},
+    journal = j # " of Things",
+}
+@book(knuth84, title = "The {\TeX}book")
+"#;
+
+    let entries = BibEntry::parse_all(bib);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].key(), "lamport94");
+    assert_eq!(
+        entries[0].title().unwrap(),
+        "LaTeX: A Document Preparation System"
+    );
+    assert_eq!(entries[1].r#type(), "book");
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let mut completer = Completer::new();
+    completer.add_bib(bib);
+
+    let citations = completer.complete_after(&document, "\\cite{");
+    assert_eq!(labels(&citations), vec!["knuth", "knuth84", "lamport94"]);
+    assert_eq!(citations[0].detail(), "bibitem on line 14");
+    assert_eq!(citations[1].detail(), "@book The \\TeXbook");
+}
@@ -0,0 +1,508 @@
+use std::str::FromStr;
+
+use crate::{
+    ast1, ast2,
+    ast3::{self, Chunk, ChunkVariant, Conditions},
+    html::Renderer,
+    plaintext::Extractor,
+    statistics::Counter,
+    traits::Validate,
+    unicode, Error, ErrorType, Limit, ParseLimits, Position,
+};
+
+#[test]
+fn structure() {
+    let content = r#"
+\documentclass{article}
+\newif\ifdraft
+\drafttrue
+\begin{document}
+\ifdraft Draft copy.\else Final copy.\fi
+
+\ifnum 1<2 less \else more\fi
+\ifcase 2 zero\or one\or two\else many\fi
+\ifx\a\b $x$\fi
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    assert_eq!(document.to_string(), content);
+    document.validate().unwrap();
+
+    let body = document.chunks_body();
+    assert_eq!(document.query().select("conditional").unwrap().len(), 4);
+
+    let ChunkVariant::Conditional(draft) = body[1].variant() else {
+        panic!("not a conditional")
+    };
+    assert_eq!(draft.label(), "ifdraft");
+    assert_eq!(
+        draft.content(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" Draft copy.".to_string())
+        )]
+    );
+    assert_eq!(
+        draft.otherwise(),
+        Some(&vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" Final copy.".to_string())
+        )])
+    );
+
+    let ChunkVariant::Conditional(number) = body[3].variant() else {
+        panic!("not a conditional")
+    };
+    assert_eq!(number.label(), "ifnum");
+    assert_eq!(
+        number.condition(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" 1<2 ".to_string())
+        )]
+    );
+    assert_eq!(
+        number.content(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text("less ".to_string())
+        )]
+    );
+
+    let ChunkVariant::Conditional(case) = body[5].variant() else {
+        panic!("not a conditional")
+    };
+    assert_eq!(case.cases().len(), 2);
+    assert_eq!(
+        case.condition(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" 2 ".to_string())
+        )]
+    );
+    assert_eq!(
+        case.cases()[1],
+        vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" two".to_string())
+        )]
+    );
+
+    let ChunkVariant::Conditional(same) = body[7].variant() else {
+        panic!("not a conditional")
+    };
+    assert_eq!(same.condition().len(), 2);
+    assert!(matches!(
+        same.content()[1].variant(),
+        ChunkVariant::MathsBlock(_)
+    ));
+}
+
+#[test]
+fn unmatched() {
+    for source in [
+        "\\ifx\\a\\b text",
+        "text \\fi more",
+        "\\iftrue a \\else b \\else c \\fi",
+    ] {
+        let document = ast3::Document::from_str(source).unwrap();
+        assert_eq!(document.to_string(), source);
+        assert!(document.query().select("conditional").unwrap().is_empty());
+    }
+
+    // `\ifthenelse` and similar are not TeX conditionals
+    let document = ast3::Document::from_str("\\ifthenelse{x}{a}{b} \\fi").unwrap();
+    assert!(document.query().select("conditional").unwrap().is_empty());
+}
+
+#[test]
+fn unbalanced_branch() {
+    // TeX skips the content of `\iffalse` without matching its braces
+    let content = r#"
+\begin{document}
+\iffalse hidden { text $ \else shown \fi
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    assert_eq!(document.to_string(), content);
+    document.validate().unwrap();
+
+    let ChunkVariant::Conditional(conditional) = document.chunks_body()[1].variant() else {
+        panic!("not a conditional")
+    };
+    assert!(conditional.is_raw());
+    assert_eq!(
+        conditional.content(),
+        &vec![Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" hidden { text $ ".to_string())
+        )]
+    );
+    assert_eq!(Extractor::new().extract(&document).text(), "shown");
+
+    // balanced content is read as usual
+    let document = ast3::Document::from_str("\\iffalse {hidden} \\fi").unwrap();
+    let ChunkVariant::Conditional(conditional) = document.chunks_preamable()[0].variant() else {
+        panic!("not a conditional")
+    };
+    assert!(!conditional.is_raw());
+
+    for source in [
+        "{\\iffalse }\\fi}",
+        "\\iffalse \\ifx\\a\\b { \\fi } \\else % {\n shown\\fi",
+        "\\iffalse \\newif\\iffoo ( \\fi",
+    ] {
+        let document = ast3::Document::from_str(source).unwrap();
+        document.validate().unwrap();
+        assert_eq!(document.query().select("conditional").unwrap().len(), 1);
+    }
+
+    // `\unless\iffalse` reads its content, which still has to balance
+    assert_eq!(
+        ast3::Document::from_str("\\unless\\iffalse { \\fi"),
+        Err(crate::Error::new(
+            Position::new(1, 17),
+            crate::ErrorType::UnclosedArgument(ast1::ScopeVariant::Curly)
+        )
+        .with_related(Position::new(1, 22)))
+    );
+}
+
+#[test]
+fn definitions() {
+    let source = "\\newif\\iffoo \\def\\ifbar{x} \\iffoo a\\fi";
+    let document = ast2::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+
+    let found = document
+        .chunks()
+        .iter()
+        .filter_map(|chunk| match chunk.variant() {
+            ast2::ChunkVariant::Conditional(conditional) => Some(conditional.label().as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(found, ["iffoo"]);
+}
+
+#[test]
+fn nested() {
+    let source = "\\iftrue a \\iffalse b \\else c \\fi d \\else {e \\ifx x} \\fi";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+
+    let ChunkVariant::Conditional(outer) = document.chunks_preamable()[0].variant() else {
+        panic!("not a conditional")
+    };
+    assert!(matches!(
+        outer.content()[1].variant(),
+        ChunkVariant::Conditional(inner) if inner.label() == "iffalse"
+    ));
+    // conditionals do not reach out of scopes
+    assert!(outer.otherwise().is_some());
+    assert_eq!(document.query().select("conditional").unwrap().len(), 2);
+}
+
+#[test]
+fn lines() {
+    let source = "a\n\\iftrue\nb\n\\else\nc\n\\fi\nd";
+    let document = ast3::Document::from_str(source).unwrap();
+    document.validate().unwrap();
+
+    let chunks = document.chunks_preamable();
+    let last = chunks.last().unwrap();
+    assert_eq!(last.line_no(), 6);
+
+    let ChunkVariant::Conditional(conditional) = chunks[1].variant() else {
+        panic!("not a conditional")
+    };
+    let branches = conditional.branches(Position::new(2, 1));
+    assert_eq!(branches[0].0, Position::new(2, 8));
+    assert_eq!(branches[1].0, Position::new(4, 6));
+}
+
+#[test]
+fn evaluate() {
+    let content = r#"
+\documentclass{article}
+\newif\ifdraft
+\drafttrue
+\begin{document}
+\ifdraft Draft copy.\else Final copy.\fi
+
+\ifnum 1<2 less \else more\fi
+\ifcase 2 zero\or one\or two\else many\fi
+\ifx\a\b $x$\fi
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let conditions = Conditions::from_document(&document);
+    assert_eq!(conditions.boolean("draft"), Some(true));
+
+    let body = document.chunks_body();
+    let evaluated = [1, 3, 5, 7].map(|index| match body[index].variant() {
+        ChunkVariant::Conditional(conditional) => conditions.evaluate(conditional),
+        _ => panic!("not a conditional"),
+    });
+    assert_eq!(
+        evaluated,
+        [
+            Some(
+                &[Chunk::new_unchecked(
+                    1,
+                    ChunkVariant::Text(" Draft copy.".to_string())
+                )][..]
+            ),
+            Some(
+                &[Chunk::new_unchecked(
+                    1,
+                    ChunkVariant::Text("less ".to_string())
+                )][..]
+            ),
+            Some(
+                &[Chunk::new_unchecked(
+                    1,
+                    ChunkVariant::Text(" two".to_string())
+                )][..]
+            ),
+            None
+        ]
+    );
+
+    let ChunkVariant::Conditional(draft) = body[1].variant() else {
+        panic!("not a conditional")
+    };
+    let conditions = Conditions::new().with_boolean("draft", false);
+    assert_eq!(
+        conditions.evaluate_or_content(draft),
+        [Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" Final copy.".to_string())
+        )]
+    );
+    assert_eq!(
+        Conditions::new().evaluate_or_content(draft),
+        [Chunk::new_unchecked(
+            1,
+            ChunkVariant::Text(" Draft copy.".to_string())
+        )]
+    );
+
+    for (source, expected) in [
+        ("\\ifnum -\"1F=-31 yes\\else no\\fi", "yes"),
+        ("\\ifnum '17>15 yes\\else no\\fi", " no"),
+        ("\\ifodd 3 odd\\fi", "odd"),
+        ("\\ifodd 4 odd\\fi", ""),
+        ("\\ifcase 5 a\\or b\\fi", ""),
+    ] {
+        let document = ast3::Document::from_str(source).unwrap();
+        let ChunkVariant::Conditional(conditional) = document.chunks_preamable()[0].variant()
+        else {
+            panic!("not a conditional")
+        };
+        let evaluated = Conditions::new().evaluate(conditional).unwrap();
+        assert_eq!(
+            evaluated
+                .iter()
+                .map(ToString::to_string)
+                .collect::<String>(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn unless() {
+    // `\unless` inverts the test
+    for (source, expected) in [
+        ("\\unless\\iftrue A\\else B\\fi", Some(" B")),
+        ("\\unless \\ifnum 1<2 less\\else more\\fi", Some(" more")),
+        ("\\unless\n\\iffalse shown\\fi", Some(" shown")),
+        ("\\unless\\ifcase 1 a\\or b\\fi", None),
+    ] {
+        let document = ast3::Document::from_str(source).unwrap();
+        assert_eq!(document.to_string(), source);
+        document.validate().unwrap();
+        assert_eq!(
+            ast1::Document::from(ast2::Document::from(document.clone())).to_string(),
+            source
+        );
+
+        let [chunk] = &document.chunks_preamable()[..] else {
+            panic!("not a single chunk")
+        };
+        let ChunkVariant::Conditional(conditional) = chunk.variant() else {
+            panic!("not a conditional")
+        };
+        assert!(conditional.unless().is_some());
+
+        let evaluated = Conditions::new()
+            .evaluate(conditional)
+            .map(|branch| branch.iter().map(ToString::to_string).collect::<String>());
+        assert_eq!(evaluated.as_deref(), expected, "{source}");
+    }
+
+    let source = "\\unless\n\\iftrue a\\fi\nb";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.chunks_preamable()[1].line_no(), 2);
+}
+
+#[test]
+fn adjacent_branch() {
+    for source in [
+        "\\iffalse\\fi",
+        "\\iffalse\\else\\fi",
+        "\\iffalse\\begin{x}\\end{x}\\fi",
+    ] {
+        assert_eq!(
+            ast1::Document::from_str(source).unwrap().to_string(),
+            source
+        );
+
+        let document = ast3::Document::from_str(source).unwrap();
+        assert_eq!(document.to_string(), source);
+        assert_eq!(
+            document.query().select("conditional").unwrap().len(),
+            1,
+            "{source}"
+        );
+    }
+
+    let source = "\\iffalse\\end{x}";
+    assert_eq!(
+        ast1::Document::from_str(source).unwrap().to_string(),
+        source
+    );
+
+    // a comment ending the label is in the branch, which is text as its brace is unclosed
+    let document = ast3::Document::from_str("\\iffalse%c\n{\\fi").unwrap();
+    assert_eq!(document.query().select("conditional").unwrap().len(), 1);
+}
+
+#[test]
+fn preamble() {
+    let source =
+        "\\newif\\ifa \\newif\\ifb \\let\\ifb\\iftrue \\iftrue\\atrue\\fi \\iffalse\\bfalse\\fi";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+    let conditions = Conditions::from_document(&document);
+
+    assert_eq!(conditions.boolean("a"), Some(true));
+    assert_eq!(conditions.boolean("b"), Some(true));
+    assert_eq!(conditions.boolean("c"), None);
+}
+
+#[test]
+fn maths() {
+    let source = "\\iftrue$\\else$\\fi text";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+    assert!(document.query().select("conditional").unwrap().is_empty());
+
+    let source = "\\iftrue$x$\\else$$y$$\\fi";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.query().select("conditional").unwrap().len(), 1);
+}
+
+#[test]
+fn reparse() {
+    let content = r#"
+\documentclass{article}
+\newif\ifdraft
+\drafttrue
+\begin{document}
+\ifdraft Draft copy.\else Final copy.\fi
+
+\ifnum 1<2 less \else more\fi
+\ifcase 2 zero\or one\or two\else many\fi
+\ifx\a\b $x$\fi
+\end{document}
+"#
+    .trim();
+
+    for (start, end, text) in [
+        (Position::new(5, 10), Position::new(5, 15), "Preview"),
+        (Position::new(5, 28), Position::new(5, 31), ""),
+        (Position::new(7, 1), Position::new(7, 1), "\\fi "),
+        (Position::new(8, 6), Position::new(8, 6), "\\ifx"),
+    ] {
+        let mut document = ast3::Document::from_str(content).unwrap();
+        document.reparse(start, end, text).unwrap();
+
+        let full = ast3::Document::from_str(&document.to_string()).unwrap();
+        assert_eq!(document, full);
+    }
+}
+
+#[test]
+fn outputs() {
+    let content = r#"
+\documentclass{article}
+\newif\ifdraft
+\drafttrue
+\begin{document}
+\ifdraft Draft copy.\else Final copy.\fi
+
+\ifnum 1<2 less \else more\fi
+\ifcase 2 zero\or one\or two\else many\fi
+\ifx\a\b $x$\fi
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+
+    let plain = Extractor::new().extract(&document);
+    assert!(plain.text().contains("Draft copy."));
+    assert!(!plain.text().contains("Final"));
+    assert!(plain.text().contains("less"));
+    assert!(!plain.text().contains("zero"));
+    let offset = plain.text().find("two").unwrap();
+    assert_eq!(plain.source_position(offset), Some(Position::new(8, 26)));
+
+    let statistics = Counter::new().count(&document);
+    assert_eq!(statistics.total().words_text, 4);
+
+    let html = Renderer::new().render(&document);
+    assert!(html.contains("Draft copy."));
+    assert!(!html.contains("Final copy."));
+
+    let source = "\\iftrue\\'e\\else\\\"o\\fi";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(unicode::document(&document), "\\iftrueé\\elseö\\fi");
+}
+
+#[test]
+fn deep() {
+    // conditionals count towards the nesting depth, as scopes do
+    let depth = ParseLimits::MAX_DEPTH;
+    let source = "\\iftrue ".repeat(depth) + &"\\fi ".repeat(depth);
+    let document = ast3::Document::from_str(&source).unwrap();
+    assert_eq!(document.to_string(), source);
+
+    let source = "\\iftrue ".repeat(1000) + &"\\fi ".repeat(1000);
+    assert_eq!(
+        ast2::Document::from_str(&source),
+        Err(Error::new(
+            Position::new(1, depth as u32 * 8 + 1),
+            ErrorType::LimitExceeded(Limit::Depth)
+        ))
+    );
+
+    // in a scope, the position is in the whole document
+    let source = "x\n{".to_string() + &"\\iftrue ".repeat(depth) + &"\\fi ".repeat(depth) + "}";
+    assert_eq!(
+        ast3::Document::from_str(&source),
+        Err(Error::new(
+            Position::new(2, depth as u32 * 8 - 6),
+            ErrorType::LimitExceeded(Limit::Depth)
+        ))
+    );
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use crate::{ast2::Document, Diagnostic, Label};
+
+#[test]
+fn error_message() {
+    let content = r#"
+\begin{document}
+    \begin{itemize}
+        \item test
+\end{document}
+"#
+    .trim();
+
+    let err = Document::from_str(content).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "`\\end{document}` on line 4, column 1 does not close any open environment"
+    );
+    assert_eq!(
+        err.r#type.to_string(),
+        "`\\end{document}` does not close any open environment"
+    );
+}
+
+#[test]
+fn render() {
+    let content = r#"
+\begin{document}
+    \begin{itemize}
+        \item test
+\end{document}
+"#
+    .trim();
+
+    let err = Document::from_str(content).unwrap_err();
+
+    assert_eq!(
+        err.render(content),
+        r#"error: `\end{document}` does not close any open environment
+ --> line 4, column 1
+  |
+2 |     \begin{itemize}
+  |     - innermost open environment begins here
+...
+4 | \end{document}
+  | ^ no matching `\begin`
+"#
+    );
+
+    let content = "\\begin x";
+    let err = Document::from_str(content).unwrap_err();
+    assert!(err
+        .render(content)
+        .ends_with("  | ^ environment label missing after this command\n"));
+}
+
+#[test]
+fn render_secondary() {
+    let content = "\\begin{a}\n\ttext\n\n\n\\end{b}";
+
+    let diagnostic = Diagnostic::error("mismatched environment")
+        .with_label(Label::primary(5, Some(6), "closed as `b`"))
+        .with_label(Label::secondary(1, Some(8), "opened as `a`"))
+        .with_label(Label::secondary(2, Some(2), "inside here"));
+
+    assert_eq!(
+        diagnostic.render_named("main.tex", content),
+        "error: mismatched environment
+ --> main.tex:5:6
+  |
+1 | \\begin{a}
+  |        - opened as `a`
+2 | \ttext
+  | \t- inside here
+...
+5 | \\end{b}
+  |      ^ closed as `b`
+"
+    );
+}
@@ -0,0 +1,266 @@
+use std::str::FromStr;
+
+use crate::{ast1, ast2, ast3, traits::Validate, InternalError};
+
+#[test]
+fn ast1_edit() {
+    let mut document = ast1::Document::from_str("a\n\\textbf{b}\nc").unwrap();
+
+    document
+        .insert_at(1, ast1::ChunkVariant::Text("x\ny\n".to_string()))
+        .unwrap();
+    assert_eq!(document.to_string(), "a\nx\ny\n\\textbf{b}\nc");
+    assert_eq!(document.chunks()[2].line_no(), 4);
+    assert_eq!(document.validate(), Ok(()));
+
+    let removed = document.remove(1);
+    assert_eq!(removed.to_string(), "x\ny\n");
+    assert_eq!(document.chunks()[1].line_no(), 2);
+    assert_eq!(document.validate(), Ok(()));
+
+    let ast1::ChunkVariant::Command(command) = document.chunks_mut()[1].variant_mut() else {
+        panic!("expected a command")
+    };
+    let old = command
+        .set_argument(
+            0,
+            ast1::Scope::new_unchecked(
+                vec![ast1::Chunk::new_unchecked(
+                    5,
+                    ast1::ChunkVariant::Text("b\nb".to_string()),
+                )],
+                ast1::ScopeVariant::Curly,
+            ),
+        )
+        .unwrap();
+    assert_eq!(old.unwrap().to_string(), "{b}");
+    document.renumber();
+    assert_eq!(document.to_string(), "a\n\\textbf{b\nb}\nc");
+    assert_eq!(document.chunks()[2].line_no(), 3);
+    assert_eq!(document.validate(), Ok(()));
+
+    document.wrap_in_environment(1..2, "center").unwrap();
+    assert_eq!(
+        document.to_string(),
+        "a\n\\begin{center}\\textbf{b\nb}\\end{center}\nc"
+    );
+    assert_eq!(document.validate(), Ok(()));
+
+    assert_eq!(
+        document.replace(0, ast1::ChunkVariant::Text("}".to_string())),
+        Err(InternalError::UnsanitisedCharInString('}'))
+    );
+    assert_eq!(
+        document.wrap_in_environment(0..1, "a}"),
+        Err(InternalError::UnsanitisedCharInString('}'))
+    );
+}
+
+#[test]
+fn ast2_edit() {
+    let mut document = ast2::Document::from_str("a\n\\item b\n\\item c\n\\textbf{d}").unwrap();
+
+    document.wrap_in_environment(1..5, "itemize").unwrap();
+    assert_eq!(
+        document.to_string(),
+        "a\n\\begin{itemize}\\item b\n\\item c\n\\end{itemize}\\textbf{d}"
+    );
+    assert_eq!(document.validate(), Ok(()));
+    assert_eq!(
+        ast2::Document::from_str(&document.to_string()),
+        Ok(document.clone())
+    );
+
+    let ast2::ChunkVariant::Environment(environment) = document.chunks_mut()[1].variant_mut()
+    else {
+        panic!("expected an environment")
+    };
+    environment
+        .insert_at(0, ast2::ChunkVariant::Text("\n".to_string()))
+        .unwrap();
+    assert_eq!(environment.content()[1].line_no(), 2);
+    document.renumber();
+    assert_eq!(document.chunks()[2].line_no(), 5);
+    assert_eq!(document.validate(), Ok(()));
+
+    let replaced = document
+        .replace(2, ast2::ChunkVariant::Text("e".to_string()))
+        .unwrap();
+    assert_eq!(replaced.to_string(), "\\textbf{d}");
+    assert_eq!(
+        document.to_string(),
+        "a\n\\begin{itemize}\n\\item b\n\\item c\n\\end{itemize}e"
+    );
+}
+
+#[test]
+fn ast3_edit() {
+    let mut document = ast3::DocumentBuilder::article()
+        .paragraph("one")
+        .paragraph("two\nlines")
+        .build();
+    let original = document.to_string();
+
+    document.wrap_in_environment(2..3, "quote").unwrap();
+    assert_eq!(
+        document.to_string(),
+        original.replace("two\nlines\n", "\\begin{quote}two\nlines\n\\end{quote}")
+    );
+    assert_eq!(document.validate(), Ok(()));
+
+    document.remove(1);
+    assert_eq!(document.validate(), Ok(()));
+    assert_eq!(
+        document.chunks_body()[1].line_no(),
+        document.chunks_body()[0].line_no() + 1
+    );
+}
+
+#[test]
+fn renumber() {
+    let scope = ast3::Scope::new_unchecked(
+        vec![
+            ast3::Chunk::new_unchecked(7, ast3::ChunkVariant::Text("a\n".to_string())),
+            ast3::Chunk::new_unchecked(7, ast3::ChunkVariant::Text("b".to_string())),
+        ],
+        ast3::ScopeVariant::Curly,
+    );
+    let mut document = ast3::Document::new_unchecked(
+        Vec::new(),
+        None,
+        Vec::new(),
+        vec![
+            ast3::Chunk::new_unchecked(3, ast3::ChunkVariant::Text("x\n".to_string())),
+            ast3::Chunk::new_unchecked(9, ast3::ChunkVariant::Scope(scope)),
+            ast3::Chunk::new_unchecked(1, ast3::ChunkVariant::Text("y".to_string())),
+        ],
+        Vec::new(),
+        String::new(),
+        String::new(),
+        vec![ast3::Chunk::new_unchecked(
+            4,
+            ast3::ChunkVariant::Text("\n".to_string()),
+        )],
+    );
+    assert!(document.validate().is_err());
+
+    document.renumber();
+    assert_eq!(document.validate(), Ok(()));
+
+    let line_nos = |chunks: &[ast3::Chunk]| chunks.iter().map(|c| c.line_no()).collect::<Vec<_>>();
+    assert_eq!(line_nos(document.chunks_body()), vec![1, 2, 3]);
+    assert_eq!(line_nos(document.chunks_trailing()), vec![3]);
+    let ast3::ChunkVariant::Scope(scope) = document.chunks_body()[1].variant() else {
+        panic!("expected a scope")
+    };
+    assert_eq!(line_nos(scope.chunks()), vec![1, 2]);
+}
+
+#[test]
+fn trailing() {
+    let content =
+        "\\documentclass{article}\n\\begin{document}\na\n\n\\textbf{b}\n\\end{document}\nx\n\ny\n";
+    let parsed = ast3::Document::from_str(content).unwrap();
+    assert_eq!(parsed.validate(), Ok(()));
+
+    let mut document = parsed.clone();
+    document.renumber();
+    assert_eq!(document, parsed);
+
+    let reparsed =
+        |document: &ast3::Document| ast3::Document::from_str(&document.to_string()).unwrap();
+
+    document
+        .insert_at(1, ast3::ChunkVariant::Text("x\ny\n".to_string()))
+        .unwrap();
+    assert_eq!(document.validate(), Ok(()));
+    assert_eq!(
+        document.chunks_trailing()[0].line_no(),
+        reparsed(&document).chunks_trailing()[0].line_no()
+    );
+
+    document.remove(0);
+    document.wrap_in_environment(0..2, "center").unwrap();
+    document.edit_chunk(0, |chunk| {
+        let ast3::ChunkVariant::Environment(environment) = chunk.variant_mut() else {
+            panic!("expected an environment")
+        };
+        environment.remove(0);
+    });
+    assert_eq!(document.validate(), Ok(()));
+
+    let mut renumbered = reparsed(&document);
+    assert_eq!(document.chunks_trailing(), renumbered.chunks_trailing());
+    renumbered.renumber();
+    assert_eq!(renumbered, reparsed(&document));
+}
+
+#[test]
+fn nested_edit() {
+    let content = r#"
+\begin{document}
+\begin{itemize}
+\item x {a} b
+\item c
+\end{itemize}
+d
+\end{document}
+"#
+    .trim();
+    let mut document = ast3::Document::from_str(content).unwrap();
+
+    // a scope in an environment, with chunks after both
+    document.edit_chunk(1, |chunk| {
+        let ast3::ChunkVariant::Environment(environment) = chunk.variant_mut() else {
+            panic!("expected an environment")
+        };
+        let Some(ast3::ChunkVariant::Scope(scope)) = environment
+            .content_mut()
+            .iter_mut()
+            .map(ast3::Chunk::variant_mut)
+            .find(|variant| matches!(variant, ast3::ChunkVariant::Scope(_)))
+        else {
+            panic!("expected a scope")
+        };
+        scope
+            .insert_at(1, ast3::ChunkVariant::Text("\nb\n".to_string()))
+            .unwrap();
+    });
+
+    let edited = content.replace("{a}", "{a\nb\n}");
+    assert_eq!(document.to_string(), edited);
+    assert_eq!(document.validate(), Ok(()));
+    assert_eq!(document.chunks_body()[2].line_no(), 7);
+
+    let ast3::ChunkVariant::Environment(environment) = document.chunks_body()[1].variant() else {
+        panic!("expected an environment")
+    };
+    let line_nos = environment
+        .content()
+        .iter()
+        .map(ast3::Chunk::line_no)
+        .collect::<Vec<_>>();
+    assert_eq!(line_nos, vec![1, 2, 2, 2, 4, 5, 5]);
+}
+
+#[test]
+fn no_body() {
+    let content = "a b\n";
+    let mut document = ast3::Document::from_str(content).unwrap();
+    assert!(!document.has_body());
+
+    assert!(matches!(
+        document.insert_at(0, ast3::ChunkVariant::Text("x".to_string())),
+        Err(InternalError::NoBody)
+    ));
+    assert!(matches!(
+        document.wrap_in_environment(0..0, "center"),
+        Err(InternalError::NoBody)
+    ));
+    assert_eq!(document.to_string(), content);
+
+    let result = std::panic::catch_unwind(move || {
+        document.insert_at_unchecked(0, ast3::ChunkVariant::Text("x".to_string()))
+    });
+    assert!(result.is_err());
+}
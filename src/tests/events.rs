@@ -0,0 +1,181 @@
+use std::{io::Read, str::FromStr};
+
+use crate::{
+    ast1::{Chunk, ChunkVariant, Document, Event, EventKind, Events, ScopeVariant},
+    ErrorType, Position,
+};
+
+// reader giving one byte at a time
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.split_first() {
+            Some((first, rest)) if !buf.is_empty() => {
+                buf[0] = *first;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+fn kinds(source: &str) -> Vec<EventKind> {
+    Events::new(source.as_bytes())
+        .map(|event| event.kind)
+        .collect()
+}
+
+// the events expected from a tree, without comments and with text merged
+fn flatten(chunks: &[Chunk], out: &mut Vec<EventKind>) {
+    fn text(out: &mut Vec<EventKind>, s: &str) {
+        match out.last_mut() {
+            Some(EventKind::Text(last)) => last.push_str(s),
+            _ if s.is_empty() => {}
+            _ => out.push(EventKind::Text(s.to_string())),
+        }
+    }
+
+    for chunk in chunks {
+        match chunk.variant() {
+            ChunkVariant::Text(s) => text(out, s),
+            ChunkVariant::Command(command) => {
+                out.push(EventKind::CommandStart(command.label().to_string()));
+
+                for (prec, arg) in command.arguments() {
+                    text(out, prec);
+                    out.push(EventKind::ArgumentOpen(arg.variant()));
+                    flatten(arg.chunks(), out);
+                    out.push(EventKind::ArgumentClose);
+                }
+            }
+            ChunkVariant::Scope(scope) => {
+                out.push(EventKind::ScopeOpen(scope.variant()));
+                flatten(scope.chunks(), out);
+                out.push(EventKind::ScopeClose);
+            }
+        }
+    }
+}
+
+#[test]
+fn commands_and_arguments() {
+    assert_eq!(
+        kinds("\\section*{Intro} see \\cite[p. 2]\n  {key} % note\n\\\\ {x}"),
+        vec![
+            EventKind::CommandStart("section".to_string()),
+            EventKind::Text("*".to_string()),
+            EventKind::ScopeOpen(ScopeVariant::Curly),
+            EventKind::Text("Intro".to_string()),
+            EventKind::ScopeClose,
+            EventKind::Text(" see ".to_string()),
+            EventKind::CommandStart("cite".to_string()),
+            EventKind::ArgumentOpen(ScopeVariant::Square),
+            EventKind::Text("p. 2".to_string()),
+            EventKind::ArgumentClose,
+            EventKind::Text("\n  ".to_string()),
+            EventKind::ArgumentOpen(ScopeVariant::Curly),
+            EventKind::Text("key".to_string()),
+            EventKind::ArgumentClose,
+            EventKind::Text(" ".to_string()),
+            EventKind::Comment(" note".to_string()),
+            EventKind::Text("\n".to_string()),
+            EventKind::CommandStart("\\".to_string()),
+            EventKind::Text(" ".to_string()),
+            EventKind::ScopeOpen(ScopeVariant::Curly),
+            EventKind::Text("x".to_string()),
+            EventKind::ScopeClose,
+        ]
+    );
+}
+
+#[test]
+fn positions() {
+    let events = Events::new("a\n\\textbf{b}%c\n".as_bytes()).collect::<Vec<_>>();
+
+    assert_eq!(
+        events[1],
+        Event {
+            kind: EventKind::CommandStart("textbf".to_string()),
+            start: Position::new(2, 1),
+            end: Position::new(2, 8),
+        }
+    );
+    assert_eq!(events[4].start, Position::new(2, 10));
+    assert_eq!(
+        events[5],
+        Event {
+            kind: EventKind::Comment("c".to_string()),
+            start: Position::new(2, 11),
+            end: Position::new(2, 13),
+        }
+    );
+    assert_eq!(events[6].start, Position::new(2, 13));
+    assert_eq!(events[6].end, Position::new(3, 1));
+}
+
+#[test]
+fn same_as_tree() {
+    for source in [
+        "\\documentclass[a4paper]{article}\n\\begin{document}\n\\end{document}",
+        "\\foo \\bar{x} {y}\n\n[z] (w) \\baz\n\n\n{\\%}",
+        "{a) ]} \\\\[2pt] \\a\\b{c}d",
+        "% see \\ref{x}\nfoo % \\\n{bar}",
+        "\\foo\\ bar\nbaz\\",
+    ] {
+        let mut expected = Vec::new();
+        flatten(Document::from_str(source).unwrap().chunks(), &mut expected);
+
+        let mut events = Vec::new();
+        for kind in kinds(source) {
+            match (events.last_mut(), kind) {
+                (_, EventKind::Comment(_)) => {}
+                (Some(EventKind::Text(last)), EventKind::Text(s)) => last.push_str(&s),
+                (_, kind) => events.push(kind),
+            }
+        }
+
+        assert_eq!(events, expected, "{source}");
+    }
+}
+
+#[test]
+fn errors() {
+    for source in ["a {b [c}", "\\foo}", "x {y"] {
+        let Some(EventKind::Error(error)) = kinds(source).pop() else {
+            panic!("expected an error for {source}")
+        };
+        assert_eq!(Err(error), Document::from_str(source), "{source}");
+    }
+
+    let events = Events::new(&b"ab\n\xff"[..]).collect::<Vec<_>>();
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        events[1].kind,
+        EventKind::Error(crate::Error::new(
+            Position::new(2, 1),
+            ErrorType::InvalidUtf8,
+        ))
+    );
+}
+
+#[test]
+fn bounded() {
+    let long = "é".repeat(10000);
+    let source = format!("\\x{{{long}}}%{long}");
+
+    let events = Events::new(Trickle(source.as_bytes())).collect::<Vec<_>>();
+    let texts = events
+        .iter()
+        .filter_map(|event| match &event.kind {
+            EventKind::Text(s) | EventKind::Comment(s) => Some(s.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert!(texts.len() > 4);
+    assert!(texts.iter().all(|s| s.len() <= 4096));
+    assert_eq!(texts.concat(), format!("{long}{long}"));
+    assert_eq!(events.last().unwrap().end, Position::new(1, 20006));
+}
@@ -0,0 +1,356 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3,
+    format::{Formatter, Sentences},
+};
+
+// the content without any whitespace
+fn tokens(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn format(formatter: &Formatter, s: &str) -> String {
+    formatter.format(&ast3::Document::from_str(s).unwrap())
+}
+
+#[test]
+fn layout() {
+    let content = r#"
+\documentclass [a4paper]{article}
+\begin{document}
+\section {Introduction}\label{sec:intro}
+Some prose with $a + b$ maths,   \textbf{bold} text and \verb|a    b| verbatim.
+     More prose.
+
+\begin{itemize}
+\item First
+     \item Second
+  \begin{enumerate} \item nested \end{enumerate}
+\end{itemize}
+\begin{tabular}{l c}
+\hline
+Name & Value\\ \hline
+a&bbbbbb \\[2pt]
+\multicolumn{2}{c}{wide} \\
+longer name & c
+\end{tabular}
+\begin{equation}
+      x   = y
+\end{equation}
+\begin{verbatim}
+  keep    this
+\end{verbatim}
+Glued\begin{center}text\end{center}.
+\end{document}
+"#
+    .trim();
+
+    assert_eq!(
+        format(&Formatter::new(), content),
+        r#"\documentclass[a4paper]{article}
+\begin{document}
+\section{Introduction}\label{sec:intro}
+Some prose with $a + b$ maths, \textbf{bold} text and \verb|a    b| verbatim.
+More prose.
+
+\begin{itemize}
+  \item First
+  \item Second
+  \begin{enumerate}
+    \item nested
+  \end{enumerate}
+\end{itemize}
+\begin{tabular}{l c}
+  \hline
+  Name        & Value \\
+  \hline
+  a           & bbbbbb \\[2pt]
+  \multicolumn{2}{c}{wide} \\
+  longer name & c
+\end{tabular}
+\begin{equation}
+  x = y
+\end{equation}
+\begin{verbatim}
+  keep    this
+\end{verbatim}
+Glued\begin{center}text\end{center}.
+\end{document}"#
+    );
+}
+
+#[test]
+fn wrap() {
+    let content = r#"
+\documentclass [a4paper]{article}
+\begin{document}
+\section {Introduction}\label{sec:intro}
+Some prose with $a + b$ maths,   \textbf{bold} text and \verb|a    b| verbatim.
+     More prose.
+
+\begin{itemize}
+\item First
+     \item Second
+  \begin{enumerate} \item nested \end{enumerate}
+\end{itemize}
+\begin{tabular}{l c}
+\hline
+Name & Value\\ \hline
+a&bbbbbb \\[2pt]
+\multicolumn{2}{c}{wide} \\
+longer name & c
+\end{tabular}
+\begin{equation}
+      x   = y
+\end{equation}
+\begin{verbatim}
+  keep    this
+\end{verbatim}
+Glued\begin{center}text\end{center}.
+\end{document}
+"#
+    .trim();
+
+    let formatter = Formatter::new().with_width(30).with_indent("\t");
+    let formatted = format(&formatter, content);
+
+    assert!(formatted.contains(
+        "Some prose with $a + b$ maths,\n\\textbf{bold} text and\n\\verb|a    b| verbatim. More\nprose.\n"
+    ));
+    assert!(formatted.contains("\t\\item First\n\t\\item Second\n"));
+}
+
+#[test]
+fn idempotent() {
+    let content = r#"
+\documentclass [a4paper]{article}
+\begin{document}
+\section {Introduction}\label{sec:intro}
+Some prose with $a + b$ maths,   \textbf{bold} text and \verb|a    b| verbatim.
+     More prose.
+
+\begin{itemize}
+\item First
+     \item Second
+  \begin{enumerate} \item nested \end{enumerate}
+\end{itemize}
+\begin{tabular}{l c}
+\hline
+Name & Value\\ \hline
+a&bbbbbb \\[2pt]
+\multicolumn{2}{c}{wide} \\
+longer name & c
+\end{tabular}
+\begin{equation}
+      x   = y
+\end{equation}
+\begin{verbatim}
+  keep    this
+\end{verbatim}
+Glued\begin{center}text\end{center}.
+\end{document}
+"#
+    .trim();
+
+    for formatter in [
+        Formatter::new(),
+        Formatter::new().with_width(20),
+        Formatter::new().with_width(1).with_unindented("itemize"),
+    ] {
+        let once = format(&formatter, content);
+        let twice = format(&formatter, &once);
+
+        assert_eq!(once, twice);
+        assert_eq!(tokens(&once), tokens(content));
+    }
+
+    // a control space ending a line is kept before a comment
+    let content = "\\begin{document}\n\\ \n%c\n\\ \n & \\a\\item \n\\end{document}";
+    let once = Formatter::new().format_str(content).unwrap();
+    assert_eq!(
+        once,
+        "\\begin{document}\n\\ \n%c\n\\ \n& \\a\\item\n\\end{document}"
+    );
+    assert_eq!(Formatter::new().format_str(&once).unwrap(), once);
+    assert_eq!(
+        Formatter::new().format_str("a\\ %c\nb"),
+        Ok("a\\ %c\nb".to_string())
+    );
+}
+
+#[test]
+fn aligned_cells() {
+    let formatter = Formatter::new();
+
+    // verbatim text in a cell is kept as it is
+    let content = "\\begin{tabular}{ll}\nx & \\verb|a    b & c| \\\\\nyy & z\n\\end{tabular}";
+    let once = format(&formatter, content);
+    assert_eq!(
+        once,
+        "\\begin{tabular}{ll}\n  x  & \\verb|a    b & c| \\\\\n  yy & z\n\\end{tabular}"
+    );
+    assert_eq!(format(&formatter, &once), once);
+
+    // rows with paragraph breaks or environments nested in a cell are not aligned
+    for content in [
+        "\\begin{tabular}{ll}\na & \\emph{b\n\nc} \\\\\nd & e\n\\end{tabular}",
+        "\\begin{tabular}{ll}\nx & \\parbox{3cm}{\\begin{tabular}{l} \\today y_i \\end{tabular}} \\\\\n\\end{tabular}",
+    ] {
+        let once = format(&formatter, content);
+        assert_eq!(format(&formatter, &once), once);
+        assert_eq!(tokens(&once), tokens(content));
+        assert_eq!(
+            ast3::Document::from_str(&once).unwrap().to_string().matches("\n\n").count(),
+            content.matches("\n\n").count()
+        );
+    }
+}
+
+#[test]
+fn sentences() {
+    let content = r#"\begin{document}
+First sentence. Second one! A third? Yes.
+  See e.g. Fig.~\ref{f} and Fig. 2. Written by J. Smith et al. Next.
+  Cited~\cite{k}. Then NASA\@. Then Dr.\ Who. Ends $x$. Here
+  \emph{text.} Lowercase. continues \verb|a. B|.
+
+\begin{itemize}
+    \item One. Two.
+\end{itemize}
+\[ a. B \]
+\begin{verbatim}
+x. Y
+\end{verbatim}
+\end{document}"#;
+
+    let mut document = ast3::Document::from_str(content).unwrap();
+    Sentences::new().apply(&mut document);
+
+    assert_eq!(
+        document.to_string(),
+        r#"\begin{document}
+First sentence.
+Second one!
+A third?
+Yes.
+  See e.g. Fig.~\ref{f} and Fig. 2.
+  Written by J. Smith et al. Next.
+  Cited~\cite{k}.
+  Then NASA\@.
+  Then Dr.\ Who.
+  Ends $x$.
+  Here
+  \emph{text.} Lowercase. continues \verb|a. B|.
+
+\begin{itemize}
+    \item One.
+    Two.
+\end{itemize}
+\[ a. B \]
+\begin{verbatim}
+x. Y
+\end{verbatim}
+\end{document}"#
+    );
+
+    // the line numbers of the transformed tree match its source
+    assert_eq!(
+        ast3::Document::from_str(&document.to_string()).unwrap(),
+        document
+    );
+}
+
+#[test]
+fn sentences_comments() {
+    let content = r#"
+% preamble
+\begin{document}
+First. Second. % after two sentences
+  Third! % third
+% on its own line
+Fourth?%
+Fifth.
+\end{document}
+"#
+    .trim();
+
+    let applied = Sentences::new().apply_str(content).unwrap();
+    assert_eq!(
+        applied,
+        r#"
+% preamble
+\begin{document}
+First.
+Second. % after two sentences
+  Third! % third
+% on its own line
+Fourth?%
+Fifth.
+\end{document}
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn comments() {
+    let content = r#"
+% preamble
+\documentclass{article}
+
+\begin{document}
+\begin{itemize}
+\item   One % first
+    % on its own line
+\item Two
+\end{itemize}
+Glued%
+together.
+
+% after a blank line
+
+  Some   prose. % a comment
+\end{document}
+"#
+    .trim();
+
+    let formatted = Formatter::new().format_str(content).unwrap();
+    assert_eq!(
+        formatted,
+        r#"
+% preamble
+\documentclass{article}
+
+\begin{document}
+\begin{itemize}
+  \item One % first
+  % on its own line
+  \item Two
+\end{itemize}
+Glued%
+together.
+
+% after a blank line
+
+Some prose. % a comment
+\end{document}
+"#
+        .trim()
+    );
+    assert_eq!(Formatter::new().format_str(&formatted).unwrap(), formatted);
+
+    // a comment ends its line when the prose around it is wrapped
+    let formatter = Formatter::new().with_width(20);
+    let formatted = formatter
+        .format_str("one two three % c\nfour five six seven eight")
+        .unwrap();
+    assert_eq!(formatted, "one two three % c\nfour\nfive six seven eight");
+    assert_eq!(formatter.format_str(&formatted).unwrap(), formatted);
+
+    // an escaped percent sign is not a comment
+    assert_eq!(
+        Formatter::new().format_str("\\begin{document}\n  100\\%   sure\n\\end{document}"),
+        Ok("\\begin{document}\n100\\% sure\n\\end{document}".to_string())
+    );
+}
@@ -0,0 +1,151 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3,
+    html::{Context, Handler, Maths, Renderer},
+};
+
+#[test]
+fn render() {
+    let content = r#"
+\documentclass{article}
+\title{Notes}
+\begin{document}
+\maketitle
+\section{Introduction}\label{sec:intro}
+Some \emph{text}, see Section~\ref{sec:later} and \eqref{eq:e}.\footnote{A note.}
+Cited \cite{knuth}, with $x < y$ and \verb|a<b|.
+
+\begin{enumerate}
+  \item First
+  \item Second
+\end{enumerate}
+\section*{Unnumbered}
+\subsection{Later}\label{sec:later}
+\begin{equation}\label{eq:e}
+  e = mc^2
+\end{equation}
+\begin{figure}
+  \includegraphics{plot.png}
+  \caption{A plot}\label{fig:plot}
+\end{figure}
+\begin{tabular}{ll}
+  \hline
+  A & B \\
+  \multicolumn{2}{c}{Both} \\
+\end{tabular}
+\begin{thebibliography}{9}
+\bibitem{knuth} D. Knuth.
+\end{thebibliography}
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let html = Renderer::new().render(&document);
+
+    for expected in [
+        "<header>\n<h1 class=\"title\">Notes</h1>\n</header>\n",
+        "<h2 id=\"sec:intro\"><span class=\"number\">1</span> Introduction</h2>\n",
+        "<p>Some <em>text</em>, see Section&nbsp;<a href=\"#sec:later\">1.1</a> and \
+         <a href=\"#eq:e\">(1)</a>.<sup class=\"footnote-ref\"><a href=\"#fn1\" id=\"fnref1\">1</a></sup>\n",
+        "Cited <cite>[<a href=\"#cite-knuth\">1</a>]</cite>, with \
+         <span class=\"math inline\">\\(x &lt; y\\)</span> and <code>a&lt;b</code>.</p>\n",
+        "<ol>\n<li>First</li>\n<li>Second</li>\n</ol>\n",
+        "<h2>Unnumbered</h2>\n",
+        "<h3 id=\"sec:later\"><span class=\"number\">1.1</span> Later</h3>\n",
+        "<div class=\"math display\" id=\"eq:e\">\\begin{equation}",
+        "<figure class=\"figure\" id=\"fig:plot\">\n<p><img src=\"plot.png\" alt=\"\"></p>\n\
+         <figcaption>Figure 1: A plot</figcaption>\n</figure>\n",
+        "<table>\n<tr><td>A</td><td>B</td></tr>\n<tr><td colspan=\"2\">Both</td></tr>\n</table>\n",
+        "<li id=\"cite-knuth\">D. Knuth.</li>\n",
+        "<li id=\"fn1\">A note. <a href=\"#fnref1\" class=\"footnote-back\">\u{21a9}</a></li>\n",
+    ] {
+        assert!(html.contains(expected), "{expected}\nnot in\n{html}");
+    }
+
+    // labels already placed as ids are not repeated as anchors
+    assert!(!html.contains("<a id=\"sec:intro\">"));
+}
+
+#[test]
+fn maths() {
+    let document = ast3::Document::from_str(r"$a$ and $$b$$").unwrap();
+
+    assert_eq!(
+        Renderer::new().render(&document),
+        "<p><span class=\"math inline\">\\(a\\)</span> and \
+         <span class=\"math display\">\\[b\\]</span></p>\n"
+    );
+    assert_eq!(
+        Renderer::new().with_maths(Maths::Source).render(&document),
+        "<p><span class=\"math inline\">$a$</span> and \
+         <span class=\"math display\">$$b$$</span></p>\n"
+    );
+    assert_eq!(
+        Renderer::new().with_maths(Maths::MathML).render(&document),
+        "<p><math><mi>a</mi></math> and <math display=\"block\"><mi>b</mi></math></p>\n"
+    );
+}
+
+#[test]
+fn links() {
+    let content = r#"
+\url{https://example.org/a\_b} \href{mailto:a@example.org}{mail}
+\href{\#sec}{here} \href{docs/a:b.html}{docs}
+
+\href{javascript:alert(1)}{a} \href{ JavaScript:alert(1)}{b}
+\href{data:text/html,x}{c}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+
+    assert_eq!(
+        Renderer::new().render(&document),
+        "<p><a href=\"https://example.org/a_b\">https://example.org/a_b</a> \
+         <a href=\"mailto:a@example.org\">mail</a>\n<a href=\"#sec\">here</a> \
+         <a href=\"docs/a:b.html\">docs</a></p>\n\
+         <p><a>a</a> <a>b</a>\n<a>c</a></p>\n"
+    );
+
+    // browsers ignore tabs in a scheme
+    let document = ast3::Document::from_str("\\url{java\tscript:alert(1)}").unwrap();
+
+    assert_eq!(
+        Renderer::new().render(&document),
+        "<p><a>java\tscript:alert(1)</a></p>\n"
+    );
+}
+
+struct Boxes;
+
+impl Handler for Boxes {
+    fn command(&self, command: &ast3::Command, context: &mut Context) -> Option<String> {
+        match command.label().as_str() {
+            "fbox" => Some(format!(
+                "<span class=\"box\">{}</span>",
+                context.render(command.arguments()[0].1.chunks())
+            )),
+            _ => None,
+        }
+    }
+
+    fn environment(&self, env: &ast3::Environment, _: &mut Context) -> Option<String> {
+        (env.label() == "tikzpicture").then(|| "<svg></svg>".to_string())
+    }
+}
+
+#[test]
+fn handler() {
+    let document = ast3::Document::from_str(
+        "\\fbox{\\emph{a}} \\mystery{b} \\dropped\n\n\\begin{tikzpicture}\\draw;\\end{tikzpicture}",
+    )
+    .unwrap();
+
+    assert_eq!(Renderer::new().render(&document), "<p><em>a</em> b</p>\n");
+    assert_eq!(
+        Renderer::new().render_with(&document, &Boxes),
+        "<p><span class=\"box\"><em>a</em></span> b</p>\n<svg></svg>\n"
+    );
+}
@@ -0,0 +1,24 @@
+use std::str::FromStr;
+
+use crate::json::{Json, JsonError};
+
+#[test]
+fn round_trip() {
+    let text = r#"{"a":[1,2.5,-3e2,true,false,null],"b":"x\"y\\z\n\u00e9\ud83d\ude00","c":{}}"#;
+    let value = Json::from_str(text).unwrap();
+
+    assert_eq!(value.pointer(&["a"]).unwrap().as_array().unwrap().len(), 6);
+    assert_eq!(value.get("b").and_then(Json::as_str), Some("x\"y\\z\né😀"));
+    assert_eq!(
+        value.to_string(),
+        r#"{"a":[1,2.5,-300,true,false,null],"b":"x\"y\\z\né😀","c":{}}"#
+    );
+}
+
+#[test]
+fn errors() {
+    assert_eq!(Json::from_str("[1,"), Err(JsonError::UnexpectedEnd));
+    assert_eq!(Json::from_str("[1 2]"), Err(JsonError::UnexpectedChar(3)));
+    assert_eq!(Json::from_str("{} x"), Err(JsonError::UnexpectedChar(3)));
+    assert_eq!(Json::from_str(r#""\q""#), Err(JsonError::Invalid(2)));
+}
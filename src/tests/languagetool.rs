@@ -0,0 +1,291 @@
+use std::str::FromStr;
+#[cfg(feature = "languagetool-client")]
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ast3,
+    json::Json,
+    languagetool::{AnnotatedText, Segment},
+    Position,
+};
+
+fn markup(markup: &str, interpret_as: &str) -> Segment {
+    Segment::Markup {
+        markup: markup.to_string(),
+        interpret_as: interpret_as.to_string(),
+    }
+}
+
+fn text(text: &str) -> Segment {
+    Segment::Text(text.to_string())
+}
+
+// offset of a string in the source in UTF-16 code units, as LanguageTool reports it
+fn offset(source: &str, s: &str) -> usize {
+    source[..source.find(s).unwrap()].encode_utf16().count()
+}
+
+#[test]
+fn annotate() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+Some $x$ text with a tpyo,
+see~\cite{k}.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let annotated = AnnotatedText::new(&document);
+
+    assert_eq!(annotated.source(), content);
+    assert_eq!(
+        annotated.segments(),
+        &vec![
+            markup(
+                "\\documentclass{article}\n\\begin{document}\n\\section{",
+                ""
+            ),
+            text("Intro"),
+            markup("}\n", "\n\n"),
+            text("Some"),
+            markup(" $x$ ", " X "),
+            text("text"),
+            markup(" ", " "),
+            text("with"),
+            markup(" ", " "),
+            text("a"),
+            markup(" ", " "),
+            text("tpyo,"),
+            markup("\n", " "),
+            text("see"),
+            markup("~\\cite{k}", " [0]"),
+            text("."),
+            markup("\n\\end{document}", ""),
+        ]
+    );
+
+    assert!(annotated
+        .to_json()
+        .to_string()
+        .starts_with(r#"{"annotation":[{"markup":"\\documentclass{article}\n\\begin{document}\n\\section{"},{"text":"Intro"},{"markup":"}\n","interpretAs":"\n\n"}"#));
+}
+
+#[test]
+fn fixture() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+Some $x$ text with a tpyo,
+see~\cite{k}.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let annotated = AnnotatedText::new(&document);
+
+    let response = Json::from_str(&format!(
+        r#"{{"software": {{"name": "LanguageTool"}}, "matches": [
+            {{"message": "Possible spelling mistake found.", "offset": {}, "length": 4,
+              "replacements": [{{"value": "typo"}}, {{"value": "tapo"}}],
+              "rule": {{"id": "MORFOLOGIK_RULE_EN_US"}}}},
+            {{"message": "missing offset"}},
+            {{"message": "Earlier match.", "offset": {}, "length": 5}}
+        ]}}"#,
+        offset(content, "tpyo"),
+        offset(content, "Intro")
+    ))
+    .unwrap();
+
+    let issues = annotated.matches(&response);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[1].start(), Position::new(3, 10));
+    assert_eq!(issues[1].end(), Position::new(3, 15));
+    assert_eq!(issues[0].start(), Position::new(4, 22));
+    assert_eq!(issues[0].end(), Position::new(4, 26));
+    assert_eq!(issues[0].rule(), "MORFOLOGIK_RULE_EN_US");
+    assert_eq!(issues[0].replacements(), &vec!["typo", "tapo"]);
+    assert_eq!(
+        issues[0].to_diagnostic().render(content),
+        "warning: Possible spelling mistake found.
+ --> line 4, column 22
+  |
+4 | Some $x$ text with a tpyo,
+  |                      ^ did you mean `typo`?
+"
+    );
+}
+
+#[cfg(feature = "languagetool-client")]
+#[test]
+fn stub_server() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+Some $x$ text with a tpyo,
+see~\cite{k}.
+\end{document}
+"#
+    .trim();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        let mut head = String::new();
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+            head.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let response = format!(
+            r#"{{"matches": [{{"message": "Spelling", "offset": {}, "length": 4, "replacements": [], "rule": {{"id": "R"}}}}]}}"#,
+            offset(content, "tpyo")
+        );
+        // a chunked response
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            10,
+            &response[..10],
+            response.len() - 10,
+            &response[10..]
+        )
+        .unwrap();
+
+        (head, String::from_utf8(body).unwrap())
+    });
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let issues = AnnotatedText::new(&document)
+        .check(&address, "en-US")
+        .unwrap();
+
+    let (head, body) = server.join().unwrap();
+    assert!(head.starts_with("POST /v2/check HTTP/1.1\r\n"));
+    assert!(body.starts_with("language=en-US&data=%7B%22annotation%22"));
+
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].message(), "Spelling");
+    assert_eq!(issues[0].start(), Position::new(4, 22));
+}
+
+#[cfg(feature = "languagetool-client")]
+// answer one request with `response`, after reading the request
+fn respond(listener: TcpListener, response: Vec<u8>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        reader.get_mut().write_all(&response).unwrap();
+    })
+}
+
+#[cfg(feature = "languagetool-client")]
+#[test]
+fn split_character() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+Some $x$ text with a tpyo,
+see~\cite{k}.
+\end{document}
+"#
+    .trim();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    // the chunks split the two bytes of `é`
+    let body = r#"{"matches": [{"message": "Épelling", "offset": 0, "length": 4, "replacements": [], "rule": {"id": "R"}}]}"#.as_bytes();
+    let split = body.iter().position(|byte| *byte == 0xC3).unwrap() + 1;
+    let mut response =
+        b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n"
+            .to_vec();
+    for chunk in [&body[..split], &body[split..]] {
+        response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        response.extend_from_slice(chunk);
+        response.extend_from_slice(b"\r\n");
+    }
+    response.extend_from_slice(b"0\r\n\r\n");
+
+    let server = respond(listener, response);
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let issues = AnnotatedText::new(&document)
+        .check(&address, "en-US")
+        .unwrap();
+    server.join().unwrap();
+
+    assert_eq!(issues[0].message(), "Épelling");
+}
+
+#[cfg(feature = "languagetool-client")]
+#[test]
+fn timeout() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+Some $x$ text with a tpyo,
+see~\cite{k}.
+\end{document}
+"#
+    .trim();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    // the connection is accepted but never answered
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        thread::sleep(Duration::from_secs(1));
+        drop(stream)
+    });
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let start = Instant::now();
+    assert!(AnnotatedText::new(&document)
+        .check_with_timeout(&address, "en-US", Duration::from_millis(100))
+        .is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    server.join().unwrap();
+}
@@ -0,0 +1,202 @@
+use std::{str::FromStr, time::Instant};
+
+use crate::{
+    ast1::{self, EventKind, Events},
+    ast2, ast3, Error, ErrorType, Limit, ParseLimits, Position,
+};
+
+fn exceeded(line: u32, column: u32, limit: Limit) -> Error {
+    Error::new(Position::new(line, column), ErrorType::LimitExceeded(limit))
+}
+
+#[test]
+fn depth() {
+    let limits = ParseLimits::new().with_max_depth(3);
+
+    assert!(ast1::Document::parse_with_limits("{[\\a(x)]}", limits).is_ok());
+    assert_eq!(
+        ast1::Document::parse_with_limits("{[\\a(\n{x})]}", limits),
+        Err(exceeded(2, 1, Limit::Depth))
+    );
+
+    // deep nesting fails instead of overflowing the stack
+    let deep = "{".repeat(100_000) + &"}".repeat(100_000);
+    assert_eq!(
+        ast1::Document::from_str(&deep),
+        Err(exceeded(1, 129, Limit::Depth))
+    );
+    assert_eq!(
+        exceeded(1, 129, Limit::Depth).to_string(),
+        "nesting depth goes over the limit on line 1, column 129"
+    );
+}
+
+#[test]
+fn within_default_limits() {
+    // every stage handles the deepest nesting the default limits allow
+    let limits = ParseLimits::default();
+    let mut source = String::new();
+
+    for _ in 0..limits.max_environment_depth() {
+        source.push_str("\\begin{a}");
+    }
+    source.push_str(&"{".repeat(limits.max_depth() - 1));
+    source.push_str("\\textbf{$x$}");
+    source.push_str(&"}".repeat(limits.max_depth() - 1));
+    for _ in 0..limits.max_environment_depth() {
+        source.push_str("\\end{a}");
+    }
+
+    let document = ast3::Document::from_str(&source).unwrap();
+    assert_eq!(document.to_string(), source);
+    assert_eq!(ast2::Document::from(document).to_string(), source);
+}
+
+#[test]
+fn max_depth() {
+    // stages 2 and 3 recurse, so the depth cannot be raised over the maximum
+    let limits = ParseLimits::new()
+        .with_max_depth(usize::MAX)
+        .with_max_environment_depth(usize::MAX);
+    assert_eq!(limits, ParseLimits::default());
+
+    let deep = "{".repeat(200_000);
+    assert_eq!(
+        ast2::Document::parse_with_limits(&deep, limits),
+        Err(exceeded(1, ParseLimits::MAX_DEPTH as u32 + 1, Limit::Depth))
+    );
+}
+
+#[test]
+fn deep_parse_time() {
+    // parsing the deepest nesting takes about as long as flat input of the same size,
+    // instead of going over each subtree once per level
+    let depth = ParseLimits::MAX_DEPTH - 1;
+    let nested = "\\x{".repeat(depth) + &"}".repeat(depth);
+    let deep = nested.repeat(100);
+    let flat = "\\x{}".repeat(depth).repeat(100);
+    assert_eq!(deep.len(), flat.len());
+
+    let time = |source: &str| {
+        let start = Instant::now();
+        ast3::Document::from_str(source).unwrap();
+        start.elapsed()
+    };
+
+    let flat = time(&flat);
+    assert!(time(&deep) < flat * 8);
+}
+
+#[test]
+fn deep_conditionals_parse_time() {
+    // nested conditionals are folded once, not once per level they are nested in
+    let depth = ParseLimits::MAX_DEPTH;
+    let nested = "\\iftrue ".repeat(depth) + &"\\fi ".repeat(depth);
+    let deep = nested.repeat(200);
+    let flat = "\\iftrue \\fi ".repeat(depth).repeat(200);
+    assert_eq!(deep.len(), flat.len());
+
+    let time = |source: &str| {
+        let start = Instant::now();
+        ast2::Document::from_str(source).unwrap();
+        start.elapsed()
+    };
+
+    let flat = time(&flat);
+    assert!(time(&deep) < flat * 8);
+
+    // nesting deeper than the limit fails early
+    let deeper = "\\iftrue ".repeat(80_000) + &"\\fi ".repeat(80_000);
+    assert_eq!(
+        ast2::Document::from_str(&deeper),
+        Err(exceeded(1, depth as u32 * 8 + 1, Limit::Depth))
+    );
+}
+
+#[test]
+fn input_len() {
+    let limits = ParseLimits::new().with_max_input_len(6);
+
+    assert!(ast1::Document::parse_with_limits("ab\ncdé", limits).is_err());
+    assert!(ast1::Document::parse_with_limits("ab\ncd", limits).is_ok());
+    assert_eq!(
+        ast1::Document::parse_with_limits("ab\ncdef", limits),
+        Err(exceeded(2, 4, Limit::InputLen))
+    );
+}
+
+#[test]
+fn nodes() {
+    // text, command, argument, text in the argument and text
+    let source = "a\\b {c}d";
+
+    assert!(
+        ast1::Document::parse_with_limits(source, ParseLimits::new().with_max_nodes(5)).is_ok()
+    );
+    assert_eq!(
+        ast1::Document::parse_with_limits(source, ParseLimits::new().with_max_nodes(4)),
+        Err(exceeded(1, 8, Limit::Nodes))
+    );
+}
+
+#[test]
+fn environment_depth() {
+    let limits = ParseLimits::new().with_max_environment_depth(1);
+
+    assert!(ast2::Document::parse_with_limits(
+        "\\begin{a}\\end{a}\\begin{b}{\\begin{c}\\end{c}}\\end{b}",
+        ParseLimits::new().with_max_environment_depth(2)
+    )
+    .is_ok());
+    assert!(
+        ast2::Document::parse_with_limits("\\begin{a}\\end{a}\\begin{b}\\end{b}", limits).is_ok()
+    );
+    assert_eq!(
+        ast3::Document::parse_with_limits("\\begin{a}\n\\begin{b}\\end{b}\\end{a}", limits),
+        Err(exceeded(2, 1, Limit::EnvironmentDepth))
+    );
+}
+
+#[test]
+fn events() {
+    let events = Events::new("{{x}}".as_bytes())
+        .with_limits(ParseLimits::new().with_max_depth(1))
+        .map(|event| event.kind)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        events,
+        vec![
+            EventKind::ScopeOpen(ast1::ScopeVariant::Curly),
+            EventKind::Error(exceeded(1, 2, Limit::Depth)),
+        ]
+    );
+}
+
+#[test]
+fn reparse() {
+    let limits = ParseLimits::default();
+
+    let source = "{".repeat(limits.max_depth()) + &"}".repeat(limits.max_depth());
+    let mut document = ast3::Document::from_str(&source).unwrap();
+    let inner = Position::new(1, limits.max_depth() as u32 + 1);
+    assert_eq!(
+        document.reparse(inner, inner, "{}"),
+        Err(exceeded(1, limits.max_depth() as u32 + 1, Limit::Depth))
+    );
+
+    let source = format!(
+        "\\begin{{document}}{}\n{}\\end{{document}}",
+        "\\begin{a}".repeat(limits.max_environment_depth() - 1),
+        "\\end{a}".repeat(limits.max_environment_depth() - 1)
+    );
+    let mut document = ast3::Document::from_str(&source).unwrap();
+    assert_eq!(
+        document.reparse(
+            Position::new(2, 1),
+            Position::new(2, 1),
+            "\\begin{b}\\end{b}"
+        ),
+        Err(exceeded(2, 1, Limit::EnvironmentDepth))
+    );
+}
@@ -0,0 +1,468 @@
+use std::{io::BufReader, str::FromStr};
+
+use crate::{
+    ast1::Catcodes,
+    ast3,
+    json::Json,
+    lsp::{read_message, write_message, Server, Source},
+    ParseLimits, Position,
+};
+
+const URI: &str = "file:///main.tex";
+
+fn request(server: &mut Server, id: u32, method: &str, params: Json) -> Json {
+    let mut out = server.handle(&Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ]));
+    assert_eq!(out.len(), 1);
+    out.remove(0)
+}
+
+fn notify(server: &mut Server, method: &str, params: Json) -> Vec<Json> {
+    server.handle(&Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ]))
+}
+
+fn open(server: &mut Server, text: &str) -> Vec<Json> {
+    notify(
+        server,
+        "textDocument/didOpen",
+        Json::object([(
+            "textDocument",
+            Json::object([
+                ("uri", URI.into()),
+                ("languageId", "latex".into()),
+                ("version", 1_u32.into()),
+                ("text", text.into()),
+            ]),
+        )]),
+    )
+}
+
+fn at(line: u32, character: u32) -> Json {
+    Json::object([
+        ("textDocument", Json::object([("uri", URI.into())])),
+        (
+            "position",
+            Json::object([("line", line.into()), ("character", character.into())]),
+        ),
+    ])
+}
+
+fn lines(locations: &Json) -> Vec<u64> {
+    locations
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| {
+            location
+                .pointer(&["range", "start", "line"])
+                .and_then(Json::as_u64)
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn diagnostics_and_incremental_sync() {
+    let mut server = Server::new();
+
+    let initialize = request(&mut server, 1, "initialize", Json::object([]));
+    assert_eq!(
+        initialize
+            .pointer(&["result", "capabilities", "textDocumentSync", "change"])
+            .and_then(Json::as_u64),
+        Some(2)
+    );
+
+    let published = open(&mut server, "\\begin{itemize}\n\\item é {x\n\\end{itemize}");
+    let diagnostics = published[0].pointer(&["params", "diagnostics"]).unwrap();
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(
+        diagnostics.as_array().unwrap()[0]
+            .pointer(&["range", "start"])
+            .unwrap()
+            .to_string(),
+        r#"{"line":1,"character":8}"#
+    );
+
+    // close the scope by inserting after `x`
+    let published = notify(
+        &mut server,
+        "textDocument/didChange",
+        Json::object([
+            (
+                "textDocument",
+                Json::object([("uri", URI.into()), ("version", 2_u32.into())]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object([
+                    (
+                        "range",
+                        Json::object([
+                            (
+                                "start",
+                                Json::object([
+                                    ("line", 1_u32.into()),
+                                    ("character", 10_u32.into()),
+                                ]),
+                            ),
+                            (
+                                "end",
+                                Json::object([
+                                    ("line", 1_u32.into()),
+                                    ("character", 10_u32.into()),
+                                ]),
+                            ),
+                        ]),
+                    ),
+                    ("text", "}".into()),
+                ])]),
+            ),
+        ]),
+    );
+
+    assert_eq!(
+        server.source(URI).unwrap().text(),
+        "\\begin{itemize}\n\\item é {x}\n\\end{itemize}"
+    );
+    assert_eq!(
+        published[0].pointer(&["params", "diagnostics"]),
+        Some(&Json::Array(Vec::new()))
+    );
+    assert_eq!(
+        published[0]
+            .pointer(&["params", "version"])
+            .and_then(Json::as_u64),
+        Some(2)
+    );
+
+    request(&mut server, 2, "shutdown", Json::Null);
+    notify(&mut server, "exit", Json::Null);
+    assert_eq!(server.exit_code(), Some(0));
+}
+
+#[test]
+fn symbols_and_folding() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+\label{sec:intro}
+\begin{figure}
+    \caption{A}
+    \label{fig:a}
+\end{figure}
+\subsection{More}
+See \ref{sec:intro} and \cref{fig:a,sec:intro}.
+\section*{End}
+\end{document}
+"#
+    .trim();
+
+    let mut server = Server::new();
+    open(&mut server, content);
+
+    let symbols = request(
+        &mut server,
+        1,
+        "textDocument/documentSymbol",
+        Json::object([("textDocument", Json::object([("uri", URI.into())]))]),
+    );
+    let symbols = symbols.get("result").unwrap().as_array().unwrap();
+
+    let names = |symbols: &Vec<Json>| {
+        symbols
+            .iter()
+            .map(|symbol| {
+                symbol
+                    .get("name")
+                    .and_then(Json::as_str)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(symbols), vec!["Intro", "End"]);
+    let intro = symbols[0].get("children").unwrap().as_array().unwrap();
+    assert_eq!(names(intro), vec!["figure", "More"]);
+    assert_eq!(
+        symbols[0]
+            .pointer(&["range", "end", "line"])
+            .and_then(Json::as_u64),
+        Some(10)
+    );
+
+    // the last section ends with the body, and the name of a starred one is selected
+    assert_eq!(
+        symbols[1].get("range").unwrap().to_string(),
+        r#"{"start":{"line":10,"character":0},"end":{"line":11,"character":0}}"#
+    );
+    assert_eq!(
+        symbols[1].get("selectionRange").unwrap().to_string(),
+        r#"{"start":{"line":10,"character":0},"end":{"line":10,"character":14}}"#
+    );
+
+    let folding = request(
+        &mut server,
+        2,
+        "textDocument/foldingRange",
+        Json::object([("textDocument", Json::object([("uri", URI.into())]))]),
+    );
+    assert_eq!(
+        folding.get("result").unwrap().to_string(),
+        r#"[{"startLine":1,"endLine":11},{"startLine":4,"endLine":7}]"#
+    );
+}
+
+#[test]
+fn definition_and_references() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+\label{sec:intro}
+\begin{figure}
+    \caption{A}
+    \label{fig:a}
+\end{figure}
+\subsection{More}
+See \ref{sec:intro} and \cref{fig:a,sec:intro}.
+\section{End}
+\end{document}
+"#
+    .trim();
+
+    let mut server = Server::new();
+    open(&mut server, content);
+
+    // on `sec:intro` in the `\cref` list
+    let definition = request(&mut server, 1, "textDocument/definition", at(9, 42));
+    assert_eq!(lines(definition.get("result").unwrap()), vec![3]);
+
+    // on `fig:a` in the `\cref` list
+    let definition = request(&mut server, 2, "textDocument/definition", at(9, 32));
+    assert_eq!(lines(definition.get("result").unwrap()), vec![6]);
+
+    let mut params = at(3, 10);
+    params.insert(
+        "context",
+        Json::object([("includeDeclaration", true.into())]),
+    );
+    let references = request(&mut server, 3, "textDocument/references", params);
+    assert_eq!(lines(references.get("result").unwrap()), vec![3, 9, 9]);
+
+    let unknown = request(&mut server, 4, "textDocument/hover", at(0, 0));
+    assert_eq!(
+        unknown.pointer(&["error", "code"]),
+        Some(&Json::Number(-32601.0))
+    );
+}
+
+#[test]
+fn transport() {
+    let message = Json::object([("jsonrpc", "2.0".into()), ("method", "exit".into())]);
+
+    let mut buffer = Vec::new();
+    write_message(&mut buffer, &message).unwrap();
+    write_message(&mut buffer, &message).unwrap();
+
+    let mut reader = BufReader::new(buffer.as_slice());
+    let first = read_message(&mut reader).unwrap().unwrap();
+    assert_eq!(Json::from_str(&first).unwrap(), message);
+    assert!(read_message(&mut reader).unwrap().is_some());
+    assert!(read_message(&mut reader).unwrap().is_none());
+
+    let reply = Server::new().handle_str("{");
+    assert_eq!(
+        reply[0].pointer(&["error", "code"]),
+        Some(&Json::Number(-32700.0))
+    );
+}
+
+#[test]
+fn completion() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Intro}
+\label{sec:intro}
+\begin{figure}
+    \caption{A}
+    \label{fig:a}
+\end{figure}
+\subsection{More}
+See \ref{sec:intro} and \cref{fig:a,sec:intro}.
+\section{End}
+\end{document}
+"#
+    .trim();
+
+    let mut server = Server::new();
+    open(&mut server, content);
+
+    // typing `\ref{` leaves the document unparsable, labels come from the last version
+    notify(
+        &mut server,
+        "textDocument/didChange",
+        Json::object([
+            (
+                "textDocument",
+                Json::object([("uri", URI.into()), ("version", 2_u32.into())]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object([
+                    (
+                        "range",
+                        Json::object([
+                            (
+                                "start",
+                                Json::object([
+                                    ("line", 10_u32.into()),
+                                    ("character", 0_u32.into()),
+                                ]),
+                            ),
+                            (
+                                "end",
+                                Json::object([
+                                    ("line", 10_u32.into()),
+                                    ("character", 0_u32.into()),
+                                ]),
+                            ),
+                        ]),
+                    ),
+                    ("text", "\\ref{fig".into()),
+                ])]),
+            ),
+        ]),
+    );
+    assert!(server.source(URI).unwrap().document().is_err());
+
+    let completion = request(&mut server, 1, "textDocument/completion", at(10, 8));
+    assert_eq!(
+        completion.get("result").unwrap().to_string(),
+        r#"[{"label":"fig:a","kind":18,"detail":"label in figure, line 7"}]"#
+    );
+}
+
+#[test]
+fn source_apply() {
+    let content = "\\documentclass{article}
+\\usepackage{amsmath}
+\\begin{document}
+First paragraph
+over two lines.
+
+Second \\textbf{bold
+text} here.
+
+\\begin{itemize}
+    \\item one
+    \\item two
+\\end{itemize}
+$x + y$
+\\end{document}
+";
+    let mut source = Source::new(content.to_string(), Some(1));
+    let range = |line: u32, character: u32| {
+        Json::object([("line", line.into()), ("character", character.into())])
+    };
+
+    source.apply(
+        &[Json::object([
+            (
+                "range",
+                Json::object([("start", range(6, 15)), ("end", range(6, 15))]),
+            ),
+            ("text", "very ".into()),
+        ])],
+        Some(2),
+    );
+
+    let edited = content.replace("Second \\textbf{", "Second \\textbf{very ");
+    assert_eq!(source.text(), edited);
+    assert_eq!(
+        source.document().ok(),
+        ast3::Document::from_str(&edited).ok().as_ref()
+    );
+}
+
+#[test]
+fn source_comments() {
+    let content =
+        "\\begin{document}\nFirst % note\nline.\n%\n\nSecond \\textbf{x} % end\n\\end{document}\n";
+    let mut source = Source::new(content.to_string(), Some(1));
+    let change = |start: (u32, u32), end: (u32, u32), text: &str| {
+        let at = |(line, character): (u32, u32)| {
+            Json::object([("line", line.into()), ("character", character.into())])
+        };
+        Json::object([
+            (
+                "range",
+                Json::object([("start", at(start)), ("end", at(end))]),
+            ),
+            ("text", text.into()),
+        ])
+    };
+
+    // within a comment, before and after one, adding, escaping and removing one
+    for (version, (start, end, text)) in [
+        ((1, 12), (1, 12), "s"),
+        ((1, 0), (1, 5), "Third"),
+        ((5, 16), (5, 16), "y"),
+        ((2, 5), (2, 5), " % more"),
+        ((1, 6), (1, 6), "\\"),
+        ((5, 19), (5, 20), ""),
+        ((3, 0), (4, 0), ""),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        source.apply(&[change(start, end, text)], Some(version as i64 + 2));
+        assert_eq!(
+            source.document(),
+            ast3::Document::from_str(source.text()).as_ref()
+        );
+    }
+    assert_eq!(
+        source.text(),
+        "\\begin{document}\nThird \\% notes\nline. % more\n\nSecond \\textbf{xy}  end\n\\end{document}\n"
+    );
+}
+
+#[test]
+fn source_catcodes() {
+    let text = "\\def\\a@b{x}\n\\a@b";
+    let mut source = Source::with_catcodes(text.to_string(), Some(1), Catcodes::for_path("x.sty"));
+
+    source.apply(
+        &[Json::object([
+            (
+                "range",
+                Json::object([
+                    ("start", source.lsp_position(Position::new(2, 5))),
+                    ("end", source.lsp_position(Position::new(2, 5))),
+                ]),
+            ),
+            ("text", "\n\\c@d".into()),
+        ])],
+        Some(2),
+    );
+
+    let expected = ast3::Document::parse_with_catcodes(
+        source.text(),
+        ParseLimits::default(),
+        Catcodes::for_path("x.sty"),
+    )
+    .unwrap();
+    assert_eq!(source.document(), Ok(&expected));
+}
@@ -0,0 +1,289 @@
+use std::str::FromStr;
+
+use crate::{
+    ast2,
+    ast3::{self, Chunk, ChunkVariant, Command, Parameter, Selector},
+    format::Formatter,
+    plaintext::Extractor,
+    traits::Validate,
+    InternalError, Position,
+};
+
+#[test]
+fn structure() {
+    let content = r#"
+\documentclass{article}
+\def\foo#1.#2\par{first #1,
+second #2}
+\gdef \bar[#1]{\def\baz##1{#1 ##1}}
+\newcommand{\qux}[1]{#1 and #1}
+\begin{document}
+\foo a.b\par
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    assert_eq!(document.to_string(), content);
+    document.validate().unwrap();
+
+    let chunks = document.chunks_preamable();
+    let ChunkVariant::MacroDefinition(foo) = chunks[2].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(foo.command(), "def");
+    assert_eq!(foo.label(), "foo");
+    assert_eq!(
+        foo.parameters(),
+        &vec![
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Parameter(Parameter::new_unchecked(1, Some(1)))
+            ),
+            Chunk::new_unchecked(1, ChunkVariant::Text(".".to_string())),
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Parameter(Parameter::new_unchecked(1, Some(2)))
+            ),
+            Chunk::new_unchecked(
+                1,
+                ChunkVariant::Command(Command::new_unchecked("par".to_string(), Vec::new()))
+            ),
+        ]
+    );
+    assert_eq!(foo.body().len(), 4);
+    assert_eq!(
+        foo.body()[2],
+        Chunk::new_unchecked(1, ChunkVariant::Text(",\nsecond ".to_string()))
+    );
+    assert_eq!(foo.arity(), 2);
+
+    let ChunkVariant::MacroDefinition(bar) = chunks[4].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(bar.command(), "gdef");
+    assert_eq!(bar.prec(), " ");
+    assert_eq!(bar.parameters().len(), 1);
+    assert_eq!(bar.parameters()[0].to_string(), "[#1]");
+    assert_eq!(bar.arity(), 1);
+
+    // `##1` is a parameter of the definition in the body
+    let ChunkVariant::MacroDefinition(inner) = bar.body()[0].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(inner.label(), "baz");
+    assert!(matches!(
+        inner.parameters()[0].variant(),
+        ChunkVariant::Parameter(parameter) if parameter.hashes() == 2 && parameter.number() == Some(1)
+    ));
+}
+
+#[test]
+fn arguments() {
+    // the body is not an argument of the last command of the parameter text
+    let document = ast2::Document::from_str("\\def\\foo#1\\par{#1}").unwrap();
+    match document.chunks()[0].variant() {
+        ast2::ChunkVariant::MacroDefinition(definition) => {
+            assert!(matches!(
+                definition.parameters()[1].variant(),
+                ast2::ChunkVariant::Command(command) if command.arguments().is_empty()
+            ));
+        }
+        _ => panic!("not a definition"),
+    }
+
+    // arguments after the body follow the definition
+    let source = "\\def\\foo{x} {y}[z]";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+
+    let chunks = document.chunks_preamable();
+    let ChunkVariant::MacroDefinition(definition) = chunks[0].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(
+        definition.body(),
+        &vec![Chunk::new_unchecked(1, ChunkVariant::Text("x".to_string()))]
+    );
+    assert_eq!(chunks.len(), 4);
+}
+
+#[test]
+fn parameters() {
+    let content = r#"
+\documentclass{article}
+\def\foo#1.#2\par{first #1,
+second #2}
+\gdef \bar[#1]{\def\baz##1{#1 ##1}}
+\newcommand{\qux}[1]{#1 and #1}
+\begin{document}
+\foo a.b\par
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let query = document.query();
+
+    let found = query.select("command[newcommand] parameter").unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].start(), Position::new(5, 22));
+
+    let found = query.select("definition[foo] > parameter").unwrap();
+    assert_eq!(found.len(), 4);
+}
+
+#[test]
+fn incomplete() {
+    for source in [
+        "\\def",
+        "\\def\\foo",
+        "\\def\\foo#1",
+        "\\def{x}",
+        "text #1 ## #",
+    ] {
+        let document = ast3::Document::from_str(source).unwrap();
+        assert_eq!(document.to_string(), source);
+        document.validate().unwrap();
+        assert!(document.query().select("definition").unwrap().is_empty());
+    }
+}
+
+#[test]
+fn conditionals() {
+    let source = "\\def\\iffoo{}\\def\\bar#1\\fi{#1}\\iffoo a\\fi";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(document.to_string(), source);
+
+    let chunks = document.chunks_preamable();
+    let ChunkVariant::MacroDefinition(first) = chunks[0].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(first.label(), "iffoo");
+    let ChunkVariant::MacroDefinition(second) = chunks[1].variant() else {
+        panic!("not a definition")
+    };
+    assert!(matches!(
+        second.parameters()[1].variant(),
+        ChunkVariant::Command(command) if command.label() == "fi"
+    ));
+    assert!(matches!(chunks[2].variant(), ChunkVariant::Conditional(_)));
+}
+
+#[test]
+fn lines() {
+    let source = "a\n\\def\n\\foo#1\n#2{\nx\n}\nb";
+    let document = ast3::Document::from_str(source).unwrap();
+    document.validate().unwrap();
+
+    let chunks = document.chunks_preamable();
+    assert_eq!(chunks[1].line_no(), 2);
+    assert_eq!(chunks.last().unwrap().line_no(), 6);
+
+    let ChunkVariant::MacroDefinition(definition) = chunks[1].variant() else {
+        panic!("not a definition")
+    };
+    assert_eq!(definition.prec(), "\n");
+    assert_eq!(
+        definition.body_start(Position::new(2, 1)),
+        Position::new(4, 4)
+    );
+    assert_eq!(definition.body()[0].line_no(), 1);
+}
+
+#[test]
+fn validate() {
+    assert_eq!(
+        ast2::Parameter::new(0, Some(1)).err(),
+        Some(InternalError::InvalidParameter)
+    );
+    assert_eq!(
+        ast3::Parameter::new(1, Some(0)).err(),
+        Some(InternalError::InvalidParameter)
+    );
+    assert_eq!(
+        ast3::MacroDefinition::new(
+            "newcommand".to_string(),
+            String::new(),
+            "foo".to_string(),
+            Vec::new(),
+            Vec::new()
+        )
+        .err(),
+        Some(InternalError::NotMacroDefinition)
+    );
+}
+
+#[test]
+fn reparse() {
+    let content = r#"
+\documentclass{article}
+\def\foo#1.#2\par{first #1,
+second #2}
+\gdef \bar[#1]{\def\baz##1{#1 ##1}}
+\newcommand{\qux}[1]{#1 and #1}
+\begin{document}
+\foo a.b\par
+\end{document}
+"#
+    .trim();
+
+    for (start, end, text) in [
+        (Position::new(2, 19), Position::new(2, 24), "third"),
+        (Position::new(2, 12), Position::new(2, 12), "#3"),
+        (Position::new(4, 27), Position::new(4, 27), "##2"),
+        (Position::new(7, 1), Position::new(7, 1), "\\def\\x"),
+    ] {
+        let mut document = ast3::Document::from_str(content).unwrap();
+        document.reparse(start, end, text).unwrap();
+
+        let full = ast3::Document::from_str(&document.to_string()).unwrap();
+        assert_eq!(document, full);
+    }
+
+    // an unbalanced body is left unchanged
+    let mut document = ast3::Document::from_str(content).unwrap();
+    assert!(document
+        .reparse(Position::new(4, 27), Position::new(4, 27), "{")
+        .is_err());
+    assert_eq!(document.to_string(), content);
+}
+
+#[test]
+fn outputs() {
+    let content = r#"
+\documentclass{article}
+\def\foo#1.#2\par{first #1,
+second #2}
+\gdef \bar[#1]{\def\baz##1{#1 ##1}}
+\newcommand{\qux}[1]{#1 and #1}
+\begin{document}
+\foo a.b\par
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+
+    let plain = Extractor::new().extract(&document);
+    assert!(!plain.text().contains("first"));
+
+    let source = "\\def\\foo#1 #2{#1\n\n\n#2}";
+    let document = ast3::Document::from_str(source).unwrap();
+    assert_eq!(
+        Formatter::new().format(&document),
+        "\\def\\foo#1 #2{#1\n\n#2}"
+    );
+
+    let selector = Selector::from_str("definition[foo]").unwrap();
+    assert_eq!(document.query().select_parsed(&selector).len(), 1);
+}
+
+#[test]
+fn deep() {
+    let depth = 100;
+    let source = "\\def\\a{".repeat(depth) + &"}".repeat(depth);
+    let document = ast3::Document::from_str(&source).unwrap();
+    assert_eq!(document.to_string(), source);
+    document.validate().unwrap();
+}
@@ -0,0 +1,225 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3,
+    markdown::{Exporter, Importer},
+    traits::Validate,
+    Position,
+};
+
+#[test]
+fn export() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Introduction}\label{sec:intro}
+Some \emph{text} and \textbf{bold}, see \href{https://example.com}{here}
+and \url{https://example.com/a\_b}.\footnote{A note.} Cited \cite[p.~2]{knuth, lamport}.
+Maths $x^2$ and \verb|a*b|, see Section~\ref{sec:intro}.
+
+\begin{itemize}
+  \item First
+  \item Second
+    \begin{enumerate}
+      \item Nested
+    \end{enumerate}
+\end{itemize}
+\subsection*{Code}
+\begin{lstlisting}[language=Python]
+print("*")
+\end{lstlisting}
+\begin{tabular}{ll}
+  \hline
+  A & B \\
+  1 & 2 \\
+  \hline
+\end{tabular}
+\[ e = mc^2 \]
+\mystery{kept}
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let markdown = Exporter::new().export(&document);
+
+    assert_eq!(
+        markdown.text(),
+        "# Introduction\n\n\
+         Some *text* and **bold**, see [here](https://example.com)\n\
+         and <https://example.com/a_b>.[^1] Cited [@knuth; @lamport, p.\u{a0}2].\n\
+         Maths $x^2$ and `a*b`, see Section\u{a0}\\ref{sec:intro}.\n\n\
+         - First\n\
+         - Second\n\n  1. Nested\n\n\
+         ## Code\n\n\
+         ```python\nprint(\"*\")\n```\n\n\
+         | A | B |\n| --- | --- |\n| 1 | 2 |\n\n\
+         $$\ne = mc^2\n$$\nkept\n\n\
+         [^1]: A note.\n"
+    );
+}
+
+#[test]
+fn export_escapes() {
+    let content = "\\begin{document}\n\
+                   1. not a list\n\n\
+                   \\# not a heading\n\n\
+                   - not an item, + nor this\n\
+                   + one\n\
+                   \\[ -x \\]\n\n\
+                   \\href{http://a.com/x)y}{link}\n\n\
+                   \\begin{tabular}{ll}\n  a|b & c \\\\\n\\end{tabular}\n\
+                   \\end{document}";
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let markdown = Exporter::new().export(&document);
+
+    assert_eq!(
+        markdown.text(),
+        "1\\. not a list\n\n\
+         \\# not a heading\n\n\
+         \\- not an item, + nor this\n\\+ one\n$$\n-x\n$$\n\n\
+         [link](<http://a.com/x)y>)\n\n\
+         | a\\|b | c |\n| --- | --- |\n"
+    );
+}
+
+#[test]
+fn warnings() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\section{Introduction}\label{sec:intro}
+Some \emph{text} and \textbf{bold}, see \href{https://example.com}{here}
+and \url{https://example.com/a\_b}.\footnote{A note.} Cited \cite[p.~2]{knuth, lamport}.
+Maths $x^2$ and \verb|a*b|, see Section~\ref{sec:intro}.
+
+\begin{itemize}
+  \item First
+  \item Second
+    \begin{enumerate}
+      \item Nested
+    \end{enumerate}
+\end{itemize}
+\subsection*{Code}
+\begin{lstlisting}[language=Python]
+print("*")
+\end{lstlisting}
+\begin{tabular}{ll}
+  \hline
+  A & B \\
+  1 & 2 \\
+  \hline
+\end{tabular}
+\[ e = mc^2 \]
+\mystery{kept}
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let markdown = Exporter::new().export(&document);
+    let warnings = markdown
+        .warnings()
+        .iter()
+        .map(|warning| (warning.message().as_str(), warning.start(), warning.end()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                "reference `\\ref{sec:intro}` has no Markdown equivalent",
+                Position::new(6, 41),
+                Position::new(6, 56)
+            ),
+            (
+                "unsupported command `\\mystery`",
+                Position::new(26, 1),
+                Position::new(26, 15)
+            ),
+        ]
+    );
+
+    let markdown = Exporter::new().with_ignored("mystery").export(&document);
+    assert_eq!(markdown.warnings().len(), 1);
+
+    let document = ast3::Document::from_str(
+        "\\begin{tabular}{ll}\n\\multicolumn{2}{c}{A} \\\\\n\\end{tabular}\n\\begin{tikzpicture}\\end{tikzpicture}",
+    )
+    .unwrap();
+    let markdown = Exporter::new().export(&document);
+
+    assert!(markdown.text().starts_with("```latex\n\\begin{tabular}"));
+    assert_eq!(markdown.warnings().len(), 2);
+    assert_eq!(markdown.warnings()[1].start(), Position::new(4, 1));
+}
+
+#[test]
+fn import() {
+    let markdown = r#"
+# Intro *now*
+
+Costs $5 & 50% of "it", see [docs](https://example.com/a_b#x)
+and <https://example.com>.  
+Maths $x^2$ and `a_b`.
+
+- First
+- Second
+  1. Nested
+
+```python
+print(1)
+```
+
+| A | B |
+|---|--:|
+| 1 | **2** |
+
+$$
+e = mc^2
+$$
+
+![A cat](cat.png)
+"#
+    .trim();
+
+    let document = Importer::new().import(markdown);
+    let latex = document.to_string();
+
+    assert_eq!(
+        latex,
+        "\\documentclass{article}\n\\usepackage{graphicx}\n\\usepackage{listings}\n\\usepackage{hyperref}\n\n\
+         \\begin{document}\n\
+         \\section{Intro \\emph{now}}\n\n\
+         Costs \\$5 \\& 50\\% of ``it'', see \\href{https://example.com/a_b\\#x}{docs}\n\
+         and \\url{https://example.com}.\\\\\n\
+         Maths $x^2$ and \\texttt{a\\_b}.\n\n\
+         \\begin{itemize}\n  \\item First\n  \\item Second\n    \\begin{enumerate}\n      \\item Nested\n    \\end{enumerate}\n\\end{itemize}\n\n\
+         \\begin{lstlisting}[language=python]\nprint(1)\n\\end{lstlisting}\n\n\
+         \\begin{tabular}{lr}\n  A & B \\\\\n  \\hline\n  1 & \\textbf{2} \\\\\n\\end{tabular}\n\n\
+         \\[\n  e = mc^2\n\\]\n\n\
+         \\begin{figure}\n  \\centering\n  \\includegraphics{cat.png}\n  \\caption{A cat}\n\\end{figure}\n\
+         \\end{document}\n"
+    );
+
+    assert!(document.validate().is_ok());
+
+    let reparsed = ast3::Document::from_str(&latex).unwrap();
+    assert_eq!(reparsed.chunks_preamable(), document.chunks_preamable());
+    assert_eq!(reparsed.chunks_body(), document.chunks_body());
+}
+
+#[test]
+fn import_escaping() {
+    let document = Importer::new()
+        .with_documentclass("book")
+        .import("# A\\B\n\n~^{x} (a [b) c] \\*not* $x}$\n");
+
+    assert!(document.validate().is_ok());
+    assert_eq!(
+        document.chunks_body().iter().map(ToString::to_string).collect::<String>(),
+        "\n\\chapter{A\\textbackslash{}B}\n\n\
+         \\textasciitilde{}\\textasciicircum{}\\{x\\} \\textparenleft{}a [b\\textparenright{} c] *not* \\$x\\}\\$\n"
+    );
+}
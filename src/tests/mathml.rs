@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use crate::{ast3, mathml};
+
+#[test]
+fn from_source() {
+    let cases = [
+        (
+            r"x^2 + y_1' = \frac{a}{b}",
+            "<math><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo>\
+             <msubsup><mi>y</mi><mn>1</mn><mo>′</mo></msubsup><mo>=</mo>\
+             <mfrac><mi>a</mi><mi>b</mi></mfrac></math>",
+        ),
+        (
+            r"\sqrt{2} \neq \sqrt[3]{\alpha}",
+            "<math><msqrt><mn>2</mn></msqrt><mo>≠</mo><mroot><mi>α</mi><mn>3</mn></mroot></math>",
+        ),
+        (
+            r"\left( \Gamma \right.",
+            "<math><mo fence=\"true\" stretchy=\"true\">(</mo><mi mathvariant=\"normal\">Γ</mi></math>",
+        ),
+        (
+            r"\mathbb{R}^n \to \mathbf{v}",
+            "<math><msup><mi mathvariant=\"double-struck\">R</mi><mi>n</mi></msup><mo>→</mo>\
+             <mi mathvariant=\"bold\">v</mi></math>",
+        ),
+        (
+            r"\sin x < 3.5 \text{ if } x",
+            "<math><mi>sin</mi><mi>x</mi><mo>&lt;</mo><mn>3.5</mn><mtext> if </mtext><mi>x</mi></math>",
+        ),
+        (
+            r"\mystery{x} + 1",
+            "<math><merror><mtext>\\mystery{x}</mtext></merror><mo>+</mo><mn>1</mn></math>",
+        ),
+    ];
+
+    for (source, expected) in cases {
+        assert_eq!(mathml::from_source(source, false), expected, "{source}");
+    }
+
+    assert_eq!(
+        mathml::from_source(r"\sum_{i=1}^n i", true),
+        "<math display=\"block\"><munderover><mo largeop=\"true\">∑</mo>\
+         <mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi></math>"
+    );
+}
+
+#[test]
+fn tables() {
+    assert_eq!(
+        mathml::from_source(r"\begin{pmatrix} 1 & 0 \\ 0 & 1 \end{pmatrix}", false),
+        "<math><mo fence=\"true\" stretchy=\"true\">(</mo><mtable columnalign=\"center\">\
+         <mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr>\
+         <mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr>\
+         </mtable><mo fence=\"true\" stretchy=\"true\">)</mo></math>"
+    );
+
+    let document =
+        ast3::Document::from_str("\\begin{align}\n  a &= b \\\\\n  &= c \\label{eq}\n\\end{align}")
+            .unwrap();
+    let ast3::ChunkVariant::Environment(env) = document.chunks_preamable()[0].variant() else {
+        panic!("not an environment")
+    };
+
+    assert_eq!(
+        mathml::from_environment(env).unwrap(),
+        "<math display=\"block\"><mtable columnalign=\"right left\">\
+         <mtr><mtd><mi>a</mi></mtd><mtd><mrow><mo>=</mo><mi>b</mi></mrow></mtd></mtr>\
+         <mtr><mtd><mrow></mrow></mtd><mtd><mrow><mo>=</mo><mi>c</mi></mrow></mtd></mtr>\
+         </mtable></math>"
+    );
+
+    let document = ast3::Document::from_str("\\begin{itemize}\\end{itemize}").unwrap();
+    let ast3::ChunkVariant::Environment(env) = document.chunks_preamable()[0].variant() else {
+        panic!("not an environment")
+    };
+    assert!(mathml::from_environment(env).is_none());
+}
@@ -4,3 +4,47 @@ mod ast1;
 mod ast2;
 #[cfg(test)]
 mod ast3;
+#[cfg(test)]
+mod builder;
+#[cfg(test)]
+mod catcodes;
+#[cfg(test)]
+mod completion;
+#[cfg(test)]
+mod conditional;
+#[cfg(test)]
+mod diagnostic;
+#[cfg(test)]
+mod edit;
+#[cfg(test)]
+mod events;
+#[cfg(test)]
+mod format;
+#[cfg(test)]
+mod html;
+#[cfg(all(test, feature = "json"))]
+mod json;
+#[cfg(all(test, feature = "json"))]
+mod languagetool;
+#[cfg(test)]
+mod limits;
+#[cfg(all(test, feature = "lsp"))]
+mod lsp;
+#[cfg(test)]
+mod macrodefinition;
+#[cfg(test)]
+mod markdown;
+#[cfg(test)]
+mod mathml;
+#[cfg(test)]
+mod plaintext;
+#[cfg(test)]
+mod query;
+#[cfg(test)]
+mod reparse;
+#[cfg(test)]
+mod statistics;
+#[cfg(test)]
+mod unicode;
+#[cfg(test)]
+mod visit;
@@ -0,0 +1,146 @@
+use std::str::FromStr;
+
+use crate::{ast3, plaintext::Extractor, Position};
+
+#[test]
+fn extract() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+\section{Introduction}\label{sec:intro}
+This is \emph{some} text with a tpyo, see~\cite{knuth} and Fig.~\ref{f}.
+Maths $x^2$ is ``quoted'' here\footnote{A note.}.
+
+\begin{itemize}
+  \item First item
+  \item[b)] Second
+\end{itemize}
+\begin{verbatim}
+dropped
+\end{verbatim}
+Hy\-phen and (round) \LaTeX.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let plain = Extractor::new().extract(&document);
+
+    assert_eq!(
+        plain.text(),
+        "Introduction\n\nThis is some text with a tpyo, see [0] and Fig. 0. \
+         Maths X is \"quoted\" here.\n\nA note.\n\nFirst item\n\nSecond\n\n\
+         Hyphen and (round) LaTeX."
+    );
+
+    let plain = Extractor::new()
+        .with_maths("M")
+        .with_citation("[1]")
+        .with_skipped("itemize")
+        .extract(&document);
+
+    assert!(plain.text().contains("see [1] and"));
+    assert!(plain.text().contains("Maths M is"));
+    assert!(!plain.text().contains("First"));
+
+    // without a document environment, the whole input is prose
+    let fragment = ast3::Document::from_str("Some \\textbf{bold} text.").unwrap();
+    assert_eq!(
+        Extractor::new().extract(&fragment).text(),
+        "Some bold text."
+    );
+}
+
+#[test]
+fn source_map() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+\section{Introduction}\label{sec:intro}
+This is \emph{some} text with a tpyo, see~\cite{knuth} and Fig.~\ref{f}.
+Maths $x^2$ is ``quoted'' here\footnote{A note.}.
+
+\begin{itemize}
+  \item First item
+  \item[b)] Second
+\end{itemize}
+\begin{verbatim}
+dropped
+\end{verbatim}
+Hy\-phen and (round) \LaTeX.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let plain = Extractor::new().extract(&document);
+    let find = |s: &str| {
+        let start = plain.text().find(s).unwrap();
+        start..start + s.len()
+    };
+
+    assert_eq!(
+        plain.source_range(find("tpyo")),
+        Some((Position::new(5, 33), Position::new(5, 37)))
+    );
+    assert_eq!(
+        plain.source_range(find("some")),
+        Some((Position::new(5, 15), Position::new(5, 19)))
+    );
+    // placeholders map to the whole command
+    assert_eq!(
+        plain.source_range(find("[0]")),
+        Some((Position::new(5, 43), Position::new(5, 55)))
+    );
+    assert_eq!(
+        plain.source_position(find("note").start),
+        Some(Position::new(6, 43))
+    );
+    // ranges spanning dropped commands
+    assert_eq!(
+        plain.source_range(find("Hyphen")),
+        Some((Position::new(15, 1), Position::new(15, 9)))
+    );
+    assert_eq!(
+        plain.source_range(find("\"quoted\"")),
+        Some((Position::new(6, 16), Position::new(6, 26)))
+    );
+
+    // every exact mapping is a copy of the source
+    let lines = content.lines().collect::<Vec<_>>();
+    for mapping in plain.mappings().iter().filter(|m| m.is_exact()) {
+        let (start, end) = (mapping.start(), mapping.end());
+        assert_eq!(start.line, end.line);
+
+        let source = lines[start.line as usize - 1]
+            .chars()
+            .skip(start.column as usize - 1)
+            .take((end.column - start.column) as usize)
+            .collect::<String>();
+        assert_eq!(source, plain.text()[mapping.output().clone()]);
+    }
+}
+
+#[test]
+fn starred() {
+    let content = r#"
+\section{One}
+Some text.
+\section*{Two}
+More text.
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let plain = Extractor::new().extract(&document);
+
+    assert_eq!(plain.text(), "One\n\nSome text.\n\nTwo\n\nMore text.");
+
+    let start = plain.text().find("Two").unwrap();
+    assert_eq!(
+        plain.source_range(start..start + 3),
+        Some((Position::new(3, 11), Position::new(3, 14)))
+    );
+}
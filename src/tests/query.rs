@@ -0,0 +1,241 @@
+use std::str::FromStr;
+
+use crate::{ast3::Node, *};
+
+#[test]
+fn named() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let query = ast.query();
+
+    let cites = query
+        .commands_named("cite")
+        .map(|m| (m.start(), m.end()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        cites,
+        vec![
+            (Position::new(5, 19), Position::new(5, 31)),
+            (Position::new(7, 5), Position::new(7, 19)),
+        ]
+    );
+
+    let figure = query.environments_named("figure").next().unwrap();
+    assert_eq!(figure.start(), Position::new(3, 1));
+    assert_eq!(figure.end(), Position::new(6, 13));
+    assert_eq!(
+        query.parent_of(figure).unwrap().node().label(),
+        Some("document")
+    );
+    assert_eq!(
+        query
+            .descendants_of(figure)
+            .filter(|m| matches!(m.node(), Node::Command(_)))
+            .count(),
+        3
+    );
+    assert_eq!(query.environments_named("document").count(), 1);
+}
+
+#[test]
+fn select() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let query = ast.query();
+
+    let labels = |selector: &str| {
+        query
+            .select(selector)
+            .unwrap()
+            .iter()
+            .map(|m| m.start())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        labels("environment[figure] command[cite]"),
+        vec![Position::new(5, 19)]
+    );
+    assert_eq!(
+        labels("environment[figure] > command[caption]"),
+        vec![Position::new(5, 5)]
+    );
+    assert_eq!(
+        labels("environment[document] > command[cite]"),
+        vec![Position::new(7, 5)]
+    );
+    assert_eq!(labels("maths command[frac]"), vec![Position::new(7, 25)]);
+    assert_eq!(
+        labels("maths[outline], command[includegraphics]"),
+        vec![Position::new(4, 5)]
+    );
+    assert_eq!(labels("command[caption] > argument > *").len(), 2);
+}
+
+#[test]
+fn selector_errors() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let query = ast.query();
+
+    assert_eq!(
+        query.select("command[cite").unwrap_err(),
+        ast3::SelectorError::UnclosedLabel(7)
+    );
+    assert_eq!(
+        query.select("> command").unwrap_err(),
+        ast3::SelectorError::MissingStep(0)
+    );
+    assert_eq!(
+        query.select("command >").unwrap_err(),
+        ast3::SelectorError::MissingStep(9)
+    );
+    assert_eq!(
+        query.select("macro").unwrap_err(),
+        ast3::SelectorError::UnknownType("macro".to_string())
+    );
+}
+
+#[test]
+fn positions_round_trip() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let query = ast.query();
+    let lines = content.split('\n').collect::<Vec<_>>();
+
+    for m in query.commands_named("cite") {
+        let line = lines[m.start().line as usize - 1];
+        let text = line
+            .chars()
+            .skip(m.start().column as usize - 1)
+            .take((m.end().column - m.start().column) as usize)
+            .collect::<String>();
+        assert!(text.starts_with("\\cite{") && text.ends_with('}'));
+    }
+}
+
+#[test]
+fn node_at() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    // inside `knuth`
+    let position = Position::new(5, 26);
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let path = ast.node_at(position);
+    assert!(matches!(path[0].node(), Node::Body(_)));
+    assert_eq!(path[1].node().label(), Some("figure"));
+    assert_eq!(path[2].node().label(), Some("caption"));
+    assert!(matches!(path[3].node(), Node::Argument(_)));
+    assert_eq!(path[4].node().label(), Some("cite"));
+    assert!(matches!(path[5].node(), Node::Argument(_)));
+    assert!(matches!(path[6].node(), Node::Text(s) if s == "knuth"));
+    assert_eq!(path.len(), 7);
+    assert_eq!(path[6].start(), Position::new(5, 25));
+
+    let ast = ast2::Document::from_str(content).unwrap();
+    let path = ast.node_at(position);
+    assert_eq!(
+        path.iter().map(|m| m.node().label()).collect::<Vec<_>>(),
+        vec![
+            Some("document"),
+            Some("figure"),
+            Some("caption"),
+            None,
+            Some("cite"),
+            None,
+            None
+        ]
+    );
+
+    let ast = ast1::Document::from_str(content).unwrap();
+    let path = ast.node_at(position);
+    assert_eq!(
+        path.iter().map(|m| m.node().label()).collect::<Vec<_>>(),
+        vec![Some("caption"), None, Some("cite"), None, None]
+    );
+
+    assert!(ast.node_at(Position::new(9, 1)).is_empty());
+}
+
+#[test]
+fn span_of() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+\begin{figure}
+    \includegraphics{a.png}
+    \caption{From \cite{knuth}}
+\end{figure}
+See \cite{lamport} and $\frac{1}{2}$.
+\end{document}
+"#
+    .trim();
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let ast3::ChunkVariant::Environment(figure) = ast.chunks_body()[1].variant() else {
+        panic!("expected figure")
+    };
+
+    assert_eq!(
+        ast.span_of((&figure.content()[1]).into()),
+        Some((Position::new(4, 5), Position::new(4, 28)))
+    );
+
+    let other = ast3::Document::from_str(content).unwrap();
+    assert_eq!(ast.span_of((&other.chunks_body()[1]).into()), None);
+}
@@ -0,0 +1,334 @@
+use std::str::FromStr;
+
+use crate::{ast1, ast2, ast3, traits::Validate, Position};
+
+// reparse an edit, checking the result against parsing the edited text from scratch
+fn check(source: &str, start: Position, end: Position, text: &str) -> ast3::Document {
+    let mut document = ast3::Document::from_str(source).unwrap();
+    document.reparse(start, end, text).unwrap();
+
+    let full = ast3::Document::from_str(&document.to_string()).unwrap();
+    assert_eq!(document, full);
+    document
+}
+
+#[test]
+fn within_paragraph() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    let document = check(
+        content,
+        Position::new(4, 7),
+        Position::new(4, 16),
+        "para\ngraph",
+    );
+    assert!(document
+        .to_string()
+        .contains("First para\ngraph\nover two lines."));
+}
+
+#[test]
+fn within_scope() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    let document = check(content, Position::new(7, 20), Position::new(8, 1), "");
+    assert!(document.to_string().contains("\\textbf{boldtext} here."));
+
+    check(content, Position::new(7, 16), Position::new(7, 16), "\n\n");
+}
+
+#[test]
+fn within_environment() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    let document = check(
+        content,
+        Position::new(12, 14),
+        Position::new(12, 14),
+        "\n    \\item three",
+    );
+    assert!(document
+        .to_string()
+        .contains("\\item three\n\\end{itemize}"));
+
+    check(
+        content,
+        Position::new(14, 2),
+        Position::new(14, 7),
+        "\\alpha",
+    );
+}
+
+#[test]
+fn paragraph_breaks() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    check(content, Position::new(5, 16), Position::new(7, 1), " ");
+    check(content, Position::new(4, 6), Position::new(4, 6), "\n\n");
+    check(content, Position::new(6, 1), Position::new(6, 1), "\n\n\n");
+}
+
+#[test]
+fn preamable() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    check(
+        content,
+        Position::new(2, 13),
+        Position::new(2, 20),
+        "graphicx",
+    );
+    check(
+        content,
+        Position::new(1, 16),
+        Position::new(1, 23),
+        "report",
+    );
+    check(
+        content,
+        Position::new(2, 1),
+        Position::new(2, 1),
+        "\\title{A}\n",
+    );
+}
+
+#[test]
+fn unbalanced() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    // braces change what follows the edit, the whole document is parsed again
+    check(content, Position::new(7, 16), Position::new(8, 5), "a} b{c");
+    check(
+        content,
+        Position::new(7, 8),
+        Position::new(8, 6),
+        "{\\emph{x}}",
+    );
+}
+
+#[test]
+fn comments() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    // a comment takes out the rest of the line, the whole document is parsed again
+    let document = check(
+        "a\n\nbc d.\n",
+        Position::new(3, 1),
+        Position::new(3, 3),
+        "%c\n",
+    );
+    assert_eq!(document.to_string(), "a\n\n\n d.\n");
+
+    check(content, Position::new(4, 7), Position::new(4, 7), "\\% ");
+}
+
+#[test]
+fn errors() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    let mut document = ast3::Document::from_str(content).unwrap();
+    let original = document.clone();
+
+    assert!(document
+        .reparse(Position::new(10, 1), Position::new(10, 1), "\\end{itemize}")
+        .is_err());
+    assert_eq!(document, original);
+}
+
+#[test]
+fn line_numbers() {
+    let content = r#"
+\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+First paragraph
+over two lines.
+
+Second \textbf{bold
+text} here.
+
+\begin{itemize}
+    \item one
+    \item two
+\end{itemize}
+$x + y$
+\end{document}
+"#
+    .trim();
+
+    let mut document = ast3::Document::from_str(content).unwrap();
+    document
+        .reparse(Position::new(4, 1), Position::new(4, 1), "\n\n\n")
+        .unwrap();
+
+    let ast3::ChunkVariant::Environment(environment) = document
+        .chunks_body()
+        .iter()
+        .find(|chunk| matches!(chunk.variant(), ast3::ChunkVariant::Environment(_)))
+        .unwrap()
+        .variant()
+    else {
+        panic!("expected an environment")
+    };
+    assert_eq!(environment.label(), "itemize");
+    assert_eq!(
+        ast3::Document::from_str(&document.to_string()),
+        Ok(document)
+    );
+}
+
+#[test]
+fn parser_line_numbers() {
+    // escapes and environments spanning lines are numbered the same as editing them in
+    for source in [
+        "\\foo\\ bar\nbaz",
+        "a\\\nb\\\\\nc",
+        "\\foo\n\\%\nx",
+        "\\begin{a}\n\nx\n\n\\end{a}\ny",
+        "x\n\n\n  y",
+    ] {
+        assert_eq!(
+            ast1::Document::from_str(source).unwrap().to_string(),
+            source
+        );
+        let document = ast2::Document::from_str(source).unwrap();
+        assert_eq!(document.validate(), Ok(()));
+        assert_eq!(ast3::Document::from_str(source).unwrap().validate(), Ok(()));
+    }
+}
@@ -0,0 +1,192 @@
+use std::str::FromStr;
+
+use crate::{
+    ast3,
+    statistics::{Counter, Counts},
+};
+
+#[test]
+fn count() {
+    let content = r#"
+\documentclass{article}
+\title{Not counted}
+\begin{document}
+Opening words here.
+\section{First Part}
+Some text with $x$ and $y$, see \cite{a, b}.\footnote{Two words.}
+\[ z \]
+\begin{figure}
+  \caption{A small caption}
+\end{figure}
+%TC:ignore
+Ignored words everywhere.
+%TC:endignore
+\subsection{Second}
+\begin{table}\caption{Data}\end{table}
+\begin{equation} e \end{equation}
+Last.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let statistics = Counter::with_source(content).count(&document);
+    let sections = statistics.sections();
+
+    assert_eq!(sections.len(), 3);
+    assert_eq!(sections[0].command(), None);
+    assert_eq!(sections[0].counts().words_text, 3);
+
+    assert_eq!(sections[1].command(), Some(&"section".to_string()));
+    assert_eq!(sections[1].title(), "First Part");
+    assert_eq!(sections[1].line(), 5);
+    assert_eq!(
+        sections[1].counts(),
+        &Counts {
+            words_text: 7,
+            words_headers: 2,
+            words_captions: 3,
+            headers: 1,
+            figures: 1,
+            tables: 0,
+            maths_inline: 2,
+            maths_display: 1,
+            citations: 2,
+            footnotes: 1,
+        }
+    );
+
+    assert_eq!(sections[2].title(), "Second");
+    assert_eq!(sections[2].line(), 14);
+    assert_eq!(
+        sections[2].counts(),
+        &Counts {
+            words_text: 1,
+            words_headers: 1,
+            words_captions: 1,
+            headers: 1,
+            tables: 1,
+            maths_display: 1,
+            ..Counts::default()
+        }
+    );
+
+    let total = statistics.total();
+    assert_eq!(total.words_text, 11);
+    assert_eq!(total.headers, 2);
+
+    // without the source, the magic comments are not known
+    let statistics = Counter::new().count(&document);
+    assert_eq!(statistics.total().words_text, 14);
+}
+
+#[test]
+fn starred() {
+    let content = r#"
+\begin{document}
+\section{One}
+First.
+\section*{Two Words}
+Second part.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let statistics = Counter::new().count(&document);
+    let sections = statistics.sections();
+
+    assert_eq!(sections.len(), 3);
+    assert_eq!(sections[2].command(), Some(&"section".to_string()));
+    assert_eq!(sections[2].title(), "Two Words");
+    assert_eq!(sections[2].line(), 4);
+    assert_eq!(
+        sections[2].counts(),
+        &Counts {
+            words_text: 2,
+            words_headers: 2,
+            headers: 1,
+            ..Counts::default()
+        }
+    );
+}
+
+#[test]
+fn no_body() {
+    let document = ast3::Document::from_str(r"One \textbf{two} \label{three} four").unwrap();
+    let statistics = Counter::new().count(&document);
+
+    assert_eq!(statistics.sections().len(), 1);
+    assert_eq!(statistics.total().words_text, 3);
+}
+
+#[test]
+fn report() {
+    let content = r#"
+\documentclass{article}
+\title{Not counted}
+\begin{document}
+Opening words here.
+\section{First Part}
+Some text with $x$ and $y$, see \cite{a, b}.\footnote{Two words.}
+\[ z \]
+\begin{figure}
+  \caption{A small caption}
+\end{figure}
+%TC:ignore
+Ignored words everywhere.
+%TC:endignore
+\subsection{Second}
+\begin{table}\caption{Data}\end{table}
+\begin{equation} e \end{equation}
+Last.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let statistics = Counter::with_source(content).count(&document);
+    let report = statistics.report();
+
+    assert!(report.starts_with("Words in text: 11\nWords in headers: 3\n"));
+    assert!(report.contains("Number of floats/tables/figures: 2 (figures 1, tables 1)\n"));
+    assert!(report.contains("  3+0+0 (0/0/0/0) _top_\n"));
+    assert!(report.contains("  7+2+3 (1/1/2/1) section: First Part\n"));
+    assert!(report.contains("  1+1+1 (1/1/0/1) subsection: Second\n"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json() {
+    let content = r#"
+\documentclass{article}
+\title{Not counted}
+\begin{document}
+Opening words here.
+\section{First Part}
+Some text with $x$ and $y$, see \cite{a, b}.\footnote{Two words.}
+\[ z \]
+\begin{figure}
+  \caption{A small caption}
+\end{figure}
+%TC:ignore
+Ignored words everywhere.
+%TC:endignore
+\subsection{Second}
+\begin{table}\caption{Data}\end{table}
+\begin{equation} e \end{equation}
+Last.
+\end{document}
+"#
+    .trim();
+
+    let document = ast3::Document::from_str(content).unwrap();
+    let statistics = Counter::with_source(content).count(&document);
+
+    let json = statistics.to_json();
+    assert_eq!(
+        json.pointer(&["total", "wordsText"])
+            .and_then(|j| j.as_u64()),
+        Some(11)
+    );
+}
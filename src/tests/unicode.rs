@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+use crate::{ast3, unicode};
+
+#[test]
+fn maths() {
+    assert_eq!(
+        unicode::maths_source(r"\alpha^2 + \beta_i \leq \infty").unwrap(),
+        "α² + βᵢ ≤ ∞"
+    );
+    assert_eq!(
+        unicode::maths_source(r"\mathbb{R}^n \to \frac{1}{2}\sqrt{x}").unwrap(),
+        "ℝⁿ → 1/2√x"
+    );
+    assert_eq!(unicode::maths_source(r"x^{\alpha+1}"), None);
+    assert_eq!(unicode::maths_source(r"\frac{a+b}{2}"), None);
+    assert_eq!(unicode::maths_source(r"\mystery"), None);
+}
+
+#[test]
+fn document() {
+    let document = ast3::Document::from_str(
+        "\\'e, \\\"o and {\\ss}, \\o x \\v{s} \\c c \\'{\\i} \\emph{\\\"{a}}\n\
+         $\\alpha^2$ but $x^{\\alpha+1}$\n\
+         \\begin{verbatim}\\\"a\\end{verbatim}",
+    )
+    .unwrap();
+
+    assert_eq!(
+        unicode::document(&document),
+        "é, ö and ß, øx š ç í \\emph{ä}\nα² but $x^{\\alpha+1}$\n\\begin{verbatim}\\\"a\\end{verbatim}"
+    );
+}
+
+#[test]
+fn escape() {
+    assert_eq!(
+        unicode::escape("“Naïve” café – 2² … ß α"),
+        "``Na\\\"{\\i}ve'' caf\\'e -- 2\\textsuperscript{2} \\ldots{} {\\ss} $\\alpha$"
+    );
+
+    let document = ast3::Document::from_str("Größe $α ≤ x²$").unwrap();
+    assert_eq!(
+        unicode::escape_document(&document),
+        "Gr\\\"o{\\ss}e $\\alpha \\leq x^{2}$"
+    );
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use crate::*;
+
+#[test]
+fn visit_counts_commands() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+    \section{Hello \emph{world}}
+    \begin{itemize}[\textbf{x}]
+        \item one $\alpha + \beta$
+    \end{itemize}
+\end{document}
+"#
+    .trim();
+
+    struct Counter(Vec<String>);
+
+    impl ast3::Visit for Counter {
+        fn visit_command(&mut self, command: &ast3::Command) {
+            self.0.push(command.label().clone());
+            ast3::visit::walk_command(self, command)
+        }
+    }
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let mut counter = Counter(Vec::new());
+    ast3::Visit::visit_document(&mut counter, &ast);
+
+    assert_eq!(
+        counter.0,
+        vec![
+            "documentclass",
+            "section",
+            "emph",
+            "textbf",
+            "item",
+            "alpha",
+            "beta"
+        ]
+    );
+}
+
+#[test]
+fn visit_mut_text() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+    \section{Hello \emph{world}}
+    \begin{itemize}[\textbf{x}]
+        \item one $\alpha + \beta$
+    \end{itemize}
+\end{document}
+"#
+    .trim();
+
+    struct Upper;
+
+    impl ast3::VisitMut for Upper {
+        fn visit_text_mut(&mut self, text: &mut String) {
+            *text = text.to_uppercase()
+        }
+    }
+
+    let mut ast = ast3::Document::from_str(content).unwrap();
+    ast3::VisitMut::visit_document_mut(&mut Upper, &mut ast);
+
+    assert!(ast.to_string().contains(r"\section{HELLO \emph{WORLD}}"));
+    assert!(ast.to_string().contains(r"\item ONE $\alpha + \beta$"));
+}
+
+#[test]
+fn fold_renames_commands() {
+    let content = r#"
+\documentclass{article}
+\begin{document}
+    \section{Hello \emph{world}}
+    \begin{itemize}[\textbf{x}]
+        \item one $\alpha + \beta$
+    \end{itemize}
+\end{document}
+"#
+    .trim();
+
+    struct Rename;
+
+    impl ast3::Fold for Rename {
+        fn fold_command(&mut self, command: ast3::Command) -> ast3::Command {
+            let (label, arguments) = ast3::visit::fold_command(self, command).decompose();
+            let label = if label == "emph" {
+                "textit".to_string()
+            } else {
+                label
+            };
+            ast3::Command::new_unchecked(label, arguments)
+        }
+    }
+
+    let ast = ast3::Document::from_str(content).unwrap();
+    let folded = ast3::Fold::fold_document(&mut Rename, ast);
+
+    assert_eq!(folded.to_string(), content.replace("emph", "textit"));
+}
+
+#[test]
+fn earlier_stages() {
+    struct Scopes(usize);
+
+    impl ast1::Visit for Scopes {
+        fn visit_scope(&mut self, scope: &ast1::Scope) {
+            self.0 += 1;
+            ast1::visit::walk_scope(self, scope)
+        }
+    }
+
+    impl ast2::Visit for Scopes {
+        fn visit_environment(&mut self, environment: &ast2::Environment) {
+            self.0 += 1;
+            ast2::visit::walk_environment(self, environment)
+        }
+    }
+
+    let content = "{a {b}} \\begin{x}\\begin{y}{c}\\end{y}\\end{x}";
+
+    let mut scopes = Scopes(0);
+    ast1::Visit::visit_document(&mut scopes, &ast1::Document::from_str(content).unwrap());
+    assert_eq!(scopes.0, 2);
+
+    let mut environments = Scopes(0);
+    ast2::Visit::visit_document(
+        &mut environments,
+        &ast2::Document::from_str(content).unwrap(),
+    );
+    assert_eq!(environments.0, 2);
+}
@@ -0,0 +1,764 @@
+//! # Unicode
+//!
+//! Converts maths and text accents to Unicode for terminals and plain text, and
+//! Unicode back to LaTeX escapes for documents using `inputenc` with a legacy encoding.
+//!
+//! |Source|Unicode|
+//! |---|---|
+//! |`\'e`, `\"{o}`, `\v{s}` and similar|`é`, `ö` and `š`|
+//! |`\ss`, `\ae`, `\o` and similar|`ß`, `æ` and `ø`|
+//! |Greek letters and symbols in maths|`α`, `≤` and similar, see [`GREEK`] and [`SYMBOLS`].|
+//! |`^` and `_` in maths|Superscript and subscript characters, such as `²` and `ᵢ`.|
+//! |`\mathbb`, `\sqrt` and simple `\frac`s|`ℝ`, `√x` and `a/b`|
+//!
+//! Maths without a faithful Unicode form, such as `x^{\alpha+1}` or `\frac{a+b}{2}`,
+//! is kept as it is in the source.
+
+use crate::{
+    ast3::{self, Chunk, ChunkVariant, Command, Scope},
+    format::VERBATIM,
+    mathml::{FUNCTIONS, GREEK, LARGE_OPERATORS, SYMBOLS},
+};
+
+/// Accent commands, their combining character, and the letters they compose with
+pub const ACCENTS: [(&str, char, &str, &str); 13] = [
+    (
+        "'",
+        '\u{301}',
+        "aeiouyAEIOUYcnszlrCNSZLR",
+        "áéíóúýÁÉÍÓÚÝćńśźĺŕĆŃŚŹĹŔ",
+    ),
+    ("`", '\u{300}', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    (
+        "^",
+        '\u{302}',
+        "aeiouAEIOUcgshjwyCGSHJWY",
+        "âêîôûÂÊÎÔÛĉĝŝĥĵŵŷĈĜŜĤĴŴŶ",
+    ),
+    ("\"", '\u{308}', "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+    ("~", '\u{303}', "anoANO", "ãñõÃÑÕ"),
+    ("=", '\u{304}', "aeiouAEIOU", "āēīōūĀĒĪŌŪ"),
+    (".", '\u{307}', "czegCZEGI", "ċżėġĊŻĖĠİ"),
+    ("u", '\u{306}', "aguAGU", "ăğŭĂĞŬ"),
+    ("v", '\u{30c}', "csznrdteCSZNRDTE", "čšžňřďťěČŠŽŇŘĎŤĚ"),
+    ("H", '\u{30b}', "ouOU", "őűŐŰ"),
+    ("c", '\u{327}', "cstCST", "çşţÇŞŢ"),
+    ("k", '\u{328}', "aeiuAEIU", "ąęįųĄĘĮŲ"),
+    ("r", '\u{30a}', "auAU", "åůÅŮ"),
+];
+
+/// Commands for letters and symbols in text
+pub const LETTERS: [(&str, char); 34] = [
+    ("ss", 'ß'),
+    ("ae", 'æ'),
+    ("AE", 'Æ'),
+    ("oe", 'œ'),
+    ("OE", 'Œ'),
+    ("o", 'ø'),
+    ("O", 'Ø'),
+    ("aa", 'å'),
+    ("AA", 'Å'),
+    ("l", 'ł'),
+    ("L", 'Ł'),
+    ("i", 'ı'),
+    ("j", 'ȷ'),
+    ("dh", 'ð'),
+    ("DH", 'Ð'),
+    ("th", 'þ'),
+    ("TH", 'Þ'),
+    ("ng", 'ŋ'),
+    ("NG", 'Ŋ'),
+    ("ldots", '…'),
+    ("textendash", '–'),
+    ("textemdash", '—'),
+    ("S", '§'),
+    ("P", '¶'),
+    ("copyright", '©'),
+    ("pounds", '£'),
+    ("texteuro", '€'),
+    ("dag", '†'),
+    ("ddag", '‡'),
+    ("textdegree", '°'),
+    ("guillemotleft", '«'),
+    ("guillemotright", '»'),
+    ("textquestiondown", '¿'),
+    ("textexclamdown", '¡'),
+];
+
+// superscript forms
+const SUPERSCRIPTS: [(char, char); 61] = [
+    ('0', '⁰'),
+    ('1', '¹'),
+    ('2', '²'),
+    ('3', '³'),
+    ('4', '⁴'),
+    ('5', '⁵'),
+    ('6', '⁶'),
+    ('7', '⁷'),
+    ('8', '⁸'),
+    ('9', '⁹'),
+    ('+', '⁺'),
+    ('-', '⁻'),
+    ('−', '⁻'),
+    ('=', '⁼'),
+    ('(', '⁽'),
+    (')', '⁾'),
+    ('a', 'ᵃ'),
+    ('b', 'ᵇ'),
+    ('c', 'ᶜ'),
+    ('d', 'ᵈ'),
+    ('e', 'ᵉ'),
+    ('f', 'ᶠ'),
+    ('g', 'ᵍ'),
+    ('h', 'ʰ'),
+    ('i', 'ⁱ'),
+    ('j', 'ʲ'),
+    ('k', 'ᵏ'),
+    ('l', 'ˡ'),
+    ('m', 'ᵐ'),
+    ('n', 'ⁿ'),
+    ('o', 'ᵒ'),
+    ('p', 'ᵖ'),
+    ('r', 'ʳ'),
+    ('s', 'ˢ'),
+    ('t', 'ᵗ'),
+    ('u', 'ᵘ'),
+    ('v', 'ᵛ'),
+    ('w', 'ʷ'),
+    ('x', 'ˣ'),
+    ('y', 'ʸ'),
+    ('z', 'ᶻ'),
+    ('A', 'ᴬ'),
+    ('B', 'ᴮ'),
+    ('D', 'ᴰ'),
+    ('E', 'ᴱ'),
+    ('G', 'ᴳ'),
+    ('H', 'ᴴ'),
+    ('I', 'ᴵ'),
+    ('J', 'ᴶ'),
+    ('K', 'ᴷ'),
+    ('L', 'ᴸ'),
+    ('M', 'ᴹ'),
+    ('N', 'ᴺ'),
+    ('O', 'ᴼ'),
+    ('P', 'ᴾ'),
+    ('R', 'ᴿ'),
+    ('T', 'ᵀ'),
+    ('U', 'ᵁ'),
+    ('W', 'ᵂ'),
+    ('′', '′'),
+    ('θ', 'ᶿ'),
+];
+
+// subscript forms
+const SUBSCRIPTS: [(char, char); 37] = [
+    ('0', '₀'),
+    ('1', '₁'),
+    ('2', '₂'),
+    ('3', '₃'),
+    ('4', '₄'),
+    ('5', '₅'),
+    ('6', '₆'),
+    ('7', '₇'),
+    ('8', '₈'),
+    ('9', '₉'),
+    ('+', '₊'),
+    ('-', '₋'),
+    ('−', '₋'),
+    ('=', '₌'),
+    ('(', '₍'),
+    (')', '₎'),
+    ('a', 'ₐ'),
+    ('e', 'ₑ'),
+    ('h', 'ₕ'),
+    ('i', 'ᵢ'),
+    ('j', 'ⱼ'),
+    ('k', 'ₖ'),
+    ('l', 'ₗ'),
+    ('m', 'ₘ'),
+    ('n', 'ₙ'),
+    ('o', 'ₒ'),
+    ('p', 'ₚ'),
+    ('r', 'ᵣ'),
+    ('s', 'ₛ'),
+    ('t', 'ₜ'),
+    ('u', 'ᵤ'),
+    ('v', 'ᵥ'),
+    ('x', 'ₓ'),
+    ('β', 'ᵦ'),
+    ('γ', 'ᵧ'),
+    ('ρ', 'ᵨ'),
+    ('χ', 'ᵪ'),
+];
+
+// double-struck letters outside the mathematical alphanumeric block
+const DOUBLE_STRUCK: [(char, char); 7] = [
+    ('C', 'ℂ'),
+    ('H', 'ℍ'),
+    ('N', 'ℕ'),
+    ('P', 'ℙ'),
+    ('Q', 'ℚ'),
+    ('R', 'ℝ'),
+    ('Z', 'ℤ'),
+];
+
+// text punctuation and its LaTeX
+const PUNCTUATION: [(char, &str); 8] = [
+    ('“', "``"),
+    ('”', "''"),
+    ('‘', "`"),
+    ('’', "'"),
+    ('–', "--"),
+    ('—', "---"),
+    ('\u{a0}', "~"),
+    ('…', "\\ldots{}"),
+];
+
+/// Converts maths and accents in a document, keeping everything else as in the source
+pub fn document(document: &ast3::Document) -> String {
+    let mut out = chunks(document.chunks_preamable());
+
+    if document.has_body() {
+        out.push_str(&format!(
+            "\\begin{}{{document}}{}{}\\end{}{{document}}",
+            document.body_begin_prec(),
+            arguments(document.body_args()),
+            chunks(document.chunks_body()),
+            document.body_end_prec()
+        ));
+    }
+
+    out.push_str(&chunks(document.chunks_trailing()));
+    out
+}
+
+/// Converts maths and accents in chunks, keeping everything else as in the source
+pub fn chunks(chunks: &[Chunk]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    // whether spaces after a control word are dropped
+    let mut skip_space = false;
+
+    while let Some(chunk) = chunks.get(i) {
+        i += 1;
+
+        let skip = std::mem::take(&mut skip_space);
+
+        match chunk.variant() {
+            ChunkVariant::Text(s) if skip && !s.contains("\n\n") => out.push_str(s.trim_start()),
+            ChunkVariant::Text(s) | ChunkVariant::ParagraphBreak(s) => out.push_str(s),
+            ChunkVariant::MathsBlock(block) => out.push_str(&maths(block)),
+            ChunkVariant::Command(command) => {
+                if let Some((c, consumed)) = accent(command, chunks.get(i)) {
+                    out.push_str(&c);
+                    i += consumed;
+                } else if let Some(c) = letter(command) {
+                    out.push(c);
+                    skip_space = command.arguments().is_empty();
+                } else {
+                    out.push_str(&format!(
+                        "\\{}{}",
+                        command.label(),
+                        arguments(command.arguments())
+                    ));
+                }
+            }
+            // `{\ss}` and `{\"o}`
+            ChunkVariant::Scope(scope) => match single(scope) {
+                Some(c) => out.push_str(&c),
+                None => out.push_str(&self::scope(scope)),
+            },
+            ChunkVariant::Environment(env) if VERBATIM.contains(&env.label().as_str()) => {
+                out.push_str(&env.to_string())
+            }
+            ChunkVariant::Environment(env) => out.push_str(&format!(
+                "\\begin{}{{{}}}{}{}\\end{}{{{}}}",
+                env.prec_begin(),
+                env.label(),
+                arguments(env.arguments()),
+                self::chunks(env.content()),
+                env.prec_end(),
+                env.label()
+            )),
+            ChunkVariant::Conditional(conditional) => {
+                out.push_str(&self::conditional(conditional, self::chunks))
+            }
+            ChunkVariant::Parameter(parameter) => out.push_str(&parameter.to_string()),
+            ChunkVariant::MacroDefinition(definition) => {
+                out.push_str(&macro_definition(definition, self::chunks))
+            }
+        }
+    }
+
+    out
+}
+
+/// Converts a maths block to Unicode, or returns its source if there is no faithful form
+pub fn maths(block: &ast3::MathsBlock) -> String {
+    let source = block
+        .content()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<String>();
+
+    maths_source(&source).unwrap_or_else(|| block.to_string())
+}
+
+/// Converts maths source to Unicode, if it has a faithful form
+pub fn maths_source(source: &str) -> Option<String> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        i: 0,
+    };
+
+    let out = parser.sequence(None)?;
+    (parser.i == parser.chars.len()).then_some(out)
+}
+
+/// Replaces non-ASCII characters with LaTeX escapes, where there are any
+///
+/// Greek letters and maths symbols are written as inline maths.
+pub fn escape(s: &str) -> String {
+    escape_with(s, false)
+}
+
+/// Replaces non-ASCII characters with LaTeX escapes in the text and maths of a document
+pub fn escape_document(document: &ast3::Document) -> String {
+    let mut out = escape_chunks(document.chunks_preamable());
+
+    if document.has_body() {
+        out.push_str(&format!(
+            "\\begin{}{{document}}{}{}\\end{}{{document}}",
+            document.body_begin_prec(),
+            escape_arguments(document.body_args()),
+            escape_chunks(document.chunks_body()),
+            document.body_end_prec()
+        ));
+    }
+
+    out.push_str(&escape_chunks(document.chunks_trailing()));
+    out
+}
+
+fn escape_chunks(chunks: &[Chunk]) -> String {
+    chunks
+        .iter()
+        .map(|chunk| match chunk.variant() {
+            ChunkVariant::Text(s) => escape(s),
+            ChunkVariant::ParagraphBreak(s) => s.to_string(),
+            ChunkVariant::MathsBlock(block) => {
+                let source = block.to_string();
+                let content = block
+                    .content()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<String>();
+                // the delimiters are ASCII and the same length on both sides
+                let open = (source.len() - content.len()) / 2;
+
+                format!(
+                    "{}{}{}",
+                    &source[..open],
+                    escape_with(&content, true),
+                    &source[open + content.len()..]
+                )
+            }
+            ChunkVariant::Command(command) => format!(
+                "\\{}{}",
+                command.label(),
+                escape_arguments(command.arguments())
+            ),
+            ChunkVariant::Scope(scope) => format!(
+                "{}{}{}",
+                scope.variant().open(),
+                escape_chunks(scope.chunks()),
+                scope.variant().close()
+            ),
+            ChunkVariant::Environment(env) => format!(
+                "\\begin{}{{{}}}{}{}\\end{}{{{}}}",
+                env.prec_begin(),
+                env.label(),
+                escape_arguments(env.arguments()),
+                escape_chunks(env.content()),
+                env.prec_end(),
+                env.label()
+            ),
+            ChunkVariant::Conditional(conditional) => self::conditional(conditional, escape_chunks),
+            ChunkVariant::Parameter(parameter) => parameter.to_string(),
+            ChunkVariant::MacroDefinition(definition) => {
+                macro_definition(definition, escape_chunks)
+            }
+        })
+        .collect()
+}
+
+fn escape_arguments(arguments: &[(String, Scope)]) -> String {
+    arguments
+        .iter()
+        .map(|(prec, scope)| {
+            format!(
+                "{prec}{}{}{}",
+                scope.variant().open(),
+                escape_chunks(scope.chunks()),
+                scope.variant().close()
+            )
+        })
+        .collect()
+}
+
+fn escape_with(s: &str, in_maths: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+
+        let symbol = GREEK
+            .iter()
+            .chain(SYMBOLS.iter())
+            .chain(LARGE_OPERATORS.iter())
+            .find(|(_, other)| other == &c)
+            .map(|(label, _)| label);
+
+        if in_maths {
+            if let Some(label) = symbol {
+                out.push_str(&format!("\\{label}"));
+                // a space ends the control word before a letter
+                if chars.peek().is_some_and(char::is_ascii_alphabetic) {
+                    out.push(' ')
+                }
+            } else if let Some((base, _)) = SUPERSCRIPTS.iter().find(|(_, other)| other == &c) {
+                out.push_str(&format!("^{{{base}}}"));
+            } else if let Some((base, _)) = SUBSCRIPTS.iter().find(|(_, other)| other == &c) {
+                out.push_str(&format!("_{{{base}}}"));
+            } else {
+                out.push(c);
+            }
+            continue;
+        }
+
+        let accent = ACCENTS.iter().find_map(|(label, _, bases, composed)| {
+            composed
+                .chars()
+                .position(|other| other == c)
+                .and_then(|n| bases.chars().nth(n))
+                .map(|base| (label, base))
+        });
+
+        if let Some((_, latex)) = PUNCTUATION.iter().find(|(other, _)| other == &c) {
+            out.push_str(latex);
+        } else if let Some((label, _)) = LETTERS.iter().find(|(_, other)| other == &c) {
+            out.push_str(&format!("{{\\{label}}}"));
+        } else if let Some((label, base)) = accent {
+            // accents go on dotless i and j
+            let base = match base {
+                'i' => "{\\i}".to_string(),
+                'j' => "{\\j}".to_string(),
+                base => base.to_string(),
+            };
+
+            match label.chars().all(char::is_alphabetic) || base.len() > 1 {
+                true => out.push_str(&format!("\\{label}{{{}}}", base.trim_matches(['{', '}']))),
+                false => out.push_str(&format!("\\{label}{base}")),
+            }
+        } else if let Some((base, _)) = SUPERSCRIPTS[..10].iter().find(|(_, other)| other == &c) {
+            out.push_str(&format!("\\textsuperscript{{{base}}}"));
+        } else if let Some(label) = symbol {
+            out.push_str(&format!("$\\{label}$"));
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+fn arguments(arguments: &[(String, Scope)]) -> String {
+    arguments
+        .iter()
+        .map(|(prec, scope)| format!("{prec}{}", self::scope(scope)))
+        .collect()
+}
+
+// the condition is kept as it is, only the branches are converted
+fn conditional(conditional: &ast3::Conditional, convert: fn(&[Chunk]) -> String) -> String {
+    let condition = conditional
+        .condition()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<String>();
+    let mut out = format!(
+        "{}{condition}{}",
+        conditional.opening(),
+        convert(conditional.content())
+    );
+
+    for case in conditional.cases() {
+        out.push_str(&format!("\\or{}", convert(case)));
+    }
+    if let Some(otherwise) = conditional.otherwise() {
+        out.push_str(&format!("\\else{}", convert(otherwise)));
+    }
+
+    out.push_str("\\fi");
+    out
+}
+
+// the parameter text is kept as it is, as it is matched against the source
+fn macro_definition(definition: &ast3::MacroDefinition, convert: fn(&[Chunk]) -> String) -> String {
+    format!(
+        "\\{}{}\\{}{}{{{}}}",
+        definition.command(),
+        definition.prec(),
+        definition.label(),
+        definition
+            .parameters()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<String>(),
+        convert(definition.body())
+    )
+}
+
+fn scope(scope: &Scope) -> String {
+    format!(
+        "{}{}{}",
+        scope.variant().open(),
+        chunks(scope.chunks()),
+        scope.variant().close()
+    )
+}
+
+// the Unicode for a scope containing a single letter or accent
+fn single(scope: &Scope) -> Option<String> {
+    if scope.variant() != ast3::ScopeVariant::Curly {
+        return None;
+    }
+
+    match scope.chunks().as_slice() {
+        [chunk] => match chunk.variant() {
+            ChunkVariant::Command(command) => letter(command)
+                .map(String::from)
+                .or_else(|| accent(command, None).map(|(c, _)| c)),
+            _ => None,
+        },
+        [first, second] => match (first.variant(), second.variant()) {
+            (ChunkVariant::Command(command), next) if !matches!(next, ChunkVariant::Text(s) if s.chars().count() > 1) => {
+                accent(command, Some(second)).map(|(c, _)| c)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// a letter command with no argument, or an empty one
+fn letter(command: &Command) -> Option<char> {
+    let empty = match command.arguments().as_slice() {
+        [] => true,
+        [(_, scope)] => scope.variant() == ast3::ScopeVariant::Curly && scope.chunks().is_empty(),
+        _ => false,
+    };
+
+    LETTERS
+        .iter()
+        .find(|(label, _)| label == command.label())
+        .filter(|_| empty)
+        .map(|(_, c)| *c)
+}
+
+// an accent, and how many of the following chunks it takes
+fn accent(command: &Command, next: Option<&Chunk>) -> Option<(String, usize)> {
+    let (_, combining, bases, composed) = ACCENTS
+        .iter()
+        .find(|(label, _, _, _)| label == command.label())?;
+
+    // the accented letter, the rest of the following text, and the chunks used
+    let (base, rest, consumed) = match (command.arguments().as_slice(), next.map(Chunk::variant)) {
+        ([(_, scope)], _) => (base(scope.chunks())?, String::new(), 0),
+        ([], Some(ChunkVariant::Scope(scope))) if scope.variant() == ast3::ScopeVariant::Curly => {
+            (base(scope.chunks())?, String::new(), 1)
+        }
+        ([], Some(ChunkVariant::Text(s))) => {
+            let s = s.trim_start_matches([' ', '\t']);
+            let c = s.chars().next().filter(|c| c.is_alphabetic())?;
+            (c, s[c.len_utf8()..].to_string(), 1)
+        }
+        ([], Some(ChunkVariant::Command(next))) => (
+            letter(next).filter(|c| "ıȷ".contains(*c))?,
+            String::new(),
+            1,
+        ),
+        _ => return None,
+    };
+
+    // dotless letters compose as their dotted forms
+    let base = match base {
+        'ı' => 'i',
+        'ȷ' => 'j',
+        c => c,
+    };
+
+    let c = match bases.chars().position(|other| other == base) {
+        Some(n) => composed.chars().nth(n)?.to_string(),
+        None => format!("{base}{combining}"),
+    };
+
+    Some((format!("{c}{rest}"), consumed))
+}
+
+// the letter in an accent's argument
+fn base(chunks: &[Chunk]) -> Option<char> {
+    match chunks {
+        [chunk] => match chunk.variant() {
+            ChunkVariant::Text(s) if s.trim().chars().count() == 1 => s.trim().chars().next(),
+            ChunkVariant::Command(command) => letter(command),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+}
+
+impl Parser {
+    // maths up to the end, or the closing brace of a group
+    fn sequence(&mut self, close: Option<char>) -> Option<String> {
+        let mut out = String::new();
+
+        while let Some(&c) = self.chars.get(self.i) {
+            if Some(c) == close {
+                return Some(out);
+            }
+
+            match c {
+                '^' | '_' => {
+                    self.i += 1;
+                    let argument = self.argument()?;
+                    let table: &[(char, char)] = match c {
+                        '^' => &SUPERSCRIPTS,
+                        _ => &SUBSCRIPTS,
+                    };
+
+                    for c in argument.chars() {
+                        out.push(table.iter().find(|(base, _)| base == &c)?.1);
+                    }
+                }
+                '\'' => {
+                    self.i += 1;
+                    out.push('′');
+                }
+                c if c.is_whitespace() => {
+                    self.i += 1;
+                    if !out.ends_with(' ') {
+                        out.push(' ');
+                    }
+                }
+                _ => out.push_str(&self.atom()?),
+            }
+        }
+
+        close.is_none().then_some(out)
+    }
+
+    // a group or single atom
+    fn argument(&mut self) -> Option<String> {
+        while self.chars.get(self.i).is_some_and(|c| c.is_whitespace()) {
+            self.i += 1;
+        }
+
+        match self.chars.get(self.i) {
+            Some('{') => {
+                self.i += 1;
+                let out = self.sequence(Some('}'))?;
+                self.i += 1;
+                Some(out.trim().to_string())
+            }
+            Some(_) => self.atom(),
+            None => None,
+        }
+    }
+
+    fn atom(&mut self) -> Option<String> {
+        let c = *self.chars.get(self.i)?;
+        self.i += 1;
+
+        match c {
+            '{' => {
+                let out = self.sequence(Some('}'))?;
+                self.i += 1;
+                Some(out)
+            }
+            '\\' => self.command(),
+            '-' => Some("−".to_string()),
+            '}' | '&' | '#' | '%' | '$' | '~' => None,
+            c => Some(c.to_string()),
+        }
+    }
+
+    fn command(&mut self) -> Option<String> {
+        let name = self.chars[self.i..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect::<String>();
+
+        let name = match name.is_empty() {
+            true => self.chars.get(self.i)?.to_string(),
+            false => name,
+        };
+        self.i += name.chars().count();
+
+        let symbol = GREEK
+            .iter()
+            .chain(SYMBOLS.iter())
+            .chain(LARGE_OPERATORS.iter())
+            .find(|(label, _)| label == &name);
+
+        if let Some((_, c)) = symbol {
+            return Some(c.to_string());
+        }
+        if FUNCTIONS.contains(&name.as_str()) {
+            return Some(name);
+        }
+
+        match name.as_str() {
+            "{" | "}" | "%" | "&" | "#" | "$" | "_" | "|" => Some(name),
+            "," | ":" | ";" | " " | "quad" | "qquad" => Some(" ".to_string()),
+            "!" | "left" | "right" | "displaystyle" | "limits" => Some(String::new()),
+            "text" | "mathrm" | "textrm" | "mbox" | "operatorname" => self.argument(),
+            "mathbb" => self
+                .argument()?
+                .chars()
+                .map(
+                    |c| match DOUBLE_STRUCK.iter().find(|(base, _)| base == &c) {
+                        Some((_, c)) => Some(*c),
+                        None if c.is_ascii_uppercase() => {
+                            char::from_u32(0x1D538 + (c as u32 - 'A' as u32))
+                        }
+                        None => None,
+                    },
+                )
+                .collect(),
+            "sqrt" => {
+                let argument = self.argument()?;
+                match argument.chars().count() {
+                    1 => Some(format!("√{argument}")),
+                    _ => Some(format!("√({argument})")),
+                }
+            }
+            "frac" | "tfrac" | "dfrac" => {
+                let num = self.argument()?;
+                let den = self.argument()?;
+                let simple = |s: &str| !s.is_empty() && s.chars().all(char::is_alphanumeric);
+                (simple(&num) && simple(&den)).then(|| format!("{num}/{den}"))
+            }
+            _ => None,
+        }
+    }
+}